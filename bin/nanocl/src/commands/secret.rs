@@ -89,6 +89,27 @@ async fn exec_secret_inspect(
   Ok(())
 }
 
+/// ## Exec secret rotate key
+///
+/// Function that execute when running `nanocl secret rotate-key`
+///
+/// ## Arguments
+///
+/// * [cli_conf](CliConfig) The cli config
+///
+/// ## Return
+///
+/// * [Result](Result) The result of the operation
+///   * [Ok](()) The operation was successful
+///   * [Err](nanocl_utils::io_error::IoError) An error occured
+///
+async fn exec_secret_rotate_key(cli_conf: &CliConfig) -> IoResult<()> {
+  let client = &cli_conf.client;
+  let res = client.rotate_secret_key().await?;
+  println!("{} secrets re-encrypted", res.count);
+  Ok(())
+}
+
 /// ## Exec secret
 ///
 /// Function that execute when running `nanocl secret`
@@ -112,5 +133,6 @@ pub async fn exec_secret(
     SecretCommand::List => exec_secret_ls(cli_conf).await,
     SecretCommand::Remove(opts) => exec_secret_rm(cli_conf, opts).await,
     SecretCommand::Inspect(opts) => exec_secret_inspect(cli_conf, opts).await,
+    SecretCommand::RotateKey => exec_secret_rotate_key(cli_conf).await,
   }
 }
@@ -12,7 +12,7 @@ use bollard_next::service::HostConfig;
 
 use nanocl_utils::io_error::{IoError, FromIo, IoResult};
 use nanocld_client::NanocldClient;
-use nanocld_client::stubs::state::StateMeta;
+use nanocld_client::stubs::state::{StateMeta, StateApplyQuery};
 use nanocld_client::stubs::cargo::{OutputKind, CargoLogQuery};
 use nanocld_client::stubs::cargo_config::{
   CargoConfigPartial, Config as ContainerConfig,
@@ -693,8 +693,22 @@ async fn exec_state_apply(
       .await?
     }
   };
-  if !opts.skip_confirm {
-    utils::print::display_format(&state_ref.format, &data)?;
+  let data = serde_json::to_value(&data).map_err(|err| {
+    err.map_err_context(|| "Unable to create json payload for the daemon")
+  })?;
+  if opts.dry_run || !opts.skip_confirm {
+    let query = StateApplyQuery {
+      dry_run: Some(true),
+      prune: Some(opts.prune),
+    };
+    let mut stream = client.apply_state(&data, Some(&query)).await?;
+    while let Some(res) = stream.next().await {
+      let res = res?;
+      utils::state::print_diff(&res);
+    }
+    if opts.dry_run {
+      return Ok(());
+    }
     utils::dialog::confirm("Are you sure to apply this state ?")
       .map_err(|err| err.map_err_context(|| "StateApply"))?;
   }
@@ -713,10 +727,11 @@ async fn exec_state_apply(
       }
     }
   }
-  let data = serde_json::to_value(&data).map_err(|err| {
-    err.map_err_context(|| "Unable to create json payload for the daemon")
-  })?;
-  let mut stream = client.apply_state(&data).await?;
+  let query = StateApplyQuery {
+    dry_run: None,
+    prune: Some(opts.prune),
+  };
+  let mut stream = client.apply_state(&data, Some(&query)).await?;
   let multiprogress = MultiProgress::new();
   multiprogress.set_move_cursor(false);
   let mut layers: HashMap<String, ProgressBar> = HashMap::new();
@@ -4,7 +4,7 @@ use nanocld_client::NanocldClient;
 use crate::config::CliConfig;
 use crate::models::{
   ProcessOpts, ProcessRow, SystemArg, SystemHttpArg, SystemHttpCommand,
-  SystemCommand,
+  SystemCommand, HttpMetricStatsRow,
 };
 use crate::utils;
 use crate::utils::print::print_table;
@@ -43,7 +43,7 @@ pub async fn exec_process(
 /// ## Exec http
 ///
 /// Function that execute when running `nanocl system http`
-/// Will print the list of http request
+/// Will print the list of http request or their statistics
 ///
 /// ## Arguments
 ///
@@ -65,6 +65,14 @@ pub async fn exec_http(
       let logs = client.list_http_metric(Some(opts.clone().into())).await?;
       utils::print::print_yml(logs)?;
     }
+    SystemHttpCommand::Stats(opts) => {
+      let stats = client.stats_http_metric(Some(opts.clone().into())).await?;
+      let rows = stats
+        .into_iter()
+        .map(HttpMetricStatsRow::from)
+        .collect::<Vec<HttpMetricStatsRow>>();
+      print_table(rows);
+    }
   }
   Ok(())
 }
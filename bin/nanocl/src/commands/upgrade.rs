@@ -65,7 +65,7 @@ pub async fn exec_upgrade(
     serde_json::from_value::<serde_json::Value>(data).map_err(|err| {
       err.map_err_context(|| "Unable to convert upgrade to json")
     })?;
-  let mut stream = client.apply_state(&data).await?;
+  let mut stream = client.apply_state(&data, None).await?;
   let multiprogress = MultiProgress::new();
   multiprogress.set_move_cursor(false);
   let mut layers: HashMap<String, ProgressBar> = HashMap::new();
@@ -20,6 +20,8 @@ pub enum SecretCommand {
   List,
   /// Inspect a secret
   Inspect(SecretInspectOpts),
+  /// Rotate the master key encrypting the secrets
+  RotateKey,
 }
 
 /// ## SecretArg
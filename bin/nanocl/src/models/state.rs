@@ -47,6 +47,12 @@ pub struct StateApplyOpts {
   /// Skip the confirmation prompt
   #[clap(long = "yes", short = 'y')]
   pub skip_confirm: bool,
+  /// Only print what would be created, updated or left unchanged
+  #[clap(long)]
+  pub dry_run: bool,
+  /// Delete the objects of the Statefile that are no longer declared
+  #[clap(long)]
+  pub prune: bool,
   /// Additional arguments to pass to the file
   #[clap(last = true, raw = true)]
   pub args: Vec<String>,
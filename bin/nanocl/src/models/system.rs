@@ -1,10 +1,12 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use tabled::Tabled;
 use chrono::TimeZone;
 
 use nanocld_client::stubs::system::ProccessQuery;
 use nanocld_client::stubs::node::NodeContainerSummary;
-use nanocld_client::stubs::http_metric::HttpMetricListQuery;
+use nanocld_client::stubs::http_metric::{
+  HttpMetricListQuery, HttpMetricStatsQuery, HttpMetricGroupBy, HttpMetricStats,
+};
 
 /// ## SystemArg
 ///
@@ -46,6 +48,8 @@ pub struct SystemHttpArg {
 pub enum SystemHttpCommand {
   /// Show HTTP metrics information
   Logs(SystemHttpLogsOpts),
+  /// Show HTTP metrics statistics
+  Stats(SystemHttpStatsOpts),
 }
 
 /// ## SystemHttpLogsOpts
@@ -74,6 +78,114 @@ impl From<SystemHttpLogsOpts> for HttpMetricListQuery {
   }
 }
 
+/// ## SystemHttpGroupBy
+///
+/// `nanocl system http stats` available groups
+///
+#[derive(Default, Clone, Debug, ValueEnum)]
+pub enum SystemHttpGroupBy {
+  #[default]
+  Host,
+  UriPrefix,
+  Upstream,
+  StatusClass,
+}
+
+/// Convert SystemHttpGroupBy to HttpMetricGroupBy
+impl From<SystemHttpGroupBy> for HttpMetricGroupBy {
+  fn from(group_by: SystemHttpGroupBy) -> Self {
+    match group_by {
+      SystemHttpGroupBy::Host => Self::Host,
+      SystemHttpGroupBy::UriPrefix => Self::UriPrefix,
+      SystemHttpGroupBy::Upstream => Self::Upstream,
+      SystemHttpGroupBy::StatusClass => Self::StatusClass,
+    }
+  }
+}
+
+/// ## SystemHttpStatsOpts
+///
+/// `nanocl system http stats` available options
+///
+#[derive(Clone, Debug, Parser)]
+pub struct SystemHttpStatsOpts {
+  /// Group the metrics by host, uri prefix, upstream or status class
+  #[clap(long, short, value_enum, default_value_t)]
+  pub group_by: SystemHttpGroupBy,
+  /// Duration of a time bucket in seconds
+  #[clap(long, short)]
+  pub bucket: Option<i64>,
+  /// Only use the metrics of the last seconds
+  #[clap(long, short)]
+  pub since: Option<i64>,
+  /// Number of segments of the uri prefix
+  #[clap(long)]
+  pub uri_depth: Option<i64>,
+}
+
+/// Convert SystemHttpStatsOpts to HttpMetricStatsQuery
+impl From<SystemHttpStatsOpts> for HttpMetricStatsQuery {
+  fn from(opts: SystemHttpStatsOpts) -> Self {
+    Self {
+      group_by: Some(opts.group_by.into()),
+      bucket: opts.bucket,
+      since: opts.since,
+      uri_depth: opts.uri_depth,
+    }
+  }
+}
+
+/// ## HttpMetricStatsRow
+///
+/// A row for the http metrics statistics table
+///
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct HttpMetricStatsRow {
+  /// Start of the time bucket
+  bucket: String,
+  /// Value of the group
+  group: String,
+  /// Number of requests
+  requests: i64,
+  /// Number of requests per second
+  rate: String,
+  /// Median of the request time
+  p50: String,
+  /// 95th percentile of the request time
+  p95: String,
+  /// 99th percentile of the request time
+  p99: String,
+  /// Percentage of requests with a 5xx status
+  errors: String,
+  /// Number of bytes sent
+  #[tabled(rename = "BYTES SENT")]
+  bytes_sent: i64,
+}
+
+/// Convert HttpMetricStats to HttpMetricStatsRow
+impl From<HttpMetricStats> for HttpMetricStatsRow {
+  fn from(stats: HttpMetricStats) -> Self {
+    let binding = chrono::Local::now();
+    let tz = binding.offset();
+    // Convert the bucket to the current timezone
+    let bucket = tz
+      .from_utc_datetime(&stats.bucket)
+      .format("%Y-%m-%d %H:%M:%S");
+    Self {
+      bucket: format!("{bucket}"),
+      group: stats.group,
+      requests: stats.requests,
+      rate: format!("{:.2}/s", stats.request_rate),
+      p50: format!("{:.3}s", stats.p50),
+      p95: format!("{:.3}s", stats.p95),
+      p99: format!("{:.3}s", stats.p99),
+      errors: format!("{:.2}%", stats.error_ratio * 100.0),
+      bytes_sent: stats.bytes_sent,
+    }
+  }
+}
+
 /// ## ProcessOpts
 ///
 /// `nanocl ps` available options
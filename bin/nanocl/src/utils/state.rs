@@ -219,3 +219,64 @@ pub fn update_progress(
     layers.insert(id.to_owned(), pg);
   }
 }
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// ## Format diff value
+///
+/// Format a json value of a diff on a single line
+///
+fn format_diff_value(value: &Option<serde_json::Value>) -> String {
+  match value {
+    None => "<none>".to_owned(),
+    Some(serde_json::Value::String(value)) => value.to_owned(),
+    Some(value) => value.to_string(),
+  }
+}
+
+/// ## Print diff
+///
+/// Print a colored diff of a state stream received during a dry run.
+/// Elements to create are printed in green, updated elements in yellow
+/// with their changed fields, pruned elements and failures in red.
+///
+/// ## Arguments
+///
+/// * [state_stream](StateStream) The state stream to print
+///
+pub fn print_diff(state_stream: &StateStream) {
+  let kind = &state_stream.kind;
+  let key = &state_stream.key;
+  match state_stream.status {
+    StateStreamStatus::Create => {
+      println!("{GREEN}+ {kind} {key}{RESET}");
+    }
+    StateStreamStatus::Update => {
+      println!("{YELLOW}~ {kind} {key}{RESET}");
+      for diff in state_stream.diff.clone().unwrap_or_default() {
+        if diff.old.is_some() {
+          let old = format_diff_value(&diff.old);
+          println!("{RED}    - {}: {old}{RESET}", diff.path);
+        }
+        if diff.new.is_some() {
+          let new = format_diff_value(&diff.new);
+          println!("{GREEN}    + {}: {new}{RESET}", diff.path);
+        }
+      }
+    }
+    StateStreamStatus::Pruned => {
+      println!("{RED}- {kind} {key}{RESET}");
+    }
+    StateStreamStatus::UnChanged => {
+      println!("  {kind} {key} (unchanged)");
+    }
+    StateStreamStatus::Failed => {
+      let context = state_stream.context.clone().unwrap_or_default();
+      println!("{RED}! {kind} {key}: {context}{RESET}");
+    }
+    _ => {}
+  }
+}
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS "cargo_scale_histories" (
+  "key" UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  "cargo_key" VARCHAR NOT NULL,
+  "node_name" VARCHAR NOT NULL,
+  "previous_replicas" INT NOT NULL,
+  "replicas" INT NOT NULL,
+  "cpu_usage" FLOAT8,
+  "memory_usage" FLOAT8,
+  "reason" VARCHAR NOT NULL
+);
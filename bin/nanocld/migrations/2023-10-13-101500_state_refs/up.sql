@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS "state_refs" (
+  "kind" VARCHAR NOT NULL,
+  "key" VARCHAR NOT NULL,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  "state_name" VARCHAR NOT NULL,
+  PRIMARY KEY ("kind", "key")
+);
+
+CREATE INDEX IF NOT EXISTS "state_refs_state_name_idx" ON "state_refs" ("state_name");
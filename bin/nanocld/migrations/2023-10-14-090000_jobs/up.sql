@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS "jobs" (
+  "key" VARCHAR NOT NULL UNIQUE PRIMARY KEY,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  "updated_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  "data" JSON NOT NULL,
+  "metadata" JSON
+);
+
+CREATE TABLE IF NOT EXISTS "job_runs" (
+  "key" UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  "finished_at" TIMESTAMPTZ,
+  "job_key" VARCHAR NOT NULL,
+  "node_name" VARCHAR NOT NULL,
+  "attempt" INT NOT NULL,
+  "status" VARCHAR NOT NULL,
+  "exit_code" INT,
+  "logs" VARCHAR
+);
+
+CREATE INDEX IF NOT EXISTS "job_runs_job_key_idx" ON "job_runs" ("job_key");
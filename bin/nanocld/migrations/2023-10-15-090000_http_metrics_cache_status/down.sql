@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "http_metrics" DROP COLUMN IF EXISTS "upstream_cache_status";
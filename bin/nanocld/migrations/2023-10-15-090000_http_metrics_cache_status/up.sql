@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE "http_metrics" ADD COLUMN IF NOT EXISTS "upstream_cache_status" VARCHAR;
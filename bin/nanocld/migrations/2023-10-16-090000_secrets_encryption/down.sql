@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- The data of the encrypted secrets is kept encrypted without his key id,
+-- export them with `nanocl secret inspect` before the revert.
+ALTER TABLE "secrets" DROP COLUMN IF EXISTS "key_id";
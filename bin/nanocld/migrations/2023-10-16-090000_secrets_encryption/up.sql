@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE "secrets" ADD COLUMN IF NOT EXISTS "key_id" VARCHAR;
@@ -3,7 +3,7 @@ use nanocl_utils::io_error::{FromIo, IoResult};
 
 use nanocl_stubs::config::DaemonConfig;
 
-use crate::{event, utils};
+use crate::{event, node, utils};
 use crate::models::DaemonState;
 
 use crate::version::VERSION;
@@ -55,18 +55,31 @@ pub async fn init(daemon_conf: &DaemonConfig) -> IoResult<DaemonState> {
     err.map_err_context(|| "Unable to connect to docker daemon")
   })?;
   ensure_state_dir(&daemon_conf.state_dir).await?;
+  utils::secret::ensure_files_dir(&daemon_conf.state_dir).await?;
   let pool = utils::store::init().await?;
   let daemon_state = DaemonState {
     pool: pool.clone(),
     docker_api: docker.clone(),
     config: daemon_conf.to_owned(),
     event_emitter: event::EventEmitter::new(),
+    node_clients: node::NodeClients::spawn(),
+    reconcile_statuses: Default::default(),
+    rollback_statuses: Default::default(),
+    completed_instances: Default::default(),
+    proxy_counters: Default::default(),
+    secret_keys: utils::secret::load_keys(&daemon_conf.secret_key_file, &pool)
+      .await?,
     version: VERSION.to_owned(),
   };
   utils::system::register_namespace("system", false, &daemon_state).await?;
   utils::system::register_namespace("global", true, &daemon_state).await?;
   utils::system::sync_containers(&docker, &pool).await?;
   utils::system::sync_vm_images(daemon_conf, &pool).await?;
+  let count = utils::secret::reencrypt(&daemon_state).await?;
+  if count > 0 {
+    log::info!("Encrypted {count} secrets with the current master key");
+  }
+  utils::secret::sync_files(None, &daemon_state).await?;
 
   Ok(daemon_state)
 }
@@ -95,6 +108,8 @@ mod tests {
       nodes: Vec::default(),
       hostname: None,
       advertise_addr: None,
+      secret_key_file: None,
+      cluster_token_file: None,
     };
     let config = config::init(&args).expect("Expect to init config");
     // test function init
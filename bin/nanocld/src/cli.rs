@@ -34,6 +34,14 @@ pub struct Cli {
   /// Address to advertise to other nodes
   #[clap(long = "advertise-addr")]
   pub(crate) advertise_addr: Option<String>,
+  /// Master key of the secrets shared by the nodes
+  /// [default: {conf_dir}/secret.key]
+  #[clap(long)]
+  pub(crate) secret_key_file: Option<String>,
+  /// Token authenticating the nodes of the cluster, the same on every node
+  /// [default: {conf_dir}/cluster.token]
+  #[clap(long)]
+  pub(crate) cluster_token_file: Option<String>,
   /// Group id
   #[clap(long, default_value = "0")]
   pub(crate) gid: u32,
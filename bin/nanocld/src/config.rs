@@ -62,6 +62,22 @@ fn gen_daemon_conf(
     unix::network::get_hostname()
       .map_err(|err| err.map_err_context(|| "Hostname"))?
   };
+  let secret_key_file = if let Some(ref secret_key_file) = args.secret_key_file
+  {
+    secret_key_file.to_owned()
+  } else if let Some(ref secret_key_file) = config.secret_key_file {
+    secret_key_file.to_owned()
+  } else {
+    format!("{}/secret.key", args.conf_dir)
+  };
+  let cluster_token_file =
+    if let Some(ref cluster_token_file) = args.cluster_token_file {
+      cluster_token_file.to_owned()
+    } else if let Some(ref cluster_token_file) = config.cluster_token_file {
+      cluster_token_file.to_owned()
+    } else {
+      format!("{}/cluster.token", args.conf_dir)
+    };
   let advertise_addr = if let Some(ref advertise_addr) = args.advertise_addr {
     advertise_addr.to_owned()
   } else {
@@ -77,6 +93,8 @@ fn gen_daemon_conf(
     advertise_addr,
     nodes: args.nodes.clone(),
     conf_dir: args.conf_dir.clone(),
+    secret_key_file,
+    cluster_token_file,
   })
 }
 
@@ -154,6 +172,8 @@ mod tests {
       hostname: None,
       advertise_addr: None,
       nodes: Vec::default(),
+      secret_key_file: None,
+      cluster_token_file: None,
     };
     let config = DaemonConfigFile {
       hosts: Some(vec![String::from("unix:///run/nanocl/nanocl.sock")]),
@@ -161,11 +181,15 @@ mod tests {
       docker_host: Some(String::from("/var/run/docker.sock")),
       gateway: None,
       hostname: None,
+      secret_key_file: None,
+      cluster_token_file: None,
     };
     let merged = gen_daemon_conf(&args, &config).unwrap();
     assert_eq!(merged.hosts, args.hosts.unwrap());
     assert_eq!(merged.state_dir, args.state_dir.unwrap());
     assert_eq!(merged.docker_host, args.docker_host.unwrap());
+    assert_eq!(merged.secret_key_file, "/etc/nanocl/secret.key");
+    assert_eq!(merged.cluster_token_file, "/etc/nanocl/cluster.token");
   }
 
   /// Test read config file
@@ -228,6 +252,8 @@ mod tests {
       advertise_addr: None,
       hostname: None,
       nodes: Vec::default(),
+      secret_key_file: None,
+      cluster_token_file: None,
     };
     let config = init(&args).unwrap();
     assert_eq!(config.hosts, args.hosts.unwrap());
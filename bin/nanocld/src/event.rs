@@ -146,6 +146,14 @@ impl EventEmitter {
     Ok(())
   }
 
+  /// Number of clients subscribed to events
+  pub fn subscriber_count(&self) -> usize {
+    match self.inner.lock() {
+      Ok(inner) => inner.clients.len(),
+      Err(_) => 0,
+    }
+  }
+
   /// Subscribe to events
   pub async fn subscribe(&self) -> Result<Client, HttpError> {
     let this = self.clone();
@@ -142,8 +142,12 @@ async fn main() -> std::io::Result<()> {
   }
   set_unix_permission().await;
   node::register(&daemon_state).await?;
-  utils::proxy::spawn_logger(&daemon_state);
+  utils::proxy::spawn_retention(&daemon_state);
   utils::metric::spawn_logger(&daemon_state);
+  utils::metric::spawn_cargo_logger(&daemon_state);
+  utils::cargo::spawn_reconciler(&daemon_state);
+  utils::cargo_autoscale::spawn_autoscaler(&daemon_state);
+  utils::job::spawn_scheduler(&daemon_state);
   match server::gen(daemon_state).await {
     Err(err) => {
       log::error!("Error while generating server {err}");
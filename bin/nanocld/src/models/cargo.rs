@@ -1,3 +1,8 @@
+use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+
+use nanocl_stubs::cargo::{CargoReconcileStatus, CargoRollbackStatus};
+
 use crate::schema::cargoes;
 
 use super::namespace::NamespaceDbModel;
@@ -56,3 +61,27 @@ pub struct CargoRevertPath {
   /// The history id
   pub id: String,
 }
+
+/// ## CargoReconcileStatuses
+///
+/// The status of the last reconciliation of each cargo by key.
+/// Shared between the reconciler and the handlers.
+///
+pub type CargoReconcileStatuses =
+  Arc<Mutex<HashMap<String, CargoReconcileStatus>>>;
+
+/// ## CargoRollbackStatuses
+///
+/// The status of the automatic rollback of the last update of each cargo by key.
+/// Shared between the update watchers and the handlers.
+///
+pub type CargoRollbackStatuses =
+  Arc<Mutex<HashMap<String, CargoRollbackStatus>>>;
+
+/// ## CargoCompletedInstances
+///
+/// The indexes of the auto removed instances of each cargo by key
+/// that exited successfully on the current node.
+/// The reconciler doesn't recreate them.
+///
+pub type CargoCompletedInstances = Arc<Mutex<HashMap<String, HashSet<usize>>>>;
@@ -0,0 +1,48 @@
+use nanocl_stubs::cargo::CargoScaleHistory;
+
+use crate::schema::cargo_scale_histories;
+
+/// ## CargoScaleHistoryDbModel
+///
+/// This structure represent a scaling decision of the autoscaler in the database.
+/// It's used to keep track of the scaling of a cargo.
+///
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable)]
+#[diesel(primary_key(key))]
+#[diesel(table_name = cargo_scale_histories)]
+pub struct CargoScaleHistoryDbModel {
+  /// The key of the scaling
+  pub(crate) key: uuid::Uuid,
+  /// The creation date
+  pub(crate) created_at: chrono::NaiveDateTime,
+  /// The key of the cargo
+  pub(crate) cargo_key: String,
+  /// The node where the cargo has been scaled
+  pub(crate) node_name: String,
+  /// The number of instances before the scaling
+  pub(crate) previous_replicas: i64,
+  /// The number of instances after the scaling
+  pub(crate) replicas: i64,
+  /// The average cpu usage of the instances
+  pub(crate) cpu_usage: Option<f64>,
+  /// The average memory usage of the instances
+  pub(crate) memory_usage: Option<f64>,
+  /// The reason of the scaling
+  pub(crate) reason: String,
+}
+
+impl From<CargoScaleHistoryDbModel> for CargoScaleHistory {
+  fn from(item: CargoScaleHistoryDbModel) -> Self {
+    CargoScaleHistory {
+      key: item.key,
+      created_at: item.created_at,
+      cargo_key: item.cargo_key,
+      node_name: item.node_name,
+      previous_replicas: item.previous_replicas.unsigned_abs() as usize,
+      replicas: item.replicas.unsigned_abs() as usize,
+      cpu_usage: item.cpu_usage,
+      memory_usage: item.memory_usage,
+      reason: item.reason,
+    }
+  }
+}
@@ -1,3 +1,6 @@
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+
 use uuid::Uuid;
 use chrono::{DateTime, FixedOffset};
 use serde::{Serialize, Deserialize, Deserializer};
@@ -108,6 +111,9 @@ pub struct HttpMetricPartial {
   /// The http accept language of the request
   #[serde(deserialize_with = "deserialize_empty_string")]
   pub http_accept_language: Option<String>,
+  /// The cache status of the response like `HIT`, `MISS` or `BYPASS`
+  #[serde(default, deserialize_with = "deserialize_empty_string")]
+  pub upstream_cache_status: Option<String>,
 }
 
 pub trait ToDbModel {
@@ -143,6 +149,7 @@ impl ToDbModel for HttpMetricPartial {
       http_user_agent: self.http_user_agent.clone(),
       http_referrer: self.http_referrer.clone(),
       http_accept_language: self.http_accept_language.clone(),
+      upstream_cache_status: self.upstream_cache_status.clone(),
     }
   }
 }
@@ -208,6 +215,41 @@ pub struct HttpMetricDbModel {
   pub http_referrer: Option<String>,
   /// The http accept language of the request
   pub http_accept_language: Option<String>,
+  /// The cache status of the response
+  pub upstream_cache_status: Option<String>,
+}
+
+/// ## HttpMetricStatsDbModel
+///
+/// This structure represent a row of the http metrics statistics
+/// aggregated by the database for a group in a time bucket.
+///
+#[derive(Clone, Debug, QueryableByName)]
+pub struct HttpMetricStatsDbModel {
+  /// Start of the time bucket
+  #[diesel(sql_type = diesel::sql_types::Timestamptz)]
+  pub bucket: chrono::NaiveDateTime,
+  /// Value of the group
+  #[diesel(sql_type = diesel::sql_types::Varchar)]
+  pub group_key: String,
+  /// Number of requests
+  #[diesel(sql_type = diesel::sql_types::Int8)]
+  pub requests: i64,
+  /// Median of the request time
+  #[diesel(sql_type = diesel::sql_types::Float8)]
+  pub p50: f64,
+  /// 95th percentile of the request time
+  #[diesel(sql_type = diesel::sql_types::Float8)]
+  pub p95: f64,
+  /// 99th percentile of the request time
+  #[diesel(sql_type = diesel::sql_types::Float8)]
+  pub p99: f64,
+  /// Number of requests with a 5xx status
+  #[diesel(sql_type = diesel::sql_types::Int8)]
+  pub errors: i64,
+  /// Number of bytes sent
+  #[diesel(sql_type = diesel::sql_types::Int8)]
+  pub bytes_sent: i64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -293,3 +335,24 @@ impl ToDbModel for StreamMetricPartial {
     }
   }
 }
+
+/// ## ProxyRequestCounter
+///
+/// Number of requests and bytes sent by the proxy
+/// for a host and a status class.
+///
+#[derive(Clone, Debug, Default)]
+pub struct ProxyRequestCounter {
+  /// Number of requests
+  pub requests: u64,
+  /// Number of bytes sent to the clients
+  pub bytes_sent: u64,
+}
+
+/// ## ProxyRequestCounters
+///
+/// The proxy request counters by host and status class
+/// of the http metrics ingested since the daemon started.
+///
+pub type ProxyRequestCounters =
+  Arc<Mutex<HashMap<(String, String), ProxyRequestCounter>>>;
@@ -0,0 +1,117 @@
+use nanocl_utils::io_error::{IoError, FromIo};
+
+use nanocl_stubs::job::{Job, JobPartial, JobRun, JobRunStatus};
+
+use crate::schema::{jobs, job_runs};
+
+/// ## JobDbModel
+///
+/// This structure represent a job in the database.
+/// The config of the job is stored as a json object.
+///
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable)]
+#[diesel(primary_key(key))]
+#[diesel(table_name = jobs)]
+pub struct JobDbModel {
+  /// The name of the job
+  pub(crate) key: String,
+  /// The creation date
+  pub(crate) created_at: chrono::NaiveDateTime,
+  /// The last update date
+  pub(crate) updated_at: chrono::NaiveDateTime,
+  /// The config of the job
+  pub(crate) data: serde_json::Value,
+  /// The metadata (user defined)
+  pub(crate) metadata: Option<serde_json::Value>,
+}
+
+impl JobDbModel {
+  /// Convert the database model to a job
+  pub fn into_job(self) -> Result<Job, IoError> {
+    let config = serde_json::from_value::<JobPartial>(self.data)
+      .map_err(|err| err.map_err_context(|| "Job"))?;
+    Ok(Job {
+      name: self.key,
+      created_at: self.created_at,
+      updated_at: self.updated_at,
+      config,
+    })
+  }
+}
+
+/// ## JobUpdateDbModel
+///
+/// This structure is used to update a job in the database.
+///
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = jobs)]
+pub struct JobUpdateDbModel {
+  /// The last update date
+  pub(crate) updated_at: Option<chrono::NaiveDateTime>,
+  /// The config of the job
+  pub(crate) data: Option<serde_json::Value>,
+  /// The metadata (user defined)
+  pub(crate) metadata: Option<serde_json::Value>,
+}
+
+/// ## JobRunDbModel
+///
+/// This structure represent a run of a job in the database.
+///
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable)]
+#[diesel(primary_key(key))]
+#[diesel(table_name = job_runs)]
+pub struct JobRunDbModel {
+  /// The key of the run
+  pub(crate) key: uuid::Uuid,
+  /// The start date
+  pub(crate) created_at: chrono::NaiveDateTime,
+  /// The end date
+  pub(crate) finished_at: Option<chrono::NaiveDateTime>,
+  /// The name of the job
+  pub(crate) job_key: String,
+  /// The node where the job has been run
+  pub(crate) node_name: String,
+  /// The attempt number, 0 for the first try
+  pub(crate) attempt: i64,
+  /// The status of the run
+  pub(crate) status: String,
+  /// The exit code of the container
+  pub(crate) exit_code: Option<i64>,
+  /// The last lines of the output of the container or the error of the run
+  pub(crate) logs: Option<String>,
+}
+
+/// ## JobRunUpdateDbModel
+///
+/// This structure is used to save the result of a run of a job.
+///
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = job_runs)]
+pub struct JobRunUpdateDbModel {
+  /// The end date
+  pub(crate) finished_at: Option<chrono::NaiveDateTime>,
+  /// The status of the run
+  pub(crate) status: Option<String>,
+  /// The exit code of the container
+  pub(crate) exit_code: Option<i64>,
+  /// The last lines of the output of the container or the error of the run
+  pub(crate) logs: Option<String>,
+}
+
+impl From<JobRunDbModel> for JobRun {
+  fn from(item: JobRunDbModel) -> Self {
+    JobRun {
+      key: item.key,
+      job_name: item.job_key,
+      node_name: item.node_name,
+      created_at: item.created_at,
+      finished_at: item.finished_at,
+      attempt: item.attempt.unsigned_abs() as usize,
+      // Unknown status are considered as failed
+      status: item.status.parse().unwrap_or(JobRunStatus::Failed),
+      exit_code: item.exit_code,
+      logs: item.logs,
+    }
+  }
+}
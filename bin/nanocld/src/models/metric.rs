@@ -23,7 +23,7 @@ pub struct MetricDbModel {
   pub expire_at: chrono::NaiveDateTime,
   /// The node where the metric come from
   pub node_name: String,
-  /// The kind of the metric (CPU, MEMORY, DISK, NETWORK)
+  /// The kind of the metric (CPU, MEMORY, DISK, NETWORK, CARGO)
   pub kind: String,
   /// The data of the metric
   pub data: serde_json::Value,
@@ -36,10 +36,12 @@ pub struct MetricDbModel {
 #[derive(Clone, Debug, Default, Insertable)]
 #[diesel(table_name = metrics)]
 pub struct MetricInsertDbModel {
-  /// The kind of the metric (CPU, MEMORY, DISK, NETWORK)
+  /// The kind of the metric (CPU, MEMORY, DISK, NETWORK, CARGO)
   pub kind: String,
   /// The node where the metric come from
   pub node_name: String,
   /// The data of the metric
   pub data: serde_json::Value,
+  /// When the metric will expire, the default retention if none
+  pub expire_at: Option<chrono::NaiveDateTime>,
 }
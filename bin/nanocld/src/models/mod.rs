@@ -26,6 +26,9 @@ pub use cargo::*;
 mod cargo_config;
 pub use cargo_config::*;
 
+mod cargo_scale_history;
+pub use cargo_scale_history::*;
+
 pub mod vm;
 pub use vm::*;
 
@@ -47,5 +50,8 @@ pub use resource_config::*;
 mod secret;
 pub use secret::*;
 
+mod job;
+pub use job::*;
+
 pub type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 pub type DBConn = PooledConnection<ConnectionManager<PgConnection>>;
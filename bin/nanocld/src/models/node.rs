@@ -1,6 +1,8 @@
 use nanocld_client::NanocldClient;
 use serde::{Serialize, Deserialize};
 
+use nanocl_stubs::cargo::{CargoScale, CargoKillOptions};
+
 use crate::schema::nodes;
 
 /// ## NodeDbModel
@@ -37,3 +39,40 @@ impl NodeDbModel {
     NanocldClient::connect_to(url, None)
   }
 }
+
+/// ## NodeCommand
+///
+/// This enum represent a command sent to a node over the websocket channel.
+/// It's used by the node owning a cargo to manage his instances on the other nodes.
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "Kind", rename_all = "PascalCase")]
+pub enum NodeCommand {
+  /// Create a number of instances of a cargo
+  #[serde(rename_all = "PascalCase")]
+  CargoCreate { key: String, number: usize },
+  /// Start the instances of a cargo
+  #[serde(rename_all = "PascalCase")]
+  CargoStart { key: String },
+  /// Stop the instances of a cargo
+  #[serde(rename_all = "PascalCase")]
+  CargoStop { key: String },
+  /// Replace the instances of a cargo with his current config
+  #[serde(rename_all = "PascalCase")]
+  CargoUpdate { key: String },
+  /// Delete the instances of a cargo
+  #[serde(rename_all = "PascalCase")]
+  CargoDelete { key: String, force: bool },
+  /// Scale the instances of a cargo up or down
+  #[serde(rename_all = "PascalCase")]
+  CargoScale { key: String, options: CargoScale },
+  /// Send a signal to the instances of a cargo
+  #[serde(rename_all = "PascalCase")]
+  CargoKill {
+    key: String,
+    options: CargoKillOptions,
+  },
+  /// Refresh the secret files of a patched secret
+  #[serde(rename_all = "PascalCase")]
+  SecretFilesSync { key: String },
+}
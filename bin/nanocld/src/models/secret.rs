@@ -1,3 +1,6 @@
+use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+
 use serde::{Serialize, Deserialize};
 use nanocl_stubs::secret::{Secret, SecretPartial};
 
@@ -7,7 +10,8 @@ use crate::schema::secrets;
 ///
 /// This structure represent the secret in the database.
 /// A secret is a key/value pair that can be used by the user to store
-/// sensitive data. It is stored as a json object in the database.
+/// sensitive data. It is stored as a json object in the database,
+/// encrypted as a [SecretEnvelope](SecretEnvelope) when it has a key id.
 ///
 #[derive(
   Clone, Serialize, Deserialize, Queryable, Identifiable, Insertable,
@@ -31,6 +35,9 @@ pub struct SecretDbModel {
   // The metadata (user defined)
   #[serde(skip_serializing_if = "Option::is_none")]
   pub(crate) metadata: Option<serde_json::Value>,
+  /// Id of the master key encrypting the data, none if not encrypted
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) key_id: Option<String>,
 }
 
 impl From<SecretPartial> for SecretDbModel {
@@ -43,6 +50,7 @@ impl From<SecretPartial> for SecretDbModel {
       immutable: secret.immutable.unwrap_or(false),
       data: secret.data,
       metadata: secret.metadata,
+      key_id: None,
     }
   }
 }
@@ -77,11 +85,50 @@ impl From<SecretDbModel> for Secret {
 ///
 /// This structure is used to update a secret in the database.
 ///
-#[derive(Debug, Default, AsChangeset)]
+#[derive(Clone, Debug, Default, AsChangeset)]
 #[diesel(table_name = secrets)]
 pub struct SecretUpdateDbModel {
+  /// The last update date
+  pub(crate) updated_at: Option<chrono::NaiveDateTime>,
   /// The secret data
   pub(crate) data: Option<serde_json::Value>,
   // The metadata (user defined)
   pub(crate) metadata: Option<serde_json::Value>,
+  /// Id of the master key encrypting the data
+  pub(crate) key_id: Option<String>,
+}
+
+/// ## SecretEnvelope
+///
+/// This structure represent the encrypted data of a secret.
+/// The data is encrypted with a random data key
+/// and the data key is encrypted with the master key.
+/// Both are base64 encoded with their nonce and tag.
+///
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SecretEnvelope {
+  /// The data key encrypted with the master key
+  pub(crate) key: String,
+  /// The data encrypted with the data key
+  pub(crate) data: String,
 }
+
+/// ## SecretKeys
+///
+/// This structure represent the master keys encrypting the data keys.
+/// The previous keys are kept to decrypt the secrets
+/// not re-encrypted yet during a rotation.
+///
+#[derive(Debug, Default)]
+pub struct SecretKeys {
+  /// Id of the key used to encrypt
+  pub(crate) current: String,
+  /// The keys by id
+  pub(crate) keys: HashMap<String, Vec<u8>>,
+  /// Path of the key file, none if the key is set by the environment
+  pub(crate) path: Option<String>,
+}
+
+/// Master keys of the secrets shared between the workers
+pub type SecretKeyring = Arc<RwLock<SecretKeys>>;
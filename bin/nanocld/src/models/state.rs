@@ -1,11 +1,17 @@
 use nanocl_stubs::config::DaemonConfig;
 use nanocl_stubs::state::{
   StateDeployment, StateCargo, StateResource, StateVirtualMachine, StateSecret,
+  StateJob,
 };
 
+use crate::schema::state_refs;
 use crate::event::EventEmitter;
+use crate::node::NodeClientsSender;
 
-use super::Pool;
+use super::{
+  Pool, CargoReconcileStatuses, CargoRollbackStatuses, CargoCompletedInstances,
+  ProxyRequestCounters, SecretKeyring,
+};
 
 /// ## DaemonState
 ///
@@ -23,6 +29,18 @@ pub struct DaemonState {
   pub(crate) config: DaemonConfig,
   /// The event emitter
   pub(crate) event_emitter: EventEmitter,
+  /// The sender used to communicate with the other nodes of the cluster
+  pub(crate) node_clients: NodeClientsSender,
+  /// The status of the last reconciliation of each cargo
+  pub(crate) reconcile_statuses: CargoReconcileStatuses,
+  /// The status of the automatic rollback of the last update of each cargo
+  pub(crate) rollback_statuses: CargoRollbackStatuses,
+  /// The auto removed instances of each cargo that exited successfully
+  pub(crate) completed_instances: CargoCompletedInstances,
+  /// The proxy request counters exported in the open metrics
+  pub(crate) proxy_counters: ProxyRequestCounters,
+  /// The master keys encrypting the secrets
+  pub(crate) secret_keys: SecretKeyring,
   /// Latest version of the daemon or version of current request
   #[allow(dead_code)]
   pub(crate) version: String,
@@ -39,4 +57,25 @@ pub enum StateData {
   VirtualMachine(StateVirtualMachine),
   Resource(StateResource),
   Secret(StateSecret),
+  Job(StateJob),
+}
+
+/// ## StateRefDbModel
+///
+/// This structure represent the link between an object (Cargo, VirtualMachine, Resource, Secret)
+/// and the `Statefile` that applied it in the database.
+/// It's used to prune the objects no longer declared in the `Statefile`.
+///
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable)]
+#[diesel(primary_key(kind, key))]
+#[diesel(table_name = state_refs)]
+pub struct StateRefDbModel {
+  /// The kind of the object (Cargo, VirtualMachine, Resource, Secret)
+  pub(crate) kind: String,
+  /// The key of the object
+  pub(crate) key: String,
+  /// The creation date
+  pub(crate) created_at: chrono::NaiveDateTime,
+  /// The name of the `Statefile` that applied the object
+  pub(crate) state_name: String,
 }
@@ -15,15 +15,14 @@ use futures::channel::mpsc;
 
 use nanocl_utils::io_error::IoResult;
 use nanocl_utils::http_error::HttpError;
-use nanocl_stubs::config::DaemonConfig;
 
-use crate::repositories;
+use crate::{utils, repositories};
 use crate::version::VERSION;
 use crate::models::{DaemonState, NodeDbModel};
 
 #[derive(Clone)]
 pub struct NodeMessage {
-  data: String,
+  pub(crate) data: String,
 }
 
 #[derive(Debug, Clone)]
@@ -38,9 +37,13 @@ impl NodeClient {
     }
   }
 
-  pub async fn connect(&self) -> Result<WsConnection<Base>, HttpError> {
+  pub async fn connect(
+    &self,
+    token: &str,
+  ) -> Result<WsConnection<Base>, HttpError> {
     let url = format!("http://{}/{VERSION}/nodes/ws", self.ip_addr);
     let con = ws::WsClient::build(url)
+      .header(utils::node::NODE_AUTH_HEADER, token)
       .finish()
       .map_err(|err| HttpError {
         msg: format!("Failed to build websocket connection: {}", err),
@@ -70,6 +73,9 @@ pub enum NodeClientsMessage {
   },
 }
 
+/// Sender used to send messages to the connected nodes
+pub type NodeClientsSender = mpsc::UnboundedSender<NodeClientsMessage>;
+
 #[derive(Default)]
 pub struct NodeClients {
   sessions: HashMap<String, mpsc::UnboundedSender<NodeMessage>>,
@@ -81,14 +87,15 @@ impl NodeClients {
       NodeClientsMessage::Connect { node_id, sender } => {
         self.sessions.insert(node_id, sender);
       }
-      NodeClientsMessage::SendMessage { node_id: _, msg } => {
-        for sender in self.sessions.values() {
-          let mut sender = sender.clone();
-          let msg = msg.clone();
-          rt::spawn(async move {
-            let _ = sender.send(msg).await;
-          });
-        }
+      NodeClientsMessage::SendMessage { node_id, msg } => {
+        let Some(sender) = self.sessions.get(&node_id) else {
+          log::warn!("Unable to send message to node {node_id}: not connected");
+          return;
+        };
+        let mut sender = sender.clone();
+        rt::spawn(async move {
+          let _ = sender.send(msg).await;
+        });
       }
       #[allow(unused_variables)]
       NodeClientsMessage::ReceiveMessage { msg } => {
@@ -96,6 +103,28 @@ impl NodeClients {
       }
     }
   }
+
+  /// ## Spawn
+  ///
+  /// Spawn the node clients in a new arbiter
+  ///
+  /// ## Returns
+  ///
+  /// - [sender](NodeClientsSender) - The sender to communicate with the node clients
+  ///
+  pub fn spawn() -> NodeClientsSender {
+    let (tx, mut rx) = mpsc::unbounded();
+    let mut clients = NodeClients::default();
+    rt::Arbiter::new().exec_fn(move || {
+      rt::spawn(async move {
+        while let Some(msg) = rx.next().await {
+          clients.handle(msg);
+        }
+        rt::Arbiter::current().stop();
+      });
+    });
+    tx
+  }
 }
 
 /// Handle messages from chat server, we simply send it to the peer websocket connection
@@ -108,17 +137,20 @@ async fn messages(
   }
 }
 
-pub fn watch_node(
-  daemon_conf: &DaemonConfig,
-  node: &NodeDbModel,
-  mut srv: mpsc::UnboundedSender<NodeClientsMessage>,
-) {
+pub fn watch_node(state: &DaemonState, node: &NodeDbModel) {
   let node = node.clone();
-  let daemon_conf = daemon_conf.clone();
+  let state = state.clone();
+  let daemon_conf = state.config.clone();
+  let mut srv = state.node_clients.clone();
   rt::spawn(async move {
     loop {
       let client = NodeClient::new(&node.ip_address);
-      match client.connect().await {
+      // A new token is signed for each attempt as it expire
+      let res = match utils::node::gen_auth_token(&state) {
+        Ok(token) => client.connect(&token).await,
+        Err(err) => Err(err),
+      };
+      match res {
         Ok(con) => {
           // start heartbeat task
           log::info!(
@@ -221,23 +253,12 @@ pub async fn register(daemon_state: &DaemonState) -> IoResult<()> {
 }
 
 pub async fn join_cluster(state: &DaemonState) -> IoResult<()> {
-  let state = state.clone();
-  let (tx, mut rx) = mpsc::unbounded();
-  let mut clients = NodeClients::default();
-  rt::Arbiter::new().exec_fn(move || {
-    rt::spawn(async move {
-      while let Some(msg) = rx.next().await {
-        clients.handle(msg);
-      }
-      rt::Arbiter::current().stop();
-    });
-  });
   let nodes =
     repositories::node::list_unless(&state.config.hostname, &state.pool)
       .await?;
   for node in nodes {
     log::info!("Connecting to node {} at {}", node.name, node.ip_address);
-    watch_node(&state.config, &node, tx.clone());
+    watch_node(state, &node);
   }
   Ok(())
 }
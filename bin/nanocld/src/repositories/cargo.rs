@@ -205,6 +205,35 @@ pub async fn find_by_key(key: &str, pool: &Pool) -> IoResult<CargoDbModel> {
   Ok(item)
 }
 
+/// ## List
+///
+/// List all cargoes in database
+///
+/// ## Arguments
+///
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<CargoDbModel>) - The cargoes found
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list(pool: &Pool) -> IoResult<Vec<CargoDbModel>> {
+  use crate::schema::cargoes::dsl;
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = dsl::cargoes
+      .order(dsl::created_at.desc())
+      .get_results(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Cargo"))?;
+    Ok::<_, IoError>(items)
+  })
+  .await?;
+  Ok(items)
+}
+
 /// ## Update by key
 ///
 /// Update a cargo item in database for given key
@@ -332,6 +361,13 @@ pub async fn inspect_by_key(key: &str, pool: &Pool) -> IoResult<Cargo> {
     container: config.container,
     metadata: config.metadata,
     secrets: config.secrets,
+    update_strategy: config.update_strategy,
+    rollback: config.rollback,
+    autoscale: config.autoscale,
+    depends_on: config.depends_on,
+    init_containers: config.init_containers,
+    sidecars: config.sidecars,
+    secret_files: config.secret_files,
   };
   let item = Cargo {
     key: item.0.key,
@@ -63,6 +63,13 @@ pub async fn create(
     container: item.container.clone(),
     metadata: item.metadata.clone(),
     secrets: item.secrets.clone(),
+    update_strategy: item.update_strategy.clone(),
+    rollback: item.rollback.clone(),
+    autoscale: item.autoscale.clone(),
+    depends_on: item.depends_on.clone(),
+    init_containers: item.init_containers.clone(),
+    sidecars: item.sidecars.clone(),
+    secret_files: item.secret_files.clone(),
   };
   Ok(config)
 }
@@ -110,6 +117,13 @@ pub async fn find_by_key(
     container: config.container,
     metadata: config.metadata,
     secrets: config.secrets,
+    update_strategy: config.update_strategy,
+    rollback: config.rollback,
+    autoscale: config.autoscale,
+    depends_on: config.depends_on,
+    init_containers: config.init_containers,
+    sidecars: config.sidecars,
+    secret_files: config.secret_files,
   })
 }
 
@@ -195,6 +209,13 @@ pub async fn list_by_cargo_key(
         container: config.container,
         metadata: config.metadata,
         secrets: config.secrets,
+        update_strategy: config.update_strategy,
+        rollback: config.rollback,
+        autoscale: config.autoscale,
+        depends_on: config.depends_on,
+        init_containers: config.init_containers,
+        sidecars: config.sidecars,
+        secret_files: config.secret_files,
       })
     })
     .collect::<Result<Vec<CargoConfig>, IoError>>()?;
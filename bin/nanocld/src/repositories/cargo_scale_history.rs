@@ -0,0 +1,153 @@
+use ntex::web;
+use diesel::prelude::*;
+
+use nanocl_utils::io_error::{IoError, FromIo, IoResult};
+
+use nanocl_stubs::generic::GenericDelete;
+use nanocl_stubs::cargo::CargoScaleHistory;
+
+use crate::utils;
+use crate::models::{Pool, CargoScaleHistoryDbModel};
+
+/// ## Create
+///
+/// Create a new cargo scale history item in database
+///
+/// ## Arguments
+///
+/// - [item](CargoScaleHistoryDbModel) - Cargo scale history item
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](CargoScaleHistory) - The created cargo scale history
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn create(
+  item: &CargoScaleHistoryDbModel,
+  pool: &Pool,
+) -> IoResult<CargoScaleHistory> {
+  use crate::schema::cargo_scale_histories::dsl;
+  let item = item.clone();
+  let pool = pool.clone();
+  let item = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let item = diesel::insert_into(dsl::cargo_scale_histories)
+      .values(item)
+      .get_result::<CargoScaleHistoryDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "CargoScaleHistory"))?;
+    Ok::<_, IoError>(item)
+  })
+  .await?;
+  Ok(item.into())
+}
+
+/// ## List by cargo key
+///
+/// List the scale histories of a cargo in database, the most recent first
+///
+/// ## Arguments
+///
+/// - [key](str) - Cargo key
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<CargoScaleHistory>) - The list of cargo scale histories
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list_by_cargo_key(
+  key: &str,
+  pool: &Pool,
+) -> IoResult<Vec<CargoScaleHistory>> {
+  use crate::schema::cargo_scale_histories::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = dsl::cargo_scale_histories
+      .filter(dsl::cargo_key.eq(key))
+      .order(dsl::created_at.desc())
+      .load::<CargoScaleHistoryDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "CargoScaleHistory"))?;
+    Ok::<_, IoError>(items)
+  })
+  .await?;
+  Ok(items.into_iter().map(|item| item.into()).collect())
+}
+
+/// ## Find last by cargo key
+///
+/// Find the most recent scale history of a cargo on a node in database
+///
+/// ## Arguments
+///
+/// - [key](str) - Cargo key
+/// - [node_name](str) - Node name
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Option<CargoScaleHistory>) - The last cargo scale history if any
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn find_last_by_cargo_key(
+  key: &str,
+  node_name: &str,
+  pool: &Pool,
+) -> IoResult<Option<CargoScaleHistory>> {
+  use crate::schema::cargo_scale_histories::dsl;
+  let key = key.to_owned();
+  let node_name = node_name.to_owned();
+  let pool = pool.clone();
+  let item = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let item = dsl::cargo_scale_histories
+      .filter(dsl::cargo_key.eq(key))
+      .filter(dsl::node_name.eq(node_name))
+      .order(dsl::created_at.desc())
+      .first::<CargoScaleHistoryDbModel>(&mut conn)
+      .optional()
+      .map_err(|err| err.map_err_context(|| "CargoScaleHistory"))?;
+    Ok::<_, IoError>(item)
+  })
+  .await?;
+  Ok(item.map(|item| item.into()))
+}
+
+/// ## Delete by cargo key
+///
+/// Delete all scale histories of a cargo in database
+///
+/// ## Arguments
+///
+/// - [key](str) - Cargo key
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](GenericDelete) - The number of deleted items
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn delete_by_cargo_key(
+  key: &str,
+  pool: &Pool,
+) -> IoResult<GenericDelete> {
+  use crate::schema::cargo_scale_histories::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let res = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let res = diesel::delete(dsl::cargo_scale_histories)
+      .filter(dsl::cargo_key.eq(key))
+      .execute(&mut conn)
+      .map_err(|err| err.map_err_context(|| "CargoScaleHistory"))?;
+    Ok::<_, IoError>(res)
+  })
+  .await?;
+  Ok(GenericDelete { count: res })
+}
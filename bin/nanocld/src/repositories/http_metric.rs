@@ -1,89 +1,60 @@
 use ntex::web;
-use diesel::{
-  prelude::*,
-  associations::HasTable,
-  query_builder::{InsertStatement, AsQuery},
-  query_dsl::LoadQuery,
-};
+use diesel::prelude::*;
 
 use nanocl_utils::io_error::{IoError, FromIo, IoResult};
 
 use nanocl_stubs::generic::GenericCount;
-use nanocl_stubs::http_metric::{HttpMetricListQuery, HttpMetricCountQuery};
+use nanocl_stubs::http_metric::{
+  HttpMetricListQuery, HttpMetricCountQuery, HttpMetricStatsQuery,
+  HttpMetricGroupBy, HttpMetricStats,
+};
 
 use crate::utils;
-use crate::models::{Pool, HttpMetricDbModel};
+use crate::models::{
+  Pool, HttpMetricDbModel, StreamMetricDbModel, HttpMetricStatsDbModel,
+};
 
-/// ## Create
+/// ## Create batch
 ///
-/// Create a new http metric item in database
+/// Create http and stream metrics in database in a single transaction
+/// so a batch is either fully saved or not saved at all
 ///
 /// ## Arguments
 ///
-/// - [item](HttpMetricDbModel) - Http metric item
+/// - [http](Vec<HttpMetricDbModel>) - Http metric items
+/// - [stream](Vec<StreamMetricDbModel>) - Stream metric items
 /// - [pool](Pool) - Database connection pool
 ///
 /// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](HttpMetricDbModel) - The created http metric item
+///   - [Ok](()) - The metrics have been created
 ///   - [Err](IoError) - Error during the operation
 ///
-pub async fn create(
-  item: &HttpMetricDbModel,
+pub async fn create_batch(
+  http: Vec<HttpMetricDbModel>,
+  stream: Vec<StreamMetricDbModel>,
   pool: &Pool,
-) -> IoResult<HttpMetricDbModel> {
-  use crate::schema::http_metrics::dsl;
-  let item = item.clone();
+) -> IoResult<()> {
+  use crate::schema::{http_metrics, stream_metrics};
   let pool = pool.clone();
-  let item = web::block(move || {
+  web::block(move || {
     let mut conn = utils::store::get_pool_conn(&pool)?;
-    let res = diesel::insert_into(dsl::http_metrics)
-      .values(item)
-      .get_result(&mut conn)
+    conn
+      .transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::insert_into(http_metrics::table)
+          .values(&http)
+          .execute(conn)?;
+        diesel::insert_into(stream_metrics::table)
+          .values(&stream)
+          .execute(conn)?;
+        Ok(())
+      })
       .map_err(|err| err.map_err_context(|| "HttpMetric"))?;
-    Ok::<_, IoError>(res)
+    Ok::<_, IoError>(())
   })
   .await?;
-  Ok(item)
-}
-
-/// ## Create
-///
-/// Create a new entry in database
-///
-/// ## Arguments
-///
-/// - [item] The database entity that must be inserted into database
-/// - [pool](Pool) - Database connection pool
-///
-/// ## Returns
-///
-/// - [Result](Result) - The result of the operation
-///   - [Ok](item) - The inserted database item
-///   - [Err](IoError) - Error during the operation
-///
-pub async fn generic_insert<T>(item: T, pool: &Pool) -> IoResult<T>
-where
-  T: Send + HasTable + 'static,
-  T: diesel::Insertable<<T as diesel::associations::HasTable>::Table>,
-  InsertStatement<
-    <T as HasTable>::Table,
-    <T as diesel::Insertable<<T as HasTable>::Table>>::Values,
-  >: AsQuery + LoadQuery<'static, PgConnection, T> + Send,
-{
-  let pool = pool.clone();
-  let item = web::block(move || {
-    let table_name = <T as HasTable>::table();
-    let mut conn = utils::store::get_pool_conn(&pool)?;
-    let res = diesel::insert_into(table_name)
-      .values(item)
-      .get_result(&mut conn)
-      .map_err(|err| err.map_err_context(|| "HttpMetric"))?;
-    Ok::<_, IoError>(res)
-  })
-  .await?;
-  Ok(item)
+  Ok(())
 }
 
 /// ## List
@@ -160,6 +131,9 @@ pub async fn count(
         query = query.filter(dsl::status.eq(status.0));
       }
     }
+    if let Some(cache_status) = filter.cache_status {
+      query = query.filter(dsl::upstream_cache_status.eq(cache_status));
+    }
     let res = query
       .count()
       .get_result(&mut conn)
@@ -170,3 +144,132 @@ pub async fn count(
   .await?;
   Ok(count)
 }
+
+/// ## Stats
+///
+/// Aggregate the http metrics not expired yet by group and time bucket.
+/// The request time percentiles are computed by the database.
+///
+/// ## Arguments
+///
+/// - [filter](HttpMetricStatsQuery) - Http metric stats filter
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<HttpMetricStats>) - The statistics ordered by bucket and group
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn stats(
+  filter: &HttpMetricStatsQuery,
+  pool: &Pool,
+) -> IoResult<Vec<HttpMetricStats>> {
+  use diesel::sql_types::{Int8, Timestamptz};
+  let bucket = filter.bucket.unwrap_or(3600);
+  let since = filter.since.unwrap_or(86400);
+  if bucket < 1 || since < 1 {
+    return Err(IoError::invalid_data(
+      "HttpMetricStatsQuery",
+      "Bucket and Since must be greater than 0",
+    ));
+  }
+  let uri_depth = filter.uri_depth.unwrap_or(1);
+  if !(1..=10).contains(&uri_depth) {
+    return Err(IoError::invalid_data(
+      "HttpMetricStatsQuery",
+      "UriDepth must be between 1 and 10",
+    ));
+  }
+  // Only known expressions are formatted in the query
+  let group_key = match filter.group_by.clone().unwrap_or_default() {
+    HttpMetricGroupBy::Host => "host".to_owned(),
+    HttpMetricGroupBy::UriPrefix => {
+      format!("substring(uri from '^(?:/[^/?]*){{1,{uri_depth}}}')")
+    }
+    HttpMetricGroupBy::Upstream => "coalesce(upstream_addr, '')".to_owned(),
+    // Integer division return a decimal on cockroachdb
+    HttpMetricGroupBy::StatusClass => {
+      "floor(status / 100)::INT::text || 'xx'".to_owned()
+    }
+  };
+  let query = format!(
+    "SELECT
+      to_timestamp(floor(extract(epoch FROM date_gmt) / $1::FLOAT8) * $1::FLOAT8)
+        AS bucket,
+      coalesce({group_key}, '') AS group_key,
+      count(*) AS requests,
+      percentile_cont(0.5) WITHIN GROUP (ORDER BY request_time) AS p50,
+      percentile_cont(0.95) WITHIN GROUP (ORDER BY request_time) AS p95,
+      percentile_cont(0.99) WITHIN GROUP (ORDER BY request_time) AS p99,
+      count(*) FILTER (WHERE status >= 500) AS errors,
+      coalesce(sum(bytes_sent), 0)::bigint AS bytes_sent
+    FROM http_metrics
+    WHERE date_gmt >= $2 AND expire_at > now()
+    GROUP BY 1, 2
+    ORDER BY 1, 2"
+  );
+  let from = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(since);
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let res = diesel::sql_query(query)
+      .bind::<Int8, _>(bucket)
+      .bind::<Timestamptz, _>(from)
+      .load::<HttpMetricStatsDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "HttpMetric"))?;
+    Ok::<_, IoError>(res)
+  })
+  .await?;
+  let items = items
+    .into_iter()
+    .map(|item| HttpMetricStats {
+      bucket: item.bucket,
+      group: item.group_key,
+      requests: item.requests,
+      request_rate: item.requests as f64 / bucket as f64,
+      p50: item.p50,
+      p95: item.p95,
+      p99: item.p99,
+      error_ratio: item.errors as f64 / item.requests.max(1) as f64,
+      bytes_sent: item.bytes_sent,
+    })
+    .collect();
+  Ok(items)
+}
+
+/// ## Delete expired
+///
+/// Delete the http and stream metrics past their expiration date
+///
+/// ## Arguments
+///
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](usize) - The number of deleted metrics
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn delete_expired(pool: &Pool) -> IoResult<usize> {
+  use crate::schema::{http_metrics, stream_metrics};
+  let pool = pool.clone();
+  let count = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let now = chrono::Utc::now().naive_utc();
+    let http = diesel::delete(
+      http_metrics::table.filter(http_metrics::expire_at.le(now)),
+    )
+    .execute(&mut conn)
+    .map_err(|err| err.map_err_context(|| "HttpMetric"))?;
+    let stream = diesel::delete(
+      stream_metrics::table.filter(stream_metrics::expire_at.le(now)),
+    )
+    .execute(&mut conn)
+    .map_err(|err| err.map_err_context(|| "StreamMetric"))?;
+    Ok::<_, IoError>(http + stream)
+  })
+  .await?;
+  Ok(count)
+}
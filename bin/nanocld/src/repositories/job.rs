@@ -0,0 +1,185 @@
+//! Repository to manage jobs in database
+//! We can create delete list update or inspect a job
+
+use ntex::web;
+use diesel::prelude::*;
+
+use nanocl_utils::io_error::{IoError, FromIo, IoResult};
+
+use nanocl_stubs::generic::GenericDelete;
+use nanocl_stubs::job::{Job, JobPartial};
+
+use crate::utils;
+use crate::models::{Pool, JobDbModel, JobUpdateDbModel};
+
+/// ## Create
+///
+/// Create a job in database
+///
+/// ## Arguments
+///
+/// - [item](JobPartial) - Job to create
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Job) - Job created
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn create(item: &JobPartial, pool: &Pool) -> IoResult<Job> {
+  use crate::schema::jobs::dsl;
+  let pool = pool.clone();
+  let item = JobDbModel {
+    key: item.name.clone(),
+    created_at: chrono::Utc::now().naive_utc(),
+    updated_at: chrono::Utc::now().naive_utc(),
+    data: serde_json::to_value(item)
+      .map_err(|err| err.map_err_context(|| "Job"))?,
+    metadata: item.metadata.clone(),
+  };
+  let item = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    diesel::insert_into(dsl::jobs)
+      .values(&item)
+      .execute(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Job"))?;
+    Ok::<_, IoError>(item)
+  })
+  .await?;
+  item.into_job()
+}
+
+/// ## List
+///
+/// List all jobs in database
+///
+/// ## Arguments
+///
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<Job>) - List of jobs
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list(pool: &Pool) -> IoResult<Vec<Job>> {
+  use crate::schema::jobs::dsl;
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = dsl::jobs
+      .order(dsl::created_at.desc())
+      .load::<JobDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Job"))?;
+    Ok::<_, IoError>(items)
+  })
+  .await?;
+  items.into_iter().map(JobDbModel::into_job).collect()
+}
+
+/// ## Find by key
+///
+/// Find a job by his name in database
+///
+/// ## Arguments
+///
+/// - [key](str) - Name of the job to find
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Job) - Job found
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn find_by_key(key: &str, pool: &Pool) -> IoResult<Job> {
+  use crate::schema::jobs::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let item = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let item = dsl::jobs
+      .filter(dsl::key.eq(key))
+      .get_result::<JobDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Job"))?;
+    Ok::<_, IoError>(item)
+  })
+  .await?;
+  item.into_job()
+}
+
+/// ## Update by key
+///
+/// Replace the config of a job in database
+///
+/// ## Arguments
+///
+/// - [key](str) - Name of the job
+/// - [item](JobPartial) - New config of the job
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Job) - The job updated
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn update_by_key(
+  key: &str,
+  item: &JobPartial,
+  pool: &Pool,
+) -> IoResult<Job> {
+  use crate::schema::jobs::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let new_item = JobUpdateDbModel {
+    updated_at: Some(chrono::Utc::now().naive_utc()),
+    data: Some(
+      serde_json::to_value(item)
+        .map_err(|err| err.map_err_context(|| "Job"))?,
+    ),
+    metadata: item.metadata.clone(),
+  };
+  let item = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let item = diesel::update(dsl::jobs.filter(dsl::key.eq(key)))
+      .set(&new_item)
+      .get_result::<JobDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Job"))?;
+    Ok::<_, IoError>(item)
+  })
+  .await?;
+  item.into_job()
+}
+
+/// ## Delete by key
+///
+/// Delete a job by his name in database
+///
+/// ## Arguments
+///
+/// - [key](str) - Name of the job to delete
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](GenericDelete) - Number of deleted jobs
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn delete_by_key(key: &str, pool: &Pool) -> IoResult<GenericDelete> {
+  use crate::schema::jobs::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let count = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let count = diesel::delete(dsl::jobs.filter(dsl::key.eq(key)))
+      .execute(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Job"))?;
+    Ok::<_, IoError>(count)
+  })
+  .await?;
+  Ok(GenericDelete { count })
+}
@@ -0,0 +1,229 @@
+use ntex::web;
+use diesel::prelude::*;
+
+use nanocl_utils::io_error::{IoError, FromIo, IoResult};
+
+use nanocl_stubs::generic::GenericDelete;
+use nanocl_stubs::job::{JobRun, JobRunStatus};
+
+use crate::utils;
+use crate::models::{Pool, JobRunDbModel, JobRunUpdateDbModel};
+
+/// ## Create
+///
+/// Create a new job run item in database
+///
+/// ## Arguments
+///
+/// - [item](JobRunDbModel) - Job run item
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](JobRun) - The created job run
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn create(item: &JobRunDbModel, pool: &Pool) -> IoResult<JobRun> {
+  use crate::schema::job_runs::dsl;
+  let item = item.clone();
+  let pool = pool.clone();
+  let item = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let item = diesel::insert_into(dsl::job_runs)
+      .values(item)
+      .get_result::<JobRunDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "JobRun"))?;
+    Ok::<_, IoError>(item)
+  })
+  .await?;
+  Ok(item.into())
+}
+
+/// ## Update by key
+///
+/// Save the result of a job run in database
+///
+/// ## Arguments
+///
+/// - [key](uuid::Uuid) - Job run key
+/// - [item](JobRunUpdateDbModel) - The result of the run
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](JobRun) - The updated job run
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn update_by_key(
+  key: &uuid::Uuid,
+  item: JobRunUpdateDbModel,
+  pool: &Pool,
+) -> IoResult<JobRun> {
+  use crate::schema::job_runs::dsl;
+  let key = *key;
+  let pool = pool.clone();
+  let item = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let item = diesel::update(dsl::job_runs.filter(dsl::key.eq(key)))
+      .set(&item)
+      .get_result::<JobRunDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "JobRun"))?;
+    Ok::<_, IoError>(item)
+  })
+  .await?;
+  Ok(item.into())
+}
+
+/// ## List by job key
+///
+/// List the runs of a job in database, the most recent first
+///
+/// ## Arguments
+///
+/// - [key](str) - Job name
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<JobRun>) - The list of job runs
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list_by_job_key(key: &str, pool: &Pool) -> IoResult<Vec<JobRun>> {
+  use crate::schema::job_runs::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = dsl::job_runs
+      .filter(dsl::job_key.eq(key))
+      .order(dsl::created_at.desc())
+      .load::<JobRunDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "JobRun"))?;
+    Ok::<_, IoError>(items)
+  })
+  .await?;
+  Ok(items.into_iter().map(|item| item.into()).collect())
+}
+
+/// ## Delete by job key
+///
+/// Delete all runs of a job in database
+///
+/// ## Arguments
+///
+/// - [key](str) - Job name
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](GenericDelete) - The number of deleted items
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn delete_by_job_key(
+  key: &str,
+  pool: &Pool,
+) -> IoResult<GenericDelete> {
+  use crate::schema::job_runs::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let res = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let res = diesel::delete(dsl::job_runs)
+      .filter(dsl::job_key.eq(key))
+      .execute(&mut conn)
+      .map_err(|err| err.map_err_context(|| "JobRun"))?;
+    Ok::<_, IoError>(res)
+  })
+  .await?;
+  Ok(GenericDelete { count: res })
+}
+
+/// ## Delete old by job key
+///
+/// Delete the runs of a job in database except the most recent ones
+///
+/// ## Arguments
+///
+/// - [key](str) - Job name
+/// - [keep](usize) - Number of runs to keep
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](GenericDelete) - The number of deleted items
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn delete_old_by_job_key(
+  key: &str,
+  keep: usize,
+  pool: &Pool,
+) -> IoResult<GenericDelete> {
+  use crate::schema::job_runs::dsl;
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let res = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let olds = dsl::job_runs
+      .select(dsl::key)
+      .filter(dsl::job_key.eq(&key))
+      .order(dsl::created_at.desc())
+      .offset(keep as i64)
+      .load::<uuid::Uuid>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "JobRun"))?;
+    let res = diesel::delete(dsl::job_runs)
+      .filter(dsl::key.eq_any(olds))
+      .execute(&mut conn)
+      .map_err(|err| err.map_err_context(|| "JobRun"))?;
+    Ok::<_, IoError>(res)
+  })
+  .await?;
+  Ok(GenericDelete { count: res })
+}
+
+/// ## Fail running by node name
+///
+/// Mark the runs of a node that are still running as failed.
+/// Used when the daemon start since the runs have been interrupted.
+///
+/// ## Arguments
+///
+/// - [node_name](str) - Node name
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](usize) - The number of updated items
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn fail_running_by_node_name(
+  node_name: &str,
+  pool: &Pool,
+) -> IoResult<usize> {
+  use crate::schema::job_runs::dsl;
+  let node_name = node_name.to_owned();
+  let pool = pool.clone();
+  let res = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let item = JobRunUpdateDbModel {
+      finished_at: Some(chrono::Utc::now().naive_utc()),
+      status: Some(JobRunStatus::Failed.to_string()),
+      exit_code: None,
+      logs: Some("Interrupted by a restart of the daemon".into()),
+    };
+    let res = diesel::update(dsl::job_runs)
+      .filter(dsl::node_name.eq(node_name))
+      .filter(dsl::status.eq(JobRunStatus::Running.to_string()))
+      .set(&item)
+      .execute(&mut conn)
+      .map_err(|err| err.map_err_context(|| "JobRun"))?;
+    Ok::<_, IoError>(res)
+  })
+  .await?;
+  Ok(res)
+}
@@ -80,3 +80,46 @@ pub async fn list_by_kind(
   .await?;
   Ok(items)
 }
+
+/// ## List by node kind since
+///
+/// List the metrics of a node with given kind created since a date,
+/// the most recent first
+///
+/// ## Arguments
+///
+/// - [node_name](str) - Node name
+/// - [kind](str) - Metric kind
+/// - [since](chrono::NaiveDateTime) - Date of the oldest metric
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<MetricDbModel>) - The list of metrics
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list_by_node_kind_since(
+  node_name: &str,
+  kind: &str,
+  since: chrono::NaiveDateTime,
+  pool: &Pool,
+) -> IoResult<Vec<MetricDbModel>> {
+  use crate::schema::metrics::dsl;
+  let node_name = node_name.to_owned();
+  let kind = kind.to_owned();
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let res = dsl::metrics
+      .filter(dsl::node_name.eq(node_name))
+      .filter(dsl::kind.eq(kind))
+      .filter(dsl::created_at.ge(since))
+      .order(dsl::created_at.desc())
+      .load::<MetricDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Metric"))?;
+    Ok::<_, IoError>(res)
+  })
+  .await?;
+  Ok(items)
+}
@@ -11,6 +11,8 @@ pub mod namespace;
 pub mod cargo;
 /// Manage cargo_configs table
 pub mod cargo_config;
+/// Manage cargo_scale_histories table
+pub mod cargo_scale_history;
 /// Manage vms table
 pub mod vm;
 /// Manage vm_configs table
@@ -25,3 +27,9 @@ pub mod resource_kind;
 pub mod resource_config;
 /// Manage secrets table
 pub mod secret;
+/// Manage state_refs table
+pub mod state_ref;
+/// Manage jobs table
+pub mod job;
+/// Manage job_runs table
+pub mod job_run;
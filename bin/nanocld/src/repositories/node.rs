@@ -157,3 +157,77 @@ pub async fn list_unless(
   .await?;
   Ok(items)
 }
+
+/// ## List by names
+///
+/// List the nodes matching the given names in database
+///
+/// ## Arguments
+///
+/// - [names](Vec<String>) - Node names
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<NodeDbModel>) - The list of node items
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list_by_names(
+  names: &[String],
+  pool: &Pool,
+) -> IoResult<Vec<NodeDbModel>> {
+  use crate::schema::nodes::dsl;
+  let names = names.to_vec();
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = dsl::nodes
+      .filter(dsl::name.eq_any(names))
+      .order(dsl::name)
+      .load::<NodeDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "nodes"))?;
+
+    Ok::<_, IoError>(items)
+  })
+  .await?;
+  Ok(items)
+}
+
+/// ## List by groups
+///
+/// List the nodes linked to the given node groups in database
+///
+/// ## Arguments
+///
+/// - [groups](Vec<String>) - Node group names
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<(String, NodeDbModel)>) - The list of node group name and node items
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list_by_groups(
+  groups: &[String],
+  pool: &Pool,
+) -> IoResult<Vec<(String, NodeDbModel)>> {
+  use crate::schema::{nodes, node_group_links};
+  let groups = groups.to_vec();
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = node_group_links::table
+      .inner_join(nodes::table)
+      .filter(node_group_links::node_group_name.eq_any(groups))
+      .select((node_group_links::node_group_name, nodes::all_columns))
+      .order(nodes::name)
+      .load::<(String, NodeDbModel)>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "node_group_links"))?;
+
+    Ok::<_, IoError>(items)
+  })
+  .await?;
+  Ok(items)
+}
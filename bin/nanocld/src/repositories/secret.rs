@@ -7,7 +7,6 @@ use diesel::prelude::*;
 use nanocl_utils::io_error::{IoError, FromIo, IoResult};
 
 use nanocl_stubs::generic::GenericDelete;
-use nanocl_stubs::secret::{SecretPartial, SecretUpdate};
 
 use crate::utils;
 use crate::models::{Pool, SecretDbModel, SecretUpdateDbModel};
@@ -18,7 +17,7 @@ use crate::models::{Pool, SecretDbModel, SecretUpdateDbModel};
 ///
 /// ## Arguments
 ///
-/// - [item](SecretPartial) - Secret to create
+/// - [item](SecretDbModel) - Secret to create
 /// - [pool](Pool) - Database connection pool
 ///
 /// ## Returns
@@ -28,7 +27,7 @@ use crate::models::{Pool, SecretDbModel, SecretUpdateDbModel};
 ///   - [Err](IoError) - Error during the operation
 ///
 pub async fn create(
-  item: &SecretPartial,
+  item: &SecretDbModel,
   pool: &Pool,
 ) -> IoResult<SecretDbModel> {
   use crate::schema::secrets::dsl;
@@ -36,7 +35,6 @@ pub async fn create(
   let pool = pool.clone();
   let item = web::block(move || {
     let mut conn = utils::store::get_pool_conn(&pool)?;
-    let item: SecretDbModel = item.clone().into();
     diesel::insert_into(dsl::secrets)
       .values(&item)
       .execute(&mut conn)
@@ -153,7 +151,7 @@ pub async fn find_by_key(key: &str, pool: &Pool) -> IoResult<SecretDbModel> {
 /// ## Arguments
 ///
 /// - [key](str) - Secret key
-/// - [item](SecretUpdate) - New secret data
+/// - [item](SecretUpdateDbModel) - New secret data
 /// - [pool](Pool) - Database connection pool
 ///
 /// ## Returns
@@ -164,7 +162,7 @@ pub async fn find_by_key(key: &str, pool: &Pool) -> IoResult<SecretDbModel> {
 ///
 pub async fn update_by_key(
   key: &str,
-  item: &SecretUpdate,
+  item: &SecretUpdateDbModel,
   pool: &Pool,
 ) -> IoResult<SecretDbModel> {
   use crate::schema::secrets::dsl;
@@ -172,10 +170,7 @@ pub async fn update_by_key(
   let item = item.clone();
   let pool = pool.clone();
   let mut secret = find_by_key(&key, &pool).await?;
-  let new_item = SecretUpdateDbModel {
-    data: Some(item.data.clone()),
-    metadata: item.metadata.clone(),
-  };
+  let new_item = item.clone();
   web::block(move || {
     let mut conn = utils::store::get_pool_conn(&pool)?;
     diesel::update(dsl::secrets.filter(dsl::key.eq(key)))
@@ -185,11 +180,98 @@ pub async fn update_by_key(
     Ok::<_, IoError>(())
   })
   .await?;
-  secret.data = item.data;
-  secret.metadata = item.metadata;
+  if let Some(updated_at) = item.updated_at {
+    secret.updated_at = updated_at;
+  }
+  if let Some(data) = item.data {
+    secret.data = data;
+  }
+  if item.metadata.is_some() {
+    secret.metadata = item.metadata;
+  }
+  if item.key_id.is_some() {
+    secret.key_id = item.key_id;
+  }
   Ok(secret)
 }
 
+/// ## List key ids
+///
+/// List the ids of the master keys encrypting the secrets
+///
+/// ## Arguments
+///
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<String>) - The ids of the master keys
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list_key_ids(pool: &Pool) -> IoResult<Vec<String>> {
+  use crate::schema::secrets::dsl;
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = dsl::secrets
+      .select(dsl::key_id)
+      .filter(dsl::key_id.is_not_null())
+      .distinct()
+      .load::<Option<String>>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "Secret"))?;
+    Ok::<_, IoError>(items.into_iter().flatten().collect())
+  })
+  .await?;
+  Ok(items)
+}
+
+/// ## Update keys
+///
+/// Update the data and the key id of secrets in a single transaction.
+/// A secret updated since it was read is skipped
+/// so a concurrent update is never overwritten.
+///
+/// ## Arguments
+///
+/// - [items](Vec<SecretDbModel>) - Secrets with their new data and key id
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](usize) - Number of updated secrets
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn update_keys(
+  items: Vec<SecretDbModel>,
+  pool: &Pool,
+) -> IoResult<usize> {
+  use crate::schema::secrets::dsl;
+  let pool = pool.clone();
+  let count = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let count = conn
+      .transaction::<_, diesel::result::Error, _>(|conn| {
+        let mut count = 0;
+        for item in &items {
+          count += diesel::update(
+            dsl::secrets
+              .filter(dsl::key.eq(&item.key))
+              .filter(dsl::updated_at.eq(item.updated_at)),
+          )
+          .set((dsl::data.eq(&item.data), dsl::key_id.eq(&item.key_id)))
+          .execute(conn)?;
+        }
+        Ok(count)
+      })
+      .map_err(|err| err.map_err_context(|| "Secret"))?;
+    Ok::<_, IoError>(count)
+  })
+  .await?;
+  Ok(count)
+}
+
 // / ## Exist by key
 // /
 // / Check if a secret exist by key in database
@@ -0,0 +1,122 @@
+use ntex::web;
+use diesel::prelude::*;
+
+use nanocl_utils::io_error::{IoError, FromIo, IoResult};
+
+use nanocl_stubs::generic::GenericDelete;
+
+use crate::utils;
+use crate::models::{Pool, StateRefDbModel};
+
+/// ## Create or update
+///
+/// Create state refs in database or move them to the given `Statefile`
+/// if they already exists
+///
+/// ## Arguments
+///
+/// - [items](Vec<StateRefDbModel>) - State ref items
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The state refs have been saved
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn create_or_update(
+  items: &[StateRefDbModel],
+  pool: &Pool,
+) -> IoResult<()> {
+  use crate::schema::state_refs::dsl;
+  let items = items.to_vec();
+  let pool = pool.clone();
+  web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    for item in items {
+      let state_name = item.state_name.clone();
+      diesel::insert_into(dsl::state_refs)
+        .values(item)
+        .on_conflict((dsl::kind, dsl::key))
+        .do_update()
+        .set(dsl::state_name.eq(state_name))
+        .execute(&mut conn)
+        .map_err(|err| err.map_err_context(|| "StateRef"))?;
+    }
+    Ok::<_, IoError>(())
+  })
+  .await?;
+  Ok(())
+}
+
+/// ## List by state name
+///
+/// List the state refs of a `Statefile` in database
+///
+/// ## Arguments
+///
+/// - [state_name](str) - Name of the `Statefile`
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<StateRefDbModel>) - The list of state refs
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn list_by_state_name(
+  state_name: &str,
+  pool: &Pool,
+) -> IoResult<Vec<StateRefDbModel>> {
+  use crate::schema::state_refs::dsl;
+  let state_name = state_name.to_owned();
+  let pool = pool.clone();
+  let items = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let items = dsl::state_refs
+      .filter(dsl::state_name.eq(state_name))
+      .load::<StateRefDbModel>(&mut conn)
+      .map_err(|err| err.map_err_context(|| "StateRef"))?;
+    Ok::<_, IoError>(items)
+  })
+  .await?;
+  Ok(items)
+}
+
+/// ## Delete by key
+///
+/// Delete the state ref of an object in database
+///
+/// ## Arguments
+///
+/// - [kind](str) - Kind of the object (Cargo, VirtualMachine, Resource, Secret)
+/// - [key](str) - Key of the object
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](GenericDelete) - The number of deleted items
+///   - [Err](IoError) - Error during the operation
+///
+pub async fn delete_by_key(
+  kind: &str,
+  key: &str,
+  pool: &Pool,
+) -> IoResult<GenericDelete> {
+  use crate::schema::state_refs::dsl;
+  let kind = kind.to_owned();
+  let key = key.to_owned();
+  let pool = pool.clone();
+  let res = web::block(move || {
+    let mut conn = utils::store::get_pool_conn(&pool)?;
+    let res = diesel::delete(dsl::state_refs)
+      .filter(dsl::kind.eq(kind))
+      .filter(dsl::key.eq(key))
+      .execute(&mut conn)
+      .map_err(|err| err.map_err_context(|| "StateRef"))?;
+    Ok::<_, IoError>(res)
+  })
+  .await?;
+  Ok(GenericDelete { count: res })
+}
@@ -270,6 +270,7 @@ pub async fn inspect_by_key(key: &str, pool: &Pool) -> IoResult<Vm> {
     password: config.password,
     ssh_key: config.ssh_key,
     metadata: config.metadata,
+    depends_on: config.depends_on,
   };
   let item = Vm {
     key: item.0.key,
@@ -72,6 +72,7 @@ pub async fn create(
     password: item.password.clone(),
     ssh_key: item.ssh_key.clone(),
     metadata: item.metadata.clone(),
+    depends_on: item.depends_on.clone(),
   };
   Ok(config)
 }
@@ -121,6 +122,7 @@ pub async fn find_by_key(key: &uuid::Uuid, pool: &Pool) -> IoResult<VmConfig> {
     password: config.password,
     ssh_key: config.ssh_key,
     metadata: config.metadata,
+    depends_on: config.depends_on,
   })
 }
 
@@ -206,6 +208,7 @@ pub async fn list_by_vm_key(key: &str, pool: &Pool) -> IoResult<Vec<VmConfig>> {
         ssh_key: config.ssh_key,
         password: config.password,
         metadata: config.metadata,
+        depends_on: config.depends_on,
       })
     })
     .collect::<Result<Vec<VmConfig>, IoError>>()?;
@@ -11,6 +11,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    cargo_scale_histories (key) {
+        key -> Uuid,
+        created_at -> Timestamptz,
+        cargo_key -> Varchar,
+        node_name -> Varchar,
+        previous_replicas -> Int8,
+        replicas -> Int8,
+        cpu_usage -> Nullable<Float8>,
+        memory_usage -> Nullable<Float8>,
+        reason -> Varchar,
+    }
+}
+
 diesel::table! {
     cargoes (key) {
         key -> Varchar,
@@ -47,6 +61,31 @@ diesel::table! {
         http_user_agent -> Nullable<Varchar>,
         http_referrer -> Nullable<Varchar>,
         http_accept_language -> Nullable<Varchar>,
+        upstream_cache_status -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    job_runs (key) {
+        key -> Uuid,
+        created_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+        job_key -> Varchar,
+        node_name -> Varchar,
+        attempt -> Int8,
+        status -> Varchar,
+        exit_code -> Nullable<Int8>,
+        logs -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    jobs (key) {
+        key -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        data -> Jsonb,
+        metadata -> Nullable<Jsonb>,
     }
 }
 
@@ -135,6 +174,16 @@ diesel::table! {
         immutable -> Bool,
         data -> Jsonb,
         metadata -> Nullable<Jsonb>,
+        key_id -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    state_refs (kind, key) {
+        kind -> Varchar,
+        key -> Varchar,
+        created_at -> Timestamptz,
+        state_name -> Varchar,
     }
 }
 
@@ -203,8 +252,11 @@ diesel::joinable!(vms -> vm_configs (config_key));
 
 diesel::allow_tables_to_appear_in_same_query!(
   cargo_configs,
+  cargo_scale_histories,
   cargoes,
   http_metrics,
+  job_runs,
+  jobs,
   metrics,
   namespaces,
   node_group_links,
@@ -215,6 +267,7 @@ diesel::allow_tables_to_appear_in_same_query!(
   resource_kinds,
   resources,
   secrets,
+  state_refs,
   stream_metrics,
   vm_configs,
   vm_images,
@@ -219,7 +219,7 @@ pub(crate) async fn stop_cargo(
   let namespace = utils::key::resolve_nsp(&qs.namespace);
   let key = utils::key::gen_key(&namespace, &path.1);
   utils::cargo::inspect_by_key(&key, &state).await?;
-  utils::cargo::stop_by_key(&key, &state.docker_api).await?;
+  utils::cargo::stop_by_key(&key, &state).await?;
   rt::spawn(async move {
     let cargo = utils::cargo::inspect_by_key(&key, &state).await.unwrap();
     let _ = state
@@ -281,7 +281,7 @@ pub(crate) async fn put_cargo(
 ) -> Result<web::HttpResponse, HttpError> {
   let namespace = utils::key::resolve_nsp(&qs.namespace);
   let key = utils::key::gen_key(&namespace, &path.1);
-  let cargo = utils::cargo::put(&key, &payload, &path.0, &state).await?;
+  let cargo = utils::cargo::put(&key, &payload, &path.0, &state, None).await?;
   rt::spawn(async move {
     let cargo = utils::cargo::inspect_by_key(&key, &state).await.unwrap();
     let _ = state
@@ -351,7 +351,7 @@ async fn kill_cargo(
 ) -> Result<web::HttpResponse, HttpError> {
   let namespace = utils::key::resolve_nsp(&qs.namespace);
   let key = utils::key::gen_key(&namespace, &path.1);
-  utils::cargo::kill_by_name(&key, &payload, &state.docker_api).await?;
+  utils::cargo::kill_by_key(&key, &payload, &state).await?;
   Ok(web::HttpResponse::Ok().into())
 }
 
@@ -382,6 +382,33 @@ async fn list_cargo_history(
   Ok(web::HttpResponse::Ok().json(&histories))
 }
 
+/// List the scaling decisions of the autoscaler of a cargo
+#[cfg_attr(feature = "dev", utoipa::path(
+  get,
+  tag = "Cargoes",
+  path = "/cargoes/{Name}/scale/histories",
+  params(
+    ("Name" = String, Path, description = "Name of the cargo"),
+    ("Namespace" = Option<String>, Query, description = "Namespace of the cargo"),
+  ),
+  responses(
+    (status = 200, description = "List of cargo scale histories", body = Vec<CargoScaleHistory>),
+  ),
+))]
+#[web::get("/cargoes/{name}/scale/histories")]
+async fn list_cargo_scale_history(
+  web::types::Query(qs): web::types::Query<GenericNspQuery>,
+  path: web::types::Path<(String, String)>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let namespace = utils::key::resolve_nsp(&qs.namespace);
+  let key = utils::key::gen_key(&namespace, &path.1);
+  let histories =
+    repositories::cargo_scale_history::list_by_cargo_key(&key, &state.pool)
+      .await?;
+  Ok(web::HttpResponse::Ok().json(&histories))
+}
+
 /// Revert a cargo to a specific history
 #[cfg_attr(feature = "dev", utoipa::path(
   patch,
@@ -416,6 +443,7 @@ async fn revert_cargo(
     &config.clone().into(),
     &path.version,
     &state,
+    None,
   )
   .await?;
   let key = cargo_key.clone();
@@ -548,6 +576,7 @@ pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(logs_cargo);
   config.service(list_cargo_instance);
   config.service(scale_cargo);
+  config.service(list_cargo_scale_history);
   config.service(stats_cargo);
 }
 
@@ -555,16 +584,19 @@ pub fn ntex_config(config: &mut web::ServiceConfig) {
 mod tests {
   use crate::services::ntex_config;
   use crate::utils::tests::*;
-  use crate::services::cargo_image::tests::ensure_test_image;
 
   use ntex::http;
   use futures::{TryStreamExt, StreamExt};
 
   use nanocl_stubs::generic::GenericNspQuery;
-  use nanocl_stubs::cargo_config::{CargoConfig, CargoConfigPartial};
+  use nanocl_stubs::secret::{SecretPartial, SecretUpdate};
+  use nanocl_stubs::cargo_config::{
+    CargoConfig, CargoConfigPartial, CargoContainer, CargoSecretFile,
+    ReplicationMode, ReplicationStatic, UpdateStrategy, CargoRollback,
+  };
   use nanocl_stubs::cargo::{
     Cargo, CargoSummary, CargoInspect, OutputLog, CargoDeleteQuery,
-    CargoListQuery, CargoScale,
+    CargoListQuery, CargoScale, CargoScaleHistory,
   };
 
   /// Test to create start patch stop and delete a cargo with valid data
@@ -690,6 +722,14 @@ mod tests {
     let histories = res.json::<Vec<CargoConfig>>().await?;
     assert!(histories.len() > 1);
 
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{}/scale/histories", response.name))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 200);
+    let scale_histories = res.json::<Vec<CargoScaleHistory>>().await?;
+    assert!(scale_histories.is_empty());
+
     let id = histories[0].key;
     let res = srv
       .patch(format!(
@@ -771,6 +811,341 @@ mod tests {
       .await?;
     assert_eq!(res.status(), 200);
 
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/scale/histories"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 200);
+    let scale_histories = res.json::<Vec<CargoScaleHistory>>().await?;
+    assert_eq!(scale_histories.len(), 2, "Expect the scalings saved");
+    let replicas = scale_histories
+      .iter()
+      .map(|history| (history.previous_replicas, history.replicas))
+      .collect::<Vec<_>>();
+    assert!(replicas.contains(&(1, 3)), "Expect a scale up");
+    assert!(replicas.contains(&(3, 2)), "Expect a scale down");
+
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/histories"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 200);
+    let histories = res.json::<Vec<CargoConfig>>().await?;
+    assert_eq!(histories.len(), 1, "Expect no config history");
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/stop"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    let res = srv
+      .delete(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send()
+      .await?;
+
+    assert_eq!(res.status(), 202);
+
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn rolling_update() -> TestRet {
+    let srv = gen_server(ntex_config).await;
+    ensure_test_image().await?;
+
+    const CARGO_NAME: &str = "api-test-rolling-update";
+    let config = CargoConfigPartial {
+      name: CARGO_NAME.to_string(),
+      container: bollard_next::container::Config {
+        image: Some("nexthat/nanocl-get-started:latest".to_string()),
+        ..Default::default()
+      },
+      replication: Some(ReplicationMode::Static(ReplicationStatic {
+        number: 2,
+      })),
+      ..Default::default()
+    };
+    let res = srv.post("/v0.10/cargoes").send_json(&config).await?;
+    assert_eq!(res.status(), 201);
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/start"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    let res = srv
+      .put(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send_json(&CargoConfigPartial {
+        update_strategy: Some(UpdateStrategy {
+          max_surge: Some(0),
+          max_unavailable: Some(0),
+          ..Default::default()
+        }),
+        ..config.clone()
+      })
+      .await?;
+    assert_eq!(res.status(), 400);
+
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/histories"))
+      .send()
+      .await?;
+    let histories = res.json::<Vec<CargoConfig>>().await?;
+    assert_eq!(histories.len(), 1, "Expect no config history");
+
+    // Only the first instance of the new config become healthy
+    // so the second batch fail after the first one succeeded
+    let res = srv
+      .put(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send_json(&CargoConfigPartial {
+        container: bollard_next::container::Config {
+          healthcheck: Some(bollard_next::service::HealthConfig {
+            test: Some(vec![
+              "CMD-SHELL".to_string(),
+              "test \"$NANOCL_CARGO_INSTANCE\" = 0".to_string(),
+            ]),
+            interval: Some(1_000_000_000),
+            retries: Some(1),
+            ..Default::default()
+          }),
+          ..config.container.clone()
+        },
+        update_strategy: Some(UpdateStrategy {
+          batch_size: Some(1),
+          timeout: Some(20),
+          ..Default::default()
+        }),
+        ..config.clone()
+      })
+      .await?;
+    assert_eq!(res.status(), 500);
+
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/inspect"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 200);
+    let cargo = res.json::<CargoInspect>().await?;
+    assert_eq!(cargo.instances.len(), 2, "Expect the old instances");
+    for instance in cargo.instances {
+      let container = instance.container;
+      let name = container.names.unwrap_or_default().join(",");
+      assert!(!name.contains("-backup"), "Expect {name} renamed");
+      assert_eq!(container.state.as_deref(), Some("running"));
+      let status = container.status.unwrap_or_default();
+      assert!(!status.contains("health"), "Expect {name} with old config");
+    }
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/stop"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    let res = srv
+      .delete(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn rollback() -> TestRet {
+    let srv = gen_server(ntex_config).await;
+    ensure_test_image().await?;
+
+    const CARGO_NAME: &str = "api-test-rollback";
+    let config = CargoConfigPartial {
+      name: CARGO_NAME.to_string(),
+      container: bollard_next::container::Config {
+        image: Some("nexthat/nanocl-get-started:latest".to_string()),
+        ..Default::default()
+      },
+      rollback: Some(CargoRollback {
+        enabled: Some(true),
+        timeout: Some(10),
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+    let res = srv.post("/v0.10/cargoes").send_json(&config).await?;
+    assert_eq!(res.status(), 201);
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/start"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/inspect"))
+      .send()
+      .await?;
+    let cargo = res.json::<CargoInspect>().await?;
+    let ids = cargo
+      .instances
+      .iter()
+      .map(|instance| instance.container.id.clone())
+      .collect::<Vec<_>>();
+
+    // The rolling update fail and restore the old instances
+    // so only the config is reverted
+    let res = srv
+      .put(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send_json(&CargoConfigPartial {
+        container: bollard_next::container::Config {
+          healthcheck: Some(bollard_next::service::HealthConfig {
+            test: Some(vec!["CMD-SHELL".to_string(), "exit 1".to_string()]),
+            interval: Some(1_000_000_000),
+            retries: Some(1),
+            ..Default::default()
+          }),
+          ..config.container.clone()
+        },
+        update_strategy: Some(UpdateStrategy {
+          timeout: Some(10),
+          ..Default::default()
+        }),
+        ..config.clone()
+      })
+      .await?;
+    assert_eq!(res.status(), 500);
+
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/inspect"))
+      .send()
+      .await?;
+    let cargo = res.json::<CargoInspect>().await?;
+    assert_eq!(cargo.config.container.healthcheck, None);
+    assert_eq!(
+      cargo
+        .instances
+        .iter()
+        .map(|instance| instance.container.id.clone())
+        .collect::<Vec<_>>(),
+      ids,
+      "Expect the old instances to be kept"
+    );
+    let status = cargo.rollback_status.expect("Expect a rollback status");
+    assert!(status.rolled_back);
+    assert!(status.finished_at.is_some());
+
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/histories"))
+      .send()
+      .await?;
+    let histories = res.json::<Vec<CargoConfig>>().await?;
+    assert_eq!(histories.len(), 3, "Expect the update and the rollback");
+
+    // The new instance exit after the update succeeded
+    // so the watcher rollback in background
+    let res = srv
+      .put(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send_json(&CargoConfigPartial {
+        container: bollard_next::container::Config {
+          cmd: Some(vec!["sh".into(), "-c".into(), "exit 1".into()]),
+          host_config: Some(bollard_next::service::HostConfig {
+            restart_policy: Some(bollard_next::service::RestartPolicy {
+              name: Some(bollard_next::service::RestartPolicyNameEnum::NO),
+              ..Default::default()
+            }),
+            ..Default::default()
+          }),
+          ..config.container.clone()
+        },
+        ..config.clone()
+      })
+      .await?;
+    assert_eq!(res.status(), 200);
+
+    let mut status = None;
+    for _ in 0..20 {
+      let mut res = srv
+        .get(format!("/v0.10/cargoes/{CARGO_NAME}/inspect"))
+        .send()
+        .await?;
+      let cargo = res.json::<CargoInspect>().await?;
+      if let Some(rollback_status) = cargo.rollback_status {
+        if rollback_status.finished_at.is_some() {
+          assert_eq!(cargo.config.container.cmd, None);
+          status = Some(rollback_status);
+          break;
+        }
+      }
+      ntex::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+    let status = status.expect("Expect the watcher to finish");
+    assert!(status.rolled_back, "Expect rolled back: {:?}", status.error);
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/stop"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    let res = srv
+      .delete(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn init_containers_and_sidecars() -> TestRet {
+    let srv = gen_server(ntex_config).await;
+    ensure_test_image().await?;
+
+    const CARGO_NAME: &str = "api-test-attached";
+    let container = bollard_next::container::Config {
+      image: Some("nexthat/nanocl-get-started:latest".to_string()),
+      ..Default::default()
+    };
+    let res = srv
+      .post("/v0.10/cargoes")
+      .send_json(&CargoConfigPartial {
+        name: CARGO_NAME.to_string(),
+        container: container.clone(),
+        init_containers: Some(vec![CargoContainer {
+          name: "init".into(),
+          container: bollard_next::container::Config {
+            cmd: Some(vec!["sh".into(), "-c".into(), "exit 0".into()]),
+            ..container.clone()
+          },
+        }]),
+        sidecars: Some(vec![CargoContainer {
+          name: "sidecar".into(),
+          container: container.clone(),
+        }]),
+        ..Default::default()
+      })
+      .await?;
+    assert_eq!(res.status(), 201);
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/start"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    let mut res = srv
+      .get(format!("/v0.10/cargoes/{CARGO_NAME}/inspect"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 200);
+    let cargo = res.json::<CargoInspect>().await?;
+    assert_eq!(
+      cargo.instance_total, 1,
+      "Expect sidecars not to be instances"
+    );
+    assert_eq!(cargo.config.init_containers.unwrap_or_default().len(), 1);
+    assert_eq!(cargo.config.sidecars.unwrap_or_default().len(), 1);
+
     let res = srv
       .post(format!("/v0.10/cargoes/{CARGO_NAME}/stop"))
       .send()
@@ -781,8 +1156,86 @@ mod tests {
       .delete(format!("/v0.10/cargoes/{CARGO_NAME}"))
       .send()
       .await?;
+    assert_eq!(res.status(), 202);
+
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn secret_files() -> TestRet {
+    let srv = gen_server(ntex_config).await;
+    ensure_test_image().await?;
+
+    const CARGO_NAME: &str = "api-test-secret-files";
+    let res = srv
+      .post("/v0.10/secrets")
+      .send_json(&SecretPartial {
+        key: CARGO_NAME.to_string(),
+        kind: "Config".to_string(),
+        immutable: None,
+        data: serde_json::json!({ "app.conf": "v1" }),
+        metadata: None,
+      })
+      .await?;
+    assert_eq!(res.status(), 201);
+
+    let res = srv
+      .post("/v0.10/cargoes")
+      .send_json(&CargoConfigPartial {
+        name: CARGO_NAME.to_string(),
+        container: bollard_next::container::Config {
+          image: Some("nexthat/nanocl-get-started:latest".to_string()),
+          ..Default::default()
+        },
+        secret_files: Some(vec![CargoSecretFile {
+          secret: CARGO_NAME.to_string(),
+          target: "/etc/app".to_string(),
+          ..Default::default()
+        }]),
+        ..Default::default()
+      })
+      .await?;
+    assert_eq!(res.status(), 201);
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/start"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
+
+    let home = std::env::var("HOME")?;
+    let dir = format!("{home}/.nanocl/state/secrets/{CARGO_NAME}.global");
+    let file = format!("{dir}/0/app.conf");
+    assert_eq!(std::fs::read_to_string(&file)?, "v1");
+
+    let res = srv
+      .patch(format!("/v0.10/secrets/{CARGO_NAME}"))
+      .send_json(&SecretUpdate {
+        data: serde_json::json!({ "app.conf": "v2" }),
+        metadata: None,
+      })
+      .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(std::fs::read_to_string(&file)?, "v2", "Expect file refresh");
+
+    let res = srv
+      .post(format!("/v0.10/cargoes/{CARGO_NAME}/stop"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 202);
 
+    let res = srv
+      .delete(format!("/v0.10/cargoes/{CARGO_NAME}"))
+      .send()
+      .await?;
     assert_eq!(res.status(), 202);
+    assert!(std::fs::metadata(&dir).is_err(), "Expect files removal");
+
+    let res = srv
+      .delete(format!("/v0.10/secrets/{CARGO_NAME}"))
+      .send()
+      .await?;
+    assert_eq!(res.status(), 200);
 
     Ok(())
   }
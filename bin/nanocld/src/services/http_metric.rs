@@ -1,8 +1,11 @@
-use ntex::web;
+use ntex::{web, http};
 
-use nanocl_stubs::http_metric::{HttpMetricListQuery, HttpMetricCountQuery};
+use nanocl_stubs::http_metric::{
+  HttpMetricListQuery, HttpMetricCountQuery, HttpMetricStatsQuery,
+  ProxyLogBatch,
+};
 
-use crate::repositories;
+use crate::{utils, repositories};
 use nanocl_utils::http_error::HttpError;
 use crate::models::DaemonState;
 
@@ -35,6 +38,7 @@ pub(crate) async fn list_http_metric(
   path = "/http_metrics/count",
   params(
     ("Status" = Option<String>, Query, description = "Filter by status", example = "200,299"),
+    ("CacheStatus" = Option<String>, Query, description = "Filter by cache status", example = "HIT"),
   ),
   responses(
     (status = 200, description = "Count of HTTP metrics founds", body = GenericCount),
@@ -49,9 +53,67 @@ pub(crate) async fn count_http_metric(
   Ok(web::HttpResponse::Ok().json(&count))
 }
 
+/// Get statistics of the http metrics of all peer nodes
+#[cfg_attr(feature = "dev", utoipa::path(
+  get,
+  tag = "HttpMetrics",
+  path = "/http_metrics/stats",
+  params(
+    ("GroupBy" = Option<String>, Query, description = "Group the metrics by host, uri prefix, upstream or status class", example = "StatusClass"),
+    ("Bucket" = Option<i64>, Query, description = "Duration of a time bucket in seconds"),
+    ("Since" = Option<i64>, Query, description = "Only use the metrics of the last seconds"),
+    ("UriDepth" = Option<i64>, Query, description = "Number of segments of the uri prefix"),
+  ),
+  responses(
+    (status = 200, description = "Statistics by time bucket and group", body = Vec<HttpMetricStats>),
+  ),
+))]
+#[web::get("/http_metrics/stats")]
+pub(crate) async fn stats_http_metric(
+  qs: web::types::Query<HttpMetricStatsQuery>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let stats = repositories::http_metric::stats(&qs, &state.pool).await?;
+  Ok(web::HttpResponse::Ok().json(&stats))
+}
+
+/// Maximum number of lines of a batch of access logs
+const MAX_INGEST_BATCH: usize = 1000;
+
+/// Save a batch of access logs of the proxy as http and stream metrics
+#[cfg_attr(feature = "dev", utoipa::path(
+  post,
+  tag = "HttpMetrics",
+  path = "/http_metrics/ingest",
+  request_body = ProxyLogBatch,
+  responses(
+    (status = 200, description = "Number of lines saved and rejected", body = ProxyLogIngestion),
+    (status = 413, description = "The batch has too many lines"),
+  ),
+))]
+#[web::post("/http_metrics/ingest")]
+pub(crate) async fn ingest_http_metric(
+  web::types::Json(payload): web::types::Json<ProxyLogBatch>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  // The proxy waits for the batch to be saved before sending the next one
+  // refusing large batches keep the time spent in a transaction bounded
+  let len = payload.http.len() + payload.stream.len();
+  if len > MAX_INGEST_BATCH {
+    return Err(HttpError::new(
+      http::StatusCode::PAYLOAD_TOO_LARGE,
+      format!("Batch of {len} lines is above the limit of {MAX_INGEST_BATCH}"),
+    ));
+  }
+  let ingestion = utils::proxy::ingest_logs(payload, &state).await?;
+  Ok(web::HttpResponse::Ok().json(&ingestion))
+}
+
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(list_http_metric);
   config.service(count_http_metric);
+  config.service(stats_http_metric);
+  config.service(ingest_http_metric);
 }
 
 #[cfg(test)]
@@ -59,7 +121,10 @@ mod tests {
 
   use ntex::http;
   use nanocl_stubs::generic::GenericCount;
-  use nanocl_stubs::http_metric::HttpMetric;
+  use nanocl_stubs::http_metric::{
+    HttpMetric, HttpMetricCountQuery, HttpMetricStatsQuery, HttpMetricGroupBy,
+    HttpMetricStats, ProxyLogBatch, ProxyLogIngestion,
+  };
 
   use crate::services::ntex_config;
   use crate::utils::tests::*;
@@ -75,6 +140,128 @@ mod tests {
     let mut resp = srv.get("/v0.5/http_metrics/count").send().await?;
     assert_eq!(resp.status(), http::StatusCode::OK);
     let _ = resp.json::<GenericCount>().await?;
+    let mut resp = srv
+      .get("/v0.5/http_metrics/count")
+      .query(&HttpMetricCountQuery {
+        status: None,
+        cache_status: Some("HIT".to_owned()),
+      })?
+      .send()
+      .await?;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let _ = resp.json::<GenericCount>().await?;
+    Ok(())
+  }
+
+  async fn test_ingest(srv: &TestServer) -> TestRet {
+    let batch = ProxyLogBatch {
+      http: vec![
+        serde_json::json!({
+          "date_gmt": "2023-10-15T10:00:00+00:00",
+          "remote_addr": "10.0.0.1",
+          "realip_remote_addr": "10.0.0.1",
+          "proxy_host": "",
+          "upstream_addr": "",
+          "server_protocol": "HTTP/1.1",
+          "request_method": "GET",
+          "host": "ingest.test",
+          "uri": "/",
+          "query_string": "",
+          "request_body": "",
+          "content_type": "",
+          "content_length": "",
+          "status": "200",
+          "bytes_sent": "120",
+          "request_time": "0.001",
+          "body_bytes_sent": "20",
+          "http_referrer": "",
+          "http_accept_language": "",
+          "http_user_agent": "",
+          "upstream_cache_status": "HIT",
+        }),
+        serde_json::json!({ "not": "a metric" }),
+      ],
+      stream: vec![],
+    };
+    let mut resp = srv
+      .post("/v0.5/http_metrics/ingest")
+      .send_json(&batch)
+      .await?;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let ingestion = resp.json::<ProxyLogIngestion>().await?;
+    assert_eq!(ingestion.http, 1);
+    assert_eq!(ingestion.rejected, 1);
+    let batch = ProxyLogBatch {
+      http: vec![serde_json::json!({}); super::MAX_INGEST_BATCH + 1],
+      stream: vec![],
+    };
+    let resp = srv
+      .post("/v0.5/http_metrics/ingest")
+      .send_json(&batch)
+      .await?;
+    assert_eq!(resp.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+    Ok(())
+  }
+
+  async fn test_stats(srv: &TestServer) -> TestRet {
+    let batch = ProxyLogBatch {
+      http: vec![serde_json::json!({
+        "date_gmt": chrono::Utc::now().to_rfc3339(),
+        "remote_addr": "10.0.0.1",
+        "realip_remote_addr": "10.0.0.1",
+        "proxy_host": "",
+        "upstream_addr": "",
+        "server_protocol": "HTTP/1.1",
+        "request_method": "GET",
+        "host": "stats.test",
+        "uri": "/missing",
+        "query_string": "",
+        "request_body": "",
+        "content_type": "",
+        "content_length": "",
+        "status": "404",
+        "bytes_sent": "120",
+        "request_time": "0.001",
+        "body_bytes_sent": "20",
+        "http_referrer": "",
+        "http_accept_language": "",
+        "http_user_agent": "",
+        "upstream_cache_status": "",
+      })],
+      stream: vec![],
+    };
+    let resp = srv
+      .post("/v0.5/http_metrics/ingest")
+      .send_json(&batch)
+      .await?;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let mut resp = srv
+      .get("/v0.5/http_metrics/stats")
+      .query(&HttpMetricStatsQuery {
+        group_by: Some(HttpMetricGroupBy::StatusClass),
+        bucket: Some(60),
+        since: None,
+        uri_depth: None,
+      })?
+      .send()
+      .await?;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let stats = resp.json::<Vec<HttpMetricStats>>().await?;
+    assert!(stats.iter().any(|stat| stat.group == "4xx"));
+    assert!(stats.iter().all(|stat| {
+      ["1xx", "2xx", "3xx", "4xx", "5xx"].contains(&stat.group.as_str())
+    }));
+    let resp = srv
+      .get("/v0.5/http_metrics/stats")
+      .query(&HttpMetricStatsQuery {
+        group_by: Some(HttpMetricGroupBy::UriPrefix),
+        bucket: Some(0),
+        since: None,
+        uri_depth: None,
+      })?
+      .send()
+      .await?;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
     Ok(())
   }
 
@@ -83,6 +270,8 @@ mod tests {
     let srv = gen_server(ntex_config).await;
     test_list(&srv).await?;
     test_count(&srv).await?;
+    test_ingest(&srv).await?;
+    test_stats(&srv).await?;
     Ok(())
   }
 }
@@ -0,0 +1,277 @@
+/*
+* Endpoints to manipulate jobs
+*/
+use ntex::rt;
+use ntex::web;
+
+use nanocl_stubs::system::Event;
+use nanocl_stubs::job::JobPartial;
+
+use crate::{utils, repositories};
+use crate::models::DaemonState;
+
+use nanocl_utils::http_error::HttpError;
+
+/// List jobs
+#[cfg_attr(feature = "dev", utoipa::path(
+  get,
+  tag = "Jobs",
+  path = "/jobs",
+  responses(
+    (status = 200, description = "List of jobs", body = [Job]),
+  ),
+))]
+#[web::get("/jobs")]
+pub(crate) async fn list_job(
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let items = repositories::job::list(&state.pool).await?;
+  Ok(web::HttpResponse::Ok().json(&items))
+}
+
+/// Get detailed information about a job with his past runs
+#[cfg_attr(feature = "dev", utoipa::path(
+  get,
+  tag = "Jobs",
+  path = "/jobs/{Name}/inspect",
+  params(
+    ("Name" = String, Path, description = "The job name to inspect")
+  ),
+  responses(
+    (status = 200, description = "Detailed information about a job", body = JobInspect),
+    (status = 404, description = "Job does not exist", body = ApiError),
+  ),
+))]
+#[web::get("/jobs/{name}/inspect")]
+pub(crate) async fn inspect_job(
+  path: web::types::Path<(String, String)>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let job = utils::job::inspect_by_key(&path.1, &state).await?;
+  Ok(web::HttpResponse::Ok().json(&job))
+}
+
+/// Create a job
+#[cfg_attr(feature = "dev", utoipa::path(
+  post,
+  request_body = JobPartial,
+  tag = "Jobs",
+  path = "/jobs",
+  responses(
+    (status = 201, description = "The created job", body = Job),
+    (status = 400, description = "Invalid job config", body = ApiError),
+    (status = 409, description = "Job already exist", body = ApiError),
+  ),
+))]
+#[web::post("/jobs")]
+pub(crate) async fn create_job(
+  web::types::Json(payload): web::types::Json<JobPartial>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  utils::job::validate(&payload)?;
+  let item = repositories::job::create(&payload, &state.pool).await?;
+  let job = item.clone();
+  rt::spawn(async move {
+    let _ = state
+      .event_emitter
+      .emit(Event::JobCreated(Box::new(job)))
+      .await;
+  });
+  Ok(web::HttpResponse::Created().json(&item))
+}
+
+/// Replace the config of a job
+#[cfg_attr(feature = "dev", utoipa::path(
+  put,
+  request_body = JobPartial,
+  tag = "Jobs",
+  path = "/jobs/{Name}",
+  params(
+    ("Name" = String, Path, description = "The job name to update")
+  ),
+  responses(
+    (status = 200, description = "The updated job", body = Job),
+    (status = 400, description = "Invalid job config", body = ApiError),
+    (status = 404, description = "Job does not exist", body = ApiError),
+  ),
+))]
+#[web::put("/jobs/{name}")]
+pub(crate) async fn put_job(
+  web::types::Json(payload): web::types::Json<JobPartial>,
+  path: web::types::Path<(String, String)>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let payload = JobPartial {
+    name: path.1.to_owned(),
+    ..payload
+  };
+  utils::job::validate(&payload)?;
+  repositories::job::find_by_key(&path.1, &state.pool).await?;
+  let item =
+    repositories::job::update_by_key(&path.1, &payload, &state.pool).await?;
+  let job = item.clone();
+  rt::spawn(async move {
+    let _ = state
+      .event_emitter
+      .emit(Event::JobPatched(Box::new(job)))
+      .await;
+  });
+  Ok(web::HttpResponse::Ok().json(&item))
+}
+
+/// Delete a job with his past runs
+#[cfg_attr(feature = "dev", utoipa::path(
+  delete,
+  tag = "Jobs",
+  path = "/jobs/{Name}",
+  params(
+    ("Name" = String, Path, description = "The job name to delete")
+  ),
+  responses(
+    (status = 200, description = "Delete response", body = GenericDelete),
+    (status = 404, description = "Job does not exist", body = ApiError),
+  ),
+))]
+#[web::delete("/jobs/{name}")]
+pub(crate) async fn delete_job(
+  path: web::types::Path<(String, String)>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let job = repositories::job::find_by_key(&path.1, &state.pool).await?;
+  let res = utils::job::delete_by_key(&path.1, &state).await?;
+  rt::spawn(async move {
+    let _ = state
+      .event_emitter
+      .emit(Event::JobDeleted(Box::new(job)))
+      .await;
+  });
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// Run a job now in background
+#[cfg_attr(feature = "dev", utoipa::path(
+  post,
+  tag = "Jobs",
+  path = "/jobs/{Name}/start",
+  params(
+    ("Name" = String, Path, description = "The job name to start")
+  ),
+  responses(
+    (status = 202, description = "The job run has been started"),
+    (status = 404, description = "Job does not exist", body = ApiError),
+  ),
+))]
+#[web::post("/jobs/{name}/start")]
+pub(crate) async fn start_job(
+  path: web::types::Path<(String, String)>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let job = repositories::job::find_by_key(&path.1, &state.pool).await?;
+  utils::job::spawn_run(job, &state);
+  Ok(web::HttpResponse::Accepted().finish())
+}
+
+pub fn ntex_config(config: &mut web::ServiceConfig) {
+  config.service(list_job);
+  config.service(create_job);
+  config.service(inspect_job);
+  config.service(put_job);
+  config.service(delete_job);
+  config.service(start_job);
+}
+
+#[cfg(test)]
+mod test_job {
+  use crate::services::ntex_config;
+
+  use serde_json::json;
+
+  use nanocl_stubs::generic::GenericDelete;
+  use nanocl_stubs::job::{Job, JobPartial, JobInspect};
+  use nanocl_stubs::cargo_config::Config as ContainerConfig;
+
+  use crate::utils::tests::*;
+
+  fn gen_job(name: &str, schedule: Option<String>) -> JobPartial {
+    JobPartial {
+      name: name.to_owned(),
+      schedule,
+      retries: Some(1),
+      backoff: Some(1),
+      container: ContainerConfig {
+        image: Some("nexthat/nanocl-get-started:latest".to_owned()),
+        cmd: Some(vec!["echo".into(), "hello".into()]),
+        ..Default::default()
+      },
+      ..Default::default()
+    }
+  }
+
+  async fn test_fail_create(srv: &TestServer) -> TestRet {
+    let resp = srv
+      .post("/v0.10/jobs")
+      .send_json(&json!({
+          "Name": 1,
+      }))
+      .await?;
+    assert!(resp.status().is_client_error());
+    let job = gen_job("test-job-invalid", Some("not a cron".into()));
+    let resp = srv.post("/v0.10/jobs").send_json(&job).await?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+  }
+
+  async fn test_create(srv: &TestServer) -> TestRet {
+    let job = gen_job("test-job", Some("0 0 * * *".into()));
+    let mut resp = srv.post("/v0.10/jobs").send_json(&job).await?;
+    assert_eq!(resp.status(), 201);
+    let body = resp.json::<Job>().await?;
+    assert_eq!(body.name, "test-job");
+    assert_eq!(body.config, job);
+    Ok(())
+  }
+
+  async fn test_list(srv: &TestServer) -> TestRet {
+    let mut resp = srv.get("/v0.10/jobs").send().await?;
+    assert!(resp.status().is_success());
+    let body = resp.json::<Vec<Job>>().await?;
+    assert!(body.iter().any(|job| job.name == "test-job"));
+    Ok(())
+  }
+
+  async fn test_inspect(srv: &TestServer) -> TestRet {
+    let mut resp = srv.get("/v0.10/jobs/test-job/inspect").send().await?;
+    assert!(resp.status().is_success());
+    let body = resp.json::<JobInspect>().await?;
+    assert!(body.next_run_at.is_some());
+    Ok(())
+  }
+
+  async fn test_start(srv: &TestServer) -> TestRet {
+    let resp = srv.post("/v0.10/jobs/test-job/start").send().await?;
+    assert_eq!(resp.status(), 202);
+    Ok(())
+  }
+
+  async fn test_delete(srv: &TestServer) -> TestRet {
+    let mut resp = srv.delete("/v0.10/jobs/test-job").send().await?;
+    assert!(resp.status().is_success());
+    let body = resp.json::<GenericDelete>().await?;
+    assert_eq!(body.count, 1);
+    let resp = srv.get("/v0.10/jobs/test-job/inspect").send().await?;
+    assert_eq!(resp.status(), 404);
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn basic() -> TestRet {
+    let srv = gen_server(ntex_config).await;
+    test_fail_create(&srv).await?;
+    test_create(&srv).await?;
+    test_list(&srv).await?;
+    test_inspect(&srv).await?;
+    test_start(&srv).await?;
+    test_delete(&srv).await?;
+    Ok(())
+  }
+}
@@ -4,7 +4,7 @@ use nanocl_stubs::metric::MetricFilterQuery;
 
 use crate::models::DaemonState;
 
-use crate::repositories;
+use crate::{utils, repositories};
 use nanocl_utils::http_error::HttpError;
 
 /// Get specific metric of all peer nodes
@@ -13,7 +13,7 @@ use nanocl_utils::http_error::HttpError;
   tag = "Metrics",
   path = "/metrics",
   params(
-    ("Kind" = MetricKind, Query, description = "Kind of the metrics CPU | MEMORY | NETWORK | DISK", example = "CPU"),
+    ("Kind" = MetricKind, Query, description = "Kind of the metrics CPU | MEMORY | NETWORK | DISK | CARGO", example = "CPU"),
   ),
   responses(
     (status = 200, description = "Kind of the metrics peer node", body = Vec<Metric>),
@@ -30,6 +30,20 @@ pub(crate) async fn list_metric(
   Ok(web::HttpResponse::Ok().json(&metrics))
 }
 
+/// Export the metrics in the open metrics text format to be scraped
+/// It's served without version on `/metrics`
+#[web::get("/metrics")]
+pub(crate) async fn export_metric(
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let metrics = utils::metric::export(&state).await?;
+  Ok(
+    web::HttpResponse::Ok()
+      .content_type(utils::metric::OPENMETRICS_CONTENT_TYPE)
+      .body(metrics),
+  )
+}
+
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(list_metric);
 }
@@ -57,10 +71,29 @@ mod tests {
     Ok(())
   }
 
+  async fn test_export(srv: &TestServer) -> TestRet {
+    let mut resp = srv.get("/metrics").send().await?;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let content_type = resp
+      .headers()
+      .get(http::header::CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or_default()
+      .to_owned();
+    assert!(content_type.starts_with("application/openmetrics-text"));
+    let body = resp.body().await?;
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("# TYPE nanocl_cargo_instances gauge"));
+    assert!(body.contains("nanocl_db_pool_max_connections "));
+    assert!(body.ends_with("# EOF\n"));
+    Ok(())
+  }
+
   #[ntex::test]
   async fn basic() -> TestRet {
     let srv = gen_server(ntex_config).await;
     test_list(&srv).await?;
+    test_export(&srv).await?;
     Ok(())
   }
 }
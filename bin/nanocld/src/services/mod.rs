@@ -21,6 +21,7 @@ mod http_metric;
 mod vm;
 mod vm_image;
 mod secret;
+mod job;
 
 pub async fn unhandled() -> Result<web::HttpResponse, HttpError> {
   Err(HttpError {
@@ -52,6 +53,9 @@ pub fn ntex_config(config: &mut web::ServiceConfig) {
     );
   }
 
+  // Registered before the versioned scope to be scraped on `/metrics`
+  config.service(metric::export_metric);
+
   let versioning = middlewares::Versioning::new(version::VERSION).finish();
 
   config.service(
@@ -69,7 +73,8 @@ pub fn ntex_config(config: &mut web::ServiceConfig) {
       .configure(vm::ntex_config)
       .configure(metric::ntex_config)
       .configure(http_metric::ntex_config)
-      .configure(secret::ntex_config),
+      .configure(secret::ntex_config)
+      .configure(job::ntex_config),
   );
 }
 
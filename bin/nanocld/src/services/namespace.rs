@@ -3,6 +3,7 @@
 */
 use ntex::web;
 
+use nanocl_stubs::system::Event;
 use nanocl_stubs::namespace::{NamespacePartial, NamespaceListQuery};
 
 use crate::{utils, repositories};
@@ -73,6 +74,10 @@ pub(crate) async fn create_namespace(
   state: web::types::State<DaemonState>,
 ) -> Result<web::HttpResponse, HttpError> {
   let item = utils::namespace::create(&payload, &state).await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::NamespaceCreated(item.name.clone()))
+    .await;
   Ok(web::HttpResponse::Created().json(&item))
 }
 
@@ -96,6 +101,10 @@ pub(crate) async fn delete_namespace(
 ) -> Result<web::HttpResponse, HttpError> {
   repositories::namespace::find_by_name(&path.1, &state.pool).await?;
   let res = utils::namespace::delete_by_name(&path.1, &state).await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::NamespaceDeleted(path.1.clone()))
+    .await;
   Ok(web::HttpResponse::Ok().json(&res))
 }
 
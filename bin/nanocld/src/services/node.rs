@@ -13,7 +13,7 @@ use futures::future::ready;
 
 use crate::{utils, repositories};
 use nanocl_utils::http_error::HttpError;
-use crate::models::{DaemonState, WsConState};
+use crate::models::{DaemonState, NodeCommand, WsConState};
 
 /// List nodes
 #[cfg_attr(feature = "dev", utoipa::path(
@@ -51,6 +51,7 @@ async fn node_ws_service(
 
   // handler service for incoming websockets frames
   let service = fn_service(move |frame| {
+    let state = state.clone();
     let item = match frame {
       ws::Frame::Ping(msg) => {
         con_state.borrow_mut().hb = Instant::now();
@@ -61,7 +62,18 @@ async fn node_ws_service(
         con_state.borrow_mut().hb = Instant::now();
         None
       }
-      ws::Frame::Text(_text) => None,
+      ws::Frame::Text(text) => {
+        // Commands are sent by the node owning a cargo
+        if let Ok(command) = serde_json::from_slice::<NodeCommand>(&text) {
+          rt::spawn(async move {
+            if let Err(err) = utils::node::exec_command(&command, &state).await
+            {
+              log::warn!("Unable to execute node command: {err}");
+            }
+          });
+        }
+        None
+      }
       ws::Frame::Binary(_bytes) => {
         // println!("[SERVER] received bytes: {:#?}", bytes);
         None
@@ -82,18 +94,30 @@ async fn node_ws_service(
 }
 
 /// Websocket endpoint for communication between nodes used internally
+/// The connecting node must be authenticated by a token signed with the shared cluster token
 #[cfg_attr(feature = "dev", utoipa::path(
   get,
   tag = "Nodes",
   path = "/nodes/ws",
+  params(
+    ("X-Nanocl-Node-Auth" = String, Header, description = "Token of the connecting node"),
+  ),
   responses(
     (status = 101, description = "Websocket connection"),
+    (status = 401, description = "Missing or invalid node token", body = ApiError),
   ),
 ))]
 async fn node_ws(
   req: web::HttpRequest,
   state: web::types::State<DaemonState>,
 ) -> Result<web::HttpResponse, web::Error> {
+  let token = req
+    .headers()
+    .get(utils::node::NODE_AUTH_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .ok_or_else(|| HttpError::unauthorized("Missing node token"))?;
+  let node_name = utils::node::verify_auth_token(token, &state).await?;
+  log::debug!("Node {node_name} connected");
   web::ws::start(
     req,
     // inject chat server send to a ws_service factory
@@ -108,3 +132,25 @@ pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(list_node);
   config.service(web::resource("/nodes/ws").route(web::get().to(node_ws)));
 }
+
+#[cfg(test)]
+mod tests {
+  use ntex::http;
+
+  use crate::services::ntex_config;
+  use crate::utils::tests::*;
+
+  #[ntex::test]
+  async fn ws_requires_token() -> TestRet {
+    let srv = gen_server(ntex_config).await;
+    let res = srv.get("/v0.10/nodes/ws").send().await?;
+    assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+    let res = srv
+      .get("/v0.10/nodes/ws")
+      .header("X-Nanocl-Node-Auth", "node:0:Zm9yZ2Vk")
+      .send()
+      .await?;
+    assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+    Ok(())
+  }
+}
@@ -31,23 +31,31 @@ use bollard_next::service::{
 };
 use nanocl_stubs::config::DaemonConfig;
 use nanocl_stubs::secret::{Secret, SecretPartial, SecretUpdate};
+use nanocl_stubs::job::{Job, JobPartial, JobRun, JobRunStatus, JobInspect};
 use nanocl_stubs::generic::GenericCount;
 use nanocl_stubs::system::{Version, HostInfo};
-use nanocl_stubs::metric::{Metric, MetricKind};
-use nanocl_stubs::http_metric::HttpMetric;
+use nanocl_stubs::metric::{Metric, MetricKind, CargoMetric};
+use nanocl_stubs::http_metric::{
+  HttpMetric, ProxyLogBatch, ProxyLogIngestion, HttpMetricGroupBy,
+  HttpMetricStats,
+};
 use nanocl_stubs::vm_image::{VmImage, VmImageResizePayload};
-use nanocl_stubs::generic::GenericDelete;
+use nanocl_stubs::generic::{
+  GenericDelete, GenericDependency, GenericDependencyCondition,
+};
 use nanocl_stubs::node::{Node, NodeContainerSummary};
 use nanocl_stubs::namespace::{
   Namespace, NamespaceSummary, NamespacePartial, NamespaceInspect,
 };
 use nanocl_stubs::cargo::{
   Cargo, CargoInspect, CargoSummary, CargoKillOptions, CreateExecOptions,
-  CargoScale, CargoStats,
+  CargoScale, CargoStats, CargoReconcileStatus, CargoRollbackStatus,
+  CargoScaleHistory,
 };
 use nanocl_stubs::cargo_config::{
   CargoConfig, CargoConfigPartial, CargoConfigUpdate, ReplicationMode,
-  ReplicationStatic,
+  ReplicationStatic, UpdateStrategy, CargoRollback, CargoAutoscale,
+  CargoContainer, CargoSecretFile,
 };
 use nanocl_stubs::cargo_image::CargoImagePartial;
 use nanocl_stubs::vm::{Vm, VmInspect, VmSummary};
@@ -62,15 +70,20 @@ use nanocl_stubs::proxy::{
   ResourceProxyRule, ProxyRuleHttp, ProxyHttpLocation, ProxySslConfig,
   ProxyRuleStream, StreamTarget, ProxyStreamProtocol, UriTarget,
   LocationTarget, HttpTarget, UrlRedirect, UpstreamTarget, ProxyRule,
-  UnixTarget, ProxySsl,
+  UnixTarget, ProxySsl, ProxySslAcme, ProxyAcmeConfig, SplitTarget,
+  WeightedTarget, CanaryTarget, RouteMatch, ProxyHealthCheck,
+  ProxyUpstreamHealth, ProxyRateLimit, ProxyBasicAuth, ProxyBasicAuthUser,
+  ProxyCache, ProxyCacheZone, ProxyCacheValid, ProxyCompression,
+  ProxyHttpProtocol, ProxyTimeouts, ProxyUpstreamTls,
 };
 use nanocl_stubs::state::{
   StateMeta, StateCargo, StateVirtualMachine, StateResource, StateDeployment,
+  StateJob,
 };
 
 use super::{
   node, system, namespace, exec, cargo, cargo_image, vm, vm_image, resource,
-  metric, http_metric, secret,
+  metric, http_metric, secret, job,
 };
 
 /// When returning a [HttpError](HttpError) the status code is stripped and the error is returned as a json object with the message field set to the error message.
@@ -217,6 +230,7 @@ impl Modify for VersionModifier {
     // System
     system::get_info,
     system::watch_event,
+    system::report_proxy_upstream_health,
     system::get_processes,
     system::get_version,
     system::get_ping,
@@ -231,6 +245,14 @@ impl Modify for VersionModifier {
     secret::create_secret,
     secret::delete_secret,
     secret::patch_secret,
+    secret::rotate_secret_key,
+    // Job
+    job::list_job,
+    job::inspect_job,
+    job::create_job,
+    job::put_job,
+    job::delete_job,
+    job::start_job,
     // Cargo
     cargo::list_cargo,
     cargo::list_cargo_instance,
@@ -248,6 +270,7 @@ impl Modify for VersionModifier {
     cargo::logs_cargo,
     cargo::scale_cargo,
     cargo::stats_cargo,
+    cargo::list_cargo_scale_history,
     // Exec
     exec::create_exec_command,
     exec::start_exec_command,
@@ -288,6 +311,8 @@ impl Modify for VersionModifier {
     // Http Metric
     http_metric::list_http_metric,
     http_metric::count_http_metric,
+    http_metric::stats_http_metric,
+    http_metric::ingest_http_metric,
   ),
   components(schemas(
     // Node
@@ -297,6 +322,12 @@ impl Modify for VersionModifier {
     Secret,
     SecretPartial,
     SecretUpdate,
+    // Job
+    Job,
+    JobPartial,
+    JobRun,
+    JobRunStatus,
+    JobInspect,
     // System
     Version,
     HostInfo,
@@ -345,7 +376,15 @@ impl Modify for VersionModifier {
     CargoConfigPartial,
     CargoConfigUpdate,
     ReplicationStatic,
+    UpdateStrategy,
+    CargoRollback,
+    CargoAutoscale,
+    CargoContainer,
+    CargoSecretFile,
     CargoScale,
+    CargoReconcileStatus,
+    CargoRollbackStatus,
+    CargoScaleHistory,
     CargoStats,
     PidsStats,
     NetworkStats,
@@ -433,6 +472,7 @@ impl Modify for VersionModifier {
     StateVirtualMachine,
     StateResource,
     StateDeployment,
+    StateJob,
     // ProxyRules
     ResourceProxyRule,
     ProxyRule,
@@ -448,15 +488,38 @@ impl Modify for VersionModifier {
     UpstreamTarget,
     UnixTarget,
     UriTarget,
+    SplitTarget,
+    WeightedTarget,
+    CanaryTarget,
+    RouteMatch,
+    ProxyHealthCheck,
+    ProxyUpstreamHealth,
+    ProxyRateLimit,
+    ProxyBasicAuth,
+    ProxyBasicAuthUser,
+    ProxyCache,
+    ProxyCacheZone,
+    ProxyCacheValid,
+    ProxyCompression,
+    ProxyHttpProtocol,
+    ProxyTimeouts,
+    ProxyUpstreamTls,
     ProxySsl,
+    ProxySslAcme,
+    ProxyAcmeConfig,
     // DnsRules
     ResourceDnsRule,
     DnsEntry,
     // Metric
     Metric,
     MetricKind,
+    CargoMetric,
     // HttpMetric
     HttpMetric,
+    ProxyLogBatch,
+    ProxyLogIngestion,
+    HttpMetricGroupBy,
+    HttpMetricStats,
     // Daemon
     DaemonConfig,
     // Error
@@ -467,6 +530,8 @@ impl Modify for VersionModifier {
     BollardDate,
     EmptyObject,
     GenericDelete,
+    GenericDependency,
+    GenericDependencyCondition,
   )),
   tags(
     (name = "CargoImages", description = "Cargo images management endpoints."),
@@ -5,10 +5,10 @@ use ntex::rt;
 use ntex::web;
 
 use nanocl_stubs::system::Event;
-use nanocl_stubs::proxy::ProxySslConfig;
+use nanocl_stubs::proxy::{ProxySslConfig, ProxyBasicAuth};
 use nanocl_stubs::secret::{Secret, SecretPartial, SecretUpdate};
 
-use crate::repositories;
+use crate::{utils, repositories};
 use crate::models::DaemonState;
 
 use nanocl_utils::http_error::HttpError;
@@ -31,7 +31,11 @@ use nanocl_utils::http_error::HttpError;
 pub(crate) async fn list_secret(
   state: web::types::State<DaemonState>,
 ) -> Result<web::HttpResponse, HttpError> {
-  let items = repositories::secret::list(&state.pool).await?;
+  let items = utils::secret::list(&state)
+    .await?
+    .into_iter()
+    .map(Secret::from)
+    .collect::<Vec<_>>();
   Ok(web::HttpResponse::Ok().json(&items))
 }
 
@@ -53,7 +57,8 @@ pub(crate) async fn inspect_secret(
   path: web::types::Path<(String, String)>,
   state: web::types::State<DaemonState>,
 ) -> Result<web::HttpResponse, HttpError> {
-  let secret = repositories::secret::find_by_key(&path.1, &state.pool).await?;
+  let secret: Secret =
+    utils::secret::inspect_by_key(&path.1, &state).await?.into();
   Ok(web::HttpResponse::Ok().json(&secret))
 }
 
@@ -92,18 +97,27 @@ pub(crate) async fn create_secret(
         },
       )?;
     }
+    "BasicAuth" => {
+      serde_json::from_value::<ProxyBasicAuth>(payload.data.clone()).map_err(
+        |e| {
+          HttpError::bad_request(format!(
+            "Invalid data for secret of kind BasicAuth: {e}",
+          ))
+        },
+      )?;
+    }
     _ => {}
   }
 
-  let item = repositories::secret::create(&payload, &state.pool).await?;
-  let secret = item.clone().into();
+  let secret: Secret = utils::secret::create(&payload, &state).await?.into();
+  let event_secret = secret.clone();
   rt::spawn(async move {
     let _ = state
       .event_emitter
-      .emit(Event::SecretCreated(Box::new(secret)))
+      .emit(Event::SecretCreated(Box::new(event_secret)))
       .await;
   });
-  Ok(web::HttpResponse::Created().json(&item))
+  Ok(web::HttpResponse::Created().json(&secret))
 }
 
 /// Delete a secret
@@ -124,8 +138,10 @@ pub(crate) async fn delete_secret(
   path: web::types::Path<(String, String)>,
   state: web::types::State<DaemonState>,
 ) -> Result<web::HttpResponse, HttpError> {
-  let secret = repositories::secret::find_by_key(&path.1, &state.pool).await?;
+  let secret = utils::secret::inspect_by_key(&path.1, &state).await?;
   let res = repositories::secret::delete_by_key(&path.1, &state.pool).await?;
+  repositories::state_ref::delete_by_key("Secret", &path.1, &state.pool)
+    .await?;
   rt::spawn(async move {
     let _ = state
       .event_emitter
@@ -156,21 +172,41 @@ async fn patch_secret(
   path: web::types::Path<(String, String)>,
   state: web::types::State<DaemonState>,
 ) -> Result<web::HttpResponse, HttpError> {
-  let item =
-    repositories::secret::update_by_key(&path.1, &payload, &state.pool).await?;
-  let secret: Secret = item.clone().into();
+  let secret: Secret = utils::secret::patch_by_key(&path.1, &payload, &state)
+    .await?
+    .into();
+  let event_secret = secret.clone();
   rt::spawn(async move {
     let _ = state
       .event_emitter
-      .emit(Event::SecretPatched(Box::new(secret)))
+      .emit(Event::SecretPatched(Box::new(event_secret)))
       .await;
   });
-  Ok(web::HttpResponse::Ok().json(&item))
+  Ok(web::HttpResponse::Ok().json(&secret))
+}
+
+/// Rotate the master key encrypting the secrets
+#[cfg_attr(feature = "dev", utoipa::path(
+  post,
+  tag = "Secrets",
+  path = "/secrets/rotate-key",
+  responses(
+    (status = 200, description = "Number of re-encrypted secrets", body = GenericCount),
+    (status = 400, description = "The master key is set by the environment", body = ApiError),
+  ),
+))]
+#[web::post("/secrets/rotate-key")]
+pub(crate) async fn rotate_secret_key(
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  let count = utils::secret::rotate_key(&state).await?;
+  Ok(web::HttpResponse::Ok().json(&count))
 }
 
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(list_secret);
   config.service(create_secret);
+  config.service(rotate_secret_key);
   config.service(inspect_secret);
   config.service(delete_secret);
   config.service(patch_secret);
@@ -182,7 +218,7 @@ mod test_secret {
 
   use serde_json::json;
 
-  use nanocl_stubs::secret::SecretPartial;
+  use nanocl_stubs::secret::{Secret, SecretPartial};
   use nanocl_stubs::generic::GenericDelete;
 
   use crate::utils::tests::*;
@@ -224,16 +260,34 @@ mod test_secret {
     let resp = srv.post("/v0.10/secrets").send().await?;
 
     assert!(resp.status().is_client_error());
+
+    let resp = srv
+      .post("/v0.10/secrets")
+      .send_json(&json!({
+          "Key": "test-basic-auth",
+          "Kind": "BasicAuth",
+          "Data": { "Users": [ { "Name": "admin" } ] },
+      }))
+      .await?;
+
+    assert_eq!(resp.status(), 400);
     Ok(())
   }
 
   async fn test_inspect_by_id(srv: &TestServer) -> TestRet {
-    let resp = srv
+    let mut resp = srv
       .get(format!("/v0.10/secrets/{key}/inspect", key = "test-secret"))
       .send()
       .await?;
 
     assert!(resp.status().is_success());
+    let body = resp.json::<Secret>().await?;
+    assert_eq!(
+      body.data,
+      json!({
+        "Tls": { "cert": "MY CERT", "key": "MY KEY" },
+      })
+    );
     Ok(())
   }
 
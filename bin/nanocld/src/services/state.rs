@@ -4,6 +4,7 @@ use ntex::channel::mpsc;
 
 use crate::utils;
 use nanocl_utils::http_error::HttpError;
+use nanocl_stubs::state::StateApplyQuery;
 use crate::models::{StateData, DaemonState};
 
 #[web::put("/state/apply")]
@@ -11,45 +12,64 @@ pub(crate) async fn apply(
   web::types::Json(payload): web::types::Json<serde_json::Value>,
   version: web::types::Path<String>,
   state: web::types::State<DaemonState>,
+  qs: web::types::Query<StateApplyQuery>,
 ) -> Result<web::HttpResponse, HttpError> {
+  let meta = utils::state::parse_meta(&payload)?;
   let state_file = utils::state::parse_state(&payload)?;
+  let qs = qs.into_inner();
+  if qs.prune.unwrap_or_default() && meta.name.is_none() {
+    return Err(HttpError::bad_request(
+      "Prune require a Name in the Statefile",
+    ));
+  }
+  utils::state::validate_dependencies(&state_file)?;
+  let dry_run = qs.dry_run.unwrap_or_default();
+  let refs = utils::state::gen_refs(&state_file);
   let (sx, rx) = mpsc::channel::<Result<Bytes, HttpError>>();
 
   rt::spawn(async move {
-    match state_file {
+    let res = match state_file {
       StateData::Deployment(data) => {
-        if let Err(err) =
-          utils::state::apply_deployment(&data, &version, &state, sx).await
-        {
-          log::warn!("{err}");
-        }
+        utils::state::apply_deployment(
+          &data,
+          &version,
+          dry_run,
+          &state,
+          sx.clone(),
+        )
+        .await
       }
       StateData::Cargo(data) => {
-        if let Err(err) =
-          utils::state::apply_cargo(&data, &version, &state, sx).await
-        {
-          log::warn!("{err}");
-        }
+        utils::state::apply_cargo(&data, &version, dry_run, &state, sx.clone())
+          .await
       }
       StateData::VirtualMachine(data) => {
-        if let Err(err) =
-          utils::state::apply_vm(&data, &version, &state, sx).await
-        {
-          log::warn!("{err}");
-        }
+        utils::state::apply_vm(&data, &version, dry_run, &state, sx.clone())
+          .await
       }
       StateData::Resource(data) => {
-        if let Err(err) = utils::state::apply_resource(&data, &state, sx).await
-        {
-          log::warn!("{err}");
-        }
+        utils::state::apply_resource(&data, dry_run, &state, sx.clone()).await
       }
       StateData::Secret(data) => {
-        if let Err(err) = utils::state::apply_secret(&data, &state, sx).await {
-          log::warn!("{err}");
-        }
+        utils::state::apply_secret(&data, dry_run, &state, sx.clone()).await
+      }
+      StateData::Job(data) => {
+        utils::state::apply_job(&data, dry_run, &state, sx.clone()).await
+      }
+    };
+    // Nothing is labeled or pruned when the Statefile couldn't be applied
+    let applied = match res {
+      Ok(applied) => applied,
+      Err(err) => {
+        log::warn!("{err}");
+        return;
       }
     };
+    if let Err(err) =
+      utils::state::apply_refs(&meta, &refs, &applied, &qs, &state, sx).await
+    {
+      log::warn!("{err}");
+    }
   });
 
   Ok(
@@ -97,6 +117,11 @@ pub(crate) async fn remove(
           log::warn!("{err}");
         }
       }
+      StateData::Job(data) => {
+        if let Err(err) = utils::state::remove_job(&data, &state, sx).await {
+          log::warn!("{err}");
+        }
+      }
     };
   });
 
@@ -116,6 +141,8 @@ pub fn ntex_config(cfg: &mut web::ServiceConfig) {
 mod tests {
   use futures::{TryStreamExt, StreamExt};
 
+  use nanocl_stubs::state::{StateStream, StateStreamStatus, StateApplyQuery};
+
   use crate::services::ntex_config;
 
   use crate::utils::tests::*;
@@ -143,6 +170,135 @@ mod tests {
       item.expect("Correct response");
     }
 
+    // Dry run examples/cargo_example.yml with a changed image
+    let mut data = parse_statefile("../../examples/cargo_example.yml")?;
+    data["Cargoes"][0]["Container"]["Image"] =
+      serde_json::Value::String("nexthat/nanocl-get-started:dry-run".into());
+    let req = srv
+      .put("/v0.5/state/apply")
+      .query(&StateApplyQuery {
+        dry_run: Some(true),
+        prune: None,
+      })
+      .unwrap()
+      .send_json(&data)
+      .await
+      .unwrap();
+    assert_eq!(req.status(), 200);
+    let mut stream = req.into_stream();
+    let mut payload = Vec::new();
+    while let Some(item) = stream.next().await {
+      payload.extend_from_slice(&item.expect("Correct response"));
+    }
+    let updates = std::str::from_utf8(&payload)
+      .unwrap()
+      .split("\r\n")
+      .filter(|line| !line.is_empty())
+      .map(|line| serde_json::from_str::<StateStream>(line).unwrap())
+      .filter(|stream| stream.status == StateStreamStatus::Update)
+      .collect::<Vec<_>>();
+    assert_eq!(updates.len(), 1, "Expect one cargo to be updated");
+    let diff = updates[0].diff.clone().unwrap_or_default();
+    assert!(
+      diff.iter().any(|diff| diff.path == "Container.Image"),
+      "Expect the image to be in the diff"
+    );
+
+    // Apply a named examples/cargo_example.yml then prune his cargo
+    let mut data = parse_statefile("../../examples/cargo_example.yml")?;
+    data["Name"] = serde_json::Value::String("cargo-example".into());
+    let req = srv.put("/v0.5/state/apply").send_json(&data).await.unwrap();
+    assert_eq!(req.status(), 200);
+    let mut stream = req.into_stream();
+    while let Some(item) = stream.next().await {
+      item.expect("Correct response");
+    }
+    data["Cargoes"] = serde_json::Value::Array(Vec::new());
+    let req = srv
+      .put("/v0.5/state/apply")
+      .query(&StateApplyQuery {
+        dry_run: None,
+        prune: Some(true),
+      })
+      .unwrap()
+      .send_json(&data)
+      .await
+      .unwrap();
+    assert_eq!(req.status(), 200);
+    let mut stream = req.into_stream();
+    let mut payload = Vec::new();
+    while let Some(item) = stream.next().await {
+      payload.extend_from_slice(&item.expect("Correct response"));
+    }
+    let pruned = std::str::from_utf8(&payload)
+      .unwrap()
+      .split("\r\n")
+      .filter(|line| !line.is_empty())
+      .map(|line| serde_json::from_str::<StateStream>(line).unwrap())
+      .filter(|stream| stream.status == StateStreamStatus::Pruned)
+      .collect::<Vec<_>>();
+    assert_eq!(pruned.len(), 1, "Expect one cargo to be pruned");
+    assert_eq!(pruned[0].key, "cargo-example.cargo-example");
+
+    // A cargo that failed to be applied is not labeled so never pruned
+    let mut data = parse_statefile("../../examples/cargo_example.yml")?;
+    data["Name"] = serde_json::Value::String("cargo-failed".into());
+    data["Cargoes"][0]["Container"]["Image"] =
+      serde_json::Value::String("nexthat/nanocl-not-existing:404".into());
+    let req = srv.put("/v0.5/state/apply").send_json(&data).await.unwrap();
+    assert_eq!(req.status(), 200);
+    let mut stream = req.into_stream();
+    while let Some(item) = stream.next().await {
+      item.expect("Correct response");
+    }
+    data["Cargoes"] = serde_json::Value::Array(Vec::new());
+    let req = srv
+      .put("/v0.5/state/apply")
+      .query(&StateApplyQuery {
+        dry_run: None,
+        prune: Some(true),
+      })
+      .unwrap()
+      .send_json(&data)
+      .await
+      .unwrap();
+    assert_eq!(req.status(), 200);
+    let mut stream = req.into_stream();
+    let mut payload = Vec::new();
+    while let Some(item) = stream.next().await {
+      payload.extend_from_slice(&item.expect("Correct response"));
+    }
+    let pruned = std::str::from_utf8(&payload)
+      .unwrap()
+      .split("\r\n")
+      .filter(|line| !line.is_empty())
+      .map(|line| serde_json::from_str::<StateStream>(line).unwrap())
+      .filter(|stream| stream.status == StateStreamStatus::Pruned)
+      .count();
+    assert_eq!(pruned, 0, "Expect the failed cargo to not be labeled");
+
+    // Prune without a Statefile name is refused
+    let data = parse_statefile("../../examples/cargo_example.yml")?;
+    let req = srv
+      .put("/v0.5/state/apply")
+      .query(&StateApplyQuery {
+        dry_run: None,
+        prune: Some(true),
+      })
+      .unwrap()
+      .send_json(&data)
+      .await
+      .unwrap();
+    assert_eq!(req.status(), 400);
+
+    // A cargo depending on itself is refused
+    let mut data = parse_statefile("../../examples/cargo_example.yml")?;
+    data["Cargoes"][0]["DependsOn"] = serde_json::json!([{
+      "Name": data["Cargoes"][0]["Name"].clone(),
+    }]);
+    let req = srv.put("/v0.5/state/apply").send_json(&data).await.unwrap();
+    assert_eq!(req.status(), 400);
+
     // Revert examples/cargo_example.yml
     let data = parse_statefile("../../examples/cargo_example.yml")?;
     let req = srv
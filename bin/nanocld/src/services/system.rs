@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use ntex::rt;
 use ntex::web;
 
 use crate::version;
@@ -7,7 +8,8 @@ use crate::version;
 use bollard_next::container::ListContainersOptions;
 
 use nanocl_stubs::node::NodeContainerSummary;
-use nanocl_stubs::system::{HostInfo, ProccessQuery};
+use nanocl_stubs::proxy::ProxyUpstreamHealth;
+use nanocl_stubs::system::{Event, HostInfo, ProccessQuery};
 
 use crate::repositories;
 use nanocl_utils::http_error::HttpError;
@@ -91,6 +93,31 @@ pub(crate) async fn watch_event(
   )
 }
 
+/// Report the health of an upstream instance, used by the proxy
+/// to broadcast the instances removed from or added back to an upstream
+#[cfg_attr(feature = "dev", utoipa::path(
+  post,
+  tag = "System",
+  request_body = ProxyUpstreamHealth,
+  path = "/events/proxy-upstream-health",
+  responses(
+    (status = 202, description = "The health change has been broadcasted"),
+  ),
+))]
+#[web::post("/events/proxy-upstream-health")]
+pub(crate) async fn report_proxy_upstream_health(
+  web::types::Json(payload): web::types::Json<ProxyUpstreamHealth>,
+  state: web::types::State<DaemonState>,
+) -> Result<web::HttpResponse, HttpError> {
+  rt::spawn(async move {
+    let _ = state
+      .event_emitter
+      .emit(Event::ProxyUpstreamHealthChanged(Box::new(payload)))
+      .await;
+  });
+  Ok(web::HttpResponse::Accepted().finish())
+}
+
 /// List instances (cargo/vm) including non running ones
 #[cfg_attr(feature = "dev", utoipa::path(
   get,
@@ -164,6 +191,7 @@ pub(crate) async fn get_processes(
 
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(watch_event);
+  config.service(report_proxy_upstream_health);
   config.service(get_info);
   config.service(get_processes);
   config.service(get_ping);
@@ -176,6 +204,7 @@ mod tests {
 
   use ntex::http;
   use nanocl_stubs::system::HostInfo;
+  use nanocl_stubs::proxy::ProxyUpstreamHealth;
 
   use crate::utils::tests::*;
 
@@ -194,6 +223,22 @@ mod tests {
     Ok(())
   }
 
+  #[ntex::test]
+  async fn report_proxy_upstream_health() -> TestRet {
+    let srv = gen_server(ntex_config).await;
+    let resp = srv
+      .post("/v0.2/events/proxy-upstream-health")
+      .send_json(&ProxyUpstreamHealth {
+        target_key: "nstore.system.c".into(),
+        port: 2379,
+        address: "127.0.0.1".into(),
+        healthy: false,
+      })
+      .await?;
+    assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+    Ok(())
+  }
+
   #[ntex::test]
   async fn system_info() -> TestRet {
     let srv = gen_server(ntex_config).await;
@@ -17,6 +17,7 @@ use futures::StreamExt;
 use futures::future::ready;
 use bollard_next::container::AttachContainerOptions;
 
+use nanocl_stubs::system::Event;
 use nanocl_stubs::cargo::OutputLog;
 use nanocl_stubs::generic::GenericNspQuery;
 use nanocl_stubs::vm_config::{VmConfigPartial, VmConfigUpdate};
@@ -107,6 +108,12 @@ pub(crate) async fn start_vm(
 
   repositories::vm::find_by_key(&key, &state.pool).await?;
   utils::vm::start_by_key(&key, &state.docker_api).await?;
+  let vm =
+    utils::vm::inspect_by_key(&key, &state.docker_api, &state.pool).await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::VmStarted(Box::new(vm)))
+    .await;
 
   Ok(web::HttpResponse::Ok().finish())
 }
@@ -136,6 +143,12 @@ pub(crate) async fn stop_vm(
 
   repositories::vm::find_by_key(&key, &state.pool).await?;
   utils::vm::stop_by_key(&key, &state.docker_api, &state.pool).await?;
+  let vm =
+    utils::vm::inspect_by_key(&key, &state.docker_api, &state.pool).await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::VmStopped(Box::new(vm)))
+    .await;
 
   Ok(web::HttpResponse::Ok().finish())
 }
@@ -163,7 +176,13 @@ pub(crate) async fn delete_vm(
   let namespace = utils::key::resolve_nsp(&qs.namespace);
   let key = utils::key::gen_key(&namespace, &name);
 
+  let vm =
+    utils::vm::inspect_by_key(&key, &state.docker_api, &state.pool).await?;
   utils::vm::delete_by_key(&key, true, &state.docker_api, &state.pool).await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::VmDeleted(Box::new(vm)))
+    .await;
 
   Ok(web::HttpResponse::Ok().finish())
 }
@@ -191,6 +210,12 @@ pub(crate) async fn create_vm(
   let namespace = utils::key::resolve_nsp(&qs.namespace);
 
   let item = utils::vm::create(&payload, &namespace, &version, &state).await?;
+  let vm = utils::vm::inspect_by_key(&item.key, &state.docker_api, &state.pool)
+    .await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::VmCreated(Box::new(vm)))
+    .await;
 
   Ok(web::HttpResponse::Ok().json(&item))
 }
@@ -248,6 +273,12 @@ pub(crate) async fn patch_vm(
   let version = path.0.clone();
 
   let vm = utils::vm::patch(&key, &payload, &version, &state).await?;
+  let inspect =
+    utils::vm::inspect_by_key(&key, &state.docker_api, &state.pool).await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::VmPatched(Box::new(inspect)))
+    .await;
 
   Ok(web::HttpResponse::Ok().json(&vm))
 }
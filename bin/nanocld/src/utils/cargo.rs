@@ -1,7 +1,11 @@
-use std::collections::HashMap;
+use std::sync::MutexGuard;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
 
 use ntex::rt;
+use ntex::time;
 use ntex::util::Bytes;
+use ntex::channel::mpsc;
 use futures::{StreamExt, TryStreamExt};
 use futures_util::TryFutureExt;
 use futures_util::stream::FuturesUnordered;
@@ -11,8 +15,10 @@ use bollard_next::container::LogOutput;
 use bollard_next::container::WaitContainerOptions;
 use bollard_next::service::{ContainerSummary, HostConfig};
 use bollard_next::service::{RestartPolicy, RestartPolicyNameEnum};
+use bollard_next::service::{ContainerStateStatusEnum, HealthStatusEnum};
 use bollard_next::container::{
   ListContainersOptions, RemoveContainerOptions, Stats,
+  InspectContainerOptions, KillContainerOptions,
 };
 
 use nanocl_utils::http_error::HttpError;
@@ -20,18 +26,148 @@ use nanocl_stubs::node::NodeContainerSummary;
 use nanocl_stubs::cargo::{
   Cargo, CargoSummary, CargoInspect, OutputLog, CargoLogQuery,
   CargoKillOptions, GenericCargoListQuery, CargoScale, CargoStats,
-  CargoStatsQuery,
+  CargoStatsQuery, CargoReconcileStatus, CargoRollbackStatus,
 };
+use nanocl_stubs::system::Event;
+use nanocl_stubs::state::StateStream;
 use nanocl_stubs::cargo_config::{
-  CargoConfigPartial, CargoConfigUpdate, ReplicationMode,
-  Config as ContainerConfig,
+  CargoConfigPartial, CargoConfigUpdate, ReplicationMode, UpdateStrategy,
+  CargoRollback, CargoContainer, Config as ContainerConfig,
 };
 
 use crate::{utils, repositories};
-use crate::models::DaemonState;
+use crate::models::{DaemonState, NodeCommand, CargoScaleHistoryDbModel};
 
 use super::stream::transform_stream;
 
+/// ## Gen instance name
+///
+/// Generate the container name of a cargo instance for the given index
+/// Example: cargo-key.c, 1-cargo-key.c, 2-cargo-key.c
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [index](usize) - The index of the instance
+///
+/// ## Returns
+///
+/// - [String](String) - The container name
+///
+fn gen_instance_name(key: &str, index: usize) -> String {
+  if index > 0 {
+    format!("{index}-{key}.c")
+  } else {
+    format!("{key}.c")
+  }
+}
+
+/// ## Secret envs
+///
+/// Fetch the environment variables of the given secrets.
+/// Secrets that are not of kind `Env` are ignored.
+///
+/// ## Arguments
+///
+/// - [secrets](Vec<String>) - The keys of the secrets
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<String>) - The environment variables
+///   - [Err](HttpError) - A secret is missing or invalid
+///
+pub(crate) async fn secret_envs(
+  secrets: &[String],
+  state: &DaemonState,
+) -> Result<Vec<String>, HttpError> {
+  let fetched_secrets = secrets
+    .iter()
+    .map(|secret| async move {
+      let secret = utils::secret::inspect_by_key(secret, state).await?;
+      if secret.kind.as_str() != "Env" {
+        return Ok::<_, HttpError>(Vec::new());
+      }
+      let envs =
+        serde_json::from_value::<Vec<String>>(secret.data).map_err(|err| {
+          HttpError::internal_server_error(format!(
+            "Invalid secret data for secret {} {err}",
+            secret.key
+          ))
+        })?;
+      Ok::<_, HttpError>(envs)
+    })
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+  // Flatten the secrets
+  Ok(fetched_secrets.into_iter().flatten().collect())
+}
+
+/// ## Gen container config
+///
+/// Generate the config of a container managed by nanocl (cargo instance
+/// or job run) from the container config of the user.
+/// The labels, environment variables and binds are added to the existing ones
+/// and the container join the network of his namespace
+/// unless a network mode is set.
+///
+/// ## Arguments
+///
+/// - [container](ContainerConfig) - The container config of the user
+/// - [namespace](str) - The namespace of the container
+/// - [labels](&[(&str, &str)]) - The labels to add
+/// - [env](Vec<String>) - The environment variables to add
+/// - [binds](Vec<String>) - The binds to add
+/// - [restart_policy](Option<RestartPolicy>) - The restart policy
+///
+/// ## Returns
+///
+/// - [ContainerConfig](ContainerConfig) - The container config
+///
+pub(crate) fn gen_container_config(
+  container: &ContainerConfig,
+  namespace: &str,
+  labels: &[(&str, &str)],
+  env: Vec<String>,
+  binds: Vec<String>,
+  restart_policy: Option<RestartPolicy>,
+) -> ContainerConfig {
+  let mut config_labels = container.labels.clone().unwrap_or_default();
+  config_labels.insert("io.nanocl".into(), "enabled".into());
+  for (key, value) in labels {
+    config_labels.insert(key.to_string(), value.to_string());
+  }
+  config_labels.insert(
+    "com.docker.compose.project".into(),
+    format!("nanocl_{namespace}"),
+  );
+  let mut config_env = container.env.clone().unwrap_or_default();
+  config_env.extend(env);
+  let host_config = container.host_config.clone().unwrap_or_default();
+  let mut config_binds = host_config.binds.clone().unwrap_or_default();
+  config_binds.extend(binds);
+  ContainerConfig {
+    labels: Some(config_labels),
+    env: Some(config_env),
+    host_config: Some(HostConfig {
+      restart_policy,
+      binds: Some(config_binds),
+      network_mode: Some(
+        host_config
+          .network_mode
+          .clone()
+          .unwrap_or(namespace.to_owned()),
+      ),
+      ..host_config
+    }),
+    ..container.clone()
+  }
+}
+
 /// ## Create instances
 ///
 /// Create instances (containers) based on the cargo config
@@ -55,76 +191,53 @@ use super::stream::transform_stream;
 ///   - [Ok](()) - The containers has been created
 ///   - [Err](HttpError) - The containers has not been created
 ///
-async fn create_instances(
+pub(crate) async fn create_instances(
   cargo: &Cargo,
   start: usize,
   number: usize,
   state: &DaemonState,
 ) -> Result<Vec<ContainerCreateResponse>, HttpError> {
-  let mut secret_envs: Vec<String> = Vec::new();
-
-  if let Some(secrets) = &cargo.config.secrets {
-    let fetched_secrets = secrets
-      .iter()
-      .map(|secret| async move {
-        let secret =
-          repositories::secret::find_by_key(secret, &state.pool).await?;
-        if secret.kind.as_str() != "Env" {
-          return Ok::<_, HttpError>(Vec::new());
-        }
-        let envs = serde_json::from_value::<Vec<String>>(secret.data).map_err(
-          |err| {
-            HttpError::internal_server_error(format!(
-              "Invalid secret data for secret {} {err}",
-              secret.key
-            ))
-          },
-        )?;
-        Ok::<_, HttpError>(envs)
-      })
-      .collect::<FuturesUnordered<_>>()
-      .collect::<Vec<_>>()
-      .await
-      .into_iter()
-      .collect::<Result<Vec<_>, _>>()?;
-    // Flatten the secrets
-    secret_envs = fetched_secrets.into_iter().flatten().collect();
-  }
+  let secret_envs =
+    secret_envs(cargo.config.secrets.as_deref().unwrap_or_default(), state)
+      .await?;
   log::debug!("Using secret envs: {secret_envs:?}");
+  let secret_binds = utils::secret::write_cargo_files(
+    &cargo.key,
+    cargo.config.secret_files.as_deref().unwrap_or_default(),
+    None,
+    state,
+  )
+  .await?;
 
   (0..number)
     .collect::<Vec<usize>>()
     .into_iter()
     .map(move |current| {
       let secret_envs = secret_envs.clone();
+      let secret_binds = secret_binds.clone();
       async move {
-        let name = if current > 0 || start > 0 {
-          format!("{}-{}.c", current + start, cargo.key)
-        } else {
-          format!("{}.c", cargo.key)
-        };
+        let name = gen_instance_name(&cargo.key, current + start);
         let create_options = bollard_next::container::CreateContainerOptions {
           name: name.clone(),
           ..Default::default()
         };
-        // Add cargo label to the container to track it
-        let mut labels =
-          cargo.config.container.labels.to_owned().unwrap_or_default();
-        labels.insert("io.nanocl".into(), "enabled".into());
-        labels.insert("io.nanocl.c".into(), cargo.key.to_owned());
-        labels.insert("io.nanocl.n".into(), cargo.namespace_name.to_owned());
-        labels.insert("io.nanocl.cnsp".into(), cargo.namespace_name.to_owned());
-        labels.insert(
-          "com.docker.compose.project".into(),
-          format!("nanocl_{}", cargo.namespace_name),
-        );
+        let index = current + start;
+        let hostname = match cargo.config.container.hostname {
+          Some(ref hostname) => {
+            if index > 0 {
+              format!("{index}-{hostname}")
+            } else {
+              hostname.to_owned()
+            }
+          }
+          None => name.replace('.', "-"),
+        };
         let auto_remove = cargo
           .config
-          .to_owned()
           .container
           .host_config
-          .unwrap_or_default()
-          .auto_remove
+          .as_ref()
+          .and_then(|host_config| host_config.auto_remove)
           .unwrap_or(false);
         let restart_policy = if auto_remove {
           None
@@ -132,68 +245,45 @@ async fn create_instances(
           Some(
             cargo
               .config
-              .to_owned()
               .container
               .host_config
-              .unwrap_or_default()
-              .restart_policy
+              .as_ref()
+              .and_then(|host_config| host_config.restart_policy.clone())
               .unwrap_or(RestartPolicy {
                 name: Some(RestartPolicyNameEnum::ALWAYS),
                 maximum_retry_count: None,
               }),
           )
         };
-        let mut env = cargo.config.container.env.clone().unwrap_or_default();
-        // add secret_envs to env
-        env.extend(secret_envs.clone());
-        let hostname = match cargo.config.container.hostname {
-          Some(ref hostname) => {
-            if current > 0 {
-              format!("{current}-{hostname}")
-            } else {
-              hostname.to_owned()
-            }
-          }
-          None => name.replace('.', "-"),
-        };
+        let mut env = secret_envs;
         env.push(format!("NANOCL_CARGO_KEY={}", cargo.key));
         env.push(format!("NANOCL_CARGO_NAMESPACE={}", cargo.namespace_name));
-        env.push(format!("NANOCL_CARGO_INSTANCE={}", current));
-        // Merge the cargo config with the container config
-        // And set his network mode to the cargo namespace
-        let config = bollard_next::container::Config {
+        env.push(format!("NANOCL_CARGO_INSTANCE={}", index));
+        let config = gen_container_config(
+          &cargo.config.container,
+          &cargo.namespace_name,
+          &[
+            ("io.nanocl.c", &cargo.key),
+            ("io.nanocl.n", &cargo.namespace_name),
+            ("io.nanocl.cnsp", &cargo.namespace_name),
+          ],
+          env,
+          secret_binds,
+          restart_policy,
+        );
+        let config = ContainerConfig {
           attach_stderr: Some(true),
           attach_stdout: Some(true),
           tty: Some(true),
           hostname: Some(hostname),
-          labels: Some(labels),
-          env: Some(env),
-          host_config: Some(HostConfig {
-            restart_policy,
-            network_mode: Some(
-              cargo
-                .config
-                .to_owned()
-                .container
-                .host_config
-                .unwrap_or_default()
-                .network_mode
-                .unwrap_or(cargo.namespace_name.to_owned()),
-            ),
-            ..cargo
-              .config
-              .to_owned()
-              .container
-              .host_config
-              .unwrap_or_default()
-          }),
-          ..cargo.config.container.to_owned()
+          ..config
         };
         let res = state
           .docker_api
           .create_container::<String>(Some(create_options), config)
           .map_err(HttpError::from)
           .await?;
+        create_sidecars(cargo, &res.id, &state.docker_api).await?;
         Ok::<_, HttpError>(res)
       }
     })
@@ -235,6 +325,309 @@ pub async fn list_instances(
   Ok(containers)
 }
 
+/// ## Gen attached name
+///
+/// Generate the container name of an init container or a sidecar
+/// from the id of the instance it's attached to.
+/// Example: logger-4f0a2b6c8d1e-cargo-key.s
+///
+/// ## Arguments
+///
+/// - [name](str) - The name of the init container or sidecar
+/// - [instance_id](str) - The id of the instance
+/// - [key](str) - The cargo key
+/// - [suffix](str) - `i` for an init container, `s` for a sidecar
+///
+/// ## Returns
+///
+/// - [String](String) - The container name
+///
+fn gen_attached_name(
+  name: &str,
+  instance_id: &str,
+  key: &str,
+  suffix: &str,
+) -> String {
+  let short_id = instance_id.get(..12).unwrap_or(instance_id);
+  format!("{name}-{short_id}-{key}.{suffix}")
+}
+
+/// ## Gen attached config
+///
+/// Generate the container config of an init container or a sidecar
+/// attached to an instance of a cargo
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo
+/// - [attached](CargoContainer) - The init container or sidecar
+/// - [instance_id](str) - The id of the instance
+/// - [kind_label](str) - `io.nanocl.ci` for an init container, `io.nanocl.cs` for a sidecar
+/// - [network_mode](String) - The network mode of the container
+///
+/// ## Returns
+///
+/// - [ContainerConfig](ContainerConfig) - The container config
+///
+fn gen_attached_config(
+  cargo: &Cargo,
+  attached: &CargoContainer,
+  instance_id: &str,
+  kind_label: &str,
+  network_mode: String,
+) -> ContainerConfig {
+  let mut labels = attached.container.labels.clone().unwrap_or_default();
+  labels.insert("io.nanocl".into(), "enabled".into());
+  labels.insert(kind_label.into(), cargo.key.to_owned());
+  labels.insert("io.nanocl.csi".into(), instance_id.to_owned());
+  labels.insert("io.nanocl.n".into(), cargo.namespace_name.to_owned());
+  let mut env = attached.container.env.clone().unwrap_or_default();
+  env.push(format!("NANOCL_CARGO_KEY={}", cargo.key));
+  env.push(format!("NANOCL_CARGO_NAMESPACE={}", cargo.namespace_name));
+  ContainerConfig {
+    labels: Some(labels),
+    env: Some(env),
+    host_config: Some(HostConfig {
+      network_mode: Some(network_mode),
+      ..attached.container.host_config.clone().unwrap_or_default()
+    }),
+    ..attached.container.clone()
+  }
+}
+
+/// ## Create sidecars
+///
+/// Create the sidecars of a cargo instance.
+/// The sidecars share the network namespace of the instance.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo
+/// - [instance_id](str) - The id of the instance
+/// - [docker_api](bollard_next::Docker) - The docker api
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The sidecars has been created
+///   - [Err](HttpError) - The sidecars has not been created
+///
+async fn create_sidecars(
+  cargo: &Cargo,
+  instance_id: &str,
+  docker_api: &bollard_next::Docker,
+) -> Result<(), HttpError> {
+  for sidecar in cargo.config.sidecars.clone().unwrap_or_default() {
+    let name = gen_attached_name(&sidecar.name, instance_id, &cargo.key, "s");
+    let config = gen_attached_config(
+      cargo,
+      &sidecar,
+      instance_id,
+      "io.nanocl.cs",
+      format!("container:{instance_id}"),
+    );
+    let create_options = bollard_next::container::CreateContainerOptions {
+      name,
+      ..Default::default()
+    };
+    docker_api
+      .create_container(Some(create_options), config)
+      .await?;
+  }
+  Ok(())
+}
+
+/// ## List sidecars
+///
+/// List the sidecars (containers) of a cargo instance
+///
+/// ## Arguments
+///
+/// - [instance_id](str) - The id of the instance
+/// - [docker_api](bollard_next::Docker) - The docker api
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<ContainerSummary>) - The sidecars have been listed
+///   - [Err](HttpError) - The sidecars have not been listed
+///
+async fn list_sidecars(
+  instance_id: &str,
+  docker_api: &bollard_next::Docker,
+) -> Result<Vec<ContainerSummary>, HttpError> {
+  let label = format!("io.nanocl.csi={instance_id}");
+  let mut filters: HashMap<&str, Vec<&str>> = HashMap::new();
+  filters.insert("label", vec![&label, "io.nanocl.cs"]);
+  let options = Some(ListContainersOptions {
+    all: true,
+    filters,
+    ..Default::default()
+  });
+  let containers = docker_api.list_containers(options).await?;
+  Ok(containers)
+}
+
+/// ## Remove sidecars
+///
+/// Remove the sidecars of a cargo instance,
+/// nothing is done if the instance doesn't exist.
+///
+/// ## Arguments
+///
+/// - [instance](str) - The id or the name of the instance
+/// - [docker_api](bollard_next::Docker) - The docker api
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The sidecars has been removed
+///   - [Err](HttpError) - The sidecars has not been removed
+///
+async fn remove_sidecars(
+  instance: &str,
+  docker_api: &bollard_next::Docker,
+) -> Result<(), HttpError> {
+  let Ok(container) = docker_api
+    .inspect_container(instance, None::<InspectContainerOptions>)
+    .await
+  else {
+    return Ok(());
+  };
+  let id = container.id.unwrap_or_default();
+  for sidecar in list_sidecars(&id, docker_api).await? {
+    docker_api
+      .remove_container(
+        &sidecar.id.unwrap_or_default(),
+        Some(RemoveContainerOptions {
+          force: true,
+          ..Default::default()
+        }),
+      )
+      .await?;
+  }
+  Ok(())
+}
+
+/// ## Run init containers
+///
+/// Run the init containers of a cargo instance one after the other.
+/// Each init container is removed once finished
+/// and must exit with a success code for the next one to run.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo
+/// - [instance_id](str) - The id of the instance
+/// - [docker_api](bollard_next::Docker) - The docker api
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The init containers succeeded
+///   - [Err](HttpError) - An init container failed
+///
+async fn run_init_containers(
+  cargo: &Cargo,
+  instance_id: &str,
+  docker_api: &bollard_next::Docker,
+) -> Result<(), HttpError> {
+  let network_mode = cargo
+    .config
+    .container
+    .host_config
+    .clone()
+    .unwrap_or_default()
+    .network_mode
+    .unwrap_or(cargo.namespace_name.to_owned());
+  let remove_options = RemoveContainerOptions {
+    force: true,
+    ..Default::default()
+  };
+  for init in cargo.config.init_containers.clone().unwrap_or_default() {
+    let name = gen_attached_name(&init.name, instance_id, &cargo.key, "i");
+    let config = gen_attached_config(
+      cargo,
+      &init,
+      instance_id,
+      "io.nanocl.ci",
+      network_mode.clone(),
+    );
+    // Remove the init container left by a previous start
+    let _ = docker_api
+      .remove_container(&name, Some(remove_options))
+      .await;
+    let create_options = bollard_next::container::CreateContainerOptions {
+      name: name.clone(),
+      ..Default::default()
+    };
+    docker_api
+      .create_container(Some(create_options), config)
+      .await?;
+    docker_api.start_container::<String>(&name, None).await?;
+    // The wait fail when the container exit with an error code
+    let _ = docker_api
+      .wait_container(&name, None::<WaitContainerOptions<String>>)
+      .try_for_each(|_| async { Ok(()) })
+      .await;
+    let container = docker_api
+      .inspect_container(&name, None::<InspectContainerOptions>)
+      .await?;
+    let exit_code = container
+      .state
+      .and_then(|state| state.exit_code)
+      .unwrap_or_default();
+    docker_api
+      .remove_container(&name, Some(remove_options))
+      .await?;
+    if exit_code != 0 {
+      return Err(HttpError::internal_server_error(format!(
+        "Init container {} of cargo {} exited with code {exit_code}",
+        init.name, cargo.key
+      )));
+    }
+  }
+  Ok(())
+}
+
+/// ## Start instance
+///
+/// Start a cargo instance (container) with its init containers and sidecars.
+/// The init containers are run to completion before the instance is started,
+/// the sidecars are started once the instance is started.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo
+/// - [instance](str) - The id or the name of the instance
+/// - [docker_api](bollard_next::Docker) - The docker api
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The instance has been started
+///   - [Err](HttpError) - The instance has not been started
+///
+async fn start_instance(
+  cargo: &Cargo,
+  instance: &str,
+  docker_api: &bollard_next::Docker,
+) -> Result<(), HttpError> {
+  let id = docker_api
+    .inspect_container(instance, None::<InspectContainerOptions>)
+    .await?
+    .id
+    .unwrap_or_default();
+  run_init_containers(cargo, &id, docker_api).await?;
+  docker_api.start_container::<String>(&id, None).await?;
+  for sidecar in list_sidecars(&id, docker_api).await? {
+    docker_api
+      .start_container::<String>(&sidecar.id.unwrap_or_default(), None)
+      .await?;
+  }
+  Ok(())
+}
+
 /// ## Create
 ///
 /// Create a cargo based on the given partial config
@@ -259,30 +652,45 @@ pub async fn create(
   version: &str,
   state: &DaemonState,
 ) -> Result<Cargo, HttpError> {
+  if let Some(autoscale) = &config.autoscale {
+    utils::cargo_autoscale::validate(autoscale, config.replication.as_ref())?;
+  }
+  if let Some(strategy) = &config.update_strategy {
+    validate_update_strategy(strategy)?;
+  }
   let cargo =
     repositories::cargo::create(namespace, config, version, &state.pool)
       .await?;
-  let number = if let Some(mode) = &cargo.config.replication {
-    match mode {
-      ReplicationMode::Static(replication_static) => replication_static.number,
-      ReplicationMode::Auto => 1,
-      ReplicationMode::Unique => 1,
-      ReplicationMode::UniqueByNode => 1,
-      _ => 1,
-    }
-  } else {
-    1
-  };
+  let mut placements =
+    match utils::node::schedule(cargo.config.replication.as_ref(), state).await
+    {
+      Ok(placements) => placements,
+      Err(err) => {
+        repositories::cargo::delete_by_key(&cargo.key, &state.pool).await?;
+        return Err(err);
+      }
+    };
+  let number = placements.remove(&state.config.hostname).unwrap_or(0);
   if let Err(err) = create_instances(&cargo, 0, number, state).await {
     repositories::cargo::delete_by_key(&cargo.key, &state.pool).await?;
     return Err(err);
   }
+  // Forward the creation of the instances to the other nodes
+  for (node_name, number) in placements {
+    let command = NodeCommand::CargoCreate {
+      key: cargo.key.clone(),
+      number,
+    };
+    if let Err(err) = utils::node::send_command(&node_name, &command, state) {
+      log::warn!("{err}");
+    }
+  }
   Ok(cargo)
 }
 
-/// ## Start by key
+/// ## Start instances
 ///
-/// The cargo instances (containers) are started in parallel
+/// The cargo instances (containers) of the current node are started in parallel
 /// If one container fails to start, the other containers will continue to start
 ///
 /// ## Arguments
@@ -296,7 +704,7 @@ pub async fn create(
 ///   - [Ok](()) - The containers has been started
 ///   - [Err](HttpError) - The containers has not been started
 ///
-pub async fn start_by_key(
+pub(crate) async fn start_instances(
   key: &str,
   state: &DaemonState,
 ) -> Result<(), HttpError> {
@@ -308,32 +716,24 @@ pub async fn start_by_key(
     .config
     .container
     .host_config
+    .clone()
     .unwrap_or_default()
     .auto_remove
     .unwrap_or(false);
   let containers = list_instances(&cargo_key, &docker_api).await?;
-  let mut autoremove_futs = Vec::new();
+  // The instances completed or stopped before run again
+  completed_instances(state)?.remove(&cargo_key);
+  let mut watched = Vec::new();
   let mut futs = Vec::new();
   for container in containers {
-    let id = container.id.unwrap_or_default();
-    if auto_remove {
-      let id = id.clone();
-      let docker_api = docker_api.clone();
-      autoremove_futs.push(async move {
-        let id = id.clone();
-        let options = Some(WaitContainerOptions {
-          condition: "removed",
-        });
-        let stream = docker_api.wait_container(&id, options);
-        if let Err(err) = stream.try_for_each(|_| async { Ok(()) }).await {
-          log::warn!("Error while waiting for container {id} {err}");
-        }
-      });
+    if let Some(index) = instance_index(&cargo_key, &container) {
+      watched.push((index, container.id.clone().unwrap_or_default()));
     }
-    let id = id.clone();
-    let docker_api = docker_api.clone();
+    let id = container.id.unwrap_or_default();
+    let cargo = &cargo;
+    let docker_api = &docker_api;
     let fut = async move {
-      if let Err(err) = docker_api.start_container::<String>(&id, None).await {
+      if let Err(err) = start_instance(cargo, &id, docker_api).await {
         log::warn!("Error while starting container {id} {err}");
       }
     };
@@ -341,32 +741,206 @@ pub async fn start_by_key(
   }
   let _ = FuturesUnordered::from_iter(futs).collect::<Vec<_>>().await;
   if auto_remove {
-    let pool = state.pool.clone();
-    rt::spawn(async move {
-      let _ = FuturesUnordered::from_iter(autoremove_futs)
-        .collect::<Vec<_>>()
-        .await;
-      if let Err(err) =
-        repositories::cargo::delete_by_key(&cargo_key, &pool).await
-      {
-        log::warn!("Error while deleting cargo {cargo_key} {err}");
-      }
-    });
+    watch_autoremove(&cargo_key, watched, state);
   }
   Ok(())
 }
 
-/// ## Stop by key
+/// ## Completed instances
 ///
-/// Stop all instances (containers) for the given cargo key.
-/// The containers are stopped in parallel.
+/// Lock the indexes of the auto removed instances that exited successfully
+///
+fn completed_instances(
+  state: &DaemonState,
+) -> Result<MutexGuard<HashMap<String, HashSet<usize>>>, HttpError> {
+  state.completed_instances.lock().map_err(|err| {
+    HttpError::internal_server_error(format!(
+      "Unable to lock completed instances: {err}"
+    ))
+  })
+}
+
+/// ## Watch autoremove
+///
+/// Spawn a background task waiting for the auto removed instances of a cargo.
+/// The instances exiting successfully are saved as completed
+/// while the crashed ones are restored by the reconciler.
+/// The cargo is deleted once all his instances on the current node completed.
 ///
 /// ## Arguments
 ///
 /// - [key](str) - The cargo key
-/// - [docker_api](bollard_next::Docker) - The docker api
-///
-/// ## Returns
+/// - [instances](Vec<(usize, String)>) - The index and the id of the instances
+/// - [state](DaemonState) - The daemon state
+///
+fn watch_autoremove(
+  key: &str,
+  instances: Vec<(usize, String)>,
+  state: &DaemonState,
+) {
+  let key = key.to_owned();
+  let state = state.clone();
+  rt::spawn(async move {
+    instances
+      .iter()
+      .map(|(index, id)| {
+        let key = &key;
+        let state = &state;
+        async move {
+          let options = Some(WaitContainerOptions {
+            condition: "removed",
+          });
+          let res = state
+            .docker_api
+            .wait_container(id, options)
+            .try_for_each(|_| async { Ok(()) })
+            .await;
+          match res {
+            Ok(_) => match completed_instances(state) {
+              Ok(mut completed) => {
+                completed.entry(key.clone()).or_default().insert(*index);
+              }
+              Err(err) => log::warn!("{err}"),
+            },
+            Err(bollard_next::errors::Error::DockerContainerWaitError {
+              code,
+              ..
+            }) => {
+              log::warn!(
+                "Instance {index} of cargo {key} exited with code {code}"
+              );
+            }
+            Err(err) => {
+              log::warn!("Error while waiting for container {id} {err}");
+            }
+          }
+        }
+      })
+      .collect::<FuturesUnordered<_>>()
+      .collect::<Vec<_>>()
+      .await;
+    // Restored instances are watched by their own task
+    match list_instances(&key, &state.docker_api).await {
+      Ok(remaining) if remaining.is_empty() => {}
+      Ok(_) => return,
+      Err(err) => {
+        log::warn!("Error while listing cargo {key} instances {err}");
+        return;
+      }
+    }
+    // The cargo has been deleted with his instances
+    let Ok(cargo) =
+      repositories::cargo::inspect_by_key(&key, &state.pool).await
+    else {
+      return;
+    };
+    let expected = match node_replicas(&cargo, &state).await {
+      Ok(expected) => expected,
+      Err(err) => {
+        log::warn!("Error while reading cargo {key} replicas {err}");
+        return;
+      }
+    };
+    let all_completed = match completed_instances(&state) {
+      Ok(completed) => (0..expected).all(|index| {
+        completed
+          .get(&key)
+          .map(|indexes| indexes.contains(&index))
+          .unwrap_or(false)
+      }),
+      Err(err) => {
+        log::warn!("{err}");
+        false
+      }
+    };
+    // Crashed instances are restored by the reconciler
+    if !all_completed {
+      return;
+    }
+    if let Err(err) =
+      repositories::cargo::delete_by_key(&key, &state.pool).await
+    {
+      log::warn!("Error while deleting cargo {key} {err}");
+    }
+    if let Ok(mut completed) = completed_instances(&state) {
+      completed.remove(&key);
+    }
+  });
+}
+
+/// ## Forward command
+///
+/// Send a command to the other nodes where the cargo has instances scheduled
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [command](NodeCommand) - The command to forward
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The command has been forwarded
+///   - [Err](HttpError) - The cargo instances cannot be scheduled
+///
+async fn forward_command(
+  key: &str,
+  command: &NodeCommand,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let cargo = repositories::cargo::inspect_by_key(key, &state.pool).await?;
+  let placements =
+    utils::node::schedule(cargo.config.replication.as_ref(), state).await?;
+  for node_name in placements.keys() {
+    if node_name == &state.config.hostname {
+      continue;
+    }
+    if let Err(err) = utils::node::send_command(node_name, command, state) {
+      log::warn!("{err}");
+    }
+  }
+  Ok(())
+}
+
+/// ## Start by key
+///
+/// Start the cargo instances (containers) of the current node
+/// and forward the start to the other nodes where the cargo is scheduled
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The containers has been started
+///   - [Err](HttpError) - The containers has not been started
+///
+pub async fn start_by_key(
+  key: &str,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  start_instances(key, state).await?;
+  let command = NodeCommand::CargoStart {
+    key: key.to_owned(),
+  };
+  forward_command(key, &command, state).await
+}
+
+/// ## Stop by key
+///
+/// Stop the cargo instances (containers) of the current node
+/// and forward the stop to the other nodes where the cargo is scheduled
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
 ///   - [Ok](()) - The containers has been stopped
@@ -374,14 +948,56 @@ pub async fn start_by_key(
 ///
 pub async fn stop_by_key(
   key: &str,
-  docker_api: &bollard_next::Docker,
+  state: &DaemonState,
 ) -> Result<(), HttpError> {
+  stop_instances(key, state).await?;
+  let command = NodeCommand::CargoStop {
+    key: key.to_owned(),
+  };
+  forward_command(key, &command, state).await
+}
+
+/// ## Stop instances
+///
+/// Stop all instances (containers) of the current node for the given cargo key.
+/// The containers are stopped in parallel.
+/// The instances are saved as completed so the reconciler doesn't restore
+/// the auto removed ones until the cargo is started again.
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The containers has been stopped
+///   - [Err](HttpError) - The containers has not been stopped
+///
+pub(crate) async fn stop_instances(
+  key: &str,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let docker_api = &state.docker_api;
   let containers = list_instances(key, docker_api).await?;
+  completed_instances(state)?
+    .entry(key.to_owned())
+    .or_default()
+    .extend(
+      containers
+        .iter()
+        .filter_map(|container| instance_index(key, container)),
+    );
   containers
     .into_iter()
     .map(|container| async {
       let id = container.id.unwrap_or_default();
-      let docker_api = docker_api.clone();
+      for sidecar in list_sidecars(&id, docker_api).await? {
+        docker_api
+          .stop_container(&sidecar.id.unwrap_or_default(), None)
+          .await?;
+      }
       docker_api
         .stop_container(&id, None)
         .await
@@ -529,6 +1145,8 @@ async fn rename_instances_original(
 /// ## Delete by key
 ///
 /// Delete a cargo by key with his given instances (containers).
+/// The deletion of the instances is sent to every other node
+/// as they can run instances of a previous replication mode.
 ///
 /// ## Arguments
 ///
@@ -546,17 +1164,56 @@ pub async fn delete_by_key(
   key: &str,
   force: Option<bool>,
   state: &DaemonState,
+) -> Result<(), HttpError> {
+  let force = force.unwrap_or(false);
+  delete_node_instances(key, force, state).await?;
+  let command = NodeCommand::CargoDelete {
+    key: key.to_owned(),
+    force,
+  };
+  utils::node::broadcast_command(&command, state).await?;
+  repositories::cargo::delete_by_key(key, &state.pool).await?;
+  repositories::cargo_config::delete_by_cargo_key(key, &state.pool).await?;
+  repositories::cargo_scale_history::delete_by_cargo_key(key, &state.pool)
+    .await?;
+  repositories::state_ref::delete_by_key("Cargo", key, &state.pool).await?;
+  Ok(())
+}
+
+/// ## Delete node instances
+///
+/// Delete the instances (containers) of a cargo on the current node
+/// with their sidecars and secret files.
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [force](bool) - Force the deletion of running instances
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The instances has been deleted
+///   - [Err](HttpError) - The instances has not been deleted
+///
+pub(crate) async fn delete_node_instances(
+  key: &str,
+  force: bool,
+  state: &DaemonState,
 ) -> Result<(), HttpError> {
   let containers = list_instances(key, &state.docker_api).await?;
   containers
     .into_iter()
     .map(|container| async {
+      let id = container.id.unwrap_or_default();
+      remove_sidecars(&id, &state.docker_api).await?;
       state
         .docker_api
         .remove_container(
-          &container.id.unwrap_or_default(),
+          &id,
           Some(RemoveContainerOptions {
-            force: force.unwrap_or(false),
+            force,
             ..Default::default()
           }),
         )
@@ -568,8 +1225,8 @@ pub async fn delete_by_key(
     .await
     .into_iter()
     .collect::<Result<Vec<_>, _>>()?;
-  repositories::cargo::delete_by_key(key, &state.pool).await?;
-  repositories::cargo_config::delete_by_cargo_key(key, &state.pool).await?;
+  completed_instances(state)?.remove(key);
+  utils::secret::remove_cargo_files(key, state).await?;
   Ok(())
 }
 
@@ -596,6 +1253,7 @@ async fn delete_instances(
   instances
     .iter()
     .map(|id| async {
+      remove_sidecars(id, &state.docker_api).await?;
       state
         .docker_api
         .remove_container(
@@ -615,62 +1273,377 @@ async fn delete_instances(
     .collect::<Result<(), _>>()
 }
 
-/// ## Put
+/// ## Wait instance ready
 ///
-/// A new history entry is added and the containers are updated
-/// with the new cargo configuration
+/// Wait for an instance (container) to be running
+/// or to be healthy if the container define an health check.
+/// It fail if the container exit, is reported unhealthy or if the timeout is reached.
 ///
 /// ## Arguments
-/// - [cargo_key](str) - The cargo key
-/// - [cargo_partial](CargoConfigPartial) - The cargo config
-/// - [version](str) - The version of the api to use
+///
+/// - [name](str) - The container name
+/// - [timeout](u64) - The timeout in seconds
+/// - [docker_api](bollard_next::Docker) - The docker api
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The instance is ready
+///   - [Err](HttpError) - The instance is not ready
+///
+async fn wait_instance_ready(
+  name: &str,
+  timeout: u64,
+  docker_api: &bollard_next::Docker,
+) -> Result<(), HttpError> {
+  let started_at = Instant::now();
+  loop {
+    let container = docker_api
+      .inspect_container(name, None::<InspectContainerOptions>)
+      .await?;
+    let container_state = container.state.unwrap_or_default();
+    if let Some(
+      ContainerStateStatusEnum::EXITED | ContainerStateStatusEnum::DEAD,
+    ) = container_state.status
+    {
+      return Err(HttpError::internal_server_error(format!(
+        "Instance {name} exited with code {}",
+        container_state.exit_code.unwrap_or_default()
+      )));
+    }
+    let running = container_state.running.unwrap_or(false);
+    match container_state.health.and_then(|health| health.status) {
+      Some(HealthStatusEnum::UNHEALTHY) => {
+        return Err(HttpError::internal_server_error(format!(
+          "Instance {name} is unhealthy"
+        )));
+      }
+      Some(HealthStatusEnum::HEALTHY) if running => return Ok(()),
+      Some(HealthStatusEnum::STARTING) => {}
+      _ if running => return Ok(()),
+      _ => {}
+    }
+    if started_at.elapsed() >= Duration::from_secs(timeout) {
+      return Err(HttpError::internal_server_error(format!(
+        "Instance {name} is not ready after {timeout} seconds"
+      )));
+    }
+    time::sleep(Duration::from_secs(1)).await;
+  }
+}
+
+/// ## Replace instances batch
+///
+/// Replace a batch of old instances by new instances created with the cargo config.
+/// Up to `max_unavailable` old instances are stopped before the new ones are started,
+/// the others are stopped when the new instances are ready.
+/// The old instances are kept until the end of the update to be restored
+/// if a next batch fail.
+/// On failure the new instances are removed and the old ones are left stopped.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo with the new config
+/// - [olds](Vec<ContainerSummary>) - The old instances to replace
+/// - [start](usize) - The index of the first new instance
+/// - [number](usize) - The number of new instances to create
+/// - [max_unavailable](usize) - Number of old instances that can be stopped first
+/// - [timeout](u64) - The timeout in seconds to wait for an instance to be ready
 /// - [state](DaemonState) - The daemon state
+/// - [sx](Option<mpsc::Sender>) - Optional sender to stream the progress
 ///
 /// ## Returns
+///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](Cargo) - The cargo has been patched
-///   - [Err](HttpError) - The cargo has not been patched
+///   - [Ok](()) - The batch has been replaced
+///   - [Err](HttpError) - The batch has not been replaced
 ///
-pub async fn put(
-  cargo_key: &str,
-  cargo_partial: &CargoConfigPartial,
-  version: &str,
+#[allow(clippy::too_many_arguments)]
+async fn replace_instances_batch(
+  cargo: &Cargo,
+  olds: &[ContainerSummary],
+  start: usize,
+  number: usize,
+  max_unavailable: usize,
+  timeout: u64,
   state: &DaemonState,
-) -> Result<Cargo, HttpError> {
-  let cargo = repositories::cargo::update_by_key(
-    cargo_key,
-    cargo_partial,
-    version,
-    &state.pool,
+  sx: Option<&mpsc::Sender<Result<Bytes, HttpError>>>,
+) -> Result<(), HttpError> {
+  let names = (start..start + number)
+    .map(|index| gen_instance_name(&cargo.key, index))
+    .collect::<Vec<_>>();
+  for old in olds.iter().take(max_unavailable) {
+    let id = old.id.clone().unwrap_or_default();
+    if old.state == Some("running".into()) {
+      state.docker_api.stop_container(&id, None).await?;
+    }
+  }
+  let res = async {
+    create_instances(cargo, start, number, state).await?;
+    names
+      .iter()
+      .map(|name| async move {
+        let key = name.trim_end_matches(".c");
+        if let Some(sx) = sx {
+          utils::state::send(
+            StateStream::new_cargo_instance_pending(key, "Starting"),
+            sx,
+          );
+        }
+        let res = async {
+          start_instance(cargo, name, &state.docker_api).await?;
+          if let Some(sx) = sx {
+            utils::state::send(
+              StateStream::new_cargo_instance_pending(key, "Waiting ready"),
+              sx,
+            );
+          }
+          wait_instance_ready(name, timeout, &state.docker_api).await
+        }
+        .await;
+        if let Some(sx) = sx {
+          match &res {
+            Ok(_) => utils::state::send(
+              StateStream::new_cargo_instance_success(key),
+              sx,
+            ),
+            Err(err) => utils::state::send(
+              StateStream::new_cargo_instance_error(key, &err.to_string()),
+              sx,
+            ),
+          }
+        }
+        res
+      })
+      .collect::<FuturesUnordered<_>>()
+      .collect::<Vec<Result<(), HttpError>>>()
+      .await
+      .into_iter()
+      .collect::<Result<Vec<_>, HttpError>>()?;
+    Ok::<_, HttpError>(())
+  }
+  .await;
+  if let Err(err) = res {
+    // Some instances may have been created before the error
+    let _ = delete_instances(&names, state).await;
+    return Err(err);
+  }
+  for old in olds.iter().skip(max_unavailable) {
+    let id = old.id.clone().unwrap_or_default();
+    if old.state == Some("running".into()) {
+      state.docker_api.stop_container(&id, None).await?;
+    }
+  }
+  Ok(())
+}
+
+/// ## Validate update strategy
+///
+/// Validate the update strategy of a cargo
+///
+/// ## Arguments
+///
+/// - [strategy](UpdateStrategy) - The update strategy
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The update strategy is valid
+///   - [Err](HttpError) - The update strategy is invalid
+///
+pub(crate) fn validate_update_strategy(
+  strategy: &UpdateStrategy,
+) -> Result<(), HttpError> {
+  let max_surge = strategy.max_surge.unwrap_or(1);
+  let max_unavailable = strategy.max_unavailable.unwrap_or(0);
+  if max_surge + max_unavailable == 0 {
+    return Err(HttpError::bad_request(
+      "UpdateStrategy MaxSurge and MaxUnavailable cannot be both 0",
+    ));
+  }
+  Ok(())
+}
+
+/// ## Restore instances
+///
+/// Restore the old instances of a cargo after a failed rolling update.
+/// The new instances are removed and the old ones are started again
+/// if they were running, so all the instances keep the previous config.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo with the new config
+/// - [olds](Vec<ContainerSummary>) - The old instances
+/// - [replaced](usize) - The number of new instances created
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The old instances has been restored
+///   - [Err](HttpError) - The old instances has not been restored
+///
+async fn restore_instances(
+  cargo: &Cargo,
+  olds: &[ContainerSummary],
+  replaced: usize,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let news = (0..replaced)
+    .map(|index| gen_instance_name(&cargo.key, index))
+    .collect::<Vec<_>>();
+  delete_instances(&news, state).await?;
+  for old in olds {
+    if old.state == Some("running".into()) {
+      let id = old.id.clone().unwrap_or_default();
+      state
+        .docker_api
+        .start_container::<String>(&id, None)
+        .await?;
+    }
+  }
+  rename_instances_original(olds, state).await
+}
+
+/// ## Rolling update
+///
+/// Update the instances of a cargo by batch according to his update strategy.
+/// The old instances are renamed as backup to free their names,
+/// then replaced batch by batch until the wanted number of instances is reached.
+/// The old instances are removed once every batch succeeded,
+/// if a batch fail the new instances are removed and all the old ones restored
+/// so the cargo never end with instances of both configs.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo with the new config
+/// - [instances](Vec<ContainerSummary>) - The current instances
+/// - [number](usize) - The number of instances wanted
+/// - [strategy](UpdateStrategy) - The update strategy
+/// - [state](DaemonState) - The daemon state
+/// - [sx](Option<mpsc::Sender>) - Optional sender to stream the progress
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The instances has been updated
+///   - [Err](HttpError) - The instances has not been updated
+///
+async fn rolling_update(
+  cargo: &Cargo,
+  instances: &[ContainerSummary],
+  number: usize,
+  strategy: &UpdateStrategy,
+  state: &DaemonState,
+  sx: Option<&mpsc::Sender<Result<Bytes, HttpError>>>,
+) -> Result<(), HttpError> {
+  validate_update_strategy(strategy)?;
+  let max_surge = strategy.max_surge.unwrap_or(1);
+  let max_unavailable = strategy.max_unavailable.unwrap_or(0);
+  let batch_size = strategy
+    .batch_size
+    .unwrap_or(max_surge + max_unavailable)
+    .clamp(1, max_surge + max_unavailable);
+  let timeout = strategy.timeout.unwrap_or(60);
+  restore_instances_backup(instances, state).await?;
+  let mut remaining = instances.to_vec();
+  // Old instances of the batches already replaced
+  let mut replaced = Vec::new();
+  let mut index = 0;
+  while index < number {
+    let batch_len = batch_size.min(number - index);
+    let olds = remaining
+      .drain(..batch_len.min(remaining.len()))
+      .collect::<Vec<_>>();
+    let res = replace_instances_batch(
+      cargo,
+      &olds,
+      index,
+      batch_len,
+      max_unavailable,
+      timeout,
+      state,
+      sx,
+    )
+    .await;
+    replaced.extend(olds);
+    if let Err(err) = res {
+      log::warn!("Unable to update cargo {} instances: {err}", cargo.key);
+      log::warn!("Rollback to previous instances");
+      let olds = replaced.into_iter().chain(remaining).collect::<Vec<_>>();
+      restore_instances(cargo, &olds, index, state).await?;
+      return Err(err);
+    }
+    index += batch_len;
+  }
+  // Remove the old instances and the ones left
+  // when the number of replicas decreased
+  delete_instances(
+    &replaced
+      .iter()
+      .chain(remaining.iter())
+      .map(|c| c.id.clone().unwrap_or_default())
+      .collect::<Vec<_>>(),
+    state,
   )
   .await?;
-  // Get the number of instance to create
-  let number = if let Some(mode) = &cargo.config.replication {
-    match mode {
-      ReplicationMode::Static(replication_static) => replication_static.number,
-      ReplicationMode::Auto => 1,
-      ReplicationMode::Unique => 1,
-      ReplicationMode::UniqueByNode => 1,
-      _ => 1,
-    }
-  } else {
-    1
-  };
-  let containers = list_instances(cargo_key, &state.docker_api).await?;
+  Ok(())
+}
+
+/// ## Update instances
+///
+/// Replace the instances of a cargo on the current node
+/// by new instances created with his current config.
+/// If the cargo define an update strategy the instances are updated
+/// with a rolling update and the progress is sent to the given sender.
+/// On failure the previous instances are restored.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo with the new config
+/// - [state](DaemonState) - The daemon state
+/// - [sx](Option<mpsc::Sender>) - Optional sender to stream the progress
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The instances has been updated
+///   - [Err](HttpError) - The instances has not been updated
+///
+pub(crate) async fn update_instances(
+  cargo: &Cargo,
+  state: &DaemonState,
+  sx: Option<&mpsc::Sender<Result<Bytes, HttpError>>>,
+) -> Result<(), HttpError> {
+  // Get the number of instance to create on the current node
+  let number = utils::node::schedule(cargo.config.replication.as_ref(), state)
+    .await?
+    .remove(&state.config.hostname)
+    .unwrap_or(0);
+  let containers = list_instances(&cargo.key, &state.docker_api).await?;
+  // The instances of the new config run again
+  completed_instances(state)?.remove(&cargo.key);
+  if number == 0 && containers.is_empty() {
+    return Ok(());
+  }
+  if let Some(strategy) = &cargo.config.update_strategy {
+    return rolling_update(cargo, &containers, number, strategy, state, sx)
+      .await;
+  }
   restore_instances_backup(&containers, state).await?;
   // Create instance with the new config
-  let new_instances = match create_instances(&cargo, 0, number, state).await {
+  let new_instances = match create_instances(cargo, 0, number, state).await {
     // If the creation of the new instance failed, we rename the old containers
     Err(err) => {
       log::warn!("Unable to create cargo instance: {}", err);
       log::warn!("Rollback to previous instance");
       rename_instances_original(&containers, state).await?;
-      Vec::default()
+      return Err(err);
     }
     Ok(instances) => instances,
   };
   // start created containers
-  match start_by_key(cargo_key, state).await {
+  match start_instances(&cargo.key, state).await {
     Err(err) => {
       log::error!("Unable to start cargo instance {} : {err}", cargo.key);
       delete_instances(
@@ -682,19 +1655,300 @@ pub async fn put(
       )
       .await?;
       rename_instances_original(&containers, state).await?;
+      return Err(err);
+    }
+    Ok(_) => {
+      // Delete old containers
+      delete_instances(
+        &containers
+          .iter()
+          .map(|c| c.id.clone().unwrap_or_default())
+          .collect::<Vec<_>>(),
+        state,
+      )
+      .await?;
+    }
+  }
+  Ok(())
+}
+
+/// ## Watch instances health
+///
+/// Watch the instances of a cargo during the rollback timeout.
+/// It fail as soon as an instance exit or has too many failed health probes.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo to watch
+/// - [rollback](CargoRollback) - The rollback config of the cargo
+/// - [docker_api](bollard_next::Docker) - The docker api
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](None) - The instances stayed healthy
+///   - [Ok](Some(HttpError)) - An instance failed
+///   - [Err](HttpError) - The instances could not be watched
+///
+async fn watch_instances_health(
+  cargo: &Cargo,
+  rollback: &CargoRollback,
+  docker_api: &bollard_next::Docker,
+) -> Result<Option<HttpError>, HttpError> {
+  let timeout = Duration::from_secs(rollback.timeout.unwrap_or(60));
+  let started_at = Instant::now();
+  while started_at.elapsed() < timeout {
+    let instances = list_instances(&cargo.key, docker_api).await?;
+    for instance in instances {
+      let id = instance.id.unwrap_or_default();
+      let container = docker_api
+        .inspect_container(&id, None::<InspectContainerOptions>)
+        .await?;
+      let name = container.name.unwrap_or_default();
+      let container_state = container.state.unwrap_or_default();
+      if let Some(
+        ContainerStateStatusEnum::EXITED | ContainerStateStatusEnum::DEAD,
+      ) = container_state.status
+      {
+        return Ok(Some(HttpError::internal_server_error(format!(
+          "Instance {name} exited with code {}",
+          container_state.exit_code.unwrap_or_default()
+        ))));
+      }
+      let Some(health) = container_state.health else {
+        continue;
+      };
+      let failing_streak = health.failing_streak.unwrap_or_default();
+      let failed = match rollback.failed_probes {
+        Some(failed_probes) => failing_streak >= failed_probes,
+        None => health.status == Some(HealthStatusEnum::UNHEALTHY),
+      };
+      if failed {
+        return Ok(Some(HttpError::internal_server_error(format!(
+          "Instance {name} failed {failing_streak} health probes"
+        ))));
+      }
+    }
+    time::sleep(Duration::from_secs(1)).await;
+  }
+  Ok(None)
+}
+
+/// ## Set rollback status
+///
+/// Save the status of the automatic rollback of a cargo
+/// so it can be returned when the cargo is inspected.
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [status](CargoRollbackStatus) - The status to save
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The status has been saved
+///   - [Err](HttpError) - The status has not been saved
+///
+fn set_rollback_status(
+  key: &str,
+  status: CargoRollbackStatus,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  state
+    .rollback_statuses
+    .lock()
+    .map_err(|err| {
+      HttpError::internal_server_error(format!(
+        "Unable to lock rollback statuses: {err}"
+      ))
+    })?
+    .insert(key.to_owned(), status);
+  Ok(())
+}
+
+/// ## Rollback
+///
+/// Revert a cargo to a previous config by adding a new history entry
+/// and updating his instances, then emit a `CargoRolledBack` event.
+/// The instances are left untouched when they already run the previous config.
+///
+/// ## Arguments
+///
+/// - [previous](Cargo) - The cargo with the config to restore
+/// - [reason](HttpError) - The error that caused the rollback
+/// - [with_instances](bool) - Replace the instances with the previous config
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The cargo has been rolled back
+///   - [Err](HttpError) - The cargo has not been rolled back
+///
+async fn rollback(
+  previous: &Cargo,
+  reason: &HttpError,
+  with_instances: bool,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  log::warn!(
+    "Rolling back cargo {} to config {}: {reason}",
+    previous.key,
+    previous.config_key
+  );
+  let cargo = repositories::cargo::update_by_key(
+    &previous.key,
+    &previous.config.clone().into(),
+    &previous.config.version,
+    &state.pool,
+  )
+  .await?;
+  if with_instances {
+    update_instances(&cargo, state, None).await?;
+    let command = NodeCommand::CargoUpdate {
+      key: cargo.key.clone(),
+    };
+    utils::node::broadcast_command(&command, state).await?;
+  }
+  let cargo = inspect_by_key(&cargo.key, state).await?;
+  let _ = state
+    .event_emitter
+    .emit(Event::CargoRolledBack(Box::new(cargo)))
+    .await;
+  Ok(())
+}
+
+/// ## Put
+///
+/// A new history entry is added and the containers are updated
+/// with the new cargo configuration.
+/// If the cargo define an update strategy the containers are updated
+/// with a rolling update and the progress is sent to the given sender.
+/// If the cargo enable the automatic rollback, the previous config is restored
+/// when the new instances fail to start or are unhealthy during the rollback timeout.
+/// The other nodes update their instances once the current node succeeded.
+///
+/// ## Arguments
+/// - [cargo_key](str) - The cargo key
+/// - [cargo_partial](CargoConfigPartial) - The cargo config
+/// - [version](str) - The version of the api to use
+/// - [state](DaemonState) - The daemon state
+/// - [sx](Option<mpsc::Sender>) - Optional sender to stream the progress
+///
+/// ## Returns
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Cargo) - The cargo has been patched
+///   - [Err](HttpError) - The cargo has not been patched
+///
+pub async fn put(
+  cargo_key: &str,
+  cargo_partial: &CargoConfigPartial,
+  version: &str,
+  state: &DaemonState,
+  sx: Option<&mpsc::Sender<Result<Bytes, HttpError>>>,
+) -> Result<Cargo, HttpError> {
+  if let Some(autoscale) = &cargo_partial.autoscale {
+    utils::cargo_autoscale::validate(
+      autoscale,
+      cargo_partial.replication.as_ref(),
+    )?;
+  }
+  if let Some(strategy) = &cargo_partial.update_strategy {
+    validate_update_strategy(strategy)?;
+  }
+  let previous =
+    repositories::cargo::inspect_by_key(cargo_key, &state.pool).await?;
+  let cargo = repositories::cargo::update_by_key(
+    cargo_key,
+    cargo_partial,
+    version,
+    &state.pool,
+  )
+  .await?;
+  let rollback_config = cargo
+    .config
+    .rollback
+    .clone()
+    .filter(|rollback| rollback.enabled.unwrap_or(false));
+  let command = NodeCommand::CargoUpdate {
+    key: cargo.key.clone(),
+  };
+  let Some(rollback_config) = rollback_config else {
+    update_instances(&cargo, state, sx).await?;
+    utils::node::broadcast_command(&command, state).await?;
+    return Ok(cargo);
+  };
+  let mut status = CargoRollbackStatus {
+    config_key: cargo.config_key,
+    started_at: chrono::Utc::now().naive_utc(),
+    finished_at: None,
+    rolled_back: false,
+    error: None,
+  };
+  if let Err(err) = update_instances(&cargo, state, sx).await {
+    // The previous instances are restored on failure, only the config is reverted
+    let res = rollback(&previous, &err, false, state).await;
+    status.finished_at = Some(chrono::Utc::now().naive_utc());
+    status.rolled_back = res.is_ok();
+    status.error = Some(match &res {
+      Ok(_) => err.to_string(),
+      Err(rollback_err) => rollback_err.to_string(),
+    });
+    set_rollback_status(&cargo.key, status, state)?;
+    res?;
+    return Err(err);
+  }
+  utils::node::broadcast_command(&command, state).await?;
+  set_rollback_status(&cargo.key, status.clone(), state)?;
+  // Watch the new instances in background and rollback if they fail
+  let state = state.clone();
+  let watched = cargo.clone();
+  rt::spawn(async move {
+    let res =
+      watch_instances_health(&watched, &rollback_config, &state.docker_api)
+        .await;
+    match res {
+      Ok(None) => {}
+      Err(err) => {
+        log::error!("Unable to watch cargo {} instances: {err}", watched.key);
+        status.error = Some(err.to_string());
+      }
+      Ok(Some(err)) => {
+        // Do not rollback if the cargo has been updated in the meantime
+        let current =
+          repositories::cargo::inspect_by_key(&watched.key, &state.pool).await;
+        match current {
+          Ok(current) if current.config_key == watched.config_key => {
+            match rollback(&previous, &err, true, &state).await {
+              Ok(_) => {
+                status.rolled_back = true;
+                status.error = Some(err.to_string());
+              }
+              Err(err) => {
+                log::error!("Unable to rollback cargo {}: {err}", watched.key);
+                status.error = Some(err.to_string());
+              }
+            }
+          }
+          Ok(_) => {
+            status.error = Some(format!(
+              "{err}, not rolled back as the cargo has been updated"
+            ));
+          }
+          Err(err) => {
+            log::error!("Unable to rollback cargo {}: {err}", watched.key);
+            status.error = Some(err.to_string());
+          }
+        }
+      }
     }
-    Ok(_) => {
-      // Delete old containers
-      delete_instances(
-        &containers
-          .iter()
-          .map(|c| c.id.clone().unwrap_or_default())
-          .collect::<Vec<_>>(),
-        state,
-      )
-      .await?;
+    status.finished_at = Some(chrono::Utc::now().naive_utc());
+    if let Err(err) = set_rollback_status(&watched.key, status, &state) {
+      log::error!("{err}");
     }
-  }
+  });
   Ok(cargo)
 }
 
@@ -833,6 +2087,26 @@ pub async fn inspect_by_key(
       running_instances += 1;
     }
   }
+  let reconcile_status = state
+    .reconcile_statuses
+    .lock()
+    .map_err(|err| {
+      HttpError::internal_server_error(format!(
+        "Unable to lock reconcile statuses: {err}"
+      ))
+    })?
+    .get(&cargo.key)
+    .cloned();
+  let rollback_status = state
+    .rollback_statuses
+    .lock()
+    .map_err(|err| {
+      HttpError::internal_server_error(format!(
+        "Unable to lock rollback statuses: {err}"
+      ))
+    })?
+    .get(&cargo.key)
+    .cloned();
   Ok(CargoInspect {
     key: cargo.key,
     name: cargo.name,
@@ -842,6 +2116,8 @@ pub async fn inspect_by_key(
     instance_total: containers.len(),
     instance_running: running_instances,
     instances: containers,
+    reconcile_status,
+    rollback_status,
   })
 }
 
@@ -880,9 +2156,45 @@ pub async fn delete_by_namespace(
   Ok(())
 }
 
+/// ## Kill by key
+///
+/// Send a signal to the first instance of a cargo on the current node
+/// and forward the signal to the other nodes where the cargo is scheduled
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [options](CargoKillOptions) - The kill options
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///  - [Ok](()) - The signal has been sent
+///  - [Err](HttpError) - The signal has not been sent
+///
+pub async fn kill_by_key(
+  key: &str,
+  options: &CargoKillOptions,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let cargo = repositories::cargo::inspect_by_key(key, &state.pool).await?;
+  let placements =
+    utils::node::schedule(cargo.config.replication.as_ref(), state).await?;
+  if placements.contains_key(&state.config.hostname) {
+    kill_by_name(key, options, &state.docker_api).await?;
+  }
+  let command = NodeCommand::CargoKill {
+    key: key.to_owned(),
+    options: options.clone(),
+  };
+  forward_command(key, &command, state).await
+}
+
 /// ## Kill by name
 ///
-/// Send a signal to a cargo instance the cargo name can be used if the cargo has only one instance
+/// Send a signal to a cargo instance of the current node
+/// the cargo name can be used if the cargo has only one instance
 /// The signal is send to one instance only
 ///
 /// ## Arguments
@@ -897,14 +2209,27 @@ pub async fn delete_by_namespace(
 ///  - [Ok](()) - The signal has been sent
 ///  - [Err](HttpError) - The signal has not been sent
 ///
-pub async fn kill_by_name(
+pub(crate) async fn kill_by_name(
   name: &str,
   options: &CargoKillOptions,
   docker_api: &bollard_next::Docker,
 ) -> Result<(), HttpError> {
   let name = format!("{name}.c");
-  let options = options.clone().into();
-  docker_api.kill_container(&name, Some(options)).await?;
+  let options: KillContainerOptions<String> = options.clone().into();
+  let id = docker_api
+    .inspect_container(&name, None::<InspectContainerOptions>)
+    .await?
+    .id
+    .unwrap_or_default();
+  docker_api
+    .kill_container(&id, Some(options.clone()))
+    .await?;
+  for sidecar in list_sidecars(&id, docker_api).await? {
+    let sidecar_id = sidecar.id.unwrap_or_default();
+    docker_api
+      .kill_container(&sidecar_id, Some(options.clone()))
+      .await?;
+  }
   Ok(())
 }
 
@@ -1016,8 +2341,43 @@ pub async fn patch(
     } else {
       cargo.config.metadata
     },
+    update_strategy: if payload.update_strategy.is_some() {
+      payload.update_strategy.clone()
+    } else {
+      cargo.config.update_strategy
+    },
+    rollback: if payload.rollback.is_some() {
+      payload.rollback.clone()
+    } else {
+      cargo.config.rollback
+    },
+    autoscale: if payload.autoscale.is_some() {
+      payload.autoscale.clone()
+    } else {
+      cargo.config.autoscale
+    },
+    depends_on: if payload.depends_on.is_some() {
+      payload.depends_on.clone()
+    } else {
+      cargo.config.depends_on
+    },
+    init_containers: if payload.init_containers.is_some() {
+      payload.init_containers.clone()
+    } else {
+      cargo.config.init_containers
+    },
+    sidecars: if payload.sidecars.is_some() {
+      payload.sidecars.clone()
+    } else {
+      cargo.config.sidecars
+    },
+    secret_files: if payload.secret_files.is_some() {
+      payload.secret_files.clone()
+    } else {
+      cargo.config.secret_files
+    },
   };
-  utils::cargo::put(key, &config, version, state).await
+  utils::cargo::put(key, &config, version, state, None).await
 }
 
 /// ## Get logs
@@ -1077,9 +2437,11 @@ pub fn get_stats(
   Ok(stream)
 }
 
-/// ## Scale
+/// ## Scale instances
 ///
-/// Scale a cargo instance up or down to the given number of instances (containers, replicas)
+/// Add or remove instances (containers, replicas) of a cargo on the current node.
+/// The number of instances isn't saved, the reconciler restore it
+/// unless the scaling is saved in the scale histories.
 ///
 /// ## Arguments
 ///
@@ -1090,10 +2452,10 @@ pub fn get_stats(
 /// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](()) - The cargo has been scaled
-///   - [Err](HttpError) - The cargo has not been scaled
+///   - [Ok](()) - The instances has been added or removed
+///   - [Err](HttpError) - The instances has not been added or removed
 ///
-pub async fn scale(
+pub(crate) async fn scale_instances(
   key: &str,
   options: &CargoScale,
   state: &DaemonState,
@@ -1111,10 +2473,12 @@ pub async fn scale(
       .iter()
       .take(to_remove)
       .map(|instance| async {
+        let id = instance.id.clone().unwrap_or_default();
+        remove_sidecars(&id, &state.docker_api).await?;
         state
           .docker_api
           .remove_container(
-            &instance.id.clone().unwrap_or_default(),
+            &id,
             Some(RemoveContainerOptions {
               force: true,
               ..Default::default()
@@ -1136,10 +2500,7 @@ pub async fn scale(
     created_instances
       .iter()
       .map(|instance| async {
-        state
-          .docker_api
-          .start_container::<String>(&instance.id, None)
-          .await?;
+        start_instance(&cargo, &instance.id, &state.docker_api).await?;
         Ok::<_, HttpError>(())
       })
       .collect::<FuturesUnordered<_>>()
@@ -1150,3 +2511,492 @@ pub async fn scale(
   }
   Ok(())
 }
+
+/// ## Scale
+///
+/// Scale a cargo up or down on the current node
+/// and forward the scaling to the other nodes where the cargo is scheduled
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [options](CargoScale) - The scale options
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The cargo has been scaled
+///   - [Err](HttpError) - The cargo has not been scaled
+///
+pub async fn scale(
+  key: &str,
+  options: &CargoScale,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  scale_node(key, options, state).await?;
+  let command = NodeCommand::CargoScale {
+    key: key.to_owned(),
+    options: options.clone(),
+  };
+  forward_command(key, &command, state).await
+}
+
+/// ## Scale node
+///
+/// Scale a cargo up or down on the current node
+/// and save the scaling in the scale histories of the cargo,
+/// the reconciler keep the number of instances until the config change.
+/// The config is left untouched so the scaling doesn't create a config history.
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [options](CargoScale) - The scale options
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The cargo has been scaled
+///   - [Err](HttpError) - The cargo has not been scaled
+///
+pub(crate) async fn scale_node(
+  key: &str,
+  options: &CargoScale,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let previous = list_instances(key, &state.docker_api).await?.len();
+  scale_instances(key, options, state).await?;
+  let replicas = list_instances(key, &state.docker_api).await?.len();
+  if replicas == previous {
+    return Ok(());
+  }
+  let item = CargoScaleHistoryDbModel {
+    key: uuid::Uuid::new_v4(),
+    created_at: chrono::Utc::now().naive_utc(),
+    cargo_key: key.to_owned(),
+    node_name: state.config.hostname.clone(),
+    previous_replicas: previous as i64,
+    replicas: replicas as i64,
+    cpu_usage: None,
+    memory_usage: None,
+    reason: "Scaled from the api".to_owned(),
+  };
+  repositories::cargo_scale_history::create(&item, &state.pool).await?;
+  Ok(())
+}
+
+/// ## Scaled replicas
+///
+/// Get the number of instances of a cargo on the current node
+/// set by his last scaling, a scaling older than his config is ignored.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Option<usize>) - The number of instances if the cargo has been scaled
+///   - [Err](HttpError) - The scale histories could not be read
+///
+async fn scaled_replicas(
+  cargo: &Cargo,
+  state: &DaemonState,
+) -> Result<Option<usize>, HttpError> {
+  let last = repositories::cargo_scale_history::find_last_by_cargo_key(
+    &cargo.key,
+    &state.config.hostname,
+    &state.pool,
+  )
+  .await?;
+  Ok(
+    last
+      .filter(|last| last.created_at >= cargo.config.created_at)
+      .map(|last| last.replicas),
+  )
+}
+
+/// ## Expected replicas
+///
+/// Get the number of instances of a cargo on the current node.
+/// A scaling replace the number scheduled by the replication mode
+/// unless the replication mode doesn't schedule the cargo on the current node.
+///
+/// ## Arguments
+///
+/// - [unconstrained](bool) - Whether the replication mode has no node constraint
+/// - [scheduled](usize) - The number of instances scheduled on the current node
+/// - [scaled](Option<usize>) - The number of instances set by the last scaling
+///
+/// ## Returns
+///
+/// - [usize](usize) - The number of instances
+///
+fn expected_replicas(
+  unconstrained: bool,
+  scheduled: usize,
+  scaled: Option<usize>,
+) -> usize {
+  match scaled {
+    Some(replicas) if unconstrained || scheduled > 0 => replicas,
+    _ => scheduled,
+  }
+}
+
+/// ## Is unconstrained
+///
+/// Whether the replication mode of a cargo has no node constraint
+///
+fn is_unconstrained(cargo: &Cargo) -> bool {
+  matches!(
+    cargo.config.replication,
+    None
+      | Some(
+        ReplicationMode::Auto
+          | ReplicationMode::Unique
+          | ReplicationMode::Static(_)
+      )
+  )
+}
+
+/// ## Node replicas
+///
+/// Get the number of instances a cargo should have on the current node
+/// from his replication mode and his last scaling.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](usize) - The number of instances
+///   - [Err](HttpError) - The cargo instances cannot be scheduled
+///
+async fn node_replicas(
+  cargo: &Cargo,
+  state: &DaemonState,
+) -> Result<usize, HttpError> {
+  let scheduled =
+    utils::node::schedule(cargo.config.replication.as_ref(), state)
+      .await?
+      .remove(&state.config.hostname)
+      .unwrap_or(0);
+  let scaled = scaled_replicas(cargo, state).await?;
+  Ok(expected_replicas(
+    is_unconstrained(cargo),
+    scheduled,
+    scaled,
+  ))
+}
+
+/// ## Instance index
+///
+/// Get the index of an instance from his container name.
+/// Return `None` if the name doesn't match the cargo instance naming,
+/// for example for backup instances during an update.
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [instance](ContainerSummary) - The instance
+///
+/// ## Returns
+///
+/// - [Option](Option<usize>) - The index of the instance
+///
+fn instance_index(key: &str, instance: &ContainerSummary) -> Option<usize> {
+  let names = instance.names.clone().unwrap_or_default();
+  let name = names.first()?.trim_start_matches('/').strip_suffix(".c")?;
+  if name == key {
+    return Some(0);
+  }
+  name.strip_suffix(&format!("-{key}"))?.parse().ok()
+}
+
+/// ## Reconcile
+///
+/// Compare the instances of a cargo on the current node with his replication mode
+/// and create the missing instances or remove the extra ones.
+/// The auto removed instances that exited successfully aren't recreated
+/// and cargoes being updated are skipped.
+/// Cargoes without node constraint are only reconciled by the node running them,
+/// or by the current node if it's the only node of the cluster.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo to reconcile
+/// - [single_node](bool) - Whether the current node is the only node of the cluster
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Option<CargoReconcileStatus>) - The status or `None` if skipped
+///   - [Err](HttpError) - The cargo has not been reconciled
+///
+pub(crate) async fn reconcile(
+  cargo: &Cargo,
+  single_node: bool,
+  state: &DaemonState,
+) -> Result<Option<CargoReconcileStatus>, HttpError> {
+  let auto_remove = cargo
+    .config
+    .container
+    .host_config
+    .clone()
+    .unwrap_or_default()
+    .auto_remove
+    .unwrap_or(false);
+  let instances = list_instances(&cargo.key, &state.docker_api).await?;
+  let mut indexes = Vec::new();
+  for instance in &instances {
+    match instance_index(&cargo.key, instance) {
+      Some(index) => indexes.push((index, instance)),
+      // An update is in progress
+      None => return Ok(None),
+    }
+  }
+  if is_unconstrained(cargo) && instances.is_empty() && !single_node {
+    return Ok(None);
+  }
+  let expected = node_replicas(cargo, state).await?;
+  let completed = completed_instances(state)?
+    .get(&cargo.key)
+    .cloned()
+    .unwrap_or_default();
+  let to_remove = indexes
+    .iter()
+    .filter(|(index, _)| *index >= expected)
+    .map(|(_, instance)| instance.id.clone().unwrap_or_default())
+    .collect::<Vec<_>>();
+  let missing = (0..expected)
+    .filter(|index| !indexes.iter().any(|(i, _)| i == index))
+    .filter(|index| !completed.contains(index))
+    .collect::<Vec<_>>();
+  // New instances are started unless all the remaining instances are stopped
+  let should_start = instances.is_empty()
+    || instances
+      .iter()
+      .any(|instance| instance.state == Some("running".into()));
+  missing
+    .iter()
+    .map(|index| async move {
+      let created = create_instances(cargo, *index, 1, state).await?;
+      if should_start {
+        for instance in &created {
+          start_instance(cargo, &instance.id, &state.docker_api).await?;
+        }
+      }
+      if auto_remove {
+        let watched = created
+          .into_iter()
+          .map(|instance| (*index, instance.id))
+          .collect::<Vec<_>>();
+        watch_autoremove(&cargo.key, watched, state);
+      }
+      Ok::<_, HttpError>(())
+    })
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<Result<_, HttpError>>>()
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, HttpError>>()?;
+  delete_instances(&to_remove, state).await?;
+  Ok(Some(CargoReconcileStatus {
+    reconciled_at: chrono::Utc::now().naive_utc(),
+    expected,
+    found: instances.len(),
+    created: missing.len(),
+    removed: to_remove.len(),
+    error: None,
+  }))
+}
+
+/// ## Reconcile all
+///
+/// Reconcile the instances of every cargo on the current node,
+/// save their status and emit a `CargoReconciled` event
+/// for every cargo that had instances created or removed.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The cargoes have been reconciled
+///   - [Err](HttpError) - The cargoes cannot be listed
+///
+async fn reconcile_all(state: &DaemonState) -> Result<(), HttpError> {
+  let single_node =
+    repositories::node::list_unless(&state.config.hostname, &state.pool)
+      .await?
+      .is_empty();
+  let cargoes = repositories::cargo::list(&state.pool).await?;
+  for cargo in cargoes {
+    let cargo =
+      repositories::cargo::inspect_by_key(&cargo.key, &state.pool).await?;
+    let status = match reconcile(&cargo, single_node, state).await {
+      Ok(None) => continue,
+      Ok(Some(status)) => status,
+      Err(err) => {
+        log::warn!("Unable to reconcile cargo {}: {err}", cargo.key);
+        CargoReconcileStatus {
+          reconciled_at: chrono::Utc::now().naive_utc(),
+          expected: 0,
+          found: 0,
+          created: 0,
+          removed: 0,
+          error: Some(err.to_string()),
+        }
+      }
+    };
+    let changed = status.created > 0 || status.removed > 0;
+    if changed {
+      log::info!(
+        "Reconciled cargo {}: {} created, {} removed",
+        cargo.key,
+        status.created,
+        status.removed
+      );
+    }
+    state
+      .reconcile_statuses
+      .lock()
+      .map_err(|err| {
+        HttpError::internal_server_error(format!(
+          "Unable to lock reconcile statuses: {err}"
+        ))
+      })?
+      .insert(cargo.key.clone(), status);
+    if changed {
+      let cargo = inspect_by_key(&cargo.key, state).await?;
+      let _ = state
+        .event_emitter
+        .emit(Event::CargoReconciled(Box::new(cargo)))
+        .await;
+    }
+  }
+  Ok(())
+}
+
+/// ## Spawn reconciler
+///
+/// Spawn a background task that periodically reconcile
+/// the instances of the cargoes with their replication mode.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+pub(crate) fn spawn_reconciler(state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    loop {
+      time::sleep(Duration::from_secs(10)).await;
+      if let Err(err) = reconcile_all(&state).await {
+        log::warn!("Unable to reconcile cargoes: {err}");
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use nanocl_stubs::cargo_config::ReplicationStatic;
+
+  use crate::utils::tests::*;
+
+  /// Wait for a cargo to have the given number of instances
+  async fn wait_instances(
+    key: &str,
+    number: usize,
+    state: &DaemonState,
+  ) -> Vec<ContainerSummary> {
+    for _ in 0..30 {
+      let instances = list_instances(key, &state.docker_api).await.unwrap();
+      if instances.len() == number {
+        return instances;
+      }
+      time::sleep(Duration::from_secs(1)).await;
+    }
+    panic!("Expect {number} instances of cargo {key}");
+  }
+
+  #[test]
+  fn expected_replicas_keep_scaling() {
+    assert_eq!(expected_replicas(true, 2, None), 2);
+    assert_eq!(expected_replicas(true, 1, Some(3)), 3);
+    assert_eq!(expected_replicas(false, 1, Some(3)), 3);
+    // A scaling doesn't schedule a constrained cargo on the current node
+    assert_eq!(expected_replicas(false, 0, Some(3)), 0);
+  }
+
+  #[ntex::test]
+  async fn reconcile_autoremove() -> TestRet {
+    let state = gen_test_state().await;
+    ensure_test_image().await?;
+
+    const CARGO_NAME: &str = "utils-test-reconcile-autoremove";
+    // The last instance complete while the others keep running
+    let config = CargoConfigPartial {
+      name: CARGO_NAME.to_owned(),
+      container: ContainerConfig {
+        image: Some("nexthat/nanocl-get-started:latest".to_owned()),
+        cmd: Some(vec![
+          "sh".into(),
+          "-c".into(),
+          "sleep 2; test \"$NANOCL_CARGO_INSTANCE\" = 2 || sleep 60".into(),
+        ]),
+        host_config: Some(HostConfig {
+          auto_remove: Some(true),
+          ..Default::default()
+        }),
+        ..Default::default()
+      },
+      replication: Some(ReplicationMode::Static(ReplicationStatic {
+        number: 3,
+      })),
+      ..Default::default()
+    };
+    let cargo = create("global", &config, "v0.10", &state).await?;
+    start_by_key(&cargo.key, &state).await?;
+    wait_instances(&cargo.key, 2, &state).await;
+
+    // The crashed instance is removed by docker
+    state
+      .docker_api
+      .kill_container(
+        &gen_instance_name(&cargo.key, 1),
+        None::<KillContainerOptions<String>>,
+      )
+      .await?;
+    wait_instances(&cargo.key, 1, &state).await;
+
+    let status = reconcile(&cargo, true, &state)
+      .await?
+      .expect("Expect the cargo to be reconciled");
+    assert_eq!(status.expected, 3);
+    assert_eq!(status.created, 1, "Expect only the crashed instance");
+    let instances = wait_instances(&cargo.key, 2, &state).await;
+    let mut indexes = instances
+      .iter()
+      .filter_map(|instance| instance_index(&cargo.key, instance))
+      .collect::<Vec<_>>();
+    indexes.sort();
+    assert_eq!(indexes, vec![0, 1]);
+
+    delete_by_key(&cargo.key, Some(true), &state).await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,358 @@
+use std::time::Duration;
+use std::collections::HashMap;
+
+use ntex::rt;
+use ntex::time;
+
+use nanocl_utils::http_error::HttpError;
+use nanocl_stubs::system::Event;
+use nanocl_stubs::metric::{MetricKind, CargoMetric};
+use nanocl_stubs::cargo::{Cargo, CargoScale, CargoScaleHistory};
+use nanocl_stubs::cargo_config::{CargoAutoscale, ReplicationMode};
+
+use crate::{utils, repositories};
+use crate::models::{DaemonState, CargoScaleHistoryDbModel};
+
+/// Relative difference between the usage and the target under which no scaling is done
+const TOLERANCE: f64 = 0.1;
+/// Duration in seconds of the cargo metrics averaged to get the usage
+const USAGE_WINDOW: i64 = 60;
+
+/// ## Validate
+///
+/// Validate the autoscale config of a cargo
+///
+/// ## Arguments
+///
+/// - [autoscale](CargoAutoscale) - The autoscale config
+/// - [replication](Option<ReplicationMode>) - The replication mode of the cargo
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The autoscale config is valid
+///   - [Err](HttpError) - The autoscale config is invalid
+///
+pub fn validate(
+  autoscale: &CargoAutoscale,
+  replication: Option<&ReplicationMode>,
+) -> Result<(), HttpError> {
+  if let Some(replication) = replication {
+    if !matches!(replication, ReplicationMode::Static(_)) {
+      return Err(HttpError::bad_request(
+        "Autoscale can only be used with a Static replication",
+      ));
+    }
+  }
+  if autoscale.target_cpu.is_none() && autoscale.target_memory.is_none() {
+    return Err(HttpError::bad_request(
+      "Autoscale require a TargetCpu or a TargetMemory",
+    ));
+  }
+  if autoscale.target_cpu == Some(0) || autoscale.target_memory == Some(0) {
+    return Err(HttpError::bad_request(
+      "Autoscale TargetCpu and TargetMemory must be greater than 0",
+    ));
+  }
+  let min_replicas = autoscale.min_replicas.unwrap_or(1);
+  if min_replicas == 0 || min_replicas > autoscale.max_replicas {
+    return Err(HttpError::bad_request(format!(
+      "Autoscale MinReplicas must be between 1 and MaxReplicas ({})",
+      autoscale.max_replicas
+    )));
+  }
+  Ok(())
+}
+
+/// ## Desired replicas
+///
+/// Compute the number of replicas needed to bring the usage near the target
+///
+fn desired_replicas(current: usize, usage: f64, target: u64) -> usize {
+  let ratio = usage / target as f64;
+  if (ratio - 1.0).abs() <= TOLERANCE {
+    return current;
+  }
+  (current as f64 * ratio).ceil() as usize
+}
+
+/// ## Decide
+///
+/// Compute the number of replicas of a cargo needed to bring
+/// the average usage of his instances near his targets.
+/// The number is clamped between the min and the max replicas.
+///
+/// ## Arguments
+///
+/// - [current](usize) - The current number of replicas
+/// - [cpu](Option<f64>) - The average cpu usage in percent
+/// - [memory](Option<f64>) - The average memory usage in percent
+/// - [autoscale](CargoAutoscale) - The autoscale config of the cargo
+///
+/// ## Returns
+///
+/// - [Option](Option) - The number of replicas and the reason,
+///   `None` without usage for the targets
+///
+fn decide(
+  current: usize,
+  cpu: Option<f64>,
+  memory: Option<f64>,
+  autoscale: &CargoAutoscale,
+) -> Option<(usize, String)> {
+  let mut desired = None;
+  let mut reasons = Vec::new();
+  if let (Some(target), Some(usage)) = (autoscale.target_cpu, cpu) {
+    desired = Some(desired_replicas(current, usage, target));
+    reasons.push(format!("cpu usage {usage:.1}% for a target of {target}%"));
+  }
+  if let (Some(target), Some(usage)) = (autoscale.target_memory, memory) {
+    let replicas = desired_replicas(current, usage, target);
+    desired = Some(desired.map_or(replicas, |d: usize| d.max(replicas)));
+    reasons.push(format!(
+      "memory usage {usage:.1}% for a target of {target}%"
+    ));
+  }
+  let min_replicas = autoscale.min_replicas.unwrap_or(1);
+  let desired = desired?.clamp(min_replicas, autoscale.max_replicas);
+  Some((desired, format!("Average {}", reasons.join(" and "))))
+}
+
+/// ## Usage
+///
+/// Compute the average cpu and memory usage of the instances of a cargo
+/// on the current node from the cargo metrics saved during the usage window.
+/// Only the metrics saved with the given number of running instances are used.
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [instances](usize) - The number of running instances
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok]((Option<f64>, Option<f64>)) - The average cpu and memory usage
+///   - [Err](HttpError) - The metrics could not be listed
+///
+async fn usage(
+  key: &str,
+  instances: usize,
+  state: &DaemonState,
+) -> Result<(Option<f64>, Option<f64>), HttpError> {
+  let since =
+    chrono::Utc::now().naive_utc() - chrono::Duration::seconds(USAGE_WINDOW);
+  let items = repositories::metric::list_by_node_kind_since(
+    &state.config.hostname,
+    &MetricKind::Cargo.to_string(),
+    since,
+    &state.pool,
+  )
+  .await?;
+  let metrics = items
+    .into_iter()
+    .filter_map(|item| {
+      serde_json::from_value::<HashMap<String, CargoMetric>>(item.data)
+        .ok()?
+        .remove(key)
+    })
+    .filter(|metric| metric.instances == instances)
+    .collect::<Vec<_>>();
+  let cpu = utils::metric::average(metrics.iter().map(|m| m.cpu_usage));
+  let memory = utils::metric::average(metrics.iter().map(|m| m.memory_usage));
+  Ok((cpu, memory))
+}
+
+/// ## Autoscale
+///
+/// Compare the average usage of the running instances of a cargo with his targets
+/// and scale the cargo if needed and allowed by the cooldowns.
+/// The usage is read from the cargo metrics
+/// and the scaling decision is saved in the cargo scale histories.
+///
+/// ## Arguments
+///
+/// - [cargo](Cargo) - The cargo to autoscale
+/// - [autoscale](CargoAutoscale) - The autoscale config of the cargo
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Option<CargoScaleHistory>) - The scaling decision if the cargo has been scaled
+///   - [Err](HttpError) - The cargo has not been autoscaled
+///
+async fn autoscale(
+  cargo: &Cargo,
+  autoscale: &CargoAutoscale,
+  state: &DaemonState,
+) -> Result<Option<CargoScaleHistory>, HttpError> {
+  let instances =
+    utils::cargo::list_instances(&cargo.key, &state.docker_api).await?;
+  let running = instances
+    .iter()
+    .filter(|instance| instance.state == Some("running".into()))
+    .count();
+  // The cargo is stopped or being updated
+  if running == 0 || running != instances.len() {
+    return Ok(None);
+  }
+  let current = instances.len();
+  let (cpu, memory) = usage(&cargo.key, current, state).await?;
+  let Some((desired, reason)) = decide(current, cpu, memory, autoscale) else {
+    return Ok(None);
+  };
+  if desired == current {
+    return Ok(None);
+  }
+  let last = repositories::cargo_scale_history::find_last_by_cargo_key(
+    &cargo.key,
+    &state.config.hostname,
+    &state.pool,
+  )
+  .await?;
+  if let Some(last) = last {
+    let cooldown = if desired > current {
+      autoscale.scale_up_cooldown.unwrap_or(60)
+    } else {
+      autoscale.scale_down_cooldown.unwrap_or(300)
+    };
+    let elapsed = chrono::Utc::now().naive_utc() - last.created_at;
+    if elapsed.num_seconds() < cooldown as i64 {
+      return Ok(None);
+    }
+  }
+  let options = CargoScale {
+    replicas: desired as isize - current as isize,
+  };
+  utils::cargo::scale_instances(&cargo.key, &options, state).await?;
+  let item = CargoScaleHistoryDbModel {
+    key: uuid::Uuid::new_v4(),
+    created_at: chrono::Utc::now().naive_utc(),
+    cargo_key: cargo.key.clone(),
+    node_name: state.config.hostname.clone(),
+    previous_replicas: current as i64,
+    replicas: desired as i64,
+    cpu_usage: cpu,
+    memory_usage: memory,
+    reason,
+  };
+  let history =
+    repositories::cargo_scale_history::create(&item, &state.pool).await?;
+  Ok(Some(history))
+}
+
+/// ## Spawn autoscaler
+///
+/// Spawn a background task that periodically autoscale
+/// the cargoes with an autoscale config.
+/// Every scaling emit a `CargoAutoscaled` and a `CargoPatched` event.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+pub(crate) fn spawn_autoscaler(state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    loop {
+      time::sleep(Duration::from_secs(30)).await;
+      let cargoes = match repositories::cargo::list(&state.pool).await {
+        Ok(cargoes) => cargoes,
+        Err(err) => {
+          log::warn!("Unable to list cargoes to autoscale: {err}");
+          continue;
+        }
+      };
+      for cargo in cargoes {
+        let cargo =
+          match repositories::cargo::inspect_by_key(&cargo.key, &state.pool)
+            .await
+          {
+            Ok(cargo) => cargo,
+            Err(err) => {
+              log::warn!("Unable to inspect cargo {}: {err}", cargo.key);
+              continue;
+            }
+          };
+        let Some(config) = &cargo.config.autoscale else {
+          continue;
+        };
+        let history = match autoscale(&cargo, config, &state).await {
+          Ok(Some(history)) => history,
+          Ok(None) => continue,
+          Err(err) => {
+            log::warn!("Unable to autoscale cargo {}: {err}", cargo.key);
+            continue;
+          }
+        };
+        log::info!(
+          "Autoscaled cargo {} from {} to {} replicas: {}",
+          cargo.key,
+          history.previous_replicas,
+          history.replicas,
+          history.reason
+        );
+        let _ = state
+          .event_emitter
+          .emit(Event::CargoAutoscaled(Box::new(history)))
+          .await;
+        if let Ok(cargo) =
+          utils::cargo::inspect_by_key(&cargo.key, &state).await
+        {
+          let _ = state
+            .event_emitter
+            .emit(Event::CargoPatched(Box::new(cargo)))
+            .await;
+        }
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn gen_autoscale() -> CargoAutoscale {
+    CargoAutoscale {
+      min_replicas: Some(2),
+      max_replicas: 6,
+      target_cpu: Some(50),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn scale_up() {
+    let autoscale = gen_autoscale();
+    let (desired, reason) = decide(2, Some(100.0), None, &autoscale).unwrap();
+    assert_eq!(desired, 4);
+    assert_eq!(reason, "Average cpu usage 100.0% for a target of 50%");
+    // The number of replicas is clamped to the max
+    let (desired, _) = decide(4, Some(200.0), None, &autoscale).unwrap();
+    assert_eq!(desired, 6);
+    // The memory usage scale up even if the cpu is low
+    let autoscale = CargoAutoscale {
+      target_memory: Some(50),
+      ..gen_autoscale()
+    };
+    let (desired, _) = decide(2, Some(10.0), Some(75.0), &autoscale).unwrap();
+    assert_eq!(desired, 3);
+  }
+
+  #[test]
+  fn scale_down() {
+    let autoscale = gen_autoscale();
+    let (desired, _) = decide(6, Some(25.0), None, &autoscale).unwrap();
+    assert_eq!(desired, 3);
+    // The number of replicas is clamped to the min
+    let (desired, _) = decide(3, Some(1.0), None, &autoscale).unwrap();
+    assert_eq!(desired, 2);
+    // No scaling within the tolerance or without usage
+    let (desired, _) = decide(4, Some(53.0), None, &autoscale).unwrap();
+    assert_eq!(desired, 4);
+    assert!(decide(4, None, Some(10.0), &autoscale).is_none());
+  }
+}
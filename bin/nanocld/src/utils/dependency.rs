@@ -0,0 +1,471 @@
+use std::time::{Duration, Instant};
+
+use ntex::time;
+use futures::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use bollard_next::container::InspectContainerOptions;
+use bollard_next::service::{
+  ContainerStateStatusEnum, HealthStatusEnum, RestartPolicy,
+  RestartPolicyNameEnum,
+};
+
+use nanocl_utils::http_error::HttpError;
+use nanocl_stubs::vm_config::VmConfigPartial;
+use nanocl_stubs::cargo_config::CargoConfigPartial;
+use nanocl_stubs::generic::{GenericDependency, GenericDependencyCondition};
+
+use crate::utils;
+use crate::models::DaemonState;
+
+/// Default time in seconds to wait for the condition of a dependency
+const DEFAULT_TIMEOUT: u64 = 300;
+
+/// ## Workload
+///
+/// A cargo or a virtual machine declared in a Statefile
+///
+#[derive(Debug, Clone, Copy)]
+pub enum Workload<'a> {
+  Cargo(&'a CargoConfigPartial),
+  VirtualMachine(&'a VmConfigPartial),
+}
+
+impl<'a> Workload<'a> {
+  /// Kind of the workload as reported in the state stream
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Workload::Cargo(_) => "Cargo",
+      Workload::VirtualMachine(_) => "VirtualMachine",
+    }
+  }
+
+  /// Name of the workload
+  pub fn name(&self) -> &'a str {
+    match self {
+      Workload::Cargo(cargo) => &cargo.name,
+      Workload::VirtualMachine(vm) => &vm.name,
+    }
+  }
+
+  /// Dependencies of the workload
+  pub fn depends_on(&self) -> &'a [GenericDependency] {
+    match self {
+      Workload::Cargo(cargo) => cargo.depends_on.as_deref(),
+      Workload::VirtualMachine(vm) => vm.depends_on.as_deref(),
+    }
+    .unwrap_or_default()
+  }
+}
+
+/// ## DependencyGraph
+///
+/// Dependency graph of the cargoes and virtual machines of a Statefile
+///
+pub struct DependencyGraph<'a> {
+  /// Namespace of the workloads
+  pub namespace: String,
+  /// Cargoes and virtual machines of the Statefile
+  pub workloads: Vec<Workload<'a>>,
+  /// Index and definition of the dependencies of each workload
+  pub dependencies: Vec<Vec<(usize, &'a GenericDependency)>>,
+  /// Index of the workloads depending on each workload
+  pub dependents: Vec<Vec<usize>>,
+}
+
+impl DependencyGraph<'_> {
+  /// Key of the workload at the given index
+  pub fn key(&self, index: usize) -> String {
+    utils::key::gen_key(&self.namespace, self.workloads[index].name())
+  }
+
+  /// Whether a dependent wait for the workload at the given index to complete
+  pub fn is_one_shot(&self, index: usize) -> bool {
+    self.dependents[index].iter().any(|dependent| {
+      self.dependencies[*dependent]
+        .iter()
+        .any(|(dependency, definition)| {
+          *dependency == index
+            && definition.condition
+              == Some(GenericDependencyCondition::Completed)
+        })
+    })
+  }
+}
+
+/// ## One shot cargo
+///
+/// Set the restart policy of a cargo awaited until his completion to `no`
+/// when it's not set, otherwise his instances would restart after exiting
+///
+/// ## Arguments
+///
+/// - [cargo](CargoConfigPartial) - The cargo to complete
+///
+/// ## Returns
+///
+/// - [CargoConfigPartial](CargoConfigPartial) - The cargo with a restart policy
+///
+pub fn one_shot_cargo(cargo: &CargoConfigPartial) -> CargoConfigPartial {
+  let mut cargo = cargo.clone();
+  let mut host_config = cargo.container.host_config.unwrap_or_default();
+  if host_config.restart_policy.is_none() {
+    host_config.restart_policy = Some(RestartPolicy {
+      name: Some(RestartPolicyNameEnum::NO),
+      maximum_retry_count: None,
+    });
+  }
+  cargo.container.host_config = Some(host_config);
+  cargo
+}
+
+/// ## Find cycle
+///
+/// Depth first search of a cycle from the given workload.
+/// Colors are 0 for unvisited, 1 for being visited and 2 for visited.
+///
+fn find_cycle(
+  index: usize,
+  graph: &DependencyGraph,
+  colors: &mut [u8],
+  path: &mut Vec<usize>,
+) -> Option<Vec<usize>> {
+  colors[index] = 1;
+  path.push(index);
+  for (dependency, _) in &graph.dependencies[index] {
+    match colors[*dependency] {
+      1 => {
+        let start = path.iter().position(|i| i == dependency).unwrap_or(0);
+        let mut cycle = path[start..].to_vec();
+        cycle.push(*dependency);
+        return Some(cycle);
+      }
+      0 => {
+        if let Some(cycle) = find_cycle(*dependency, graph, colors, path) {
+          return Some(cycle);
+        }
+      }
+      _ => {}
+    }
+  }
+  path.pop();
+  colors[index] = 2;
+  None
+}
+
+/// ## Build graph
+///
+/// Build the dependency graph of the cargoes and virtual machines of a Statefile
+/// and ensure every dependency is declared and there is no cycle
+///
+/// ## Arguments
+///
+/// - [namespace](str) - The namespace of the workloads
+/// - [cargoes](Vec<CargoConfigPartial>) - The cargoes of the Statefile
+/// - [vms](Vec<VmConfigPartial>) - The virtual machines of the Statefile
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](DependencyGraph) - The dependency graph
+///   - [Err](HttpError) - A dependency is unknown or there is a cycle
+///
+pub fn build_graph<'a>(
+  namespace: &str,
+  cargoes: &'a [CargoConfigPartial],
+  vms: &'a [VmConfigPartial],
+) -> Result<DependencyGraph<'a>, HttpError> {
+  let workloads = cargoes
+    .iter()
+    .map(Workload::Cargo)
+    .chain(vms.iter().map(Workload::VirtualMachine))
+    .collect::<Vec<_>>();
+  let mut dependencies = Vec::with_capacity(workloads.len());
+  let mut dependents = vec![Vec::new(); workloads.len()];
+  for (index, workload) in workloads.iter().enumerate() {
+    let mut workload_dependencies = Vec::new();
+    for dependency in workload.depends_on() {
+      let matches = workloads
+        .iter()
+        .enumerate()
+        .filter(|(_, other)| other.name() == dependency.name)
+        .map(|(other_index, _)| other_index)
+        .collect::<Vec<_>>();
+      let dependency_index = match matches.as_slice() {
+        [dependency_index] => *dependency_index,
+        [] => {
+          return Err(HttpError::bad_request(format!(
+            "{} {} depends on {} which is not declared in the Statefile",
+            workload.kind(),
+            workload.name(),
+            dependency.name
+          )))
+        }
+        _ => {
+          return Err(HttpError::bad_request(format!(
+            "{} {} depends on {} which is ambiguous between a cargo and a virtual machine",
+            workload.kind(),
+            workload.name(),
+            dependency.name
+          )))
+        }
+      };
+      workload_dependencies.push((dependency_index, dependency));
+      dependents[dependency_index].push(index);
+    }
+    dependencies.push(workload_dependencies);
+  }
+  let graph = DependencyGraph {
+    namespace: namespace.to_owned(),
+    workloads,
+    dependencies,
+    dependents,
+  };
+  // Instances restarted after exiting would never complete
+  for (index, workload) in graph.workloads.iter().enumerate() {
+    let Workload::Cargo(cargo) = workload else {
+      continue;
+    };
+    if !graph.is_one_shot(index) {
+      continue;
+    }
+    let policy = cargo
+      .container
+      .host_config
+      .as_ref()
+      .and_then(|host_config| host_config.restart_policy.as_ref())
+      .and_then(|restart_policy| restart_policy.name);
+    if let Some(
+      policy @ (RestartPolicyNameEnum::ALWAYS
+      | RestartPolicyNameEnum::UNLESS_STOPPED),
+    ) = policy
+    {
+      return Err(HttpError::bad_request(format!(
+        "Cargo {} must complete for its dependents but its restart policy is {policy}",
+        cargo.name
+      )));
+    }
+  }
+  let mut colors = vec![0; graph.workloads.len()];
+  while let Some(index) = colors.iter().position(|color| *color == 0) {
+    if let Some(cycle) = find_cycle(index, &graph, &mut colors, &mut Vec::new())
+    {
+      let cycle = cycle
+        .iter()
+        .map(|index| graph.workloads[*index].name())
+        .collect::<Vec<_>>()
+        .join(" -> ");
+      return Err(HttpError::bad_request(format!(
+        "Dependency cycle detected: {cycle}"
+      )));
+    }
+  }
+  Ok(graph)
+}
+
+/// ## Condition name
+///
+/// Human readable name of a dependency condition
+///
+fn condition_name(condition: &GenericDependencyCondition) -> &'static str {
+  match condition {
+    GenericDependencyCondition::Started => "started",
+    GenericDependencyCondition::Healthy => "healthy",
+    GenericDependencyCondition::Completed => "completed",
+  }
+}
+
+/// ## Wait container
+///
+/// Wait for a container to reach a dependency condition.
+/// It fail if the container exit with an error code, is reported unhealthy
+/// or if the timeout is reached.
+///
+async fn wait_container(
+  name: &str,
+  condition: &GenericDependencyCondition,
+  timeout: u64,
+  docker_api: &bollard_next::Docker,
+) -> Result<(), HttpError> {
+  let started_at = Instant::now();
+  loop {
+    let container = docker_api
+      .inspect_container(name, None::<InspectContainerOptions>)
+      .await?;
+    let container_state = container.state.unwrap_or_default();
+    let exit_code = container_state.exit_code.unwrap_or_default();
+    let exited = matches!(
+      container_state.status,
+      Some(ContainerStateStatusEnum::EXITED | ContainerStateStatusEnum::DEAD)
+    );
+    if exited && exit_code != 0 {
+      return Err(HttpError::internal_server_error(format!(
+        "Instance {name} exited with code {exit_code}"
+      )));
+    }
+    let running = container_state.running.unwrap_or(false);
+    let ready = match condition {
+      GenericDependencyCondition::Started => running || exited,
+      GenericDependencyCondition::Completed => exited,
+      GenericDependencyCondition::Healthy => {
+        if exited {
+          return Err(HttpError::internal_server_error(format!(
+            "Instance {name} exited before being healthy"
+          )));
+        }
+        match container_state.health.and_then(|health| health.status) {
+          Some(HealthStatusEnum::UNHEALTHY) => {
+            return Err(HttpError::internal_server_error(format!(
+              "Instance {name} is unhealthy"
+            )));
+          }
+          Some(HealthStatusEnum::STARTING) => false,
+          _ => running,
+        }
+      }
+    };
+    if ready {
+      return Ok(());
+    }
+    if started_at.elapsed() >= Duration::from_secs(timeout) {
+      return Err(HttpError::internal_server_error(format!(
+        "Instance {name} is not {} after {timeout} seconds",
+        condition_name(condition)
+      )));
+    }
+    time::sleep(Duration::from_secs(1)).await;
+  }
+}
+
+/// ## Wait dependency
+///
+/// Wait for all the instances of a workload to reach the condition of a dependency
+///
+/// ## Arguments
+///
+/// - [graph](DependencyGraph) - The dependency graph
+/// - [index](usize) - The index of the workload to wait for
+/// - [dependency](GenericDependency) - The dependency definition
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The condition is reached
+///   - [Err](HttpError) - The condition has not been reached
+///
+pub async fn wait_dependency(
+  graph: &DependencyGraph<'_>,
+  index: usize,
+  dependency: &GenericDependency,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let key = graph.key(index);
+  let condition = dependency.condition.unwrap_or_default();
+  let timeout = dependency.timeout.unwrap_or(DEFAULT_TIMEOUT);
+  let names = match graph.workloads[index] {
+    Workload::Cargo(_) => utils::cargo::list_instances(&key, &state.docker_api)
+      .await?
+      .into_iter()
+      .filter_map(|instance| instance.id)
+      .collect::<Vec<_>>(),
+    Workload::VirtualMachine(_) => vec![format!("{key}.v")],
+  };
+  if names.is_empty() {
+    return Err(HttpError::internal_server_error(format!(
+      "{} {key} has no instance",
+      graph.workloads[index].kind()
+    )));
+  }
+  names
+    .iter()
+    .map(|name| wait_container(name, &condition, timeout, &state.docker_api))
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use bollard_next::service::HostConfig;
+
+  fn gen_cargo(name: &str, depends_on: &[&str]) -> CargoConfigPartial {
+    CargoConfigPartial {
+      name: name.to_owned(),
+      depends_on: Some(
+        depends_on
+          .iter()
+          .map(|name| GenericDependency {
+            name: name.to_string(),
+            condition: None,
+            timeout: None,
+          })
+          .collect(),
+      ),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn build_graph_ordering() {
+    let cargoes = vec![
+      gen_cargo("api", &["db", "cache"]),
+      gen_cargo("db", &[]),
+      gen_cargo("cache", &[]),
+    ];
+    let graph = build_graph("global", &cargoes, &[]).unwrap();
+    assert_eq!(graph.dependencies[0].len(), 2);
+    assert_eq!(graph.dependents[1], vec![0]);
+    assert_eq!(graph.dependents[2], vec![0]);
+    assert_eq!(graph.key(0), "api.global");
+  }
+
+  #[test]
+  fn build_graph_errors() {
+    let cargoes = vec![gen_cargo("api", &["db"])];
+    let err = build_graph("global", &cargoes, &[]).err().unwrap();
+    assert_eq!(err.status, ntex::http::StatusCode::BAD_REQUEST);
+    let cargoes = vec![
+      gen_cargo("api", &["db"]),
+      gen_cargo("db", &["worker"]),
+      gen_cargo("worker", &["api"]),
+    ];
+    let err = build_graph("global", &cargoes, &[]).err().unwrap();
+    assert_eq!(err.status, ntex::http::StatusCode::BAD_REQUEST);
+    assert_eq!(
+      err.msg,
+      "Dependency cycle detected: api -> db -> worker -> api"
+    );
+  }
+
+  #[test]
+  fn build_graph_one_shot() {
+    let mut cargoes =
+      vec![gen_cargo("api", &["migrate"]), gen_cargo("migrate", &[])];
+    let depends_on = cargoes[0].depends_on.as_mut().unwrap();
+    depends_on[0].condition = Some(GenericDependencyCondition::Completed);
+    let graph = build_graph("global", &cargoes, &[]).unwrap();
+    assert!(graph.is_one_shot(1));
+    assert!(!graph.is_one_shot(0));
+    let migrate = one_shot_cargo(&cargoes[1]);
+    let policy = migrate
+      .container
+      .host_config
+      .and_then(|host_config| host_config.restart_policy)
+      .and_then(|restart_policy| restart_policy.name);
+    assert_eq!(policy, Some(RestartPolicyNameEnum::NO));
+    cargoes[1].container.host_config = Some(HostConfig {
+      restart_policy: Some(RestartPolicy {
+        name: Some(RestartPolicyNameEnum::ALWAYS),
+        maximum_retry_count: None,
+      }),
+      ..Default::default()
+    });
+    let err = build_graph("global", &cargoes, &[]).err().unwrap();
+    assert_eq!(err.status, ntex::http::StatusCode::BAD_REQUEST);
+  }
+}
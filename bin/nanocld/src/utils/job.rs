@@ -0,0 +1,622 @@
+use std::str::FromStr;
+use std::time::Duration;
+use std::collections::HashMap;
+
+use ntex::rt;
+use ntex::http;
+use ntex::time;
+use futures::TryStreamExt;
+
+use bollard_next::image::CreateImageOptions;
+use bollard_next::service::{RestartPolicy, RestartPolicyNameEnum};
+use bollard_next::container::{
+  CreateContainerOptions, InspectContainerOptions, ListContainersOptions,
+  LogsOptions, RemoveContainerOptions, WaitContainerOptions,
+};
+
+use nanocl_utils::http_error::HttpError;
+use nanocl_stubs::system::Event;
+use nanocl_stubs::generic::GenericDelete;
+use nanocl_stubs::job::{Job, JobPartial, JobRun, JobRunStatus, JobInspect};
+
+use crate::{utils, repositories};
+use crate::models::{DaemonState, JobRunDbModel, JobRunUpdateDbModel};
+
+/// Default time in seconds to wait before the first retry of a failed run
+const DEFAULT_BACKOFF: u64 = 10;
+/// Default number of past runs to keep for a job
+const DEFAULT_HISTORY_LIMIT: usize = 10;
+/// Number of lines of output saved for a run
+const LOGS_TAIL: &str = "200";
+
+/// ## Parse schedule
+///
+/// Parse the cron expression of a job.
+/// Expressions without seconds (5 fields) are run at the second 0.
+///
+/// ## Arguments
+///
+/// - [schedule](str) - The cron expression
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](cron::Schedule) - The parsed schedule
+///   - [Err](HttpError) - The expression is invalid
+///
+pub(crate) fn parse_schedule(
+  schedule: &str,
+) -> Result<cron::Schedule, HttpError> {
+  let expression = if schedule.split_whitespace().count() == 5 {
+    format!("0 {schedule}")
+  } else {
+    schedule.to_owned()
+  };
+  cron::Schedule::from_str(&expression).map_err(|err| {
+    HttpError::bad_request(format!("Invalid schedule {schedule}: {err}"))
+  })
+}
+
+/// ## Validate
+///
+/// Validate the config of a job before saving it
+///
+/// ## Arguments
+///
+/// - [item](JobPartial) - The job config
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The config is valid
+///   - [Err](HttpError) - The config is invalid
+///
+pub(crate) fn validate(item: &JobPartial) -> Result<(), HttpError> {
+  if item.name.is_empty() {
+    return Err(HttpError::bad_request("Job name cannot be empty"));
+  }
+  if item.container.image.is_none() {
+    return Err(HttpError::bad_request(format!(
+      "Job {} must have an image",
+      item.name
+    )));
+  }
+  if let Some(schedule) = &item.schedule {
+    parse_schedule(schedule)?;
+  }
+  Ok(())
+}
+
+/// ## Next run at
+///
+/// Get the date of the next scheduled run of a job
+///
+/// ## Arguments
+///
+/// - [item](JobPartial) - The job config
+///
+/// ## Returns
+///
+/// - [Option](Option) - The date of the next run if the job is scheduled
+///
+fn next_run_at(item: &JobPartial) -> Option<chrono::NaiveDateTime> {
+  let schedule = parse_schedule(item.schedule.as_deref()?).ok()?;
+  schedule
+    .upcoming(chrono::Utc)
+    .next()
+    .map(|date| date.naive_utc())
+}
+
+/// ## Inspect by key
+///
+/// Get detailed information about a job with his past runs
+///
+/// ## Arguments
+///
+/// - [key](str) - The job name
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](JobInspect) - The job information
+///   - [Err](HttpError) - The job doesn't exist
+///
+pub(crate) async fn inspect_by_key(
+  key: &str,
+  state: &DaemonState,
+) -> Result<JobInspect, HttpError> {
+  let job = repositories::job::find_by_key(key, &state.pool).await?;
+  let runs = repositories::job_run::list_by_job_key(key, &state.pool).await?;
+  Ok(JobInspect {
+    next_run_at: next_run_at(&job.config),
+    name: job.name,
+    created_at: job.created_at,
+    updated_at: job.updated_at,
+    config: job.config,
+    runs,
+  })
+}
+
+/// ## Delete by key
+///
+/// Delete a job with his runs and remove his running containers
+///
+/// ## Arguments
+///
+/// - [key](str) - The job name
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](GenericDelete) - The job has been deleted
+///   - [Err](HttpError) - The job has not been deleted
+///
+pub(crate) async fn delete_by_key(
+  key: &str,
+  state: &DaemonState,
+) -> Result<GenericDelete, HttpError> {
+  let label = format!("io.nanocl.j={key}");
+  let mut filters: HashMap<&str, Vec<&str>> = HashMap::new();
+  filters.insert("label", vec![&label]);
+  let options = Some(ListContainersOptions {
+    all: true,
+    filters,
+    ..Default::default()
+  });
+  let containers = state.docker_api.list_containers(options).await?;
+  for container in containers {
+    state
+      .docker_api
+      .remove_container(
+        &container.id.unwrap_or_default(),
+        Some(RemoveContainerOptions {
+          force: true,
+          ..Default::default()
+        }),
+      )
+      .await?;
+  }
+  let res = repositories::job::delete_by_key(key, &state.pool).await?;
+  repositories::job_run::delete_by_job_key(key, &state.pool).await?;
+  repositories::state_ref::delete_by_key("Job", key, &state.pool).await?;
+  Ok(res)
+}
+
+/// ## Pull image if missing
+///
+/// Pull the image of a job if it's not already on the node
+///
+/// ## Arguments
+///
+/// - [image](str) - The image name with an optional tag
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The image is available
+///   - [Err](HttpError) - The image could not be pulled
+///
+async fn pull_image_if_missing(
+  image: &str,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  if state.docker_api.inspect_image(image).await.is_ok() {
+    return Ok(());
+  }
+  let (from_image, tag) = match image.rsplit_once(':') {
+    Some((name, tag)) if !tag.contains('/') => (name, tag),
+    _ => (image, "latest"),
+  };
+  state
+    .docker_api
+    .create_image(
+      Some(CreateImageOptions {
+        from_image,
+        tag,
+        ..Default::default()
+      }),
+      None,
+      None,
+    )
+    .try_collect::<Vec<_>>()
+    .await?;
+  Ok(())
+}
+
+/// ## Create container
+///
+/// Create the container of a job run with the builder of the cargoes.
+/// The container is named after the job so only one run of a job
+/// can exist at a time on a node, a concurrent create fail with a conflict.
+///
+/// ## Arguments
+///
+/// - [job](Job) - The job
+/// - [name](str) - The name of the container
+/// - [secret_envs](Vec<String>) - The environment variables of the secrets
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The container has been created
+///   - [Err](HttpError) - The container could not be created
+///
+async fn create_container(
+  job: &Job,
+  name: &str,
+  secret_envs: &[String],
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let mut env = secret_envs.to_vec();
+  env.push(format!("NANOCL_JOB_NAME={}", job.name));
+  let mut config = utils::cargo::gen_container_config(
+    &job.config.container,
+    "global",
+    &[("io.nanocl.j", &job.name)],
+    env,
+    Vec::new(),
+    Some(RestartPolicy {
+      name: Some(RestartPolicyNameEnum::NO),
+      maximum_retry_count: None,
+    }),
+  );
+  // The container is inspected after his exit and removed by us
+  if let Some(host_config) = config.host_config.as_mut() {
+    host_config.auto_remove = Some(false);
+  }
+  state
+    .docker_api
+    .create_container(
+      Some(CreateContainerOptions {
+        name,
+        ..Default::default()
+      }),
+      config,
+    )
+    .await?;
+  Ok(())
+}
+
+/// ## Remove container
+///
+/// Remove the container of a job run
+///
+/// ## Arguments
+///
+/// - [name](str) - The name of the container
+/// - [state](DaemonState) - The daemon state
+///
+async fn remove_container(name: &str, state: &DaemonState) {
+  let _ = state
+    .docker_api
+    .remove_container(
+      name,
+      Some(RemoveContainerOptions {
+        force: true,
+        ..Default::default()
+      }),
+    )
+    .await;
+}
+
+/// ## Wait container
+///
+/// Start a created container of a job and wait for it to exit
+///
+/// ## Arguments
+///
+/// - [name](str) - The name of the container
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok]((i64, String)) - The exit code and the last lines of output
+///   - [Err](HttpError) - The container could not be started
+///
+async fn wait_container(
+  name: &str,
+  state: &DaemonState,
+) -> Result<(i64, String), HttpError> {
+  state
+    .docker_api
+    .start_container::<String>(name, None)
+    .await?;
+  // The wait fail when the container exit with an error code
+  let _ = state
+    .docker_api
+    .wait_container(name, None::<WaitContainerOptions<String>>)
+    .try_for_each(|_| async { Ok(()) })
+    .await;
+  let exit_code = state
+    .docker_api
+    .inspect_container(name, None::<InspectContainerOptions>)
+    .await?
+    .state
+    .and_then(|state| state.exit_code)
+    .unwrap_or_default();
+  let logs = state
+    .docker_api
+    .logs(
+      name,
+      Some(LogsOptions::<String> {
+        stdout: true,
+        stderr: true,
+        tail: LOGS_TAIL.into(),
+        ..Default::default()
+      }),
+    )
+    .try_fold(String::new(), |mut logs, output| async move {
+      logs.push_str(&output.to_string());
+      Ok(logs)
+    })
+    .await
+    .unwrap_or_default();
+  Ok((exit_code, logs))
+}
+
+/// ## Run attempt
+///
+/// Run a job once and save the result in a new job run.
+/// Nothing is done if a run of the job already exist on the node.
+///
+/// ## Arguments
+///
+/// - [job](Job) - The job
+/// - [attempt](usize) - The attempt number, 0 for the first try
+/// - [secret_envs](Vec<String>) - The environment variables of the secrets
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Option<JobRun>) - The finished job run or None if already running
+///   - [Err](HttpError) - The job run could not be saved
+///
+async fn run_attempt(
+  job: &Job,
+  attempt: usize,
+  secret_envs: &[String],
+  state: &DaemonState,
+) -> Result<Option<JobRun>, HttpError> {
+  let name = format!("{}.j", job.name);
+  let created = create_container(job, &name, secret_envs, state).await;
+  if let Err(err) = &created {
+    if err.status == http::StatusCode::CONFLICT {
+      return Ok(None);
+    }
+  }
+  let key = uuid::Uuid::new_v4();
+  let item = JobRunDbModel {
+    key,
+    created_at: chrono::Utc::now().naive_utc(),
+    finished_at: None,
+    job_key: job.name.to_owned(),
+    node_name: state.config.hostname.to_owned(),
+    attempt: attempt as i64,
+    status: JobRunStatus::Running.to_string(),
+    exit_code: None,
+    logs: None,
+  };
+  if let Err(err) = repositories::job_run::create(&item, &state.pool).await {
+    remove_container(&name, state).await;
+    return Err(err.into());
+  }
+  let res = match created {
+    Ok(()) => {
+      let res = wait_container(&name, state).await;
+      remove_container(&name, state).await;
+      res
+    }
+    Err(err) => Err(err),
+  };
+  let update = match res {
+    Ok((exit_code, logs)) => JobRunUpdateDbModel {
+      finished_at: Some(chrono::Utc::now().naive_utc()),
+      status: Some(if exit_code == 0 {
+        JobRunStatus::Succeeded.to_string()
+      } else {
+        JobRunStatus::Failed.to_string()
+      }),
+      exit_code: Some(exit_code),
+      logs: Some(logs),
+    },
+    Err(err) => JobRunUpdateDbModel {
+      finished_at: Some(chrono::Utc::now().naive_utc()),
+      status: Some(JobRunStatus::Failed.to_string()),
+      exit_code: None,
+      logs: Some(err.to_string()),
+    },
+  };
+  let run =
+    repositories::job_run::update_by_key(&key, update, &state.pool).await?;
+  Ok(Some(run))
+}
+
+/// ## Run
+///
+/// Run a job to completion, a failed run is retried
+/// with an exponential backoff until the number of retries is reached.
+/// Nothing is done if the job is already running.
+///
+/// ## Arguments
+///
+/// - [job](Job) - The job to run
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The job has been run
+///   - [Err](HttpError) - The job could not be run
+///
+pub(crate) async fn run(
+  job: &Job,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let config = &job.config;
+  let image = config.container.image.clone().unwrap_or_default();
+  let retries = config.retries.unwrap_or_default();
+  let backoff = config.backoff.unwrap_or(DEFAULT_BACKOFF);
+  let history_limit = config.history_limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+  let secret_envs = utils::cargo::secret_envs(
+    config.secrets.as_deref().unwrap_or_default(),
+    state,
+  )
+  .await?;
+  pull_image_if_missing(&image, state).await?;
+  for attempt in 0..=retries {
+    let run = match run_attempt(job, attempt, &secret_envs, state).await? {
+      Some(run) => run,
+      None => {
+        log::warn!("Job {} is already running, skipping", job.name);
+        break;
+      }
+    };
+    let status = run.status;
+    log::info!("Job {} attempt {attempt}: {status}", job.name);
+    let state_ptr = state.clone();
+    rt::spawn(async move {
+      let _ = state_ptr
+        .event_emitter
+        .emit(Event::JobRunFinished(Box::new(run)))
+        .await;
+    });
+    if status == JobRunStatus::Succeeded {
+      break;
+    }
+    if attempt < retries {
+      let delay = backoff.saturating_mul(2_u64.saturating_pow(attempt as u32));
+      time::sleep(Duration::from_secs(delay)).await;
+    }
+  }
+  repositories::job_run::delete_old_by_job_key(
+    &job.name,
+    history_limit,
+    &state.pool,
+  )
+  .await?;
+  Ok(())
+}
+
+/// ## Spawn run
+///
+/// Run a job in background
+///
+/// ## Arguments
+///
+/// - [job](Job) - The job to run
+/// - [state](DaemonState) - The daemon state
+///
+pub(crate) fn spawn_run(job: Job, state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    if let Err(err) = run(&job, &state).await {
+      log::warn!("Unable to run job {}: {err}", job.name);
+    }
+  });
+}
+
+/// ## Is scheduler node
+///
+/// Scheduled jobs are only run by the node with the lowest name
+/// so they are run once in the cluster
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](bool) - True if the current node run the scheduled jobs
+///   - [Err](HttpError) - The nodes could not be listed
+///
+async fn is_scheduler_node(state: &DaemonState) -> Result<bool, HttpError> {
+  let hostname = &state.config.hostname;
+  let nodes = repositories::node::list_unless(hostname, &state.pool).await?;
+  Ok(nodes.iter().all(|node| node.name > *hostname))
+}
+
+/// ## Schedule
+///
+/// Run the jobs that have a scheduled run between the two given dates
+///
+/// ## Arguments
+///
+/// - [from](chrono::DateTime<chrono::Utc>) - The start date (excluded)
+/// - [to](chrono::DateTime<chrono::Utc>) - The end date (included)
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The due jobs have been started
+///   - [Err](HttpError) - The jobs could not be listed
+///
+async fn schedule(
+  from: chrono::DateTime<chrono::Utc>,
+  to: chrono::DateTime<chrono::Utc>,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let jobs = repositories::job::list(&state.pool).await?;
+  let due = jobs
+    .into_iter()
+    .filter(|job| {
+      let Some(schedule) = &job.config.schedule else {
+        return false;
+      };
+      let Ok(schedule) = parse_schedule(schedule) else {
+        return false;
+      };
+      schedule
+        .after(&from)
+        .next()
+        .map_or(false, |next| next <= to)
+    })
+    .collect::<Vec<_>>();
+  if due.is_empty() || !is_scheduler_node(state).await? {
+    return Ok(());
+  }
+  for job in due {
+    log::info!("Running scheduled job {}", job.name);
+    spawn_run(job, state);
+  }
+  Ok(())
+}
+
+/// ## Spawn scheduler
+///
+/// Spawn a background task that run the jobs following their schedule.
+/// The runs left running by a previous start of the daemon are marked as failed.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+pub(crate) fn spawn_scheduler(state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    if let Err(err) = repositories::job_run::fail_running_by_node_name(
+      &state.config.hostname,
+      &state.pool,
+    )
+    .await
+    {
+      log::warn!("Unable to clean interrupted job runs: {err}");
+    }
+    let mut last_tick = chrono::Utc::now();
+    loop {
+      time::sleep(Duration::from_secs(1)).await;
+      let now = chrono::Utc::now();
+      if let Err(err) = schedule(last_tick, now, &state).await {
+        log::warn!("Unable to schedule jobs: {err}");
+      }
+      last_tick = now;
+    }
+  });
+}
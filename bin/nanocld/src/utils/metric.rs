@@ -1,13 +1,93 @@
+use std::fmt::Write;
 use std::time::Duration;
+use std::collections::BTreeMap;
 
 use ntex::rt;
 use futures::StreamExt;
-use ntex::time::interval;
+use ntex::time::{interval, sleep};
+use futures_util::stream::FuturesUnordered;
+use bollard_next::container::{Stats, StatsOptions};
 use metrsd_client::{MetrsdClient, MetrsdEvent};
+use metrsd_client::stubs::{CpuInfo, MemoryInfo, DiskInfo, NetworkInfo};
 
+use nanocl_utils::http_error::HttpError;
+use nanocl_stubs::metric::{MetricKind, CargoMetric};
+
+use crate::{utils, repositories};
 use crate::repositories::metric;
 use crate::models::{Pool, MetricInsertDbModel, DaemonState};
 
+/// Interval in seconds between two saves of the cargo metrics
+pub(crate) const CARGO_METRIC_INTERVAL: u64 = 15;
+/// Content type of the open metrics text format
+pub(crate) const OPENMETRICS_CONTENT_TYPE: &str =
+  "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// ## MetricFamily
+///
+/// A metric and its samples rendered in the open metrics text format
+///
+struct MetricFamily {
+  /// Name of the metric
+  name: &'static str,
+  /// Type of the metric `gauge` or `counter`
+  kind: &'static str,
+  /// Description of the metric
+  help: &'static str,
+  /// Labels and value of each sample
+  samples: Vec<(String, f64)>,
+}
+
+impl MetricFamily {
+  fn gauge(name: &'static str, help: &'static str) -> Self {
+    Self {
+      name,
+      kind: "gauge",
+      help,
+      samples: Vec::new(),
+    }
+  }
+
+  fn counter(name: &'static str, help: &'static str) -> Self {
+    Self {
+      name,
+      kind: "counter",
+      help,
+      samples: Vec::new(),
+    }
+  }
+
+  /// Add a sample with the given labels
+  fn add(&mut self, labels: &[(&str, &str)], value: f64) {
+    let labels = labels
+      .iter()
+      .map(|(name, value)| {
+        let value = value
+          .replace('\\', "\\\\")
+          .replace('"', "\\\"")
+          .replace('\n', "\\n");
+        format!("{name}=\"{value}\"")
+      })
+      .collect::<Vec<_>>()
+      .join(",");
+    self.samples.push((labels, value));
+  }
+
+  /// Write the metadata and the samples of the metric
+  fn render(&self, output: &mut String) {
+    let _ = writeln!(output, "# TYPE {} {}", self.name, self.kind);
+    let _ = writeln!(output, "# HELP {} {}", self.name, self.help);
+    let suffix = if self.kind == "counter" { "_total" } else { "" };
+    for (labels, value) in &self.samples {
+      if labels.is_empty() {
+        let _ = writeln!(output, "{}{suffix} {value}", self.name);
+      } else {
+        let _ = writeln!(output, "{}{suffix}{{{labels}}} {value}", self.name);
+      }
+    }
+  }
+}
+
 /// ## Save metric
 ///
 /// Save metric event send by [metrsd](http://github.com/nxthat/metrsd) to the database
@@ -28,6 +108,7 @@ async fn save_metric(node: &str, ev: &MetrsdEvent, pool: &Pool) {
         kind: "CPU".into(),
         node_name: node.to_owned(),
         data: serde_json::to_value(cpus).unwrap(),
+        ..Default::default()
       };
       let _ = metric::create(&item, pool).await;
     }
@@ -36,6 +117,7 @@ async fn save_metric(node: &str, ev: &MetrsdEvent, pool: &Pool) {
         kind: "MEMORY".into(),
         node_name: node.to_owned(),
         data: serde_json::to_value(mem).unwrap(),
+        ..Default::default()
       };
       let _ = metric::create(&item, pool).await;
     }
@@ -44,6 +126,7 @@ async fn save_metric(node: &str, ev: &MetrsdEvent, pool: &Pool) {
         kind: "DISK".into(),
         node_name: node.to_owned(),
         data: serde_json::to_value(disk).unwrap(),
+        ..Default::default()
       };
       let _ = metric::create(&item, pool).await;
     }
@@ -52,6 +135,7 @@ async fn save_metric(node: &str, ev: &MetrsdEvent, pool: &Pool) {
         kind: "NETWORK".into(),
         node_name: node.to_owned(),
         data: serde_json::to_value(net).unwrap(),
+        ..Default::default()
       };
       let _ = metric::create(&item, pool).await;
     }
@@ -98,3 +182,543 @@ pub(crate) fn spawn_logger(state: &DaemonState) {
     });
   });
 }
+
+/// ## Cpu usage
+///
+/// Compute the cpu usage in percent of one cpu from docker stats
+///
+fn cpu_usage(stats: &Stats) -> Option<f64> {
+  let cpu_delta = stats
+    .cpu_stats
+    .cpu_usage
+    .total_usage
+    .checked_sub(stats.precpu_stats.cpu_usage.total_usage)?;
+  let system_delta = stats
+    .cpu_stats
+    .system_cpu_usage?
+    .checked_sub(stats.precpu_stats.system_cpu_usage?)?;
+  if system_delta == 0 {
+    return None;
+  }
+  let cpus = stats.cpu_stats.online_cpus.unwrap_or(1) as f64;
+  Some(cpu_delta as f64 / system_delta as f64 * cpus * 100.0)
+}
+
+/// ## Memory usage
+///
+/// Compute the memory usage in percent of the limit from docker stats
+///
+fn memory_usage(stats: &Stats) -> Option<f64> {
+  let usage = stats.memory_stats.usage?;
+  let limit = stats.memory_stats.limit?;
+  if limit == 0 {
+    return None;
+  }
+  Some(usage as f64 / limit as f64 * 100.0)
+}
+
+/// ## Average
+///
+/// Compute the average of the given values, `None` if there is no value
+///
+pub(crate) fn average(
+  values: impl Iterator<Item = Option<f64>>,
+) -> Option<f64> {
+  let values = values.flatten().collect::<Vec<_>>();
+  if values.is_empty() {
+    return None;
+  }
+  Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// ## Instance stats
+///
+/// Get the stats of an instance (container) once
+///
+async fn instance_stats(
+  id: &str,
+  docker_api: &bollard_next::Docker,
+) -> Result<Stats, HttpError> {
+  let options = StatsOptions {
+    stream: false,
+    one_shot: false,
+  };
+  let mut stream = Box::pin(docker_api.stats(id, Some(options)));
+  match stream.next().await {
+    Some(stats) => Ok(stats?),
+    None => Err(HttpError::internal_server_error(format!(
+      "Unable to get stats of instance {id}"
+    ))),
+  }
+}
+
+/// ## Cargo metric
+///
+/// Compute the usage of the running instances of a cargo on the current node.
+/// The instances whose stats can't be read are skipped.
+///
+/// ## Arguments
+///
+/// - [key](str) - The cargo key
+/// - [state](DaemonState) - Daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Option<CargoMetric>) - The usage, `None` if no instance is running
+///   - [Err](HttpError) - The instances could not be listed
+///
+async fn cargo_metric(
+  key: &str,
+  state: &DaemonState,
+) -> Result<Option<CargoMetric>, HttpError> {
+  let instances = utils::cargo::list_instances(key, &state.docker_api).await?;
+  let stats = instances
+    .iter()
+    .filter(|instance| instance.state == Some("running".into()))
+    .filter_map(|instance| instance.id.as_deref())
+    .map(|id| instance_stats(id, &state.docker_api))
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .filter_map(Result::ok)
+    .collect::<Vec<_>>();
+  if stats.is_empty() {
+    return Ok(None);
+  }
+  Ok(Some(CargoMetric {
+    instances: stats.len(),
+    cpu_usage: average(stats.iter().map(cpu_usage)),
+    memory_usage: average(stats.iter().map(memory_usage)),
+  }))
+}
+
+/// ## Save cargo metrics
+///
+/// Save the usage of the cargoes running on the current node
+/// in a `CARGO` metric, it's read by the autoscaler.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - Daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The metric has been saved
+///   - [Err](HttpError) - The cargoes could not be listed
+///
+async fn save_cargo_metrics(state: &DaemonState) -> Result<(), HttpError> {
+  let cargoes = repositories::cargo::list(&state.pool).await?;
+  let mut data = BTreeMap::new();
+  for cargo in cargoes {
+    match cargo_metric(&cargo.key, state).await {
+      Ok(Some(metric)) => {
+        data.insert(cargo.key, metric);
+      }
+      Ok(None) => {}
+      Err(err) => {
+        log::warn!("Unable to get the usage of cargo {}: {err}", cargo.key);
+      }
+    }
+  }
+  let item = MetricInsertDbModel {
+    kind: MetricKind::Cargo.to_string(),
+    node_name: state.config.hostname.clone(),
+    data: serde_json::to_value(data).map_err(|err| {
+      HttpError::internal_server_error(format!(
+        "Unable to serialize cargo metrics: {err}"
+      ))
+    })?,
+    // The usage is only kept a day since a save happen every few seconds
+    expire_at: Some(chrono::Utc::now().naive_utc() + chrono::Duration::days(1)),
+  };
+  metric::create(&item, &state.pool).await?;
+  Ok(())
+}
+
+/// ## Spawn cargo logger
+///
+/// Spawn a background task that periodically save
+/// the usage of the cargoes running on the current node.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - Daemon state
+///
+pub(crate) fn spawn_cargo_logger(state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    loop {
+      sleep(Duration::from_secs(CARGO_METRIC_INTERVAL)).await;
+      if let Err(err) = save_cargo_metrics(&state).await {
+        log::warn!("Unable to save cargo metrics: {err}");
+      }
+    }
+  });
+}
+
+/// ## Cargo families
+///
+/// Gather the number of instances of each cargo by state
+/// and the cpu, memory and network stats of their running instances
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - Daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<MetricFamily>) - The cargo and container metrics
+///   - [Err](HttpError) - The cargoes could not be listed
+///
+async fn cargo_families(
+  state: &DaemonState,
+) -> Result<Vec<MetricFamily>, HttpError> {
+  let mut instances = MetricFamily::gauge(
+    "nanocl_cargo_instances",
+    "Number of instances of a cargo by state",
+  );
+  // Cargo name, namespace, instance name and id of the running instances
+  let mut running: Vec<(String, String, String, String)> = Vec::new();
+  let cargoes = repositories::cargo::list(&state.pool).await?;
+  for cargo in cargoes {
+    // A cargo whose instances can't be listed is skipped
+    // to still export the metrics of the others
+    let containers =
+      match utils::cargo::list_instances(&cargo.key, &state.docker_api).await {
+        Ok(containers) => containers,
+        Err(err) => {
+          log::warn!("Unable to list instances of {}: {err}", cargo.key);
+          continue;
+        }
+      };
+    let mut states = BTreeMap::from([("running".to_owned(), 0)]);
+    for container in containers {
+      let container_state =
+        container.state.unwrap_or_else(|| "unknown".to_owned());
+      if container_state == "running" {
+        let name = container
+          .names
+          .unwrap_or_default()
+          .first()
+          .map(|name| name.replace('/', ""))
+          .unwrap_or_default();
+        if let Some(id) = container.id {
+          running.push((
+            cargo.name.clone(),
+            cargo.namespace_name.clone(),
+            name,
+            id,
+          ));
+        }
+      }
+      *states.entry(container_state).or_default() += 1;
+    }
+    for (container_state, count) in states {
+      instances.add(
+        &[
+          ("cargo", cargo.name.as_str()),
+          ("namespace", cargo.namespace_name.as_str()),
+          ("state", container_state.as_str()),
+        ],
+        count as f64,
+      );
+    }
+  }
+  let mut cpu = MetricFamily::gauge(
+    "nanocl_container_cpu_usage_percent",
+    "Cpu usage of a cargo instance in percent of one cpu",
+  );
+  let mut memory = MetricFamily::gauge(
+    "nanocl_container_memory_usage_bytes",
+    "Memory used by a cargo instance",
+  );
+  let mut memory_limit = MetricFamily::gauge(
+    "nanocl_container_memory_limit_bytes",
+    "Memory limit of a cargo instance",
+  );
+  let mut received = MetricFamily::counter(
+    "nanocl_container_network_receive_bytes",
+    "Bytes received by a cargo instance",
+  );
+  let mut transmitted = MetricFamily::counter(
+    "nanocl_container_network_transmit_bytes",
+    "Bytes transmitted by a cargo instance",
+  );
+  let stats = running
+    .iter()
+    .map(|(_, _, _, id)| instance_stats(id, &state.docker_api))
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<_>>()
+    .await;
+  for stats in stats {
+    let stats = match stats {
+      Ok(stats) => stats,
+      Err(err) => {
+        log::debug!("Skipping instance stats: {err}");
+        continue;
+      }
+    };
+    let Some((cargo, namespace, name, _)) =
+      running.iter().find(|(_, _, _, id)| *id == stats.id)
+    else {
+      continue;
+    };
+    let labels = [
+      ("cargo", cargo.as_str()),
+      ("namespace", namespace.as_str()),
+      ("instance", name.as_str()),
+    ];
+    if let Some(usage) = cpu_usage(&stats) {
+      cpu.add(&labels, usage);
+    }
+    if let Some(usage) = stats.memory_stats.usage {
+      memory.add(&labels, usage as f64);
+    }
+    if let Some(limit) = stats.memory_stats.limit {
+      memory_limit.add(&labels, limit as f64);
+    }
+    let networks = stats.networks.unwrap_or_default();
+    let (rx_bytes, tx_bytes) =
+      networks.values().fold((0, 0), |(rx, tx), net| {
+        (rx + net.rx_bytes, tx + net.tx_bytes)
+      });
+    received.add(&labels, rx_bytes as f64);
+    transmitted.add(&labels, tx_bytes as f64);
+  }
+  Ok(vec![
+    instances,
+    cpu,
+    memory,
+    memory_limit,
+    received,
+    transmitted,
+  ])
+}
+
+/// ## Node families
+///
+/// Gather the latest cpu, memory, disk and network metrics of each node
+///
+/// ## Arguments
+///
+/// - [pool](Pool) - Database pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<MetricFamily>) - The node metrics
+///   - [Err](HttpError) - The metrics could not be listed
+///
+async fn node_families(pool: &Pool) -> Result<Vec<MetricFamily>, HttpError> {
+  let mut cpu = MetricFamily::gauge(
+    "nanocl_node_cpu_usage_percent",
+    "Cpu usage of a node by cpu",
+  );
+  let items = metric::list_by_kind(&MetricKind::Cpu.to_string(), pool).await?;
+  for item in items {
+    let Ok(cpus) = serde_json::from_value::<Vec<CpuInfo>>(item.data) else {
+      continue;
+    };
+    for info in cpus {
+      cpu.add(
+        &[
+          ("node", item.node_name.as_str()),
+          ("cpu", info.name.as_str()),
+        ],
+        info.usage as f64,
+      );
+    }
+  }
+  let mut memory_total = MetricFamily::gauge(
+    "nanocl_node_memory_total_bytes",
+    "Total memory of a node",
+  );
+  let mut memory_used = MetricFamily::gauge(
+    "nanocl_node_memory_used_bytes",
+    "Memory used by a node",
+  );
+  let items =
+    metric::list_by_kind(&MetricKind::Memory.to_string(), pool).await?;
+  for item in items {
+    let Ok(info) = serde_json::from_value::<MemoryInfo>(item.data) else {
+      continue;
+    };
+    let labels = [("node", item.node_name.as_str())];
+    memory_total.add(&labels, info.total as f64);
+    memory_used.add(&labels, info.used as f64);
+  }
+  let mut disk_total = MetricFamily::gauge(
+    "nanocl_node_disk_total_bytes",
+    "Total space of a disk of a node",
+  );
+  let mut disk_available = MetricFamily::gauge(
+    "nanocl_node_disk_available_bytes",
+    "Available space of a disk of a node",
+  );
+  let items = metric::list_by_kind(&MetricKind::Disk.to_string(), pool).await?;
+  for item in items {
+    let Ok(disks) = serde_json::from_value::<Vec<DiskInfo>>(item.data) else {
+      continue;
+    };
+    for info in disks {
+      let labels = [
+        ("node", item.node_name.as_str()),
+        ("device", info.device_name.as_str()),
+        ("mount_point", info.mount_point.as_str()),
+      ];
+      disk_total.add(&labels, info.total_space as f64);
+      disk_available.add(&labels, info.available_space as f64);
+    }
+  }
+  let mut received = MetricFamily::counter(
+    "nanocl_node_network_receive_bytes",
+    "Bytes received by a network interface of a node",
+  );
+  let mut transmitted = MetricFamily::counter(
+    "nanocl_node_network_transmit_bytes",
+    "Bytes transmitted by a network interface of a node",
+  );
+  let items =
+    metric::list_by_kind(&MetricKind::Network.to_string(), pool).await?;
+  for item in items {
+    let Ok(networks) = serde_json::from_value::<Vec<NetworkInfo>>(item.data)
+    else {
+      continue;
+    };
+    for info in networks {
+      let labels = [
+        ("node", item.node_name.as_str()),
+        ("interface", info.name.as_str()),
+      ];
+      received.add(&labels, info.received as f64);
+      transmitted.add(&labels, info.transmitted as f64);
+    }
+  }
+  Ok(vec![
+    cpu,
+    memory_total,
+    memory_used,
+    disk_total,
+    disk_available,
+    received,
+    transmitted,
+  ])
+}
+
+/// ## Daemon families
+///
+/// Gather the proxy request counters and the internal stats of the daemon
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - Daemon state
+///
+/// ## Returns
+///
+/// - [Vec<MetricFamily>](Vec<MetricFamily>) - The proxy and daemon metrics
+///
+fn daemon_families(state: &DaemonState) -> Vec<MetricFamily> {
+  let mut requests = MetricFamily::counter(
+    "nanocl_proxy_requests",
+    "Requests handled by the proxy by host and status class",
+  );
+  let mut bytes_sent = MetricFamily::counter(
+    "nanocl_proxy_sent_bytes",
+    "Bytes sent by the proxy by host and status class",
+  );
+  if let Ok(counters) = state.proxy_counters.lock() {
+    let counters = counters.iter().collect::<BTreeMap<_, _>>();
+    for ((host, status_class), counter) in counters {
+      let labels = [
+        ("host", host.as_str()),
+        ("status_class", status_class.as_str()),
+      ];
+      requests.add(&labels, counter.requests as f64);
+      bytes_sent.add(&labels, counter.bytes_sent as f64);
+    }
+  }
+  let mut subscribers = MetricFamily::gauge(
+    "nanocl_event_subscribers",
+    "Number of clients subscribed to the events",
+  );
+  subscribers.add(&[], state.event_emitter.subscriber_count() as f64);
+  let pool_state = state.pool.state();
+  let mut connections = MetricFamily::gauge(
+    "nanocl_db_pool_connections",
+    "Number of connections of the database pool by state",
+  );
+  let idle = pool_state.idle_connections;
+  connections.add(&[("state", "idle")], idle as f64);
+  connections.add(
+    &[("state", "active")],
+    pool_state.connections.saturating_sub(idle) as f64,
+  );
+  let mut max_connections = MetricFamily::gauge(
+    "nanocl_db_pool_max_connections",
+    "Maximum number of connections of the database pool",
+  );
+  max_connections.add(&[], state.pool.max_size() as f64);
+  vec![
+    requests,
+    bytes_sent,
+    subscribers,
+    connections,
+    max_connections,
+  ]
+}
+
+/// ## Export
+///
+/// Export the metrics of the cargoes, the nodes, the proxy and the daemon
+/// in the open metrics text format so they can be scraped by prometheus.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - Daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](String) - The metrics in the open metrics text format
+///   - [Err](HttpError) - The metrics could not be gathered
+///
+pub(crate) async fn export(state: &DaemonState) -> Result<String, HttpError> {
+  let mut families = cargo_families(state).await?;
+  families.extend(node_families(&state.pool).await?);
+  families.extend(daemon_families(state));
+  let mut output = String::new();
+  for family in families {
+    family.render(&mut output);
+  }
+  output.push_str("# EOF\n");
+  Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render() {
+    let mut requests = MetricFamily::counter("requests", "Requests");
+    requests.add(&[("host", "a\"b\\c"), ("status_class", "2xx")], 3.0);
+    let mut subscribers = MetricFamily::gauge("subscribers", "Subscribers");
+    subscribers.add(&[], 1.5);
+    let mut output = String::new();
+    requests.render(&mut output);
+    subscribers.render(&mut output);
+    assert_eq!(
+      output,
+      "# TYPE requests counter\n\
+       # HELP requests Requests\n\
+       requests_total{host=\"a\\\"b\\\\c\",status_class=\"2xx\"} 3\n\
+       # TYPE subscribers gauge\n\
+       # HELP subscribers Subscribers\n\
+       subscribers 1.5\n"
+    );
+  }
+}
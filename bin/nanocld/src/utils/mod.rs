@@ -12,9 +12,14 @@ pub mod exec;
 pub mod vm_image;
 pub mod cargo;
 pub mod cargo_image;
+pub mod cargo_autoscale;
+pub mod job;
+pub mod secret;
+pub mod dependency;
 pub mod metric;
 pub mod ctrl_client;
 pub mod system;
+pub mod node;
 
 #[cfg(test)]
 pub mod tests {
@@ -25,12 +30,15 @@ pub mod tests {
   use ntex::web::{*, self};
   use ntex::http::client::ClientResponse;
   use ntex::http::client::error::SendRequestError;
+  use futures::{StreamExt, TryStreamExt};
 
   use nanocl_stubs::config::DaemonConfig;
+  use nanocl_stubs::cargo_image::CargoImagePartial;
 
   use crate::version::VERSION;
   use crate::services;
   use crate::event::EventEmitter;
+  use crate::node::NodeClients;
   use crate::models::{Pool, DaemonState};
 
   pub use ntex::web::test::TestServer;
@@ -111,19 +119,15 @@ pub mod tests {
       .expect("Failed to connect to store at: {ip_addr}")
   }
 
-  /// ## Gen server
+  /// ## Gen test state
   ///
-  /// Generate a test server for tests purpose
-  ///
-  /// ## Arguments
-  ///
-  /// - [routes](Config) Routes to configure
+  /// Generate a daemon state for tests purpose
   ///
   /// ## Returns
   ///
-  /// - [TestServer](TestServer) - The test server
+  /// - [DaemonState](DaemonState) - The daemon state
   ///
-  pub async fn gen_server(routes: Config) -> test::TestServer {
+  pub async fn gen_test_state() -> DaemonState {
     before();
     // Build a test daemon config
     let home = env::var("HOME").expect("Failed to get home dir");
@@ -139,13 +143,38 @@ pub mod tests {
     let docker_api = gen_docker_client();
     // Create postgres pool
     let pool = gen_postgre_pool().await;
-    let daemon_state = DaemonState {
+    let secret_keys = secret::load_keys(&config.secret_key_file, &pool)
+      .await
+      .expect("Failed to load secret keys");
+    DaemonState {
       config,
       docker_api,
       pool,
       event_emitter,
+      node_clients: NodeClients::spawn(),
+      reconcile_statuses: Default::default(),
+      rollback_statuses: Default::default(),
+      completed_instances: Default::default(),
+      proxy_counters: Default::default(),
+      secret_keys,
       version: VERSION.to_owned(),
-    };
+    }
+  }
+
+  /// ## Gen server
+  ///
+  /// Generate a test server for tests purpose
+  ///
+  /// ## Arguments
+  ///
+  /// - [routes](Config) Routes to configure
+  ///
+  /// ## Returns
+  ///
+  /// - [TestServer](TestServer) - The test server
+  ///
+  pub async fn gen_server(routes: Config) -> test::TestServer {
+    let daemon_state = gen_test_state().await;
     // Create test server
     test::server(move || {
       App::new()
@@ -154,4 +183,27 @@ pub mod tests {
         .default_service(web::route().to(services::unhandled))
     })
   }
+
+  /// ## Ensure test image
+  ///
+  /// Pull the image used by the tests if it's missing
+  ///
+  /// ## Returns
+  ///
+  /// - [TestRet](TestRet) - The result of the operation
+  ///
+  pub async fn ensure_test_image() -> TestRet {
+    let srv = gen_server(services::ntex_config).await;
+    let image = CargoImagePartial {
+      name: "nexthat/nanocl-get-started:latest".to_owned(),
+    };
+    let res = srv.post("/v0.2/cargoes/images").send_json(&image).await?;
+    let mut stream = res.into_stream();
+    while let Some(chunk) = stream.next().await {
+      if let Err(err) = chunk {
+        panic!("Error while creating image {}", &err);
+      }
+    }
+    Ok(())
+  }
 }
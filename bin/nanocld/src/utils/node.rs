@@ -0,0 +1,512 @@
+use std::collections::HashMap;
+
+use openssl::base64;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use openssl::hash::MessageDigest;
+
+use nanocl_utils::http_error::HttpError;
+use nanocl_utils::io_error::{FromIo, IoError, IoResult};
+use nanocl_stubs::cargo_config::ReplicationMode;
+
+use crate::{utils, repositories};
+use crate::node::{NodeMessage, NodeClientsMessage};
+use crate::models::{DaemonState, NodeCommand, NodeDbModel};
+
+/// Header authenticating a node connecting to the websocket of another node
+pub const NODE_AUTH_HEADER: &str = "X-Nanocl-Node-Auth";
+/// Maximum age in seconds of an authentication token
+const AUTH_TOKEN_MAX_AGE: i64 = 60;
+
+/// ## Parse number
+///
+/// Convert a number of replicas from a replication mode to an usize
+///
+fn parse_number(number: i64) -> Result<usize, HttpError> {
+  usize::try_from(number).map_err(|err| {
+    HttpError::bad_request(format!(
+      "Invalid number of replicas {number}: {err}"
+    ))
+  })
+}
+
+/// ## Find nodes by names
+///
+/// Find the nodes matching the given names and ensure they all exist
+///
+async fn find_nodes_by_names(
+  names: &[String],
+  state: &DaemonState,
+) -> Result<Vec<NodeDbModel>, HttpError> {
+  let nodes = repositories::node::list_by_names(names, &state.pool).await?;
+  if let Some(name) = names
+    .iter()
+    .find(|name| !nodes.iter().any(|node| &node.name == *name))
+  {
+    return Err(HttpError::bad_request(format!("Node {name} not found")));
+  }
+  Ok(nodes)
+}
+
+/// ## Find nodes by groups
+///
+/// Find the nodes of each given group and ensure no group is empty
+///
+async fn find_nodes_by_groups(
+  groups: &[String],
+  state: &DaemonState,
+) -> Result<HashMap<String, Vec<NodeDbModel>>, HttpError> {
+  let links = repositories::node::list_by_groups(groups, &state.pool).await?;
+  let mut nodes_by_group: HashMap<String, Vec<NodeDbModel>> = HashMap::new();
+  for (group, node) in links {
+    nodes_by_group.entry(group).or_default().push(node);
+  }
+  if let Some(group) = groups
+    .iter()
+    .find(|group| !nodes_by_group.contains_key(*group))
+  {
+    return Err(HttpError::bad_request(format!(
+      "Node group {group} not found or empty"
+    )));
+  }
+  Ok(nodes_by_group)
+}
+
+/// ## Schedule
+///
+/// Compute the number of instances to create on each node of the cluster
+/// for the given replication mode.
+/// Modes without node constraint are scheduled on the current node.
+///
+/// ## Arguments
+///
+/// - [replication](Option<ReplicationMode>) - The replication mode of the cargo
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](HashMap<String, usize>) - The number of instances by node name
+///   - [Err](HttpError) - The instances cannot be scheduled
+///
+pub async fn schedule(
+  replication: Option<&ReplicationMode>,
+  state: &DaemonState,
+) -> Result<HashMap<String, usize>, HttpError> {
+  let hostname = state.config.hostname.clone();
+  let mut placements: HashMap<String, usize> = HashMap::new();
+  match replication {
+    None | Some(ReplicationMode::Auto) | Some(ReplicationMode::Unique) => {
+      placements.insert(hostname, 1);
+    }
+    Some(ReplicationMode::Static(replication_static)) => {
+      placements.insert(hostname, replication_static.number);
+    }
+    Some(ReplicationMode::UniqueByNode) => {
+      let nodes = repositories::node::list(&state.pool).await?;
+      for node in nodes {
+        placements.insert(node.name, 1);
+      }
+    }
+    Some(ReplicationMode::StaticByNodes(replication_static)) => {
+      let nodes = repositories::node::list(&state.pool).await?;
+      for node in nodes {
+        placements.insert(node.name, replication_static.number);
+      }
+    }
+    Some(ReplicationMode::UniqueByNodeNames { names }) => {
+      let nodes = find_nodes_by_names(names, state).await?;
+      for node in nodes {
+        placements.insert(node.name, 1);
+      }
+    }
+    Some(ReplicationMode::StaticByNodeNames { names, number }) => {
+      let number = parse_number(*number)?;
+      let nodes = find_nodes_by_names(names, state).await?;
+      for node in nodes {
+        placements.insert(node.name, number);
+      }
+    }
+    Some(ReplicationMode::UniqueByNodeGroups { groups }) => {
+      let nodes_by_group = find_nodes_by_groups(groups, state).await?;
+      for nodes in nodes_by_group.values() {
+        // Pick the lowest node name so every node of the cluster agrees
+        if let Some(node) = nodes.iter().min_by_key(|node| &node.name) {
+          *placements.entry(node.name.clone()).or_default() += 1;
+        }
+      }
+    }
+    Some(ReplicationMode::StaticByNodeGroups { groups, number }) => {
+      let number = parse_number(*number)?;
+      let nodes_by_group = find_nodes_by_groups(groups, state).await?;
+      for nodes in nodes_by_group.values() {
+        // Spread the replicas of the group across his nodes ordered by name
+        for index in 0..number {
+          let node = &nodes[index % nodes.len()];
+          *placements.entry(node.name.clone()).or_default() += 1;
+        }
+      }
+    }
+  }
+  placements.retain(|_, number| *number > 0);
+  Ok(placements)
+}
+
+/// ## Send command
+///
+/// Send a command to a node over the websocket channel
+///
+/// ## Arguments
+///
+/// - [node_name](str) - The name of the node
+/// - [command](NodeCommand) - The command to send
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The command has been sent
+///   - [Err](HttpError) - The command has not been sent
+///
+pub fn send_command(
+  node_name: &str,
+  command: &NodeCommand,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let data = serde_json::to_string(command).map_err(|err| {
+    HttpError::internal_server_error(format!(
+      "Unable to serialize node command: {err}"
+    ))
+  })?;
+  state
+    .node_clients
+    .unbounded_send(NodeClientsMessage::SendMessage {
+      node_id: node_name.to_owned(),
+      msg: NodeMessage { data },
+    })
+    .map_err(|err| {
+      HttpError::internal_server_error(format!(
+        "Unable to send command to node {node_name}: {err}"
+      ))
+    })?;
+  Ok(())
+}
+
+/// ## Broadcast command
+///
+/// Send a command to every other node over the websocket channel.
+/// A node that can't be reached is logged and skipped.
+///
+/// ## Arguments
+///
+/// - [command](NodeCommand) - The command to send
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The command has been sent
+///   - [Err](HttpError) - The nodes can't be listed
+///
+pub async fn broadcast_command(
+  command: &NodeCommand,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  let nodes = repositories::node::list(&state.pool).await?;
+  for node in nodes {
+    if node.name == state.config.hostname {
+      continue;
+    }
+    if let Err(err) = send_command(&node.name, command, state) {
+      log::warn!("{err}");
+    }
+  }
+  Ok(())
+}
+
+/// ## Read cluster token
+///
+/// Read the token shared by the nodes of the cluster from `cluster_token_file`
+///
+fn read_cluster_token(state: &DaemonState) -> IoResult<Vec<u8>> {
+  let path = &state.config.cluster_token_file;
+  let token = std::fs::read_to_string(path).map_err(|err| {
+    err.map_err_context(|| format!("Unable to read cluster token {path}"))
+  })?;
+  let token = token.trim();
+  if token.is_empty() {
+    return Err(IoError::invalid_data(
+      "ClusterToken",
+      &format!("{path} is empty"),
+    ));
+  }
+  Ok(token.as_bytes().to_vec())
+}
+
+/// ## Sign
+///
+/// Compute the HMAC-SHA256 of data with the cluster token
+///
+fn sign(token: &[u8], data: &str) -> IoResult<Vec<u8>> {
+  let crypto_error = |err| {
+    IoError::new(
+      "ClusterToken",
+      std::io::Error::new(std::io::ErrorKind::Other, err),
+    )
+  };
+  let pkey = PKey::hmac(token).map_err(crypto_error)?;
+  let mut signer =
+    Signer::new(MessageDigest::sha256(), &pkey).map_err(crypto_error)?;
+  signer
+    .sign_oneshot_to_vec(data.as_bytes())
+    .map_err(crypto_error)
+}
+
+/// ## Gen auth token
+///
+/// Generate a token authenticating the current node to the other nodes.
+/// It contains the node name and the current time signed with the token
+/// shared by the nodes of the cluster.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](String) - The token
+///   - [Err](HttpError) - The token can't be signed
+///
+pub fn gen_auth_token(state: &DaemonState) -> Result<String, HttpError> {
+  let data = format!(
+    "{}:{}",
+    state.config.hostname,
+    chrono::Utc::now().timestamp()
+  );
+  let signature = sign(&read_cluster_token(state)?, &data)?;
+  Ok(format!("{data}:{}", base64::encode_block(&signature)))
+}
+
+/// ## Verify auth token
+///
+/// Check the token of a node connecting to the current node.
+/// The token must be signed with the cluster token, be recent
+/// and come from a node registered in the cluster.
+///
+/// ## Arguments
+///
+/// - [token](str) - The token
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](String) - The name of the authenticated node
+///   - [Err](HttpError) - The token is invalid
+///
+pub async fn verify_auth_token(
+  token: &str,
+  state: &DaemonState,
+) -> Result<String, HttpError> {
+  let unauthorized = || HttpError::unauthorized("Invalid node token");
+  let (data, signature) = token.rsplit_once(':').ok_or_else(unauthorized)?;
+  let (node_name, timestamp) =
+    data.rsplit_once(':').ok_or_else(unauthorized)?;
+  let timestamp = timestamp.parse::<i64>().map_err(|_| unauthorized())?;
+  let age = chrono::Utc::now().timestamp() - timestamp;
+  if age.abs() > AUTH_TOKEN_MAX_AGE {
+    return Err(HttpError::unauthorized("Expired node token"));
+  }
+  let signature =
+    base64::decode_block(signature).map_err(|_| unauthorized())?;
+  let token = read_cluster_token(state).map_err(|err| {
+    log::error!("{err}");
+    unauthorized()
+  })?;
+  let expected = sign(&token, data)?;
+  if expected.len() != signature.len() || !memcmp::eq(&expected, &signature) {
+    return Err(unauthorized());
+  }
+  repositories::node::find_by_name(node_name, &state.pool)
+    .await
+    .map_err(|_| {
+      HttpError::unauthorized(format!("Unknown node {node_name}"))
+    })?;
+  Ok(node_name.to_owned())
+}
+
+/// ## Exec command
+///
+/// Execute a command received from another node on the current node
+///
+/// ## Arguments
+///
+/// - [command](NodeCommand) - The command to execute
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The command has been executed
+///   - [Err](HttpError) - The command has not been executed
+///
+pub async fn exec_command(
+  command: &NodeCommand,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  log::debug!("Executing node command: {command:?}");
+  match command {
+    NodeCommand::CargoCreate { key, number } => {
+      let cargo = repositories::cargo::inspect_by_key(key, &state.pool).await?;
+      utils::cargo::create_instances(&cargo, 0, *number, state).await?;
+    }
+    NodeCommand::CargoStart { key } => {
+      utils::cargo::start_instances(key, state).await?;
+    }
+    NodeCommand::CargoStop { key } => {
+      utils::cargo::stop_instances(key, state).await?;
+    }
+    NodeCommand::CargoUpdate { key } => {
+      let cargo = repositories::cargo::inspect_by_key(key, &state.pool).await?;
+      utils::cargo::update_instances(&cargo, state, None).await?;
+    }
+    NodeCommand::CargoDelete { key, force } => {
+      utils::cargo::delete_node_instances(key, *force, state).await?;
+    }
+    NodeCommand::CargoScale { key, options } => {
+      utils::cargo::scale_node(key, options, state).await?;
+    }
+    NodeCommand::CargoKill { key, options } => {
+      utils::cargo::kill_by_name(key, options, &state.docker_api).await?;
+    }
+    NodeCommand::SecretFilesSync { key } => {
+      utils::secret::sync_files(Some(key), state).await?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use nanocl_stubs::cargo::CargoKillOptions;
+
+  use crate::utils::tests::*;
+
+  #[ntex::test]
+  async fn auth_token() {
+    let mut state = gen_test_state().await;
+    let path = "/tmp/nanocl-test-cluster.token";
+    std::fs::write(path, "test-cluster-token\n").unwrap();
+    state.config.cluster_token_file = path.to_owned();
+    let token = gen_auth_token(&state).unwrap();
+    // The token is signed but the test node isn't registered
+    let err = verify_auth_token(&token, &state).await.unwrap_err();
+    assert!(err.msg.starts_with("Unknown node"), "{}", err.msg);
+    let (data, _) = token.rsplit_once(':').unwrap();
+    let forged = format!("{data}:Zm9yZ2Vk");
+    let err = verify_auth_token(&forged, &state).await.unwrap_err();
+    assert_eq!(err.msg, "Invalid node token");
+    let data = format!(
+      "{}:{}",
+      state.config.hostname,
+      chrono::Utc::now().timestamp() - AUTH_TOKEN_MAX_AGE - 1
+    );
+    let signature = sign(b"test-cluster-token", &data).unwrap();
+    let expired = format!("{data}:{}", base64::encode_block(&signature));
+    let err = verify_auth_token(&expired, &state).await.unwrap_err();
+    assert_eq!(err.msg, "Expired node token");
+    // A token signed with another cluster token is rejected
+    let data = format!(
+      "{}:{}",
+      state.config.hostname,
+      chrono::Utc::now().timestamp()
+    );
+    let signature = sign(b"other-cluster-token", &data).unwrap();
+    let other = format!("{data}:{}", base64::encode_block(&signature));
+    let err = verify_auth_token(&other, &state).await.unwrap_err();
+    assert_eq!(err.msg, "Invalid node token");
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[ntex::test]
+  async fn schedule_by_node_groups() {
+    use diesel::prelude::*;
+    use crate::schema::{nodes, node_groups, node_group_links};
+
+    const GROUP: &str = "test-schedule-group";
+    const NODES: [&str; 2] = ["test-schedule-b", "test-schedule-a"];
+    let state = gen_test_state().await;
+    for name in NODES {
+      let node = NodeDbModel {
+        name: name.to_owned(),
+        ip_address: "127.0.0.1".to_owned(),
+      };
+      repositories::node::create_if_not_exists(&node, &state.pool)
+        .await
+        .unwrap();
+    }
+    let mut conn = utils::store::get_pool_conn(&state.pool).unwrap();
+    diesel::insert_into(node_groups::table)
+      .values(node_groups::name.eq(GROUP))
+      .on_conflict_do_nothing()
+      .execute(&mut conn)
+      .unwrap();
+    for name in NODES {
+      diesel::insert_into(node_group_links::table)
+        .values((
+          node_group_links::node_name.eq(name),
+          node_group_links::node_group_name.eq(GROUP),
+        ))
+        .execute(&mut conn)
+        .unwrap();
+    }
+    let replication = ReplicationMode::UniqueByNodeGroups {
+      groups: vec![GROUP.to_owned()],
+    };
+    // Each node of the group must compute the same placement
+    let mut placements = Vec::new();
+    for hostname in NODES {
+      let mut state = state.clone();
+      state.config.hostname = hostname.to_owned();
+      placements.push(schedule(Some(&replication), &state).await.unwrap());
+    }
+    diesel::delete(node_group_links::table)
+      .filter(node_group_links::node_group_name.eq(GROUP))
+      .execute(&mut conn)
+      .unwrap();
+    diesel::delete(node_groups::table)
+      .filter(node_groups::name.eq(GROUP))
+      .execute(&mut conn)
+      .unwrap();
+    diesel::delete(nodes::table)
+      .filter(nodes::name.eq_any(NODES))
+      .execute(&mut conn)
+      .unwrap();
+    assert_eq!(placements[0], placements[1]);
+    assert_eq!(
+      placements[0],
+      HashMap::from([("test-schedule-a".to_owned(), 1)])
+    );
+  }
+
+  #[test]
+  fn command_serialization() {
+    let command = NodeCommand::CargoKill {
+      key: "test.global".to_owned(),
+      options: CargoKillOptions {
+        signal: "SIGTERM".to_owned(),
+      },
+    };
+    let data = serde_json::to_string(&command).unwrap();
+    let parsed = serde_json::from_str::<NodeCommand>(&data).unwrap();
+    let NodeCommand::CargoKill { key, options } = parsed else {
+      panic!("Expect a CargoKill command got {data}");
+    };
+    assert_eq!(key, "test.global");
+    assert_eq!(options.signal, "SIGTERM");
+  }
+}
@@ -1,113 +1,110 @@
-use ntex::rt;
-use futures::StreamExt;
-use bollard_next::container::{LogsOptions, LogOutput};
+use std::time::Duration;
 
-use crate::{
-  repositories,
-  models::{ToDbModel, StreamMetricPartial},
+use ntex::{rt, time};
+
+use nanocl_utils::io_error::IoResult;
+
+use nanocl_stubs::http_metric::{ProxyLogBatch, ProxyLogIngestion};
+
+use crate::repositories;
+use crate::models::{
+  DaemonState, ToDbModel, HttpMetricPartial, StreamMetricPartial,
 };
-use crate::models::{DaemonState, HttpMetricPartial};
 
-/// ## Spawn logger
+/// ## Ingest logs
 ///
-/// Create a background thread that will watch the logs of the `ncproxy.system.c` container
-/// The `ncproxy` is a container that run to update the proxy rules.
-/// He will print http metrics to the logs.
-/// This function will parse the logs and save the metrics to the database.
+/// Save the access log lines pushed by the `ncproxy.system.c` container
+/// as http and stream metrics.
+/// Lines that are not valid metrics are rejected without failing the batch
+/// so a bad line can't block the ingestion.
+/// The saved http metrics are added to the proxy request counters.
 ///
 /// ## Arguments
 ///
+/// - [batch](ProxyLogBatch) - The access log lines to save
 /// - [state](DaemonState) - Daemon state
 ///
-pub(crate) fn spawn_logger(state: &DaemonState) {
-  let state = state.clone();
-  rt::Arbiter::new().exec_fn(move || {
-    rt::spawn(async move {
-      let now = chrono::Utc::now().timestamp();
-      let mut res = state.docker_api.logs(
-        "ncproxy.system.c",
-        Some(LogsOptions::<String> {
-          follow: true,
-          stdout: true,
-          stderr: true,
-          since: now,
-          ..Default::default()
-        }),
-      );
-      while let Some(log) = res.next().await {
-        match log {
-          Err(e) => {
-            log::warn!("Failed to get log: {}", e);
-            continue;
-          }
-          Ok(log) => {
-            let log = match &log {
-              LogOutput::StdOut { message } => {
-                String::from_utf8_lossy(message).to_string()
-              }
-              LogOutput::StdErr { message } => {
-                String::from_utf8_lossy(message).to_string()
-              }
-              LogOutput::Console { message } => {
-                String::from_utf8_lossy(message).to_string()
-              }
-              _ => continue,
-            };
-            match &log {
-              log if log.starts_with("#HTTP") => {
-                let log = log.trim_start_matches("#HTTP");
-                let http_metric =
-                  serde_json::from_str::<HttpMetricPartial>(log);
-                match http_metric {
-                  Ok(http_metric) => {
-                    let http_metric =
-                      http_metric.to_db_model(&state.config.hostname);
-                    let res = repositories::http_metric::create(
-                      &http_metric,
-                      &state.pool,
-                    )
-                    .await;
-                    if let Err(e) = res {
-                      log::warn!("Failed to save http metric: {}", e);
-                    }
-                  }
-                  Err(e) => {
-                    log::warn!("Failed to parse http metric: {}", e);
-                  }
-                }
-              }
-              log if log.starts_with("#STREAM") => {
-                let trimmed_log = log.trim_start_matches("#STREAM");
-                let stream_metric =
-                  serde_json::from_str::<StreamMetricPartial>(trimmed_log);
-
-                match stream_metric
-                  .map(|metric| metric.to_db_model(&state.config.hostname))
-                {
-                  Ok(stream_db_model) => {
-                    let insert_result =
-                      repositories::http_metric::generic_insert(
-                        stream_db_model,
-                        &state.pool,
-                      )
-                      .await;
-
-                    if let Err(db_error) = insert_result {
-                      log::warn!("Failed to save tcp metric: {db_error}");
-                    }
-                  }
-                  Err(stream_parsing_error) => {
-                    log::warn!(
-                      "Failed to parse tcp metric: {stream_parsing_error}"
-                    );
-                  }
-                }
-              }
-              _ => {}
-            }
-          }
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](ProxyLogIngestion) - The number of lines saved and rejected
+///   - [Err](IoError) - The metrics could not be saved
+///
+pub(crate) async fn ingest_logs(
+  batch: ProxyLogBatch,
+  state: &DaemonState,
+) -> IoResult<ProxyLogIngestion> {
+  let mut rejected = 0;
+  let http = batch
+    .http
+    .into_iter()
+    .filter_map(|line| {
+      match serde_json::from_value::<HttpMetricPartial>(line) {
+        Ok(metric) => Some(metric.to_db_model(&state.config.hostname)),
+        Err(err) => {
+          log::warn!("Failed to parse http metric: {err}");
+          rejected += 1;
+          None
+        }
+      }
+    })
+    .collect::<Vec<_>>();
+  let stream = batch
+    .stream
+    .into_iter()
+    .filter_map(|line| {
+      match serde_json::from_value::<StreamMetricPartial>(line) {
+        Ok(metric) => Some(metric.to_db_model(&state.config.hostname)),
+        Err(err) => {
+          log::warn!("Failed to parse stream metric: {err}");
+          rejected += 1;
+          None
         }
       }
-    });
+    })
+    .collect::<Vec<_>>();
+  let ingestion = ProxyLogIngestion {
+    http: http.len(),
+    stream: stream.len(),
+    rejected,
+  };
+  let counts = http
+    .iter()
+    .map(|metric| {
+      let status_class = format!("{}xx", metric.status / 100);
+      ((metric.host.clone(), status_class), metric.bytes_sent)
+    })
+    .collect::<Vec<_>>();
+  repositories::http_metric::create_batch(http, stream, &state.pool).await?;
+  if let Ok(mut counters) = state.proxy_counters.lock() {
+    for (key, bytes_sent) in counts {
+      let counter = counters.entry(key).or_default();
+      counter.requests += 1;
+      counter.bytes_sent += bytes_sent.max(0) as u64;
+    }
+  }
+  Ok(ingestion)
+}
+
+/// ## Spawn retention
+///
+/// Spawn a background task that delete every hour
+/// the http and stream metrics past their expiration date
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - Daemon state
+///
+pub(crate) fn spawn_retention(state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    loop {
+      match repositories::http_metric::delete_expired(&state.pool).await {
+        Ok(0) => {}
+        Ok(count) => log::debug!("Deleted {count} expired metrics"),
+        Err(err) => log::warn!("Unable to delete expired metrics: {err}"),
+      }
+      time::sleep(Duration::from_secs(3600)).await;
+    }
   });
 }
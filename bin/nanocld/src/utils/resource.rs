@@ -223,5 +223,7 @@ pub async fn delete(resource: &Resource, pool: &Pool) -> Result<(), HttpError> {
   repositories::resource::delete_by_key(&resource.name, pool).await?;
   repositories::resource_config::delete_by_resource_key(&resource.name, pool)
     .await?;
+  repositories::state_ref::delete_by_key("Resource", &resource.name, pool)
+    .await?;
   Ok(())
 }
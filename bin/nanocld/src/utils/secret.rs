@@ -0,0 +1,1141 @@
+use std::fs;
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+use tokio::process::Command;
+
+use openssl::sha;
+use openssl::rand;
+use openssl::base64;
+use openssl::error::ErrorStack;
+use openssl::symm::{self, Cipher};
+
+use nanocl_utils::http_error::HttpError;
+use nanocl_utils::io_error::{FromIo, IoError, IoResult};
+
+use nanocl_stubs::generic::GenericCount;
+use nanocl_stubs::secret::{SecretPartial, SecretUpdate};
+use nanocl_stubs::cargo_config::CargoSecretFile;
+
+use crate::{utils, repositories};
+use crate::models::{
+  Pool, DaemonState, NodeCommand, SecretDbModel, SecretEnvelope, SecretKeyring,
+  SecretKeys, SecretUpdateDbModel,
+};
+
+/// Environment variable to set the master key instead of the key file
+const SECRET_KEY_ENV: &str = "NANOCL_SECRET_KEY";
+/// Length of the master key and the data keys (AES-256)
+const KEY_LEN: usize = 32;
+/// Length of the nonce of AES-GCM
+const NONCE_LEN: usize = 12;
+/// Length of the authentication tag of AES-GCM
+const TAG_LEN: usize = 16;
+/// Number of passes to re-encrypt the secrets updated during a pass
+const REENCRYPT_PASSES: usize = 3;
+/// Name of the directory of the secret files in the state directory
+const SECRET_FILES_DIR: &str = "secrets";
+/// Default permissions of the secret files
+const DEFAULT_FILE_MODE: u32 = 0o444;
+
+/// ## Crypto error
+///
+/// Convert an openssl error into an io error
+///
+fn crypto_error(err: ErrorStack) -> IoError {
+  IoError::new(
+    "Secret",
+    std::io::Error::new(std::io::ErrorKind::Other, err),
+  )
+}
+
+/// ## Gen key
+///
+/// Generate a random key for AES-256-GCM
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<u8>) - The generated key
+///   - [Err](IoError) - Error during the operation
+///
+fn gen_key() -> IoResult<Vec<u8>> {
+  let mut key = vec![0; KEY_LEN];
+  rand::rand_bytes(&mut key).map_err(crypto_error)?;
+  Ok(key)
+}
+
+/// ## Key id
+///
+/// Get the id of a master key from his hash,
+/// it's saved with the secrets to find the key to decrypt them
+///
+/// ## Arguments
+///
+/// - [key](Vec<u8>) - The master key
+///
+/// ## Returns
+///
+/// - [String](String) - The id of the key
+///
+fn key_id(key: &[u8]) -> String {
+  sha::sha256(key)[..8]
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect()
+}
+
+/// ## Parse key
+///
+/// Parse a base64 encoded master key
+///
+/// ## Arguments
+///
+/// - [data](str) - The base64 encoded key
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<u8>) - The key
+///   - [Err](IoError) - The key is invalid
+///
+fn parse_key(data: &str) -> IoResult<Vec<u8>> {
+  let key = base64::decode_block(data.trim()).map_err(|_| {
+    IoError::invalid_data("SecretKey", "master key must be base64 encoded")
+  })?;
+  if key.len() != KEY_LEN {
+    return Err(IoError::invalid_data(
+      "SecretKey",
+      &format!("master key must be {KEY_LEN} bytes long"),
+    ));
+  }
+  Ok(key)
+}
+
+/// ## Write key
+///
+/// Write a base64 encoded master key readable only by his owner
+///
+/// ## Arguments
+///
+/// - [path](str) - The path of the key file
+/// - [key](Vec<u8>) - The master key
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The key is written
+///   - [Err](IoError) - Error during the operation
+///
+fn write_key(path: &str, key: &[u8]) -> IoResult<()> {
+  let mut file = fs::OpenOptions::new()
+    .write(true)
+    .create(true)
+    .truncate(true)
+    .mode(0o600)
+    .open(path)
+    .map_err(|err| {
+      err.map_err_context(|| format!("Unable to create key file {path}"))
+    })?;
+  file
+    .write_all(base64::encode_block(key).as_bytes())
+    .and_then(|_| file.sync_all())
+    .map_err(|err| {
+      err.map_err_context(|| format!("Unable to write key file {path}"))
+    })?;
+  Ok(())
+}
+
+/// ## Read key file
+///
+/// Read a base64 encoded master key from a file
+///
+/// ## Arguments
+///
+/// - [path](str) - The path of the key file
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Option<Vec<u8>>) - The key, none if the file doesn't exist
+///   - [Err](IoError) - The file can't be read or the key is invalid
+///
+fn read_key_file(path: &str) -> IoResult<Option<Vec<u8>>> {
+  match fs::read_to_string(path) {
+    Ok(data) => Ok(Some(parse_key(&data)?)),
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(err) => Err(
+      err
+        .map_err_context(|| format!("Unable to read key file {path}"))
+        .into(),
+    ),
+  }
+}
+
+/// ## Load keys
+///
+/// Load the master key from the environment variable `NANOCL_SECRET_KEY`
+/// or from the key file set by `secret_key_file`.
+/// Every node of a cluster share the same database so they must share
+/// the same key, the key file is only generated when no secret is encrypted.
+/// The key of an interrupted rotation is loaded too
+/// to decrypt the secrets already re-encrypted with it.
+///
+/// ## Arguments
+///
+/// - [path](str) - The path of the key file
+/// - [pool](Pool) - Database connection pool
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](SecretKeyring) - The master keys
+///   - [Err](IoError) - The master key is invalid or missing
+///
+pub(crate) async fn load_keys(
+  path: &str,
+  pool: &Pool,
+) -> IoResult<SecretKeyring> {
+  let mut keys = SecretKeys::default();
+  let key_ids = repositories::secret::list_key_ids(pool).await?;
+  let key = if let Ok(data) = std::env::var(SECRET_KEY_ENV) {
+    parse_key(&data)?
+  } else {
+    let key = match read_key_file(path)? {
+      Some(key) => key,
+      None if !key_ids.is_empty() => {
+        return Err(IoError::not_found(
+          "SecretKey",
+          &format!(
+            "{path} doesn't exist but secrets are encrypted \
+            with the master key {}, copy the key of the other nodes \
+            or set {SECRET_KEY_ENV}",
+            key_ids.join(", ")
+          ),
+        ));
+      }
+      None => {
+        if let Some(dir) = std::path::Path::new(path).parent() {
+          fs::create_dir_all(dir).map_err(|err| {
+            err
+              .map_err_context(|| format!("Unable to create {}", dir.display()))
+          })?;
+        }
+        let key = gen_key()?;
+        write_key(path, &key)?;
+        log::info!("Generated the secret master key {path}");
+        key
+      }
+    };
+    if let Some(next) = read_key_file(&format!("{path}.next"))? {
+      log::warn!("Found the key of an interrupted rotation, rotate it again");
+      keys.keys.insert(key_id(&next), next);
+    }
+    keys.path = Some(path.to_owned());
+    key
+  };
+  keys.current = key_id(&key);
+  keys.keys.insert(keys.current.clone(), key);
+  let unknown = key_ids
+    .into_iter()
+    .filter(|id| !keys.keys.contains_key(id))
+    .collect::<Vec<_>>();
+  if !unknown.is_empty() {
+    return Err(IoError::invalid_data(
+      "SecretKey",
+      &format!(
+        "secrets are encrypted with the master key {} unknown to this node, \
+        use the key of the other nodes",
+        unknown.join(", ")
+      ),
+    ));
+  }
+  Ok(Arc::new(RwLock::new(keys)))
+}
+
+/// ## Refresh keys
+///
+/// Reload the key file when a secret is encrypted with an unknown master key,
+/// the key may have been rotated by another node sharing the key file
+///
+/// ## Arguments
+///
+/// - [id](str) - The id of the master key of a secret
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The keys are up to date
+///   - [Err](IoError) - The key file can't be read
+///
+fn refresh_keys(id: &str, state: &DaemonState) -> IoResult<()> {
+  let path = {
+    let keys = read_keys(state)?;
+    match &keys.path {
+      Some(path) if !keys.keys.contains_key(id) => path.clone(),
+      _ => return Ok(()),
+    }
+  };
+  let next = read_key_file(&format!("{path}.next"))?;
+  let Some(key) = read_key_file(&path)? else {
+    return Ok(());
+  };
+  let mut keys = state
+    .secret_keys
+    .write()
+    .map_err(|_| IoError::interupted("SecretKeys", "Unable to lock"))?;
+  if let Some(next) = next {
+    keys.keys.insert(key_id(&next), next);
+  }
+  keys.current = key_id(&key);
+  let current = keys.current.clone();
+  keys.keys.insert(current, key);
+  Ok(())
+}
+
+/// ## Read keys
+///
+/// Lock the master keys for reading
+///
+fn read_keys(state: &DaemonState) -> IoResult<RwLockReadGuard<SecretKeys>> {
+  state
+    .secret_keys
+    .read()
+    .map_err(|_| IoError::interupted("SecretKeys", "Unable to lock"))
+}
+
+/// ## Seal
+///
+/// Encrypt data with AES-256-GCM authenticated with the key of the secret
+///
+/// ## Arguments
+///
+/// - [key](Vec<u8>) - The encryption key
+/// - [data](Vec<u8>) - The data to encrypt
+/// - [aad](str) - The key of the secret
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](String) - The nonce, the encrypted data and the tag in base64
+///   - [Err](IoError) - Error during the operation
+///
+fn seal(key: &[u8], data: &[u8], aad: &str) -> IoResult<String> {
+  let mut nonce = [0; NONCE_LEN];
+  rand::rand_bytes(&mut nonce).map_err(crypto_error)?;
+  let mut tag = [0; TAG_LEN];
+  let encrypted = symm::encrypt_aead(
+    Cipher::aes_256_gcm(),
+    key,
+    Some(&nonce),
+    aad.as_bytes(),
+    data,
+    &mut tag,
+  )
+  .map_err(crypto_error)?;
+  Ok(base64::encode_block(
+    &[&nonce[..], &encrypted[..], &tag[..]].concat(),
+  ))
+}
+
+/// ## Open
+///
+/// Decrypt data sealed with AES-256-GCM
+///
+/// ## Arguments
+///
+/// - [key](Vec<u8>) - The encryption key
+/// - [sealed](str) - The sealed data in base64
+/// - [aad](str) - The key of the secret
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<u8>) - The decrypted data
+///   - [Err](IoError) - The data is invalid or altered
+///
+fn open(key: &[u8], sealed: &str, aad: &str) -> IoResult<Vec<u8>> {
+  let sealed = base64::decode_block(sealed).map_err(crypto_error)?;
+  if sealed.len() < NONCE_LEN + TAG_LEN {
+    return Err(IoError::invalid_data(
+      "Secret",
+      "encrypted data is too short",
+    ));
+  }
+  let (nonce, rest) = sealed.split_at(NONCE_LEN);
+  let (encrypted, tag) = rest.split_at(rest.len() - TAG_LEN);
+  symm::decrypt_aead(
+    Cipher::aes_256_gcm(),
+    key,
+    Some(nonce),
+    aad.as_bytes(),
+    encrypted,
+    tag,
+  )
+  .map_err(crypto_error)
+}
+
+/// ## Get key
+///
+/// Get a master key by id
+///
+fn get_key<'a>(keys: &'a SecretKeys, id: &str) -> IoResult<&'a [u8]> {
+  // Not a not found error so it's not mistaken for a missing secret
+  keys.keys.get(id).map(|key| key.as_slice()).ok_or_else(|| {
+    IoError::new(
+      "SecretKey",
+      std::io::Error::new(
+        std::io::ErrorKind::Other,
+        format!("master key {id} is missing"),
+      ),
+    )
+  })
+}
+
+/// ## Encrypt
+///
+/// Encrypt the data of a secret with a new data key
+/// encrypted with the current master key
+///
+/// ## Arguments
+///
+/// - [key](str) - The key of the secret
+/// - [data](serde_json::Value) - The data to encrypt
+/// - [keys](SecretKeys) - The master keys
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](serde_json::Value) - The encrypted data as a secret envelope
+///   - [Err](IoError) - Error during the operation
+///
+fn encrypt(
+  key: &str,
+  data: &serde_json::Value,
+  keys: &SecretKeys,
+) -> IoResult<serde_json::Value> {
+  let master_key = get_key(keys, &keys.current)?;
+  let data_key = gen_key()?;
+  let data =
+    serde_json::to_vec(data).map_err(|err| err.map_err_context(|| "Secret"))?;
+  let envelope = SecretEnvelope {
+    key: seal(master_key, &data_key, key)?,
+    data: seal(&data_key, &data, key)?,
+  };
+  let envelope = serde_json::to_value(envelope)
+    .map_err(|err| err.map_err_context(|| "Secret"))?;
+  Ok(envelope)
+}
+
+/// ## Decrypt
+///
+/// Decrypt the data of a secret, a secret without key id is not encrypted
+///
+/// ## Arguments
+///
+/// - [item](SecretDbModel) - The secret to decrypt
+/// - [keys](SecretKeys) - The master keys
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](SecretDbModel) - The secret with his decrypted data
+///   - [Err](IoError) - The master key is missing or the data is altered
+///
+fn decrypt(
+  mut item: SecretDbModel,
+  keys: &SecretKeys,
+) -> IoResult<SecretDbModel> {
+  let Some(id) = &item.key_id else {
+    return Ok(item);
+  };
+  let envelope = serde_json::from_value::<SecretEnvelope>(item.data.clone())
+    .map_err(|err| err.map_err_context(|| "Secret"))?;
+  let data_key = open(get_key(keys, id)?, &envelope.key, &item.key)?;
+  let data = open(&data_key, &envelope.data, &item.key)?;
+  item.data = serde_json::from_slice(&data)
+    .map_err(|err| err.map_err_context(|| "Secret"))?;
+  Ok(item)
+}
+
+/// ## Rewrap
+///
+/// Encrypt the data key of a secret with the current master key,
+/// the data of a secret not encrypted yet is encrypted
+///
+/// ## Arguments
+///
+/// - [item](SecretDbModel) - The secret to re-encrypt
+/// - [keys](SecretKeys) - The master keys
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](SecretDbModel) - The re-encrypted secret
+///   - [Err](IoError) - Error during the operation
+///
+fn rewrap(
+  mut item: SecretDbModel,
+  keys: &SecretKeys,
+) -> IoResult<SecretDbModel> {
+  match &item.key_id {
+    None => {
+      item.data = encrypt(&item.key, &item.data, keys)?;
+    }
+    Some(id) => {
+      let mut envelope =
+        serde_json::from_value::<SecretEnvelope>(item.data.clone())
+          .map_err(|err| err.map_err_context(|| "Secret"))?;
+      let data_key = open(get_key(keys, id)?, &envelope.key, &item.key)?;
+      envelope.key = seal(get_key(keys, &keys.current)?, &data_key, &item.key)?;
+      item.data = serde_json::to_value(envelope)
+        .map_err(|err| err.map_err_context(|| "Secret"))?;
+    }
+  }
+  item.key_id = Some(keys.current.clone());
+  Ok(item)
+}
+
+/// ## Create
+///
+/// Encrypt and save a secret
+///
+/// ## Arguments
+///
+/// - [item](SecretPartial) - The secret to create
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](SecretDbModel) - The created secret with his decrypted data
+///   - [Err](HttpError) - Error during the operation
+///
+pub(crate) async fn create(
+  item: &SecretPartial,
+  state: &DaemonState,
+) -> Result<SecretDbModel, HttpError> {
+  let mut db_item: SecretDbModel = item.clone().into();
+  {
+    let keys = read_keys(state)?;
+    db_item.data = encrypt(&item.key, &item.data, &keys)?;
+    db_item.key_id = Some(keys.current.clone());
+  }
+  let mut db_item = repositories::secret::create(&db_item, &state.pool).await?;
+  db_item.data = item.data.clone();
+  Ok(db_item)
+}
+
+/// ## Inspect by key
+///
+/// Get a secret with his decrypted data
+///
+/// ## Arguments
+///
+/// - [key](str) - The key of the secret
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](SecretDbModel) - The secret with his decrypted data
+///   - [Err](HttpError) - Error during the operation
+///
+pub(crate) async fn inspect_by_key(
+  key: &str,
+  state: &DaemonState,
+) -> Result<SecretDbModel, HttpError> {
+  let item = repositories::secret::find_by_key(key, &state.pool).await?;
+  if let Some(id) = &item.key_id {
+    refresh_keys(id, state)?;
+  }
+  let item = decrypt(item, &*read_keys(state)?)?;
+  Ok(item)
+}
+
+/// ## List
+///
+/// List the secrets with their decrypted data,
+/// a secret that can't be decrypted is logged and skipped
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<SecretDbModel>) - The secrets with their decrypted data
+///   - [Err](HttpError) - Error during the operation
+///
+pub(crate) async fn list(
+  state: &DaemonState,
+) -> Result<Vec<SecretDbModel>, HttpError> {
+  let items = repositories::secret::list(&state.pool).await?;
+  let ids = items
+    .iter()
+    .filter_map(|item| item.key_id.as_deref())
+    .collect::<HashSet<_>>();
+  for id in ids {
+    refresh_keys(id, state)?;
+  }
+  let keys = read_keys(state)?;
+  let items = items
+    .into_iter()
+    .filter_map(|item| {
+      let key = item.key.clone();
+      match decrypt(item, &keys) {
+        Ok(item) => Some(item),
+        Err(err) => {
+          log::warn!("Unable to decrypt secret {key}: {err}");
+          None
+        }
+      }
+    })
+    .collect();
+  Ok(items)
+}
+
+/// ## Patch by key
+///
+/// Encrypt and update the data of a secret
+///
+/// ## Arguments
+///
+/// - [key](str) - The key of the secret
+/// - [item](SecretUpdate) - The new data of the secret
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](SecretDbModel) - The updated secret with his decrypted data
+///   - [Err](HttpError) - Error during the operation
+///
+pub(crate) async fn patch_by_key(
+  key: &str,
+  item: &SecretUpdate,
+  state: &DaemonState,
+) -> Result<SecretDbModel, HttpError> {
+  let update = {
+    let keys = read_keys(state)?;
+    SecretUpdateDbModel {
+      updated_at: Some(chrono::Utc::now().naive_utc()),
+      data: Some(encrypt(key, &item.data, &keys)?),
+      metadata: item.metadata.clone(),
+      key_id: Some(keys.current.clone()),
+    }
+  };
+  let mut db_item =
+    repositories::secret::update_by_key(key, &update, &state.pool).await?;
+  db_item.data = item.data.clone();
+  sync_files(Some(key), state).await?;
+  // The other nodes refresh the files of their own instances
+  let command = NodeCommand::SecretFilesSync {
+    key: key.to_owned(),
+  };
+  utils::node::broadcast_command(&command, state).await?;
+  Ok(db_item)
+}
+
+/// ## Reencrypt
+///
+/// Re-encrypt the secrets not encrypted with the current master key.
+/// It encrypt the secrets saved before the encryption at rest
+/// and finish a rotation.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](usize) - Number of re-encrypted secrets
+///   - [Err](IoError) - Error during the operation
+///
+pub(crate) async fn reencrypt(state: &DaemonState) -> IoResult<usize> {
+  let mut count = 0;
+  for _ in 0..REENCRYPT_PASSES {
+    let items = repositories::secret::list(&state.pool).await?;
+    let items = {
+      let keys = read_keys(state)?;
+      items
+        .into_iter()
+        .filter(|item| item.key_id.as_deref() != Some(keys.current.as_str()))
+        .map(|item| rewrap(item, &keys))
+        .collect::<IoResult<Vec<_>>>()?
+    };
+    if items.is_empty() {
+      return Ok(count);
+    }
+    count += repositories::secret::update_keys(items, &state.pool).await?;
+  }
+  Err(IoError::interupted(
+    "Secret",
+    "Secrets are updated too often to be re-encrypted, retry later",
+  ))
+}
+
+/// ## Rotate key
+///
+/// Generate a new master key and re-encrypt the data keys of every secrets.
+/// The new key is written next to the current one and replace it
+/// once every secret is re-encrypted.
+///
+/// ## Arguments
+///
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](GenericCount) - Number of re-encrypted secrets
+///   - [Err](HttpError) - Error during the operation
+///
+pub(crate) async fn rotate_key(
+  state: &DaemonState,
+) -> Result<GenericCount, HttpError> {
+  let Some(path) = read_keys(state)?.path.clone() else {
+    return Err(HttpError::bad_request(format!(
+      "The master key is set by {SECRET_KEY_ENV} and can't be rotated"
+    )));
+  };
+  let key = gen_key()?;
+  let next_path = format!("{path}.next");
+  write_key(&next_path, &key)?;
+  {
+    let mut keys = state
+      .secret_keys
+      .write()
+      .map_err(|_| IoError::interupted("SecretKeys", "Unable to lock"))?;
+    let id = key_id(&key);
+    keys.keys.insert(id.clone(), key);
+    keys.current = id;
+  }
+  let count = reencrypt(state).await?;
+  fs::rename(&next_path, &path).map_err(|err| {
+    err.map_err_context(|| format!("Unable to replace key file {path}"))
+  })?;
+  log::info!("Rotated the secret master key, {count} secrets re-encrypted");
+  Ok(GenericCount {
+    count: count as i64,
+  })
+}
+
+/// ## Is mount point
+///
+/// Check if a path is a mount point in the mount namespace of the daemon
+///
+async fn is_mount_point(path: &str) -> bool {
+  let Ok(mountinfo) = tokio::fs::read_to_string("/proc/self/mountinfo").await
+  else {
+    return false;
+  };
+  mountinfo
+    .lines()
+    .any(|line| line.split(' ').nth(4) == Some(path))
+}
+
+/// ## Ensure files dir
+///
+/// Ensure the directory of the secret files exists in the state directory
+/// and mount a tmpfs on it so the secrets are never written on disk.
+/// Nothing is mounted if the host already mounted a tmpfs on it,
+/// which is required when the daemon run without the mount capability.
+/// The files are written on disk with a warning if the tmpfs can't be mounted.
+///
+/// ## Arguments
+///
+/// - [state_dir](str) - The state dir path
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The directory is ready
+///   - [Err](IoError) - The directory can't be created
+///
+pub(crate) async fn ensure_files_dir(state_dir: &str) -> IoResult<()> {
+  let dir = format!("{state_dir}/{SECRET_FILES_DIR}");
+  tokio::fs::create_dir_all(&dir)
+    .await
+    .map_err(|err| err.map_err_context(|| format!("Unable to create {dir}")))?;
+  tokio::fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))
+    .await
+    .map_err(|err| {
+      err.map_err_context(|| format!("Unable to set permissions of {dir}"))
+    })?;
+  if is_mount_point(&dir).await {
+    return Ok(());
+  }
+  let output = Command::new("mount")
+    .args(["-t", "tmpfs", "-o", "mode=0700", "tmpfs", &dir])
+    .output()
+    .await;
+  let err = match output {
+    Ok(output) if output.status.success() => {
+      log::info!("Mounted a tmpfs on {dir} for the secret files");
+      return Ok(());
+    }
+    Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+    Err(err) => err.to_string(),
+  };
+  log::warn!("Unable to mount a tmpfs on {dir}: {err}");
+  log::warn!("Mount a tmpfs on {dir} from the host to protect them");
+  log::warn!("Secret files will be written on disk");
+  Ok(())
+}
+
+/// ## Gen files
+///
+/// Generate the files of a secret from his data.
+/// The data must be an object, or a list of `KEY=VALUE` for a secret
+/// of kind `Env`. String values are written as is and other values as json,
+/// null values are rejected.
+///
+/// ## Arguments
+///
+/// - [item](SecretDbModel) - The secret with his decrypted data
+/// - [keys](Option<Vec<String>>) - The keys to write, every key if none
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<(String, Vec<u8>)>) - The name and content of the files
+///   - [Err](HttpError) - The data or a key is invalid
+///
+fn gen_files(
+  item: &SecretDbModel,
+  keys: Option<&[String]>,
+) -> Result<Vec<(String, Vec<u8>)>, HttpError> {
+  let data: Vec<(String, serde_json::Value)> = match &item.data {
+    serde_json::Value::Object(map) => map.clone().into_iter().collect(),
+    serde_json::Value::Array(envs) if item.kind == "Env" => envs
+      .iter()
+      .filter_map(|env| env.as_str()?.split_once('='))
+      .map(|(name, value)| (name.to_owned(), serde_json::Value::from(value)))
+      .collect(),
+    _ => {
+      return Err(HttpError::bad_request(format!(
+        "Secret {} can't be mounted as files, his data must be an object",
+        item.key
+      )))
+    }
+  };
+  let data = match keys {
+    None => data,
+    Some(keys) => keys
+      .iter()
+      .map(|key| {
+        data
+          .iter()
+          .find(|(name, _)| name == key)
+          .cloned()
+          .ok_or_else(|| {
+            HttpError::bad_request(format!(
+              "Secret {} has no key {key}",
+              item.key
+            ))
+          })
+      })
+      .collect::<Result<Vec<_>, _>>()?,
+  };
+  data
+    .into_iter()
+    .map(|(name, value)| {
+      if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(HttpError::bad_request(format!(
+          "Secret {} key {name} is not a valid file name",
+          item.key
+        )));
+      }
+      let content = match value {
+        serde_json::Value::String(value) => value.into_bytes(),
+        serde_json::Value::Null => {
+          return Err(HttpError::bad_request(format!(
+            "Secret {} key {name} is null",
+            item.key
+          )))
+        }
+        value => value.to_string().into_bytes(),
+      };
+      Ok((name, content))
+    })
+    .collect()
+}
+
+/// ## Write files
+///
+/// Write the files of a secret in a directory and remove the stale ones.
+/// Each file is written next to his path then renamed,
+/// so a container never read a partially written file.
+///
+/// ## Arguments
+///
+/// - [dir](str) - The directory of the files
+/// - [files](Vec<(String, Vec<u8>)>) - The name and content of the files
+/// - [mode](u32) - The permissions of the files
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The files are written
+///   - [Err](IoError) - Error during the operation
+///
+async fn write_files(
+  dir: &str,
+  files: &[(String, Vec<u8>)],
+  mode: u32,
+) -> IoResult<()> {
+  tokio::fs::create_dir_all(dir)
+    .await
+    .map_err(|err| err.map_err_context(|| format!("Unable to create {dir}")))?;
+  for (name, content) in files {
+    let path = format!("{dir}/{name}");
+    let tmp_path = format!("{dir}/.{name}.tmp");
+    tokio::fs::write(&tmp_path, content).await.map_err(|err| {
+      err.map_err_context(|| format!("Unable to write {tmp_path}"))
+    })?;
+    tokio::fs::set_permissions(&tmp_path, fs::Permissions::from_mode(mode))
+      .await
+      .map_err(|err| {
+        err.map_err_context(|| format!("Unable to set mode of {tmp_path}"))
+      })?;
+    tokio::fs::rename(&tmp_path, &path).await.map_err(|err| {
+      err.map_err_context(|| format!("Unable to replace {path}"))
+    })?;
+  }
+  let mut entries = tokio::fs::read_dir(dir)
+    .await
+    .map_err(|err| err.map_err_context(|| format!("Unable to read {dir}")))?;
+  while let Some(entry) = entries.next_entry().await? {
+    let name = entry.file_name().to_string_lossy().to_string();
+    if files.iter().any(|(file, _)| *file == name) {
+      continue;
+    }
+    tokio::fs::remove_file(entry.path()).await.map_err(|err| {
+      err.map_err_context(|| format!("Unable to remove {dir}/{name}"))
+    })?;
+  }
+  Ok(())
+}
+
+/// ## Cargo files dir
+///
+/// Get the directory of the secret files of a cargo
+///
+fn cargo_files_dir(cargo_key: &str, state: &DaemonState) -> String {
+  format!("{}/{SECRET_FILES_DIR}/{cargo_key}", state.config.state_dir)
+}
+
+/// ## Write cargo files
+///
+/// Write the secret files of a cargo in the state directory.
+/// The files of each secret are written in their own directory
+/// bind mounted read only in the instances.
+///
+/// ## Arguments
+///
+/// - [cargo_key](str) - The cargo key
+/// - [secret_files](Vec<CargoSecretFile>) - The secrets mounted as files
+/// - [secret](Option<str>) - Only write the files of this secret
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<String>) - The binds of the files in the instances
+///   - [Err](HttpError) - A secret is missing or invalid
+///
+pub(crate) async fn write_cargo_files(
+  cargo_key: &str,
+  secret_files: &[CargoSecretFile],
+  secret: Option<&str>,
+  state: &DaemonState,
+) -> Result<Vec<String>, HttpError> {
+  let cargo_dir = cargo_files_dir(cargo_key, state);
+  let mut binds = Vec::new();
+  for (index, secret_file) in secret_files.iter().enumerate() {
+    let dir = format!("{cargo_dir}/{index}");
+    binds.push(format!("{dir}:{}:ro", secret_file.target));
+    if secret.is_some_and(|secret| secret != secret_file.secret) {
+      continue;
+    }
+    let item = inspect_by_key(&secret_file.secret, state).await?;
+    let files = gen_files(&item, secret_file.keys.as_deref())?;
+    let mode = secret_file.mode.unwrap_or(DEFAULT_FILE_MODE);
+    write_files(&dir, &files, mode).await?;
+  }
+  Ok(binds)
+}
+
+/// ## Sync files
+///
+/// Write the secret files of the cargoes with instances on the current node,
+/// the files of a patched secret are refreshed in place and every file
+/// is written again at boot since the tmpfs is empty after a reboot.
+/// A cargo with invalid secret files is logged and skipped.
+///
+/// ## Arguments
+///
+/// - [secret](Option<str>) - Only write the files of this secret
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The files are written
+///   - [Err](IoError) - The cargoes can't be listed
+///
+pub(crate) async fn sync_files(
+  secret: Option<&str>,
+  state: &DaemonState,
+) -> IoResult<()> {
+  let cargoes = repositories::cargo::list(&state.pool).await?;
+  for cargo in cargoes {
+    let config =
+      repositories::cargo_config::find_by_key(&cargo.config_key, &state.pool)
+        .await?;
+    let secret_files = config.secret_files.unwrap_or_default();
+    let is_mounted = match secret {
+      Some(secret) => secret_files.iter().any(|file| file.secret == secret),
+      None => !secret_files.is_empty(),
+    };
+    if !is_mounted {
+      continue;
+    }
+    match utils::cargo::list_instances(&cargo.key, &state.docker_api).await {
+      Ok(instances) if instances.is_empty() => continue,
+      Ok(_) => {}
+      Err(err) => {
+        log::warn!("Unable to list instances of {}: {err}", cargo.key);
+        continue;
+      }
+    }
+    if let Err(err) =
+      write_cargo_files(&cargo.key, &secret_files, secret, state).await
+    {
+      log::warn!("Unable to write secret files of {}: {err}", cargo.key);
+    }
+  }
+  Ok(())
+}
+
+/// ## Remove cargo files
+///
+/// Remove the secret files of a deleted cargo
+///
+/// ## Arguments
+///
+/// - [cargo_key](str) - The cargo key
+/// - [state](DaemonState) - The daemon state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The files are removed
+///   - [Err](IoError) - Error during the operation
+///
+pub(crate) async fn remove_cargo_files(
+  cargo_key: &str,
+  state: &DaemonState,
+) -> IoResult<()> {
+  let dir = cargo_files_dir(cargo_key, state);
+  match tokio::fs::remove_dir_all(&dir).await {
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(
+      err
+        .map_err_context(|| format!("Unable to remove {dir}"))
+        .into(),
+    ),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn envelope() {
+    let key = gen_key().unwrap();
+    let mut keys = SecretKeys {
+      current: key_id(&key),
+      ..Default::default()
+    };
+    keys.keys.insert(keys.current.clone(), key);
+    let data = serde_json::json!(["PASSWORD=secret"]);
+    let mut item: SecretDbModel = SecretPartial {
+      key: "env".to_owned(),
+      kind: "Env".to_owned(),
+      immutable: None,
+      data: data.clone(),
+      metadata: None,
+    }
+    .into();
+    // A secret saved before the encryption at rest
+    assert_eq!(decrypt(item.clone(), &keys).unwrap().data, data);
+    item = rewrap(item, &keys).unwrap();
+    assert_ne!(item.data, data);
+    assert_eq!(decrypt(item.clone(), &keys).unwrap().data, data);
+    // Rotate the master key
+    let previous = keys.current.clone();
+    let key = gen_key().unwrap();
+    keys.current = key_id(&key);
+    keys.keys.insert(keys.current.clone(), key);
+    item = rewrap(item, &keys).unwrap();
+    assert_eq!(item.key_id.as_deref(), Some(keys.current.as_str()));
+    keys.keys.remove(&previous);
+    assert_eq!(decrypt(item.clone(), &keys).unwrap().data, data);
+    // The data is bound to the key of the secret
+    item.key = "other".to_owned();
+    assert!(decrypt(item, &keys).is_err());
+    assert!(parse_key("not a key").is_err());
+  }
+
+  #[ntex::test]
+  async fn files() {
+    let mut item: SecretDbModel = SecretPartial {
+      key: "tls".to_owned(),
+      kind: "Tls".to_owned(),
+      immutable: None,
+      data: serde_json::json!({
+        "Certificate": "CERT",
+        "CertificateKey": "KEY",
+        "Dhparam": "DH",
+      }),
+      metadata: None,
+    }
+    .into();
+    let keys = vec!["Certificate".to_owned()];
+    let files = gen_files(&item, Some(&keys)).unwrap();
+    assert_eq!(files, vec![("Certificate".to_owned(), b"CERT".to_vec())]);
+    let missing = vec!["Chain".to_owned()];
+    assert!(gen_files(&item, Some(&missing)).is_err());
+    let files = gen_files(&item, None).unwrap();
+    assert_eq!(files.len(), 3);
+    let dir = "/tmp/nanocl-secret-files";
+    write_files(dir, &files, 0o400).await.unwrap();
+    let mode = fs::metadata(format!("{dir}/CertificateKey"))
+      .unwrap()
+      .permissions()
+      .mode();
+    assert_eq!(mode & 0o777, 0o400);
+    assert_eq!(fs::read_to_string(format!("{dir}/Dhparam")).unwrap(), "DH");
+    // Refreshing the files remove the stale ones
+    let files = gen_files(&item, Some(&keys)).unwrap();
+    write_files(dir, &files, 0o400).await.unwrap();
+    assert!(fs::metadata(format!("{dir}/CertificateKey")).is_err());
+    fs::remove_dir_all(dir).unwrap();
+    item.kind = "Env".to_owned();
+    item.data = serde_json::json!(["USER=admin", "PASSWORD=a=b"]);
+    let files = gen_files(&item, None).unwrap();
+    assert_eq!(files[1], ("PASSWORD".to_owned(), b"a=b".to_vec()));
+    item.data = serde_json::json!({ "../passwd": "root" });
+    assert!(gen_files(&item, None).is_err());
+    item.data = serde_json::json!({ "Dhparam": null });
+    assert!(gen_files(&item, None).is_err());
+  }
+}
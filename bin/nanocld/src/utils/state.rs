@@ -2,6 +2,8 @@ use ntex::rt;
 use ntex::http;
 use ntex::util::Bytes;
 use ntex::channel::mpsc;
+use serde::Serialize;
+use serde_json::Value;
 use futures_util::StreamExt;
 use futures_util::stream::FuturesUnordered;
 
@@ -9,16 +11,18 @@ use nanocl_utils::http_error::HttpError;
 
 use nanocl_stubs::system::Event;
 use nanocl_stubs::resource::ResourcePartial;
+use nanocl_stubs::job::JobPartial;
 use nanocl_stubs::secret::{SecretPartial, SecretUpdate};
 use nanocl_stubs::cargo_config::CargoConfigPartial;
 use nanocl_stubs::vm_config::{VmConfigPartial, VmDiskConfig};
 use nanocl_stubs::state::{
   StateDeployment, StateCargo, StateVirtualMachine, StateResource, StateMeta,
-  StateStream, StateSecret,
+  StateStream, StateSecret, StateDiff, StateApplyQuery, StateJob,
 };
 
 use crate::{utils, repositories};
-use crate::models::{StateData, DaemonState};
+use crate::models::{StateData, DaemonState, StateRefDbModel};
+use crate::utils::dependency::{DependencyGraph, Workload};
 
 /// ## Stream to bytes
 ///
@@ -52,13 +56,98 @@ fn stream_to_bytes(state_stream: StateStream) -> Result<Bytes, HttpError> {
 /// - [state_stream](StateStream) - The state stream to send
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
-fn send(
+pub(crate) fn send(
   state_stream: StateStream,
   sx: &mpsc::Sender<Result<Bytes, HttpError>>,
 ) {
   let _ = sx.send(stream_to_bytes(state_stream));
 }
 
+/// ## Diff value
+///
+/// Recursively compare two json values and push the changed fields.
+/// Null values are considered as missing fields.
+///
+fn diff_value(
+  path: &str,
+  current: Option<&Value>,
+  desired: Option<&Value>,
+  diffs: &mut Vec<StateDiff>,
+) {
+  let current = current.filter(|value| !value.is_null());
+  let desired = desired.filter(|value| !value.is_null());
+  match (current, desired) {
+    (Some(Value::Object(current)), Some(Value::Object(desired))) => {
+      let mut keys = current.keys().chain(desired.keys()).collect::<Vec<_>>();
+      keys.sort();
+      keys.dedup();
+      for key in keys {
+        let path = if path.is_empty() {
+          key.to_owned()
+        } else {
+          format!("{path}.{key}")
+        };
+        diff_value(&path, current.get(key), desired.get(key), diffs);
+      }
+    }
+    (current, desired) if current != desired => {
+      diffs.push(StateDiff {
+        path: path.to_owned(),
+        old: current.cloned(),
+        new: desired.cloned(),
+      });
+    }
+    _ => {}
+  }
+}
+
+/// ## Diff
+///
+/// Compute the field level changes between the current and the desired config
+///
+/// ## Arguments
+///
+/// - [current](Serialize) - The current config
+/// - [desired](Serialize) - The desired config
+///
+/// ## Returns
+///
+/// - [Vec](Vec<StateDiff>) - The list of changed fields
+///
+pub fn diff<T>(current: &T, desired: &T) -> Vec<StateDiff>
+where
+  T: Serialize,
+{
+  let current = serde_json::to_value(current).unwrap_or_default();
+  let desired = serde_json::to_value(desired).unwrap_or_default();
+  let mut diffs = Vec::new();
+  diff_value("", Some(&current), Some(&desired), &mut diffs);
+  diffs
+}
+
+/// ## Parse Meta
+///
+/// Parse the metadata of the state payload
+///
+/// ## Arguments
+///
+/// - [data](serde_json::Value) - The state payload
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](StateMeta) - The state metadata
+///   - [Err](HttpError) - An http response error if something went wrong
+///
+pub fn parse_meta(data: &serde_json::Value) -> Result<StateMeta, HttpError> {
+  serde_json::from_value::<StateMeta>(data.to_owned()).map_err(|err| {
+    HttpError {
+      status: http::StatusCode::BAD_REQUEST,
+      msg: format!("unable to serialize payload {err}"),
+    }
+  })
+}
+
 /// ## Parse State
 ///
 /// Parse the state payload and return the data
@@ -74,13 +163,7 @@ fn send(
 ///   - [Err](HttpError) - An http response error if something went wrong
 ///
 pub fn parse_state(data: &serde_json::Value) -> Result<StateData, HttpError> {
-  let meta =
-    serde_json::from_value::<StateMeta>(data.to_owned()).map_err(|err| {
-      HttpError {
-        status: http::StatusCode::BAD_REQUEST,
-        msg: format!("unable to serialize payload {err}"),
-      }
-    })?;
+  let meta = parse_meta(data)?;
   match meta.kind.as_str() {
     "Deployment" => {
       let data = serde_json::from_value::<StateDeployment>(data.to_owned())
@@ -122,6 +205,16 @@ pub fn parse_state(data: &serde_json::Value) -> Result<StateData, HttpError> {
         })?;
       Ok(StateData::Secret(data))
     }
+    "Job" => {
+      let data =
+        serde_json::from_value::<StateJob>(data.to_owned()).map_err(|err| {
+          HttpError {
+            status: http::StatusCode::BAD_REQUEST,
+            msg: format!("unable to serialize payload {err}"),
+          }
+        })?;
+      Ok(StateData::Job(data))
+    }
     _ => Err(HttpError {
       status: http::StatusCode::BAD_REQUEST,
       msg: format!("Unknown Statefile Kind: {}", meta.kind),
@@ -129,6 +222,94 @@ pub fn parse_state(data: &serde_json::Value) -> Result<StateData, HttpError> {
   }
 }
 
+/// ## Apply secret item
+///
+/// Create a secret or update it if it's not up to date
+///
+/// ## Arguments
+///
+/// - [secret](SecretPartial) - The secret to apply
+/// - [dry_run](bool) - Only report what would be changed
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [bool](bool) - Whether the secret is applied or up to date
+///
+async fn apply_secret_item(
+  secret: &SecretPartial,
+  dry_run: bool,
+  state: &DaemonState,
+  sx: &mpsc::Sender<Result<Bytes, HttpError>>,
+) -> bool {
+  let key = secret.key.to_owned();
+  send(StateStream::new_secret_pending(&key), sx);
+  match utils::secret::inspect_by_key(&key, state).await {
+    Ok(existing) => {
+      let existing: SecretPartial = existing.clone().into();
+      if existing == *secret {
+        send(StateStream::new_secret_unchanged(&key), sx);
+        return true;
+      }
+      if dry_run {
+        // Never send the values of a secret back
+        let diff = diff(&existing, secret)
+          .into_iter()
+          .map(|diff| StateDiff {
+            old: diff.old.map(|_| Value::String("<redacted>".into())),
+            new: diff.new.map(|_| Value::String("<redacted>".into())),
+            ..diff
+          })
+          .collect();
+        send(StateStream::new_update("Secret", &key, diff), sx);
+        return true;
+      }
+      if let Err(err) = utils::secret::patch_by_key(
+        &key,
+        &SecretUpdate {
+          data: secret.data.to_owned(),
+          metadata: secret.metadata.to_owned(),
+        },
+        state,
+      )
+      .await
+      {
+        send(StateStream::new_secret_error(&key, &err.to_string()), sx);
+        return false;
+      }
+    }
+    Err(err) if err.status == http::StatusCode::NOT_FOUND => {
+      if dry_run {
+        send(StateStream::new_create("Secret", &key), sx);
+        return true;
+      }
+      if let Err(err) = utils::secret::create(secret, state).await {
+        send(StateStream::new_secret_error(&key, &err.to_string()), sx);
+        return false;
+      }
+    }
+    // The secret exists but can't be read, e.g. his master key is missing
+    Err(err) => {
+      send(StateStream::new_secret_error(&key, &err.to_string()), sx);
+      return false;
+    }
+  };
+  let key_ptr = key.clone();
+  let state_ptr = state.clone();
+  rt::spawn(async move {
+    let secret = utils::secret::inspect_by_key(&key_ptr, &state_ptr)
+      .await
+      .unwrap();
+    let _ = state_ptr
+      .event_emitter
+      .emit(Event::SecretPatched(Box::new(secret.into())))
+      .await;
+  });
+  send(StateStream::new_secret_success(&key), sx);
+  true
+}
+
 /// ## Apply Secret
 ///
 /// Apply the list of secrets to the system.
@@ -138,205 +319,536 @@ pub fn parse_state(data: &serde_json::Value) -> Result<StateData, HttpError> {
 /// ## Arguments
 ///
 /// - [data](Vec<SecretPartial>) - The list of secrets to apply
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
+/// ## Returns
+///
+/// - [Vec](Vec<(String, String)>) - The kind and key of the applied secrets
+///
 async fn apply_secrets(
   data: &[SecretPartial],
+  dry_run: bool,
   state: &DaemonState,
   sx: &mpsc::Sender<Result<Bytes, HttpError>>,
-) {
+) -> Vec<(String, String)> {
   data
     .iter()
     .map(|secret| async {
-      let key = secret.key.to_owned();
-      send(StateStream::new_secret_pending(&key), sx);
-      match repositories::secret::find_by_key(&key, &state.pool).await {
-        Ok(existing) => {
-          let existing: SecretPartial = existing.clone().into();
-          if existing == *secret {
-            send(StateStream::new_secret_unchanged(&key), sx);
-            return;
-          }
-          if let Err(err) = repositories::secret::update_by_key(
-            &key,
-            &SecretUpdate {
-              data: secret.data.to_owned(),
-              metadata: secret.metadata.to_owned(),
-            },
-            &state.pool,
-          )
-          .await
-          {
-            send(StateStream::new_secret_error(&key, &err.to_string()), sx);
-            return;
-          }
-        }
-        Err(_err) => {
-          if let Err(err) =
-            repositories::secret::create(secret, &state.pool).await
-          {
-            send(StateStream::new_secret_error(&key, &err.to_string()), sx);
-            return;
-          }
-        }
-      };
-      let key_ptr = key.clone();
-      let state_ptr = state.clone();
-      rt::spawn(async move {
-        let secret =
-          repositories::secret::find_by_key(&key_ptr, &state_ptr.pool)
-            .await
-            .unwrap();
-        let _ = state_ptr
-          .event_emitter
-          .emit(Event::SecretPatched(Box::new(secret.into())))
-          .await;
-      });
-      send(StateStream::new_secret_success(&key), sx);
+      let applied = apply_secret_item(secret, dry_run, state, sx).await;
+      applied.then(|| ("Secret".to_owned(), secret.key.clone()))
     })
     .collect::<FuturesUnordered<_>>()
     .collect::<Vec<_>>()
-    .await;
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
 }
 
-/// ## Apply cargoes
+/// ## Apply job item
 ///
-/// Apply the list of cargoes to the system.
-/// It will create the cargoes if they don't exist, and start them.
-/// If they exists but are not up to date, it will update them.
+/// Create a job or update it if it's not up to date
 ///
 /// ## Arguments
 ///
-/// - [namespace](str) - The namespace name
-/// - [data](Vec<CargoConfigPartial>) - The list of cargoes to apply
-/// - [version](str) - The version of the cargoes
+/// - [job](JobPartial) - The job to apply
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
-async fn apply_cargoes(
-  namespace: &str,
-  data: &[CargoConfigPartial],
-  version: &str,
+/// ## Returns
+///
+/// - [bool](bool) - Whether the job is applied or up to date
+///
+async fn apply_job_item(
+  job: &JobPartial,
+  dry_run: bool,
   state: &DaemonState,
   sx: &mpsc::Sender<Result<Bytes, HttpError>>,
-) {
+) -> bool {
+  let key = job.name.to_owned();
+  send(StateStream::new_job_pending(&key), sx);
+  if let Err(err) = utils::job::validate(job) {
+    send(StateStream::new_job_error(&key, &err.to_string()), sx);
+    return false;
+  }
+  let res = match repositories::job::find_by_key(&key, &state.pool).await {
+    Ok(existing) => {
+      if existing.config == *job {
+        send(StateStream::new_job_unchanged(&key), sx);
+        return true;
+      }
+      if dry_run {
+        let diff = diff(&existing.config, job);
+        send(StateStream::new_update("Job", &key, diff), sx);
+        return true;
+      }
+      repositories::job::update_by_key(&key, job, &state.pool).await
+    }
+    Err(_err) => {
+      if dry_run {
+        send(StateStream::new_create("Job", &key), sx);
+        return true;
+      }
+      repositories::job::create(job, &state.pool).await
+    }
+  };
+  let job = match res {
+    Ok(job) => job,
+    Err(err) => {
+      send(StateStream::new_job_error(&key, &err.to_string()), sx);
+      return false;
+    }
+  };
+  let event_emitter = state.event_emitter.clone();
+  rt::spawn(async move {
+    let _ = event_emitter.emit(Event::JobPatched(Box::new(job))).await;
+  });
+  send(StateStream::new_job_success(&key), sx);
+  true
+}
+
+/// ## Apply jobs
+///
+/// Create or update jobs from a list of jobs
+///
+/// ## Arguments
+///
+/// - [data](Vec<JobPartial>) - The list of jobs to apply
+/// - [dry_run](bool) - Only report what would be changed
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [Vec](Vec<(String, String)>) - The kind and key of the applied jobs
+///
+async fn apply_jobs(
+  data: &[JobPartial],
+  dry_run: bool,
+  state: &DaemonState,
+  sx: &mpsc::Sender<Result<Bytes, HttpError>>,
+) -> Vec<(String, String)> {
   data
     .iter()
-    .map(|cargo| async {
-      let key = utils::key::gen_key(namespace, &cargo.name);
-      send(StateStream::new_cargo_pending(&key), sx);
-      match utils::cargo::inspect_by_key(&key, state).await {
-        Ok(existing) => {
-          let existing: CargoConfigPartial = existing.into();
-          if existing == *cargo {
-            send(StateStream::new_cargo_unchanged(&key), sx);
-            return;
-          }
-          if let Err(err) = utils::cargo::put(&key, cargo, version, state).await
-          {
-            send(StateStream::new_cargo_error(&key, &err.to_string()), sx);
-            return;
-          }
-        }
-        Err(_err) => {
-          if let Err(err) =
-            utils::cargo::create(namespace, cargo, version, state).await
-          {
-            send(StateStream::new_cargo_error(&key, &err.to_string()), sx);
-            return;
-          }
-          let res = utils::cargo::start_by_key(&key, state).await;
-          if let Err(err) = res {
-            send(StateStream::new_cargo_error(&key, &err.to_string()), sx);
-            return;
-          }
-        }
-      };
-      let key_ptr = key.clone();
-      let state_ptr = state.clone();
-      rt::spawn(async move {
-        let cargo = utils::cargo::inspect_by_key(&key_ptr, &state_ptr)
-          .await
-          .unwrap();
-        let _ = state_ptr
-          .event_emitter
-          .emit(Event::CargoPatched(Box::new(cargo)))
-          .await;
-      });
-      send(StateStream::new_cargo_success(&key), sx);
+    .map(|job| async {
+      let applied = apply_job_item(job, dry_run, state, sx).await;
+      applied.then(|| ("Job".to_owned(), job.name.clone()))
     })
     .collect::<FuturesUnordered<_>>()
     .collect::<Vec<_>>()
-    .await;
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
 }
 
-/// ## Apply VMS
+/// ## Apply cargo item
 ///
-/// This will apply a list of VMs to the system.
-/// It will create or update VMs as needed.
+/// Apply a cargo to the system.
+/// It will create the cargo if it doesn't exist, and start it.
+/// If it exists but is not up to date, it will update it.
 ///
 /// ## Arguments
 ///
-/// - [namespace](str) - The namespace to apply the VMs to
-/// - [data](Vec<VmConfigPartial>) - The VMs to apply
-/// - [version](str) - The version of the VMs
+/// - [namespace](str) - The namespace name
+/// - [cargo](CargoConfigPartial) - The cargo to apply
+/// - [version](str) - The version of the cargo
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
-pub async fn apply_vms(
+/// ## Returns
+///
+/// - [bool](bool) - Whether the cargo is applied or up to date
+///
+async fn apply_cargo_item(
   namespace: &str,
-  data: &[VmConfigPartial],
+  cargo: &CargoConfigPartial,
   version: &str,
+  dry_run: bool,
   state: &DaemonState,
   sx: &mpsc::Sender<Result<Bytes, HttpError>>,
-) {
-  data
-    .iter()
-    .map(|vm| async {
-      let key = utils::key::gen_key(namespace, &vm.name);
-      send(StateStream::new_vm_pending(&key), sx);
-      match utils::vm::inspect_by_key(&key, &state.docker_api, &state.pool)
-        .await
+) -> bool {
+  let key = utils::key::gen_key(namespace, &cargo.name);
+  match utils::cargo::inspect_by_key(&key, state).await {
+    Ok(existing) => {
+      let existing: CargoConfigPartial = existing.into();
+      if existing == *cargo {
+        send(StateStream::new_cargo_unchanged(&key), sx);
+        return true;
+      }
+      if dry_run {
+        let diff = diff(&existing, cargo);
+        send(StateStream::new_update("Cargo", &key, diff), sx);
+        return true;
+      }
+      if let Err(err) =
+        utils::cargo::put(&key, cargo, version, state, Some(sx)).await
       {
-        Ok(existing) => {
-          let existing: VmConfigPartial = existing.into();
-          let vm = VmConfigPartial {
-            disk: VmDiskConfig {
-              image: format!("{}.{}", vm.disk.image, &key),
-              size: Some(vm.disk.size.unwrap_or(20)),
-            },
-            host_config: Some(vm.host_config.clone().unwrap_or_default()),
-            ..vm.clone()
-          };
-          if existing == vm {
-            send(StateStream::new_vm_unchanged(&key), sx);
-            return;
-          }
-          if let Err(err) = utils::vm::put(&key, &vm, version, state).await {
-            send(StateStream::new_vm_error(&key, &err.to_string()), sx);
-            return;
-          }
+        send(StateStream::new_cargo_error(&key, &err.to_string()), sx);
+        return false;
+      }
+    }
+    Err(_err) => {
+      if dry_run {
+        send(StateStream::new_create("Cargo", &key), sx);
+        return true;
+      }
+      if let Err(err) =
+        utils::cargo::create(namespace, cargo, version, state).await
+      {
+        send(StateStream::new_cargo_error(&key, &err.to_string()), sx);
+        return false;
+      }
+      let res = utils::cargo::start_by_key(&key, state).await;
+      if let Err(err) = res {
+        send(StateStream::new_cargo_error(&key, &err.to_string()), sx);
+        return false;
+      }
+    }
+  };
+  let key_ptr = key.clone();
+  let state_ptr = state.clone();
+  rt::spawn(async move {
+    let cargo = utils::cargo::inspect_by_key(&key_ptr, &state_ptr)
+      .await
+      .unwrap();
+    let _ = state_ptr
+      .event_emitter
+      .emit(Event::CargoPatched(Box::new(cargo)))
+      .await;
+  });
+  send(StateStream::new_cargo_success(&key), sx);
+  true
+}
+
+/// ## Apply VM item
+///
+/// Apply a VM to the system.
+/// It will create the VM if it doesn't exist, and start it.
+/// If it exists but is not up to date, it will update it.
+///
+/// ## Arguments
+///
+/// - [namespace](str) - The namespace to apply the VM to
+/// - [vm](VmConfigPartial) - The VM to apply
+/// - [version](str) - The version of the VM
+/// - [dry_run](bool) - Only report what would be changed
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [bool](bool) - Whether the VM is applied or up to date
+///
+async fn apply_vm_item(
+  namespace: &str,
+  vm: &VmConfigPartial,
+  version: &str,
+  dry_run: bool,
+  state: &DaemonState,
+  sx: &mpsc::Sender<Result<Bytes, HttpError>>,
+) -> bool {
+  let key = utils::key::gen_key(namespace, &vm.name);
+  match utils::vm::inspect_by_key(&key, &state.docker_api, &state.pool).await {
+    Ok(existing) => {
+      let existing: VmConfigPartial = existing.into();
+      let vm = VmConfigPartial {
+        disk: VmDiskConfig {
+          image: format!("{}.{}", vm.disk.image, &key),
+          size: Some(vm.disk.size.unwrap_or(20)),
+        },
+        host_config: Some(vm.host_config.clone().unwrap_or_default()),
+        ..vm.clone()
+      };
+      if existing == vm {
+        send(StateStream::new_vm_unchanged(&key), sx);
+        return true;
+      }
+      if dry_run {
+        let diff = diff(&existing, &vm);
+        send(StateStream::new_update("VirtualMachine", &key, diff), sx);
+        return true;
+      }
+      if let Err(err) = utils::vm::put(&key, &vm, version, state).await {
+        send(StateStream::new_vm_error(&key, &err.to_string()), sx);
+        return false;
+      }
+    }
+    Err(_err) => {
+      if dry_run {
+        send(StateStream::new_create("VirtualMachine", &key), sx);
+        return true;
+      }
+      if let Err(err) = utils::vm::create(vm, namespace, version, state).await {
+        send(StateStream::new_vm_error(&key, &err.to_string()), sx);
+        return false;
+      }
+      let res = utils::vm::start_by_key(&key, &state.docker_api).await;
+      if let Err(err) = res {
+        send(StateStream::new_vm_error(&key, &err.to_string()), sx);
+        return false;
+      }
+    }
+  };
+  send(StateStream::new_vm_success(&key), sx);
+  true
+}
+
+/// ## Apply workload
+///
+/// Wait for the dependencies of a cargo or a VM to reach their condition
+/// then apply it. Nothing is awaited on a dry run.
+///
+/// ## Arguments
+///
+/// - [graph](DependencyGraph) - The dependency graph of the Statefile
+/// - [index](usize) - The index of the workload to apply
+/// - [dependency_failed](bool) - Whether a dependency has not been applied
+/// - [version](str) - The version of the workload
+/// - [dry_run](bool) - Only report what would be changed
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [bool](bool) - Whether the workload is applied or up to date
+///
+async fn apply_workload(
+  graph: &DependencyGraph<'_>,
+  index: usize,
+  dependency_failed: bool,
+  version: &str,
+  dry_run: bool,
+  state: &DaemonState,
+  sx: &mpsc::Sender<Result<Bytes, HttpError>>,
+) -> bool {
+  let workload = graph.workloads[index];
+  let key = graph.key(index);
+  if dependency_failed {
+    let err = "A dependency has not been applied";
+    send(StateStream::new_error(workload.kind(), &key, err), sx);
+    return false;
+  }
+  match workload {
+    Workload::Cargo(_) => send(StateStream::new_cargo_pending(&key), sx),
+    Workload::VirtualMachine(_) => send(StateStream::new_vm_pending(&key), sx),
+  }
+  if !dry_run {
+    let res = graph.dependencies[index]
+      .iter()
+      .map(|(dependency_index, dependency)| {
+        utils::dependency::wait_dependency(
+          graph,
+          *dependency_index,
+          dependency,
+          state,
+        )
+      })
+      .collect::<FuturesUnordered<_>>()
+      .collect::<Vec<_>>()
+      .await
+      .into_iter()
+      .collect::<Result<Vec<_>, _>>();
+    if let Err(err) = res {
+      let err = format!("Dependency not ready: {err}");
+      send(StateStream::new_error(workload.kind(), &key, &err), sx);
+      return false;
+    }
+  }
+  match workload {
+    Workload::Cargo(cargo) if graph.is_one_shot(index) => {
+      let cargo = utils::dependency::one_shot_cargo(cargo);
+      apply_cargo_item(&graph.namespace, &cargo, version, dry_run, state, sx)
+        .await
+    }
+    Workload::Cargo(cargo) => {
+      apply_cargo_item(&graph.namespace, cargo, version, dry_run, state, sx)
+        .await
+    }
+    Workload::VirtualMachine(vm) => {
+      apply_vm_item(&graph.namespace, vm, version, dry_run, state, sx).await
+    }
+  }
+}
+
+/// ## Apply workloads
+///
+/// Apply the cargoes and the VMs of a Statefile in the order of their dependencies.
+/// A workload is applied as soon as all its dependencies reached their condition,
+/// independent workloads are applied in parallel.
+/// Workloads depending on a workload that failed are not applied.
+///
+/// ## Arguments
+///
+/// - [namespace](str) - The namespace name
+/// - [cargoes](Vec<CargoConfigPartial>) - The list of cargoes to apply
+/// - [vms](Vec<VmConfigPartial>) - The list of VMs to apply
+/// - [version](str) - The version of the workloads
+/// - [dry_run](bool) - Only report what would be changed
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<(String, String)>) - The kind and key of the applied workloads
+///   - [Err](HttpError) - A dependency is unknown or there is a cycle
+///
+async fn apply_workloads(
+  namespace: &str,
+  cargoes: &[CargoConfigPartial],
+  vms: &[VmConfigPartial],
+  version: &str,
+  dry_run: bool,
+  state: &DaemonState,
+  sx: &mpsc::Sender<Result<Bytes, HttpError>>,
+) -> Result<Vec<(String, String)>, HttpError> {
+  let graph = utils::dependency::build_graph(namespace, cargoes, vms)?;
+  let mut remaining = graph
+    .dependencies
+    .iter()
+    .map(|dependencies| dependencies.len())
+    .collect::<Vec<_>>();
+  let mut dependency_failed = vec![false; graph.workloads.len()];
+  let apply = |index: usize, dependency_failed: bool| {
+    let graph = &graph;
+    async move {
+      let applied = apply_workload(
+        graph,
+        index,
+        dependency_failed,
+        version,
+        dry_run,
+        state,
+        sx,
+      )
+      .await;
+      (index, applied)
+    }
+  };
+  let mut running = remaining
+    .iter()
+    .enumerate()
+    .filter(|(_, count)| **count == 0)
+    .map(|(index, _)| apply(index, false))
+    .collect::<FuturesUnordered<_>>();
+  let mut refs = Vec::new();
+  while let Some((index, applied)) = running.next().await {
+    if applied {
+      let kind = graph.workloads[index].kind().to_owned();
+      refs.push((kind, graph.key(index)));
+    }
+    for dependent in &graph.dependents[index] {
+      dependency_failed[*dependent] |= !applied;
+      remaining[*dependent] -= 1;
+      if remaining[*dependent] == 0 {
+        running.push(apply(*dependent, dependency_failed[*dependent]));
+      }
+    }
+  }
+  Ok(refs)
+}
+
+/// ## Validate dependencies
+///
+/// Ensure the dependencies of the cargoes and VMs of a Statefile
+/// are declared and do not form a cycle
+///
+/// ## Arguments
+///
+/// - [data](StateData) - The state data
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The dependencies are valid
+///   - [Err](HttpError) - A dependency is unknown or there is a cycle
+///
+pub fn validate_dependencies(data: &StateData) -> Result<(), HttpError> {
+  match data {
+    StateData::Deployment(data) => {
+      utils::dependency::build_graph(
+        "global",
+        data.cargoes.as_deref().unwrap_or_default(),
+        data.virtual_machines.as_deref().unwrap_or_default(),
+      )?;
+    }
+    StateData::Cargo(data) => {
+      utils::dependency::build_graph("global", &data.cargoes, &[])?;
+    }
+    StateData::VirtualMachine(data) => {
+      utils::dependency::build_graph("global", &[], &data.virtual_machines)?;
+    }
+    _ => {}
+  }
+  Ok(())
+}
+
+/// ## Apply resource item
+///
+/// Create a resource or update it if it's not up to date
+///
+/// ## Arguments
+///
+/// - [resource](ResourcePartial) - The resource to apply
+/// - [dry_run](bool) - Only report what would be changed
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [bool](bool) - Whether the resource is applied or up to date
+///
+async fn apply_resource_item(
+  resource: &ResourcePartial,
+  dry_run: bool,
+  state: &DaemonState,
+  sx: &mpsc::Sender<Result<Bytes, HttpError>>,
+) -> bool {
+  let key = resource.name.to_owned();
+  send(StateStream::new_resource_pending(&key), sx);
+  let res =
+    match repositories::resource::inspect_by_key(&key, &state.pool).await {
+      Err(_) if dry_run => {
+        send(StateStream::new_create("Resource", &key), sx);
+        return true;
+      }
+      Err(_) => utils::resource::create(resource, &state.pool).await,
+      Ok(cur_resource) => {
+        let casted: ResourcePartial = cur_resource.into();
+        if *resource == casted {
+          send(StateStream::new_resource_unchanged(&key), sx);
+          return true;
         }
-        Err(_err) => {
-          if let Err(err) =
-            utils::vm::create(vm, namespace, version, state).await
-          {
-            send(StateStream::new_vm_error(&key, &err.to_string()), sx);
-            return;
-          }
-          let res = utils::vm::start_by_key(&key, &state.docker_api).await;
-          if let Err(err) = res {
-            send(StateStream::new_vm_error(&key, &err.to_string()), sx);
-            return;
-          }
+        if dry_run {
+          let diff = diff(&casted, resource);
+          send(StateStream::new_update("Resource", &key, diff), sx);
+          return true;
         }
-      };
-      send(StateStream::new_vm_success(&key), sx);
-    })
-    .collect::<FuturesUnordered<_>>()
-    .collect::<Vec<_>>()
-    .await;
+        utils::resource::patch(&resource.clone(), &state.pool).await
+      }
+    };
+  if let Err(err) = res {
+    send(StateStream::new_resource_error(&key, &err.to_string()), sx);
+    return false;
+  }
+  let key_ptr = key.to_owned();
+  let pool_ptr = state.pool.clone();
+  let event_emitter = state.event_emitter.clone();
+  rt::spawn(async move {
+    let resource = repositories::resource::inspect_by_key(&key_ptr, &pool_ptr)
+      .await
+      .unwrap();
+    let _ = event_emitter
+      .emit(Event::ResourcePatched(Box::new(resource)))
+      .await;
+  });
+  send(StateStream::new_resource_success(&key), sx);
+  true
 }
 
 /// ## Apply resources
@@ -347,58 +859,32 @@ pub async fn apply_vms(
 /// ## Arguments
 ///
 /// - [data](Vec<ResourcePartial>) - The list of resources to apply
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
 /// ## Returns
 ///
-/// - [Result](Result) - The result of the operation
-///   - [Ok](()) - The operation was successful
-///   - [Err](HttpError) - An http response error if something went wrong
+/// - [Vec](Vec<(String, String)>) - The kind and key of the applied resources
 ///
 async fn apply_resources(
   data: &[ResourcePartial],
+  dry_run: bool,
   state: &DaemonState,
   sx: &mpsc::Sender<Result<Bytes, HttpError>>,
-) {
+) -> Vec<(String, String)> {
   data
     .iter()
     .map(|resource| async {
-      let key = resource.name.to_owned();
-      send(StateStream::new_resource_pending(&key), sx);
-      let res =
-        match repositories::resource::inspect_by_key(&key, &state.pool).await {
-          Err(_) => utils::resource::create(resource, &state.pool).await,
-          Ok(cur_resource) => {
-            let casted: ResourcePartial = cur_resource.into();
-            if *resource == casted {
-              send(StateStream::new_resource_unchanged(&key), sx);
-              return;
-            }
-            utils::resource::patch(&resource.clone(), &state.pool).await
-          }
-        };
-      if let Err(err) = res {
-        send(StateStream::new_resource_error(&key, &err.to_string()), sx);
-        return;
-      }
-      let key_ptr = key.to_owned();
-      let pool_ptr = state.pool.clone();
-      let event_emitter = state.event_emitter.clone();
-      rt::spawn(async move {
-        let resource =
-          repositories::resource::inspect_by_key(&key_ptr, &pool_ptr)
-            .await
-            .unwrap();
-        let _ = event_emitter
-          .emit(Event::ResourcePatched(Box::new(resource)))
-          .await;
-      });
-      send(StateStream::new_resource_success(&key), sx);
+      let applied = apply_resource_item(resource, dry_run, state, sx).await;
+      applied.then(|| ("Resource".to_owned(), resource.name.clone()))
     })
     .collect::<FuturesUnordered<_>>()
     .collect::<Vec<_>>()
-    .await;
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
 }
 
 /// ## Remove secrets
@@ -427,20 +913,22 @@ async fn remove_secrets(
     .map(|secret| async {
       let key = secret.key.to_owned();
       send(StateStream::new_secret_pending(&key), sx);
-      let secret =
-        match repositories::secret::find_by_key(&key, &state.pool).await {
-          Ok(secret) => secret,
-          Err(_) => {
-            send(StateStream::new_secret_not_found(&key), sx);
-            return;
-          }
-        };
+      let secret = match utils::secret::inspect_by_key(&key, state).await {
+        Ok(secret) => secret,
+        Err(_) => {
+          send(StateStream::new_secret_not_found(&key), sx);
+          return;
+        }
+      };
       if let Err(err) =
         repositories::secret::delete_by_key(&secret.key, &state.pool).await
       {
         send(StateStream::new_secret_error(&key, &err.to_string()), sx);
         return;
       }
+      let _ =
+        repositories::state_ref::delete_by_key("Secret", &key, &state.pool)
+          .await;
       let secret_ptr = secret.clone();
       let event_emitter = state.event_emitter.clone();
       rt::spawn(async move {
@@ -454,6 +942,47 @@ async fn remove_secrets(
     .collect::<Vec<_>>()
     .await;
 }
+/// ## Remove jobs
+///
+/// Delete jobs from the system based on a list of jobs
+///
+/// ## Arguments
+///
+/// - [data](Vec<JobPartial>) - The list of jobs to delete
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+async fn remove_jobs(
+  data: &[JobPartial],
+  state: &DaemonState,
+  sx: &mpsc::Sender<Result<Bytes, HttpError>>,
+) {
+  data
+    .iter()
+    .map(|job| async {
+      let key = job.name.to_owned();
+      send(StateStream::new_job_pending(&key), sx);
+      let job = match repositories::job::find_by_key(&key, &state.pool).await {
+        Ok(job) => job,
+        Err(_) => {
+          send(StateStream::new_job_not_found(&key), sx);
+          return;
+        }
+      };
+      if let Err(err) = utils::job::delete_by_key(&key, state).await {
+        send(StateStream::new_job_error(&key, &err.to_string()), sx);
+        return;
+      }
+      let event_emitter = state.event_emitter.clone();
+      rt::spawn(async move {
+        let _ = event_emitter.emit(Event::JobDeleted(Box::new(job))).await;
+      });
+      send(StateStream::new_job_success(&key), sx);
+    })
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<_>>()
+    .await;
+}
 
 /// ## Remove cargoes
 ///
@@ -618,40 +1147,53 @@ async fn remove_resources(
 ///
 /// - [data](StateDeployment) - The deployment statefile
 /// - [version](str) - The version of the deployment
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
 /// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](()) - The operation was successful
+///   - [Ok](Vec<(String, String)>) - The kind and key of the applied objects
 ///   - [Err](HttpError) - An http response error if something went wrong
 ///
 pub async fn apply_deployment(
   data: &StateDeployment,
   version: &str,
+  dry_run: bool,
   state: &DaemonState,
   sx: mpsc::Sender<Result<Bytes, HttpError>>,
-) -> Result<(), HttpError> {
+) -> Result<Vec<(String, String)>, HttpError> {
   let namespace = if let Some(namespace) = &data.namespace {
-    utils::namespace::create_if_not_exists(namespace, state).await?;
+    if !dry_run {
+      utils::namespace::create_if_not_exists(namespace, state).await?;
+    }
     namespace.to_owned()
   } else {
     "global".into()
   };
+  let mut refs = Vec::new();
   if let Some(secrets) = &data.secrets {
-    apply_secrets(secrets, state, &sx).await;
-  }
-  if let Some(cargoes) = &data.cargoes {
-    apply_cargoes(&namespace, cargoes, version, state, &sx).await;
+    refs.extend(apply_secrets(secrets, dry_run, state, &sx).await);
   }
-  if let Some(vms) = &data.virtual_machines {
-    apply_vms(&namespace, vms, version, state, &sx).await;
+  let workload_refs = apply_workloads(
+    &namespace,
+    data.cargoes.as_deref().unwrap_or_default(),
+    data.virtual_machines.as_deref().unwrap_or_default(),
+    version,
+    dry_run,
+    state,
+    &sx,
+  )
+  .await?;
+  refs.extend(workload_refs);
+  if let Some(jobs) = &data.jobs {
+    refs.extend(apply_jobs(jobs, dry_run, state, &sx).await);
   }
   if let Some(resources) = &data.resources {
-    apply_resources(resources, state, &sx).await;
+    refs.extend(apply_resources(resources, dry_run, state, &sx).await);
   }
-  Ok(())
+  Ok(refs)
 }
 
 /// ## Apply Cargo
@@ -663,29 +1205,33 @@ pub async fn apply_deployment(
 ///
 /// - [data](StateCargo) - The cargo statefile
 /// - [version](str) - The version of the cargo
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
 /// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](()) - The operation was successful
+///   - [Ok](Vec<(String, String)>) - The kind and key of the applied objects
 ///   - [Err](HttpError) - An http response error if something went wrong
 ///
 pub async fn apply_cargo(
   data: &StateCargo,
   version: &str,
+  dry_run: bool,
   state: &DaemonState,
   sx: mpsc::Sender<Result<Bytes, HttpError>>,
-) -> Result<(), HttpError> {
+) -> Result<Vec<(String, String)>, HttpError> {
   let namespace = if let Some(namespace) = &data.namespace {
-    utils::namespace::create_if_not_exists(namespace, state).await?;
+    if !dry_run {
+      utils::namespace::create_if_not_exists(namespace, state).await?;
+    }
     namespace.to_owned()
   } else {
     "global".into()
   };
-  apply_cargoes(&namespace, &data.cargoes, version, state, &sx).await;
-  Ok(())
+  apply_workloads(&namespace, &data.cargoes, &[], version, dry_run, state, &sx)
+    .await
 }
 
 /// ## Apply VM
@@ -697,29 +1243,41 @@ pub async fn apply_cargo(
 ///
 /// - [data](StateVirtualMachine) - The VM statefile data
 /// - [version](str) - The version of the VMs
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
 /// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](()) - The operation was successful
+///   - [Ok](Vec<(String, String)>) - The kind and key of the applied objects
 ///   - [Err](HttpError) - An http response error if something went wrong
 ///
 pub async fn apply_vm(
   data: &StateVirtualMachine,
   version: &str,
+  dry_run: bool,
   state: &DaemonState,
   sx: mpsc::Sender<Result<Bytes, HttpError>>,
-) -> Result<(), HttpError> {
+) -> Result<Vec<(String, String)>, HttpError> {
   let namespace = if let Some(namespace) = &data.namespace {
-    utils::namespace::create_if_not_exists(namespace, state).await?;
+    if !dry_run {
+      utils::namespace::create_if_not_exists(namespace, state).await?;
+    }
     namespace.to_owned()
   } else {
     "global".into()
   };
-  apply_vms(&namespace, &data.virtual_machines, version, state, &sx).await;
-  Ok(())
+  apply_workloads(
+    &namespace,
+    &[],
+    &data.virtual_machines,
+    version,
+    dry_run,
+    state,
+    &sx,
+  )
+  .await
 }
 
 /// ## Apply Resource
@@ -730,22 +1288,23 @@ pub async fn apply_vm(
 /// ## Arguments
 ///
 /// - [data](StateResource) - The resource statefile
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
 /// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](()) - The operation was successful
+///   - [Ok](Vec<(String, String)>) - The kind and key of the applied objects
 ///   - [Err](HttpError) - An http response error if something went wrong
 ///
 pub async fn apply_resource(
   data: &StateResource,
+  dry_run: bool,
   state: &DaemonState,
   sx: mpsc::Sender<Result<Bytes, HttpError>>,
-) -> Result<(), HttpError> {
-  apply_resources(&data.resources, state, &sx).await;
-  Ok(())
+) -> Result<Vec<(String, String)>, HttpError> {
+  Ok(apply_resources(&data.resources, dry_run, state, &sx).await)
 }
 
 /// ## Apply Secret
@@ -756,22 +1315,49 @@ pub async fn apply_resource(
 /// ## Arguments
 ///
 /// - [data](StateSecret) - The secret Statefile
+/// - [dry_run](bool) - Only report what would be changed
 /// - [state](DaemonState) - The system state
 /// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
 ///
 /// ## Returns
 ///
 /// - [Result](Result) - The result of the operation
-///   - [Ok](()) - The operation was successful
+///   - [Ok](Vec<(String, String)>) - The kind and key of the applied objects
 ///   - [Err](HttpError) - An http response error if something went wrong
 ///
 pub async fn apply_secret(
   data: &StateSecret,
+  dry_run: bool,
   state: &DaemonState,
   sx: mpsc::Sender<Result<Bytes, HttpError>>,
-) -> Result<(), HttpError> {
-  apply_secrets(&data.secrets, state, &sx).await;
-  Ok(())
+) -> Result<Vec<(String, String)>, HttpError> {
+  Ok(apply_secrets(&data.secrets, dry_run, state, &sx).await)
+}
+/// ## Apply Job
+///
+/// Apply a Statefile Kind Job to the system.
+/// It will create jobs or update them if they are not up to date.
+///
+/// ## Arguments
+///
+/// - [data](StateJob) - The job Statefile
+/// - [dry_run](bool) - Only report what would be changed
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](Vec<(String, String)>) - The kind and key of the applied objects
+///   - [Err](HttpError) - An http response error if something went wrong
+///
+pub async fn apply_job(
+  data: &StateJob,
+  dry_run: bool,
+  state: &DaemonState,
+  sx: mpsc::Sender<Result<Bytes, HttpError>>,
+) -> Result<Vec<(String, String)>, HttpError> {
+  Ok(apply_jobs(&data.jobs, dry_run, state, &sx).await)
 }
 
 /// ## Remove Deployment
@@ -800,6 +1386,9 @@ pub async fn remove_deployment(
   } else {
     "global".into()
   };
+  if let Some(jobs) = &data.jobs {
+    remove_jobs(jobs, state, &sx).await;
+  }
   if let Some(cargoes) = &data.cargoes {
     remove_cargoes(&namespace, cargoes, state, &sx).await;
   }
@@ -924,3 +1513,275 @@ pub async fn remove_secret(
   remove_secrets(&data.secrets, state, &sx).await;
   Ok(())
 }
+/// ## Remove job
+///
+/// This will remove all content of a Kind Job Statefile from the system.
+///
+/// ## Arguments
+///
+/// - [data](StateJob) - The job statefile data
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The operation was successful
+///   - [Err](HttpError) - An http response error if something went wrong
+///
+pub async fn remove_job(
+  data: &StateJob,
+  state: &DaemonState,
+  sx: mpsc::Sender<Result<Bytes, HttpError>>,
+) -> Result<(), HttpError> {
+  remove_jobs(&data.jobs, state, &sx).await;
+  Ok(())
+}
+
+/// ## Gen namespaced refs
+///
+/// Generate the (kind, key) pairs of objects living in a namespace
+///
+fn gen_namespaced_refs<'a>(
+  kind: &str,
+  namespace: &Option<String>,
+  names: impl Iterator<Item = &'a String>,
+) -> Vec<(String, String)> {
+  let namespace = namespace.as_deref().unwrap_or("global");
+  names
+    .map(|name| (kind.to_owned(), utils::key::gen_key(namespace, name)))
+    .collect()
+}
+
+/// ## Gen refs
+///
+/// Generate the list of objects declared in a Statefile as (kind, key) pairs
+///
+/// ## Arguments
+///
+/// - [data](StateData) - The state data
+///
+/// ## Returns
+///
+/// - [Vec](Vec<(String, String)>) - The kind and key of the declared objects
+///
+pub fn gen_refs(data: &StateData) -> Vec<(String, String)> {
+  match data {
+    StateData::Deployment(data) => {
+      let mut refs = Vec::new();
+      if let Some(cargoes) = &data.cargoes {
+        let names = cargoes.iter().map(|cargo| &cargo.name);
+        refs.extend(gen_namespaced_refs("Cargo", &data.namespace, names));
+      }
+      if let Some(vms) = &data.virtual_machines {
+        let names = vms.iter().map(|vm| &vm.name);
+        refs.extend(gen_namespaced_refs(
+          "VirtualMachine",
+          &data.namespace,
+          names,
+        ));
+      }
+      if let Some(resources) = &data.resources {
+        refs.extend(
+          resources
+            .iter()
+            .map(|resource| ("Resource".to_owned(), resource.name.clone())),
+        );
+      }
+      if let Some(secrets) = &data.secrets {
+        refs.extend(
+          secrets
+            .iter()
+            .map(|secret| ("Secret".to_owned(), secret.key.clone())),
+        );
+      }
+      if let Some(jobs) = &data.jobs {
+        refs
+          .extend(jobs.iter().map(|job| ("Job".to_owned(), job.name.clone())));
+      }
+      refs
+    }
+    StateData::Cargo(data) => {
+      let names = data.cargoes.iter().map(|cargo| &cargo.name);
+      gen_namespaced_refs("Cargo", &data.namespace, names)
+    }
+    StateData::VirtualMachine(data) => {
+      let names = data.virtual_machines.iter().map(|vm| &vm.name);
+      gen_namespaced_refs("VirtualMachine", &data.namespace, names)
+    }
+    StateData::Resource(data) => data
+      .resources
+      .iter()
+      .map(|resource| ("Resource".to_owned(), resource.name.clone()))
+      .collect(),
+    StateData::Secret(data) => data
+      .secrets
+      .iter()
+      .map(|secret| ("Secret".to_owned(), secret.key.clone()))
+      .collect(),
+    StateData::Job(data) => data
+      .jobs
+      .iter()
+      .map(|job| ("Job".to_owned(), job.name.clone()))
+      .collect(),
+  }
+}
+
+/// ## Prune ref
+///
+/// Delete an object no longer declared in his Statefile and his state ref.
+/// Objects already deleted are considered as pruned.
+///
+/// ## Arguments
+///
+/// - [item](StateRefDbModel) - The state ref of the object
+/// - [state](DaemonState) - The system state
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The object has been pruned
+///   - [Err](HttpError) - An http response error if something went wrong
+///
+async fn prune_ref(
+  item: &StateRefDbModel,
+  state: &DaemonState,
+) -> Result<(), HttpError> {
+  match item.kind.as_str() {
+    "Cargo" => {
+      if let Ok(cargo) = utils::cargo::inspect_by_key(&item.key, state).await {
+        utils::cargo::delete_by_key(&item.key, Some(true), state).await?;
+        let _ = state
+          .event_emitter
+          .emit(Event::CargoDeleted(Box::new(cargo)))
+          .await;
+      }
+    }
+    "VirtualMachine" => {
+      let res =
+        utils::vm::inspect_by_key(&item.key, &state.docker_api, &state.pool)
+          .await;
+      if res.is_ok() {
+        utils::vm::delete_by_key(
+          &item.key,
+          true,
+          &state.docker_api,
+          &state.pool,
+        )
+        .await?;
+      }
+    }
+    "Resource" => {
+      if let Ok(resource) =
+        repositories::resource::inspect_by_key(&item.key, &state.pool).await
+      {
+        utils::resource::delete(&resource, &state.pool).await?;
+        let _ = state
+          .event_emitter
+          .emit(Event::ResourceDeleted(Box::new(resource)))
+          .await;
+      }
+    }
+    "Secret" => {
+      if let Ok(secret) = utils::secret::inspect_by_key(&item.key, state).await
+      {
+        repositories::secret::delete_by_key(&item.key, &state.pool).await?;
+        let _ = state
+          .event_emitter
+          .emit(Event::SecretDeleted(Box::new(secret.into())))
+          .await;
+      }
+    }
+    "Job" => {
+      if let Ok(job) =
+        repositories::job::find_by_key(&item.key, &state.pool).await
+      {
+        utils::job::delete_by_key(&item.key, state).await?;
+        let _ = state
+          .event_emitter
+          .emit(Event::JobDeleted(Box::new(job)))
+          .await;
+      }
+    }
+    _ => {}
+  }
+  repositories::state_ref::delete_by_key(&item.kind, &item.key, &state.pool)
+    .await?;
+  Ok(())
+}
+
+/// ## Apply refs
+///
+/// Label the objects of a named Statefile successfully applied with his name.
+/// When prune is enabled the objects labeled with his name
+/// that are no longer declared are deleted.
+///
+/// ## Arguments
+///
+/// - [meta](StateMeta) - The Statefile metadata
+/// - [refs](Vec<(String, String)>) - The kind and key of the declared objects
+/// - [applied](Vec<(String, String)>) - The kind and key of the applied objects
+/// - [qs](StateApplyQuery) - The apply options
+/// - [state](DaemonState) - The system state
+/// - [sx](mpsc::Sender<Result<Bytes, HttpError>>) - The response sender
+///
+/// ## Returns
+///
+/// - [Result](Result) - The result of the operation
+///   - [Ok](()) - The operation was successful
+///   - [Err](HttpError) - An http response error if something went wrong
+///
+pub async fn apply_refs(
+  meta: &StateMeta,
+  refs: &[(String, String)],
+  applied: &[(String, String)],
+  qs: &StateApplyQuery,
+  state: &DaemonState,
+  sx: mpsc::Sender<Result<Bytes, HttpError>>,
+) -> Result<(), HttpError> {
+  let Some(state_name) = &meta.name else {
+    return Ok(());
+  };
+  let dry_run = qs.dry_run.unwrap_or_default();
+  if qs.prune.unwrap_or_default() {
+    let orphans =
+      repositories::state_ref::list_by_state_name(state_name, &state.pool)
+        .await?
+        .into_iter()
+        .filter(|item| {
+          !refs
+            .iter()
+            .any(|(kind, key)| kind == &item.kind && key == &item.key)
+        })
+        .collect::<Vec<_>>();
+    orphans
+      .iter()
+      .map(|item| async {
+        if !dry_run {
+          if let Err(err) = prune_ref(item, state).await {
+            let err = err.to_string();
+            send(StateStream::new_error(&item.kind, &item.key, &err), &sx);
+            return;
+          }
+        }
+        send(StateStream::new_pruned(&item.kind, &item.key), &sx);
+      })
+      .collect::<FuturesUnordered<_>>()
+      .collect::<Vec<_>>()
+      .await;
+  }
+  if dry_run {
+    return Ok(());
+  }
+  let items = applied
+    .iter()
+    .map(|(kind, key)| StateRefDbModel {
+      kind: kind.to_owned(),
+      key: key.to_owned(),
+      created_at: chrono::Utc::now().naive_utc(),
+      state_name: state_name.to_owned(),
+    })
+    .collect::<Vec<_>>();
+  repositories::state_ref::create_or_update(&items, &state.pool).await?;
+  Ok(())
+}
@@ -209,6 +209,8 @@ pub async fn delete_by_key(
   repositories::vm::delete_by_key(vm_key, pool).await?;
   repositories::vm_config::delete_by_vm_key(&vm.key, pool).await?;
   utils::vm_image::delete_by_name(&vm.config.disk.image, pool).await?;
+  repositories::state_ref::delete_by_key("VirtualMachine", vm_key, pool)
+    .await?;
   Ok(())
 }
 
@@ -514,6 +516,11 @@ pub async fn patch(
     } else {
       old_config.metadata
     },
+    depends_on: if config.depends_on.is_some() {
+      config.depends_on.clone()
+    } else {
+      old_config.depends_on
+    },
   };
   put(vm_key, &vm_partial, version, state).await
 }
@@ -0,0 +1,438 @@
+use std::rc::Rc;
+use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use ntex::rt;
+use ntex::rt::JoinHandle;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use trust_dns_proto::op::Message;
+
+use nanocl_utils::io_error::{FromIo, IoError, IoResult};
+
+use crate::zone::{self, Zone};
+
+/// Port of the upstream servers when not specified
+const UPSTREAM_PORT: u16 = 53;
+/// Maximum duration to wait for an upstream server to answer
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+/// Maximum size of a dns message
+const MAX_MESSAGE_SIZE: usize = 65535;
+/// Maximum duration to wait for the next query of a tcp connection
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parse the address of an upstream server with an optional port
+fn parse_upstream(upstream: &str) -> IoResult<SocketAddr> {
+  if let Ok(addr) = upstream.parse::<SocketAddr>() {
+    return Ok(addr);
+  }
+  let ip = upstream.parse::<IpAddr>().map_err(|err| {
+    IoError::invalid_input("Dns", &format!("{upstream} is invalid: {err}"))
+  })?;
+  Ok(SocketAddr::new(ip, UPSTREAM_PORT))
+}
+
+/// Forward a query to an upstream server over udp
+async fn forward_udp(
+  upstream: SocketAddr,
+  query: &[u8],
+) -> std::io::Result<Vec<u8>> {
+  let local: IpAddr = match upstream {
+    SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+    SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+  };
+  let socket = UdpSocket::bind(SocketAddr::new(local, 0)).await?;
+  socket.connect(upstream).await?;
+  socket.send(query).await?;
+  let mut buf = vec![0; MAX_MESSAGE_SIZE];
+  let len = socket.recv(&mut buf).await?;
+  buf.truncate(len);
+  Ok(buf)
+}
+
+/// Read a dns message prefixed by his length from a tcp stream
+async fn read_tcp(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+  let len = stream.read_u16().await?;
+  let mut buf = vec![0; len as usize];
+  stream.read_exact(&mut buf).await?;
+  Ok(buf)
+}
+
+/// Write a dns message prefixed by his length to a tcp stream
+async fn write_tcp(stream: &mut TcpStream, data: &[u8]) -> std::io::Result<()> {
+  stream.write_u16(data.len() as u16).await?;
+  stream.write_all(data).await?;
+  stream.flush().await
+}
+
+/// Forward a query to an upstream server over tcp
+async fn forward_tcp(
+  upstream: SocketAddr,
+  query: &[u8],
+) -> std::io::Result<Vec<u8>> {
+  let mut stream = TcpStream::connect(upstream).await?;
+  write_tcp(&mut stream, query).await?;
+  read_tcp(&mut stream).await
+}
+
+/// Truncate an udp response larger than the size accepted by the client
+/// Only the header and the queries are kept with the TC bit set
+/// so the client retry over tcp
+fn truncate_udp(request: &Message, response: Vec<u8>) -> Option<Vec<u8>> {
+  if response.len() <= request.max_payload() as usize {
+    return Some(response);
+  }
+  let mut response = Message::from_vec(&response).ok()?;
+  response.take_answers();
+  response.take_name_servers();
+  response.take_additionals();
+  response.set_truncated(true);
+  response.to_vec().ok()
+}
+
+/// Tasks answering the queries of a network on a listen address
+struct Listener {
+  /// Network whose records are answered
+  network: String,
+  /// Task answering the queries received over udp
+  udp: JoinHandle<()>,
+  /// Task accepting the tcp connections
+  tcp: JoinHandle<()>,
+}
+
+impl Listener {
+  /// Stop to answer the queries, the pending ones are still answered
+  fn stop(self) {
+    self.udp.abort();
+    self.tcp.abort();
+  }
+}
+
+/// Dns server answering from his zone and forwarding the unknown names
+/// It listen on the address of each network given by `set_listeners`
+#[derive(Clone)]
+pub struct DnsServer {
+  /// Records answered by the server
+  pub(crate) zone: Zone,
+  /// Port to listen on
+  port: u16,
+  /// Servers to resolve the names not existing in the zone
+  upstreams: Arc<Vec<SocketAddr>>,
+  /// Network served on each listen address
+  listeners: Arc<Mutex<HashMap<IpAddr, Listener>>>,
+}
+
+impl DnsServer {
+  /// Create a new DnsServer forwarding to the given upstream servers
+  pub(crate) fn new(upstreams: &[String], port: u16) -> IoResult<Self> {
+    let upstreams = upstreams
+      .iter()
+      .map(|upstream| parse_upstream(upstream))
+      .collect::<IoResult<Vec<_>>>()?;
+    Ok(Self {
+      zone: Zone::default(),
+      port,
+      upstreams: Arc::new(upstreams),
+      listeners: Arc::new(Mutex::new(HashMap::new())),
+    })
+  }
+
+  /// Start to answer the queries of a network on the given address
+  /// Nothing is done if the address is already listened for the network
+  pub(crate) fn listen(&self, network: &str, address: &str) -> IoResult<()> {
+    let ip = address.parse::<IpAddr>().map_err(|err| {
+      IoError::invalid_input("Address", &format!("{address} is invalid: {err}"))
+    })?;
+    let Ok(mut listeners) = self.listeners.lock() else {
+      return Err(IoError::interupted("Listeners", "Unable to lock"));
+    };
+    match listeners.remove(&ip) {
+      Some(listener) if listener.network == network => {
+        listeners.insert(ip, listener);
+        return Ok(());
+      }
+      // The address moved to another network
+      Some(listener) => listener.stop(),
+      None => {}
+    }
+    let addr = SocketAddr::new(ip, self.port);
+    let socket = std::net::UdpSocket::bind(addr).map_err(|err| {
+      err.map_err_context(|| format!("Unable to listen on udp {addr}"))
+    })?;
+    let listener = std::net::TcpListener::bind(addr).map_err(|err| {
+      err.map_err_context(|| format!("Unable to listen on tcp {addr}"))
+    })?;
+    socket.set_nonblocking(true)?;
+    listener.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(socket)?;
+    let listener = TcpListener::from_std(listener)?;
+    log::info!("Listening for dns queries of {network} on {addr}");
+    listeners.insert(
+      ip,
+      Listener {
+        network: network.to_owned(),
+        udp: rt::spawn(self.clone().serve_udp(network.to_owned(), socket)),
+        tcp: rt::spawn(self.clone().serve_tcp(network.to_owned(), listener)),
+      },
+    );
+    Ok(())
+  }
+
+  /// Listen on the given addresses of each network
+  /// and stop to listen on the others
+  /// Return the errors of the addresses that couldn't be listened
+  pub(crate) fn set_listeners(
+    &self,
+    addresses: &HashMap<String, String>,
+  ) -> Vec<(String, IoError)> {
+    let mut errors = Vec::new();
+    for (network, address) in addresses {
+      if let Err(err) = self.listen(network, address) {
+        errors.push((network.clone(), err));
+      }
+    }
+    let Ok(mut listeners) = self.listeners.lock() else {
+      return errors;
+    };
+    let stale = listeners
+      .iter()
+      .filter(|(ip, listener)| {
+        let address = addresses
+          .get(&listener.network)
+          .and_then(|address| address.parse::<IpAddr>().ok());
+        address != Some(**ip)
+      })
+      .map(|(ip, _)| *ip)
+      .collect::<Vec<_>>();
+    for ip in stale {
+      if let Some(listener) = listeners.remove(&ip) {
+        log::info!("Stop listening for dns queries of {}", listener.network);
+        listener.stop();
+      }
+    }
+    errors
+  }
+
+  /// Forward a query to the upstream servers until one answer
+  async fn forward(&self, query: &[u8], tcp: bool) -> Option<Vec<u8>> {
+    for upstream in self.upstreams.iter() {
+      let res = if tcp {
+        ntex::time::timeout(FORWARD_TIMEOUT, forward_tcp(*upstream, query))
+          .await
+      } else {
+        ntex::time::timeout(FORWARD_TIMEOUT, forward_udp(*upstream, query))
+          .await
+      };
+      match res {
+        Ok(Ok(response)) => return Some(response),
+        Ok(Err(err)) => {
+          log::warn!("Unable to forward query to {upstream}: {err}");
+        }
+        Err(_) => {
+          log::warn!("Timeout while forwarding query to {upstream}");
+        }
+      }
+    }
+    None
+  }
+
+  /// Answer a query from the zone of a network or from the upstream servers
+  /// Return `None` if the query is not a valid dns message
+  async fn handle(
+    &self,
+    network: &str,
+    query: &[u8],
+    tcp: bool,
+  ) -> Option<Vec<u8>> {
+    let request = match Message::from_vec(query) {
+      Ok(request) => request,
+      Err(err) => {
+        log::debug!("Ignoring invalid dns query: {err}");
+        return None;
+      }
+    };
+    let response = match self.zone.resolve(network, &request) {
+      Some(response) => response.to_vec().ok()?,
+      None => match self.forward(query, tcp).await {
+        Some(response) => response,
+        None => zone::gen_failure(&request).to_vec().ok()?,
+      },
+    };
+    if tcp {
+      return Some(response);
+    }
+    truncate_udp(&request, response)
+  }
+
+  /// Answer the queries received on an udp socket
+  async fn serve_udp(self, network: String, socket: UdpSocket) {
+    let socket = Rc::new(socket);
+    let mut buf = vec![0; MAX_MESSAGE_SIZE];
+    loop {
+      let (len, peer) = match socket.recv_from(&mut buf).await {
+        Ok(res) => res,
+        Err(err) => {
+          log::warn!("Unable to receive dns query: {err}");
+          continue;
+        }
+      };
+      let query = buf[..len].to_vec();
+      let server = self.clone();
+      let network = network.clone();
+      let socket = socket.clone();
+      rt::spawn(async move {
+        let Some(response) = server.handle(&network, &query, false).await
+        else {
+          return;
+        };
+        if let Err(err) = socket.send_to(&response, peer).await {
+          log::warn!("Unable to send dns response to {peer}: {err}");
+        }
+      });
+    }
+  }
+
+  /// Answer the queries received on the connections of a tcp listener
+  async fn serve_tcp(self, network: String, listener: TcpListener) {
+    loop {
+      let (mut stream, peer) = match listener.accept().await {
+        Ok(res) => res,
+        Err(err) => {
+          log::warn!("Unable to accept dns connection: {err}");
+          continue;
+        }
+      };
+      let server = self.clone();
+      let network = network.clone();
+      rt::spawn(async move {
+        // A connection can send multiple queries until it's closed
+        // or idle for too long
+        while let Ok(Ok(query)) =
+          ntex::time::timeout(TCP_IDLE_TIMEOUT, read_tcp(&mut stream)).await
+        {
+          let Some(response) = server.handle(&network, &query, true).await
+          else {
+            break;
+          };
+          let res = ntex::time::timeout(
+            TCP_IDLE_TIMEOUT,
+            write_tcp(&mut stream, &response),
+          )
+          .await;
+          match res {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+              log::warn!("Unable to send dns response to {peer}: {err}");
+              break;
+            }
+            Err(_) => {
+              log::warn!("Timeout while sending dns response to {peer}");
+              break;
+            }
+          }
+        }
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use trust_dns_proto::op::{MessageType, OpCode, Query, ResponseCode};
+  use trust_dns_proto::rr::{RData, Record, RecordType};
+  use trust_dns_proto::rr::rdata::A;
+
+  fn gen_query(name: &str) -> Vec<u8> {
+    let mut request = Message::new();
+    request
+      .set_id(42)
+      .set_message_type(MessageType::Query)
+      .set_op_code(OpCode::Query)
+      .set_recursion_desired(true);
+    request
+      .add_query(Query::query(zone::parse_name(name).unwrap(), RecordType::A));
+    request.to_vec().unwrap()
+  }
+
+  #[test]
+  fn upstreams() {
+    assert_eq!(
+      parse_upstream("1.1.1.1").unwrap(),
+      "1.1.1.1:53".parse::<SocketAddr>().unwrap()
+    );
+    assert_eq!(
+      parse_upstream("[::1]:5353").unwrap(),
+      "[::1]:5353".parse::<SocketAddr>().unwrap()
+    );
+    assert!(DnsServer::new(&["wrong".to_owned()], 53).is_err());
+  }
+
+  #[ntex::test]
+  async fn serve() {
+    let server = DnsServer::new(&[], 10053).unwrap();
+    server.zone.set_networks(HashMap::from([(
+      "Private".to_owned(),
+      vec![Record::from_rdata(
+        zone::parse_name("app.test.com").unwrap(),
+        60,
+        RData::A(A(Ipv4Addr::new(10, 0, 0, 2))),
+      )],
+    )]));
+    let addresses =
+      HashMap::from([("Private".to_owned(), "127.0.0.1".to_owned())]);
+    assert!(server.set_listeners(&addresses).is_empty());
+    // Listening twice on the same address is a no-op
+    server.listen("Private", "127.0.0.1").unwrap();
+    let query = gen_query("app.test.com");
+    let addr = "127.0.0.1:10053".parse::<SocketAddr>().unwrap();
+    let response = forward_udp(addr, &query).await.unwrap();
+    let response = Message::from_vec(&response).unwrap();
+    assert_eq!(response.id(), 42);
+    assert_eq!(response.answers().len(), 1);
+    let response = forward_tcp(addr, &query).await.unwrap();
+    let response = Message::from_vec(&response).unwrap();
+    assert_eq!(response.answers().len(), 1);
+    // Unknown names fail without upstream servers
+    let query = gen_query("none.com");
+    let response = forward_udp(addr, &query).await.unwrap();
+    let response = Message::from_vec(&response).unwrap();
+    assert_eq!(response.response_code(), ResponseCode::ServFail);
+    // The listeners of the removed networks are stopped
+    assert!(server.set_listeners(&HashMap::new()).is_empty());
+    assert!(server.listeners.lock().unwrap().is_empty());
+  }
+
+  #[ntex::test]
+  async fn truncate() {
+    let server = DnsServer::new(&[], 10056).unwrap();
+    let records = (0..64)
+      .map(|i| {
+        Record::from_rdata(
+          zone::parse_name("app.test.com").unwrap(),
+          60,
+          RData::A(A(Ipv4Addr::new(10, 0, 0, i))),
+        )
+      })
+      .collect::<Vec<_>>();
+    server
+      .zone
+      .set_networks(HashMap::from([("Private".to_owned(), records)]));
+    server.listen("Private", "127.0.0.1").unwrap();
+    let query = gen_query("app.test.com");
+    let addr = "127.0.0.1:10056".parse::<SocketAddr>().unwrap();
+    // Too large for udp without edns, the client must retry over tcp
+    let response = forward_udp(addr, &query).await.unwrap();
+    assert!(response.len() <= 512);
+    let response = Message::from_vec(&response).unwrap();
+    assert!(response.truncated());
+    assert!(response.answers().is_empty());
+    let response = forward_tcp(addr, &query).await.unwrap();
+    let response = Message::from_vec(&response).unwrap();
+    assert!(!response.truncated());
+    assert_eq!(response.answers().len(), 64);
+  }
+}
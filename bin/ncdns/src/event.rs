@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use ntex::rt;
 use ntex::http;
 use futures::StreamExt;
@@ -5,13 +7,46 @@ use nanocl_utils::versioning;
 use nanocl_utils::http_client_error::HttpClientError;
 use nanocld_client::NanocldClient;
 use nanocld_client::stubs::system::Event;
-use nanocld_client::stubs::dns::ResourceDnsRule;
 use nanocld_client::stubs::resource::ResourcePartial;
 
-use crate::dnsmasq::Dnsmasq;
-use crate::utils::update_entries;
+use crate::dns_server::DnsServer;
+use crate::utils::{sync_rules, sync_records, update_records};
 use crate::version;
 
+/// Interval between two synchronizations of the records without event
+/// to catch the instances restarted by docker with a new ip address,
+/// the events only update the records of their cargo or vm
+const RESYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Check if an event can change the records of the cargoes and vms
+fn is_record_event(event: &Event) -> bool {
+  matches!(
+    event,
+    Event::NamespaceDeleted(_)
+      | Event::CargoCreated(_)
+      | Event::CargoDeleted(_)
+      | Event::CargoStarted(_)
+      | Event::CargoStopped(_)
+      | Event::CargoPatched(_)
+      | Event::CargoRolledBack(_)
+      | Event::CargoReconciled(_)
+      | Event::CargoAutoscaled(_)
+      | Event::VmCreated(_)
+      | Event::VmDeleted(_)
+      | Event::VmStarted(_)
+      | Event::VmStopped(_)
+      | Event::VmPatched(_)
+  )
+}
+
+/// Synchronize the records and log the error if any
+async fn resync(dns: &DnsServer, client: &NanocldClient) -> Instant {
+  if let Err(err) = sync_records(dns, client).await {
+    log::error!("Unable to synchronize the records: {err}");
+  }
+  Instant::now()
+}
+
 async fn ensure_resource_config(client: &NanocldClient) {
   let formated_version = versioning::format_version(version::VERSION);
   let dns_rule_kind = ResourcePartial {
@@ -59,7 +94,7 @@ async fn ensure_resource_config(client: &NanocldClient) {
   }
 }
 
-async fn r#loop(dnsmasq: &Dnsmasq, client: &NanocldClient) {
+async fn r#loop(dns: &DnsServer, client: &NanocldClient) {
   loop {
     log::info!("Subscribing to nanocl daemon events..");
     match client.watch_events().await {
@@ -69,37 +104,47 @@ async fn r#loop(dnsmasq: &Dnsmasq, client: &NanocldClient) {
       Ok(mut stream) => {
         log::info!("Subscribed to nanocl daemon events");
         ensure_resource_config(client).await;
-        while let Some(event) = stream.next().await {
-          let Ok(e) = event else {
-            break;
+        if let Err(err) = sync_rules(None, dns, client).await {
+          log::error!("Unable to synchronize the DnsRules: {err}");
+        }
+        let mut last_sync = resync(dns, client).await;
+        loop {
+          let event = ntex::time::timeout(RESYNC_INTERVAL, stream.next()).await;
+          let e = match event {
+            // No event during the interval
+            Err(_) => {
+              last_sync = resync(dns, client).await;
+              continue;
+            }
+            Ok(Some(Ok(e))) => e,
+            Ok(_) => break,
           };
+          if is_record_event(&e) {
+            if let Err(err) = update_records(&e, dns, client).await {
+              log::warn!("Unable to update the records after {e}: {err}");
+            }
+          }
+          if last_sync.elapsed() >= RESYNC_INTERVAL {
+            last_sync = resync(dns, client).await;
+          }
           match e {
-            Event::ResourceCreated(resource) => {
-              let dns_rule =
-                serde_json::from_value::<ResourceDnsRule>(resource.data);
-              let Ok(dns_rule) = dns_rule else {
-                log::warn!("Unable to serialize the DnsRule");
-                continue;
-              };
-              if let Err(err) = update_entries(&dns_rule, dnsmasq, client).await
-              {
-                log::error!("Unable to update the DnsRule: {err}");
+            Event::ResourceCreated(resource)
+            | Event::ResourcePatched(resource)
+            | Event::ResourceDeleted(resource)
+              if resource.kind == "DnsRule" =>
+            {
+              if let Err(err) = sync_rules(None, dns, client).await {
+                log::error!("Unable to synchronize the DnsRules: {err}");
               }
             }
-            Event::ResourcePatched(resource) => {
-              let dns_rule =
-                serde_json::from_value::<ResourceDnsRule>(resource.data);
-              let Ok(dns_rule) = dns_rule else {
-                log::warn!("Unable to serialize the DnsRule");
-                continue;
-              };
-              if let Err(err) = update_entries(&dns_rule, dnsmasq, client).await
-              {
-                log::error!("Unable to update the DnsRule: {err}");
+            // Listen on the network of a created namespace, stop on a deleted one
+            Event::NamespaceCreated(_) | Event::NamespaceDeleted(_) => {
+              if let Err(err) = sync_rules(None, dns, client).await {
+                log::error!("Unable to synchronize the DnsRules: {err}");
               }
             }
-            Event::ResourceDeleted(resource) => {
-              log::info!("Resource deleted: {resource:#?}");
+            _ if is_record_event(&e) => {
+              log::debug!("Records updated after event: {e}");
             }
             _ => {
               log::info!("Ignoring event: {e}");
@@ -116,8 +161,8 @@ async fn r#loop(dnsmasq: &Dnsmasq, client: &NanocldClient) {
 }
 
 /// Spawn new thread with event loop to watch for nanocld events
-pub(crate) fn spawn(dnsmasq: &Dnsmasq) {
-  let dnsmasq = dnsmasq.clone();
+pub(crate) fn spawn(dns: &DnsServer) {
+  let dns = dns.clone();
   rt::Arbiter::new().exec_fn(move || {
     #[allow(unused)]
     let mut client = NanocldClient::connect_with_unix_default();
@@ -127,7 +172,7 @@ pub(crate) fn spawn(dnsmasq: &Dnsmasq) {
         NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
     }
     ntex::rt::spawn(async move {
-      r#loop(&dnsmasq, &client).await;
+      r#loop(&dns, &client).await;
     });
   });
 }
@@ -7,12 +7,13 @@ mod cli;
 mod utils;
 mod event;
 mod server;
+mod zone;
 mod version;
-mod dnsmasq;
 mod services;
+mod dns_server;
 
 use cli::Cli;
-use dnsmasq::Dnsmasq;
+use dns_server::DnsServer;
 
 async fn run(cli: &Cli) -> IoResult<()> {
   logger::enable_logger("ncdns");
@@ -21,11 +22,10 @@ async fn run(cli: &Cli) -> IoResult<()> {
 
   // Spawn a new thread to listen events from nanocld
 
-  let conf_dir = cli.conf_dir.to_owned().unwrap_or("/etc".into());
-  let dnsmasq = Dnsmasq::new(&conf_dir).with_dns(cli.dns.clone()).ensure()?;
-  event::spawn(&dnsmasq);
+  let dns = DnsServer::new(&cli.dns, cli.port)?;
+  event::spawn(&dns);
 
-  let server = server::generate(&cli.host, &dnsmasq)?;
+  let server = server::generate(&cli.host, &dns)?;
   server.await?;
 
   Ok(())
@@ -53,8 +53,8 @@ mod tests {
       "ncdns",
       "--host",
       "wrong://dsadsa",
-      "--conf-dir",
-      "/tmp/ncdns",
+      "--port",
+      "10055",
       "--dns",
       "1.1.1.1",
     ]);
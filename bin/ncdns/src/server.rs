@@ -4,16 +4,13 @@ use nanocl_utils::ntex::middlewares;
 use nanocl_utils::io_error::{IoResult, IoError};
 
 use crate::services;
-use crate::dnsmasq::Dnsmasq;
+use crate::dns_server::DnsServer;
 
-pub fn generate(
-  host: &str,
-  dnsmasq: &Dnsmasq,
-) -> IoResult<ntex::server::Server> {
-  let dnsmasq = dnsmasq.clone();
+pub fn generate(host: &str, dns: &DnsServer) -> IoResult<ntex::server::Server> {
+  let dns = dns.clone();
   let mut server = web::HttpServer::new(move || {
     web::App::new()
-      .state(dnsmasq.clone())
+      .state(dns.clone())
       .wrap(middlewares::SerializeError)
       .configure(services::ntex_config)
       .default_service(web::route().to(services::unhandled))
@@ -49,23 +46,23 @@ pub fn generate(
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::dnsmasq::Dnsmasq;
+  use crate::dns_server::DnsServer;
   use nanocl_utils::io_error::IoResult;
 
   #[ntex::test]
   async fn generate_unix_and_tcp() -> IoResult<()> {
-    let dnsmasq = Dnsmasq::new("/tmp/ncdns");
-    let server = generate("unix:///tmp/ncdns.sock", &dnsmasq)?;
+    let dns = DnsServer::new(&[], 53)?;
+    let server = generate("unix:///tmp/ncdns.sock", &dns)?;
     server.stop(true).await;
-    let server = generate("tcp://0.0.0.0:9987", &dnsmasq)?;
+    let server = generate("tcp://0.0.0.0:9987", &dns)?;
     server.stop(true).await;
     Ok(())
   }
 
   #[test]
   fn generate_wrong_host() -> IoResult<()> {
-    let dnsmasq = Dnsmasq::new("/tmp/ncdns");
-    let server = generate("wrong://dsadsa", &dnsmasq);
+    let dns = DnsServer::new(&[], 53)?;
+    let server = generate("wrong://dsadsa", &dns);
     assert!(server.is_err());
     Ok(())
   }
@@ -1,7 +1,7 @@
 use utoipa::OpenApi;
 
 use nanocld_client::stubs::system::Version;
-use nanocld_client::stubs::dns::{ResourceDnsRule, DnsEntry};
+use nanocld_client::stubs::dns::{ResourceDnsRule, DnsEntry, DnsRecordType};
 
 use super::{rule, system};
 
@@ -41,6 +41,7 @@ impl utoipa::Modify for VersionModifier {
   components(schemas(
     ResourceDnsRule,
     DnsEntry,
+    DnsRecordType,
     Version,
   )),
   tags(
@@ -5,8 +5,8 @@ use nanocl_utils::http_error::HttpError;
 use nanocld_client::NanocldClient;
 use nanocld_client::stubs::dns::ResourceDnsRule;
 
-use crate::utils::{self, remove_entries};
-use crate::dnsmasq::Dnsmasq;
+use crate::utils;
+use crate::dns_server::DnsServer;
 
 /// Create/Update a new DnsRule
 #[cfg_attr(feature = "dev", utoipa::path(
@@ -24,8 +24,8 @@ use crate::dnsmasq::Dnsmasq;
 #[web::put("/rules/{name}")]
 pub(crate) async fn apply_rule(
   // To follow the ressource service convention, we have to use a tuple
-  _path: web::types::Path<(String, String)>,
-  dnsmasq: web::types::State<Dnsmasq>,
+  path: web::types::Path<(String, String)>,
+  dns: web::types::State<DnsServer>,
   web::types::Json(payload): web::types::Json<ResourceDnsRule>,
 ) -> Result<web::HttpResponse, HttpError> {
   #[allow(unused)]
@@ -35,8 +35,7 @@ pub(crate) async fn apply_rule(
     client =
       NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
   }
-  utils::write_entries(&payload, &dnsmasq, &client).await?;
-  utils::reload_service(&client).await?;
+  utils::sync_rules(Some((&path.1, Some(&payload))), &dns, &client).await?;
   Ok(web::HttpResponse::Ok().json(&payload))
 }
 
@@ -55,7 +54,7 @@ pub(crate) async fn apply_rule(
 #[web::delete("/rules/{name}")]
 pub(crate) async fn remove_rule(
   path: web::types::Path<(String, String)>,
-  dnsmasq: web::types::State<Dnsmasq>,
+  dns: web::types::State<DnsServer>,
 ) -> Result<web::HttpResponse, HttpError> {
   #[allow(unused)]
   let mut client = NanocldClient::connect_with_unix_default();
@@ -64,16 +63,7 @@ pub(crate) async fn remove_rule(
     client =
       NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
   }
-  let rule = client.inspect_resource(&path.1).await?;
-  let dns_rule =
-    serde_json::from_value::<ResourceDnsRule>(rule.data).map_err(|err| {
-      HttpError::bad_request(format!(
-        "Unable to serialize the DnsRule: {}",
-        err
-      ))
-    })?;
-  remove_entries(&dns_rule, &dnsmasq, &client).await?;
-  utils::reload_service(&client).await?;
+  utils::sync_rules(Some((&path.1, None)), &dns, &client).await?;
   Ok(web::HttpResponse::Ok().finish())
 }
 
@@ -1,9 +1,17 @@
+use std::collections::{BTreeMap, HashMap};
+
+use trust_dns_proto::rr::Record;
+use nanocld_client::bollard_next::service::ContainerSummary;
+use nanocld_client::stubs::vm::VmInspect;
 use nanocld_client::stubs::dns::ResourceDnsRule;
+use nanocld_client::stubs::cargo::CargoInspect;
+use nanocld_client::stubs::system::Event;
 use nanocld_client::{NanocldClient, stubs::resource::ResourceQuery};
 
 use nanocl_utils::io_error::{FromIo, IoResult, IoError};
 
-use crate::dnsmasq::Dnsmasq;
+use crate::zone::{self, LOCAL_DOMAIN};
+use crate::dns_server::DnsServer;
 
 /// Get gateway of given namespace
 async fn get_namespace_addr(
@@ -56,120 +64,380 @@ async fn get_network_addr(
   Ok(addr)
 }
 
-/// Reload the dns service
-/// TODO: use a better way to reload the service, we may have to move from dnsmasq to something else
-pub(crate) async fn reload_service(client: &NanocldClient) -> IoResult<()> {
-  client.restart_cargo("ndns", Some("system".into())).await?;
-  Ok(())
+/// Ttl of the records of the cargoes and virtual machines
+/// kept short since their instances can be replaced at any time
+const RECORD_TTL: u32 = 10;
+
+/// Get the domain name of the records of a cargo or a vm from his key
+/// `<name>.<namespace>.c` for a cargo or `<name>.<namespace>.v` for a vm
+fn get_target_key_name(key: &str) -> IoResult<String> {
+  let invalid = || {
+    IoError::invalid_data(
+      "TargetKey",
+      &format!("{key} must be <name>.<namespace>.c or <name>.<namespace>.v"),
+    )
+  };
+  let (key, kind) = key.rsplit_once('.').ok_or_else(invalid)?;
+  let (name, namespace) = key.split_once('.').ok_or_else(invalid)?;
+  if name.is_empty() || namespace.is_empty() || namespace.contains('.') {
+    return Err(invalid());
+  }
+  match kind {
+    "c" => Ok(format!("{name}.{namespace}.{LOCAL_DOMAIN}")),
+    "v" => Ok(format!("{name}.{namespace}.vm.{LOCAL_DOMAIN}")),
+    _ => Err(invalid()),
+  }
 }
 
-/// Convert a ResourceDnsRule into a dnsmasq config and write it to a file
-pub(crate) async fn write_entries(
+/// Convert the entries of a DnsRule into records
+/// after resolving their namespace gateway and their cargo or vm target
+async fn gen_rule_records(
   dns_rule: &ResourceDnsRule,
-  dnsmasq: &Dnsmasq,
   client: &NanocldClient,
-) -> IoResult<()> {
-  let listen_address = get_network_addr(&dns_rule.network, client).await?;
-  let mut file_content = format!("listen-address={listen_address}\n");
+) -> IoResult<Vec<Record>> {
+  let mut records = Vec::new();
   for entry in &dns_rule.entries {
-    let ip_address = match entry.ip_address.as_str() {
-      namespace if namespace.ends_with(".nsp") => {
-        let namespace = namespace.trim_end_matches(".nsp");
-        get_namespace_addr(namespace, client).await?
+    let mut entry = entry.clone();
+    if let Some(namespace) =
+      entry.ip_address.strip_suffix(".nsp").map(str::to_owned)
+    {
+      entry.ip_address = get_namespace_addr(&namespace, client).await?;
+    }
+    if let Some(key) = &entry.target_key {
+      if entry.target.is_some() {
+        return Err(IoError::invalid_data(
+          "DnsEntry",
+          &format!("{}: Target and TargetKey are exclusive", entry.name),
+        ));
       }
-      _ => entry.ip_address.clone(),
-    };
-    file_content += &format!("address=/{}/{}\n", entry.name, ip_address);
+      entry.target = Some(get_target_key_name(key)?);
+    }
+    records.push(zone::gen_entry_record(&entry)?);
   }
-  dnsmasq
-    .write_config(&dns_rule.network, &file_content)
-    .await?;
-  Ok(())
+  Ok(records)
 }
 
-pub(crate) async fn update_entries(
-  dns_rule: &ResourceDnsRule,
-  dnsmasq: &Dnsmasq,
+/// Synchronize the zone and the listen addresses with the DnsRule resources.
+/// The server listen on the private network and on the network of every
+/// namespace to serve the records of the cargoes and vms,
+/// the public network is only listened while a DnsRule use it.
+/// The pending rule is applied over the stored ones since nanocld
+/// call us before saving or deleting it.
+/// An invalid pending rule return an error, the other invalid rules are skipped
+pub(crate) async fn sync_rules(
+  pending: Option<(&str, Option<&ResourceDnsRule>)>,
+  dns: &DnsServer,
   client: &NanocldClient,
 ) -> IoResult<()> {
   let query = ResourceQuery {
-    contains: Some(
-      serde_json::json!({ "Network": dns_rule.network }).to_string(),
-    ),
+    contains: None,
     kind: Some("DnsRule".into()),
   };
   let resources = client.list_resource(Some(query)).await.map_err(|err| {
     err.map_err_context(|| "Unable to list resources from nanocl daemon")
   })?;
-  let mut entries = Vec::new();
+  let mut rules = BTreeMap::new();
   for resource in resources {
-    let mut dns_rule = serde_json::from_value::<ResourceDnsRule>(resource.data)
+    match serde_json::from_value::<ResourceDnsRule>(resource.data) {
+      Ok(dns_rule) => {
+        rules.insert(resource.name, dns_rule);
+      }
+      Err(err) => {
+        log::warn!("Unable to serialize DnsRule {}: {err}", resource.name);
+      }
+    }
+  }
+  let pending_name = pending.map(|(name, _)| name.to_owned());
+  let mut pending_network = None;
+  match pending {
+    Some((name, Some(dns_rule))) => {
+      pending_network = Some(dns_rule.network.clone());
+      rules.insert(name.to_owned(), dns_rule.clone());
+    }
+    Some((name, None)) => {
+      rules.remove(name);
+    }
+    None => {}
+  }
+  let mut networks: HashMap<String, Vec<Record>> = HashMap::new();
+  let mut addresses = HashMap::new();
+  for (name, dns_rule) in &rules {
+    let res = async {
+      let records = gen_rule_records(dns_rule, client).await?;
+      if !addresses.contains_key(&dns_rule.network) {
+        let address = get_network_addr(&dns_rule.network, client).await?;
+        addresses.insert(dns_rule.network.clone(), address);
+      }
+      Ok::<_, IoError>(records)
+    }
+    .await;
+    match res {
+      Ok(mut records) => {
+        networks
+          .entry(dns_rule.network.clone())
+          .or_default()
+          .append(&mut records);
+      }
+      Err(err) if pending_name.as_ref() == Some(name) => return Err(err),
+      Err(err) => {
+        log::warn!("Unable to apply the DnsRule {name}: {err}");
+      }
+    }
+  }
+  let namespaces = client.list_namespace().await.map_err(|err| {
+    err.map_err_context(|| "Unable to list namespaces from nanocl daemon")
+  })?;
+  let base_networks = namespaces
+    .into_iter()
+    .map(|namespace| format!("{}.nsp", namespace.name))
+    .chain(std::iter::once("Private".to_owned()));
+  for network in base_networks {
+    if addresses.contains_key(&network) {
+      continue;
+    }
+    match get_network_addr(&network, client).await {
+      Ok(address) => {
+        addresses.insert(network, address);
+      }
+      Err(err) => {
+        log::warn!("Unable to get the address of {network}: {err}");
+      }
+    }
+  }
+  for (network, err) in dns.set_listeners(&addresses) {
+    if pending_network.as_ref() == Some(&network) {
+      return Err(err);
+    }
+    log::warn!("Unable to listen for dns queries of {network}: {err}");
+  }
+  dns.zone.set_networks(networks);
+  Ok(())
+}
+
+/// Get the ip address of an instance in the network of his namespace
+/// if he is running and not failing his health check
+fn healthy_instance_ip(
+  instance: &ContainerSummary,
+  namespace: &str,
+) -> Option<String> {
+  if instance.state.as_deref() != Some("running") {
+    return None;
+  }
+  let status = instance.status.as_deref().unwrap_or_default();
+  if status.contains("(unhealthy)") || status.contains("(health: starting)") {
+    return None;
+  }
+  let ip_address = instance
+    .network_settings
+    .as_ref()?
+    .networks
+    .as_ref()?
+    .get(namespace)?
+    .ip_address
+    .clone()?;
+  if ip_address.is_empty() {
+    return None;
+  }
+  Some(ip_address)
+}
+
+/// Convert the names and ip addresses of the instances into records
+/// A name without address has no record so it's not resolved
+fn gen_records(
+  records: &BTreeMap<String, Vec<String>>,
+) -> IoResult<Vec<Record>> {
+  let mut zone_records = Vec::new();
+  for (name, ip_addresses) in records {
+    for ip_address in ip_addresses {
+      zone_records.push(zone::gen_record(name, ip_address, RECORD_TTL)?);
+    }
+  }
+  Ok(zone_records)
+}
+
+/// Get the domain name and the healthy ip addresses of a cargo
+/// `<cargo>.<namespace>.nanocl.internal`
+fn cargo_target(cargo: &CargoInspect) -> (String, Vec<String>) {
+  let namespace = &cargo.namespace_name;
+  let mut ip_addresses = cargo
+    .instances
+    .iter()
+    .filter_map(|instance| healthy_instance_ip(&instance.container, namespace))
+    .collect::<Vec<_>>();
+  ip_addresses.sort();
+  (
+    format!("{}.{namespace}.{LOCAL_DOMAIN}", cargo.name),
+    ip_addresses,
+  )
+}
+
+/// Get the domain name and the healthy ip address of a virtual machine
+/// `<vm>.<namespace>.vm.nanocl.internal`
+fn vm_target(vm: &VmInspect) -> (String, Vec<String>) {
+  let namespace = &vm.namespace_name;
+  let mut ip_addresses = vm
+    .instances
+    .iter()
+    .filter_map(|instance| healthy_instance_ip(instance, namespace))
+    .collect::<Vec<_>>();
+  ip_addresses.sort();
+  (
+    format!("{}.{namespace}.vm.{LOCAL_DOMAIN}", vm.name),
+    ip_addresses,
+  )
+}
+
+/// Generate the records of the cargoes and virtual machines of a namespace
+/// The cargoes come with their instances from the inspection of the namespace
+async fn gen_namespace_records(
+  namespace: &str,
+  client: &NanocldClient,
+) -> IoResult<BTreeMap<String, Vec<String>>> {
+  let inspect = client.inspect_namespace(namespace).await.map_err(|err| {
+    err.map_err_context(|| format!("Unable to inspect namespace {namespace}"))
+  })?;
+  let mut records = inspect
+    .cargoes
+    .iter()
+    .map(cargo_target)
+    .collect::<BTreeMap<_, _>>();
+  let vms =
+    client
+      .list_vm(Some(namespace.to_owned()))
+      .await
       .map_err(|err| {
-        err.map_err_context(|| "Unable to serialize the DnsRule")
+        err.map_err_context(|| format!("Unable to list vms of {namespace}"))
       })?;
-    entries.append(&mut dns_rule.entries);
-  }
-  let listen_address = get_network_addr(&dns_rule.network, client).await?;
-  let mut file_content =
-    format!("bind-dynamic\nlisten-address={listen_address}\n");
-  for entry in &entries {
-    let ip_address = match entry.ip_address.as_str() {
-      namespace if namespace.ends_with(".nsp") => {
-        let namespace = namespace.trim_end_matches(".nsp");
-        get_namespace_addr(namespace, client).await?
+  for vm in vms {
+    let res = client
+      .inspect_vm(&vm.name, Some(namespace.to_owned()))
+      .await;
+    match res {
+      Ok(vm) => {
+        let (name, ip_addresses) = vm_target(&vm);
+        records.insert(name, ip_addresses);
       }
-      _ => entry.ip_address.clone(),
-    };
-    file_content += &format!("address=/{}/{}\n", entry.name, ip_address);
-  }
-  dnsmasq
-    .write_config(&dns_rule.network, &file_content)
-    .await?;
-  reload_service(client).await?;
-  Ok(())
+      Err(err) => {
+        log::warn!("Unable to inspect vm {}: {err}", vm.key);
+      }
+    }
+  }
+  Ok(records)
 }
 
-pub(crate) async fn remove_entries(
-  dns_rule: &ResourceDnsRule,
-  dnsmasq: &Dnsmasq,
+/// Synchronize the records of the cargoes and virtual machines of every
+/// namespaces, they are served on every listen address of the server.
+/// A namespace that can't be synchronized keep his previous records
+pub(crate) async fn sync_records(
+  dns: &DnsServer,
   client: &NanocldClient,
 ) -> IoResult<()> {
-  let content = dnsmasq.read_config(&dns_rule.network).await?;
-  println!("{}", content);
-  let mut file_content = String::new();
-  let lines = content.lines();
-  let listen_address = get_network_addr(&dns_rule.network, client).await?;
-  let empty_entries = format!("listen-address={listen_address}\n");
-  for line in lines {
-    let mut found = false;
-    for entry in &dns_rule.entries {
-      if line.starts_with(&format!("address=/{}/", entry.name)) {
-        found = true;
-        println!("Found {}", line);
-        break;
-      }
+  let namespaces = client.list_namespace().await.map_err(|err| {
+    err.map_err_context(|| "Unable to list namespaces from nanocl daemon")
+  })?;
+  let namespaces = namespaces
+    .into_iter()
+    .map(|namespace| namespace.name)
+    .collect::<Vec<_>>();
+  for namespace in &namespaces {
+    let res = async {
+      let records = gen_namespace_records(namespace, client).await?;
+      gen_records(&records)
     }
-    if !found {
-      file_content.push_str(&format!("{line}\n"));
+    .await;
+    match res {
+      Ok(records) => dns.zone.set_namespace(namespace, records),
+      Err(err) => {
+        log::warn!("Unable to synchronize the records of {namespace}: {err}");
+      }
     }
   }
-  println!("{}", file_content);
-  if file_content == empty_entries {
-    dnsmasq.remove_config(&dns_rule.network).await?;
-    return Ok(());
-  }
-  dnsmasq
-    .write_config(&dns_rule.network, &file_content)
-    .await?;
+  dns.zone.retain_namespaces(&namespaces);
   Ok(())
 }
 
+/// Replace the records of a cargo or a vm
+fn set_target(
+  dns: &DnsServer,
+  namespace: &str,
+  (name, ip_addresses): (String, Vec<String>),
+) -> IoResult<()> {
+  let records = ip_addresses
+    .iter()
+    .map(|ip_address| zone::gen_record(&name, ip_address, RECORD_TTL))
+    .collect::<IoResult<Vec<_>>>()?;
+  dns
+    .zone
+    .set_target(namespace, &zone::parse_name(&name)?, records);
+  Ok(())
+}
+
+/// Update only the records of the cargo, the vm or the namespace of an event
+/// The events carry the inspection of their cargo or vm
+/// except `CargoAutoscaled` which require to inspect the cargo
+pub(crate) async fn update_records(
+  event: &Event,
+  dns: &DnsServer,
+  client: &NanocldClient,
+) -> IoResult<()> {
+  match event {
+    Event::CargoDeleted(cargo) => {
+      let (name, _) = cargo_target(cargo);
+      set_target(dns, &cargo.namespace_name, (name, Vec::new()))
+    }
+    Event::CargoCreated(cargo)
+    | Event::CargoStarted(cargo)
+    | Event::CargoStopped(cargo)
+    | Event::CargoPatched(cargo)
+    | Event::CargoRolledBack(cargo)
+    | Event::CargoReconciled(cargo) => {
+      set_target(dns, &cargo.namespace_name, cargo_target(cargo))
+    }
+    Event::CargoAutoscaled(history) => {
+      let Some((name, namespace)) = history.cargo_key.split_once('.') else {
+        return Err(IoError::invalid_data(
+          "CargoKey",
+          &format!("{} must be <name>.<namespace>", history.cargo_key),
+        ));
+      };
+      let cargo = client
+        .inspect_cargo(name, Some(namespace.to_owned()))
+        .await
+        .map_err(|err| {
+          err.map_err_context(|| {
+            format!("Unable to inspect cargo {}", history.cargo_key)
+          })
+        })?;
+      set_target(dns, namespace, cargo_target(&cargo))
+    }
+    Event::VmDeleted(vm) => {
+      let (name, _) = vm_target(vm);
+      set_target(dns, &vm.namespace_name, (name, Vec::new()))
+    }
+    Event::VmCreated(vm)
+    | Event::VmStarted(vm)
+    | Event::VmStopped(vm)
+    | Event::VmPatched(vm) => {
+      set_target(dns, &vm.namespace_name, vm_target(vm))
+    }
+    Event::NamespaceDeleted(namespace) => {
+      dns.zone.remove_namespace(namespace);
+      Ok(())
+    }
+    _ => Ok(()),
+  }
+}
+
 #[cfg(test)]
 pub mod tests {
+  use super::*;
+
   use nanocl_utils::logger;
+  use nanocld_client::bollard_next::service::{
+    ContainerSummaryNetworkSettings, EndpointSettings,
+  };
 
   use crate::services;
-  use crate::dnsmasq::Dnsmasq;
 
   // Before a test
   pub fn before() {
@@ -181,13 +449,74 @@ pub mod tests {
   // Generate a test server
   pub fn generate_server() -> ntex::web::test::TestServer {
     before();
-    let dnsmasq = Dnsmasq::new("/tmp/dnsmasq");
-    dnsmasq.ensure().unwrap();
+    let dns = DnsServer::new(&[], 10054).unwrap();
     // Create test server
     ntex::web::test::server(move || {
       ntex::web::App::new()
-        .state(dnsmasq.clone())
+        .state(dns.clone())
         .configure(services::ntex_config)
     })
   }
+
+  fn gen_instance(
+    state: &str,
+    status: &str,
+    ip_address: &str,
+  ) -> ContainerSummary {
+    ContainerSummary {
+      state: Some(state.to_owned()),
+      status: Some(status.to_owned()),
+      network_settings: Some(ContainerSummaryNetworkSettings {
+        networks: Some(HashMap::from([(
+          "global".to_owned(),
+          EndpointSettings {
+            ip_address: Some(ip_address.to_owned()),
+            ..Default::default()
+          },
+        )])),
+      }),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn records() {
+    let instances = [
+      gen_instance("running", "Up 2 minutes", "10.0.0.2"),
+      gen_instance("running", "Up 2 minutes (healthy)", "10.0.0.3"),
+      gen_instance("running", "Up 2 minutes (unhealthy)", "10.0.0.4"),
+      gen_instance("exited", "Exited (0) 1 minute ago", "10.0.0.5"),
+    ];
+    let ip_addresses = instances
+      .iter()
+      .filter_map(|instance| healthy_instance_ip(instance, "global"))
+      .collect::<Vec<_>>();
+    assert_eq!(ip_addresses, vec!["10.0.0.2", "10.0.0.3"]);
+    assert_eq!(healthy_instance_ip(&instances[0], "other"), None);
+    let records = BTreeMap::from([
+      ("app.global.nanocl.internal".to_owned(), ip_addresses),
+      ("stopped.global.nanocl.internal".to_owned(), Vec::new()),
+    ]);
+    let records = gen_records(&records).unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|record| {
+      record.name().to_ascii() == "app.global.nanocl.internal."
+        && record.ttl() == RECORD_TTL
+    }));
+  }
+
+  #[test]
+  fn target_keys() {
+    assert_eq!(
+      get_target_key_name("app.global.c").unwrap(),
+      "app.global.nanocl.internal."
+    );
+    assert_eq!(
+      get_target_key_name("db.global.v").unwrap(),
+      "db.global.vm.nanocl.internal."
+    );
+    assert!(get_target_key_name("app.global").is_err());
+    assert!(get_target_key_name("app.global.x").is_err());
+    assert!(get_target_key_name("app.c").is_err());
+  }
 }
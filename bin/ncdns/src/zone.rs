@@ -0,0 +1,450 @@
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+
+use trust_dns_proto::rr::rdata::{A, AAAA, CNAME, MX, SRV, TXT};
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+use trust_dns_proto::op::{Message, MessageType, OpCode, ResponseCode};
+
+use nanocl_utils::io_error::{IoError, IoResult};
+use nanocld_client::stubs::dns::{DnsEntry, DnsRecordType};
+
+/// Domain of the records managed by nanocl
+/// Names under it are never forwarded to the upstream servers
+pub const LOCAL_DOMAIN: &str = "nanocl.internal.";
+/// Maximum number of CNAME followed to answer a query
+const MAX_CNAME_HOPS: usize = 8;
+/// Ttl of the entries without ttl
+const DEFAULT_TTL: u32 = 60;
+/// Priority of the SRV and MX entries without priority
+const DEFAULT_PRIORITY: u16 = 10;
+/// Maximum length of a TXT record string
+const MAX_TXT_LENGTH: usize = 255;
+
+/// Parse a domain name as a fully qualified name
+pub fn parse_name(name: &str) -> IoResult<Name> {
+  let mut name = Name::from_ascii(name).map_err(|err| {
+    IoError::invalid_data("DnsName", &format!("{name} is invalid: {err}"))
+  })?;
+  name.set_fqdn(true);
+  Ok(name)
+}
+
+/// Generate a record of a name resolving to a value,
+/// an A or AAAA record for an ip address and a CNAME record for a name
+pub fn gen_record(name: &str, value: &str, ttl: u32) -> IoResult<Record> {
+  let rdata = match value.parse::<IpAddr>() {
+    Ok(IpAddr::V4(ip)) => RData::A(A(ip)),
+    Ok(IpAddr::V6(ip)) => RData::AAAA(AAAA(ip)),
+    Err(_) => RData::CNAME(CNAME(parse_name(value)?)),
+  };
+  Ok(Record::from_rdata(parse_name(name)?, ttl, rdata))
+}
+
+/// Convert an entry of a DnsRule into a record after validating it
+/// The namespace gateway and the cargo or vm key must be already resolved
+pub fn gen_entry_record(entry: &DnsEntry) -> IoResult<Record> {
+  let invalid = |msg: &str| {
+    IoError::invalid_data("DnsEntry", &format!("{}: {msg}", entry.name))
+  };
+  // A wildcard is only allowed as the first label
+  let base_name = entry.name.strip_prefix("*.").unwrap_or(&entry.name);
+  if base_name.contains('*') {
+    return Err(invalid("wildcard must be the first label"));
+  }
+  let name = parse_name(&entry.name)?;
+  let ip =
+    match entry.ip_address.as_str() {
+      "" => None,
+      ip => Some(ip.parse::<IpAddr>().map_err(|err| {
+        invalid(&format!("ip address {ip} is invalid: {err}"))
+      })?),
+    };
+  let target = entry.target.as_deref().map(parse_name).transpose()?;
+  let kind = match (entry.kind, ip) {
+    (Some(kind), _) => kind,
+    (None, Some(IpAddr::V6(_))) => DnsRecordType::AAAA,
+    (None, _) => DnsRecordType::A,
+  };
+  let priority = entry.priority.unwrap_or(DEFAULT_PRIORITY);
+  let rdata = match kind {
+    DnsRecordType::A => match ip {
+      Some(IpAddr::V4(ip)) => RData::A(A(ip)),
+      _ => return Err(invalid("A record require an ipv4 address")),
+    },
+    DnsRecordType::AAAA => match ip {
+      Some(IpAddr::V6(ip)) => RData::AAAA(AAAA(ip)),
+      _ => return Err(invalid("AAAA record require an ipv6 address")),
+    },
+    DnsRecordType::CNAME => {
+      let target = target.ok_or_else(|| invalid("CNAME require a target"))?;
+      RData::CNAME(CNAME(target))
+    }
+    DnsRecordType::SRV => {
+      let target = target.ok_or_else(|| invalid("SRV require a target"))?;
+      let port = entry.port.ok_or_else(|| invalid("SRV require a port"))?;
+      let weight = entry.weight.unwrap_or_default();
+      RData::SRV(SRV::new(priority, weight, port, target))
+    }
+    DnsRecordType::TXT => {
+      let text = entry.text.clone().unwrap_or_default();
+      if text.is_empty() {
+        return Err(invalid("TXT record require a text"));
+      }
+      if text.iter().any(|text| text.len() > MAX_TXT_LENGTH) {
+        return Err(invalid("TXT record strings are limited to 255 bytes"));
+      }
+      RData::TXT(TXT::new(text))
+    }
+    DnsRecordType::MX => {
+      let target = target.ok_or_else(|| invalid("MX require a target"))?;
+      RData::MX(MX::new(priority, target))
+    }
+  };
+  let ttl = entry.ttl.unwrap_or(DEFAULT_TTL);
+  Ok(Record::from_rdata(name, ttl, rdata))
+}
+
+/// Get the key of a name in the zone, names are case insensitive
+fn name_key(name: &Name) -> String {
+  name.to_lowercase().to_ascii()
+}
+
+/// Records of a zone by name
+type Records = HashMap<String, Vec<Record>>;
+
+#[derive(Debug, Default)]
+struct ZoneInner {
+  /// Records served only on the listen address of their network
+  networks: HashMap<String, Records>,
+  /// Records of the cargoes and vms by namespace served on every listen address
+  global: HashMap<String, Records>,
+}
+
+impl ZoneInner {
+  /// Get the records of a key for a network, the global records included
+  fn lookup_key(&self, network: &str, key: &str) -> Vec<Record> {
+    let mut records = self
+      .networks
+      .get(network)
+      .and_then(|records| records.get(key))
+      .cloned()
+      .unwrap_or_default();
+    for global in self.global.values() {
+      if let Some(global) = global.get(key) {
+        records.extend(global.iter().cloned());
+      }
+    }
+    records
+  }
+
+  /// Get the records of a name for a network
+  /// or the records of the closest wildcard renamed to the name
+  fn lookup(&self, network: &str, name: &Name) -> Vec<Record> {
+    let key = name_key(name);
+    let records = self.lookup_key(network, &key);
+    if !records.is_empty() {
+      return records;
+    }
+    let mut parent = key.as_str();
+    while let Some((_, rest)) = parent.split_once('.') {
+      if rest.is_empty() {
+        break;
+      }
+      let records = self.lookup_key(network, &format!("*.{rest}"));
+      if !records.is_empty() {
+        return records
+          .into_iter()
+          .map(|mut record| {
+            record.set_name(name.clone());
+            record
+          })
+          .collect();
+      }
+      parent = rest;
+    }
+    Vec::new()
+  }
+}
+
+/// In memory records answered by the dns server
+/// Updated in place so changes are served without restart
+#[derive(Clone, Debug, Default)]
+pub struct Zone {
+  inner: Arc<RwLock<ZoneInner>>,
+}
+
+/// Group records by the key of their name
+fn group_records(records: Vec<Record>) -> Records {
+  let mut grouped = Records::new();
+  for record in records {
+    grouped
+      .entry(name_key(record.name()))
+      .or_default()
+      .push(record);
+  }
+  grouped
+}
+
+/// Generate an empty response to a request
+fn gen_response(request: &Message, code: ResponseCode) -> Message {
+  let mut response = Message::new();
+  response
+    .set_id(request.id())
+    .set_message_type(MessageType::Response)
+    .set_op_code(request.op_code())
+    .set_recursion_desired(request.recursion_desired())
+    .set_recursion_available(true)
+    .set_response_code(code);
+  response.add_queries(request.queries().to_vec());
+  response
+}
+
+/// Generate an empty response to a request that couldn't be answered
+pub fn gen_failure(request: &Message) -> Message {
+  gen_response(request, ResponseCode::ServFail)
+}
+
+impl Zone {
+  /// Replace the records of every network
+  pub fn set_networks(&self, networks: HashMap<String, Vec<Record>>) {
+    let Ok(mut inner) = self.inner.write() else {
+      return;
+    };
+    inner.networks = networks
+      .into_iter()
+      .map(|(network, records)| (network, group_records(records)))
+      .collect();
+  }
+
+  /// Replace the records of the cargoes and vms of a namespace
+  pub fn set_namespace(&self, namespace: &str, records: Vec<Record>) {
+    let Ok(mut inner) = self.inner.write() else {
+      return;
+    };
+    inner
+      .global
+      .insert(namespace.to_owned(), group_records(records));
+  }
+
+  /// Remove the records of the namespaces not in the given list
+  pub fn retain_namespaces(&self, namespaces: &[String]) {
+    let Ok(mut inner) = self.inner.write() else {
+      return;
+    };
+    inner
+      .global
+      .retain(|namespace, _| namespaces.contains(namespace));
+  }
+
+  /// Remove the records of the cargoes and vms of a namespace
+  pub fn remove_namespace(&self, namespace: &str) {
+    let Ok(mut inner) = self.inner.write() else {
+      return;
+    };
+    inner.global.remove(namespace);
+  }
+
+  /// Replace the records of a cargo or a vm of a namespace
+  /// The name is removed when there is no record
+  pub fn set_target(&self, namespace: &str, name: &Name, records: Vec<Record>) {
+    let Ok(mut inner) = self.inner.write() else {
+      return;
+    };
+    let global = inner.global.entry(namespace.to_owned()).or_default();
+    if records.is_empty() {
+      global.remove(&name_key(name));
+      return;
+    }
+    global.insert(name_key(name), records);
+  }
+
+  /// Answer a request from the records of the given network
+  /// Return `None` when the name is unknown and must be forwarded
+  pub fn resolve(&self, network: &str, request: &Message) -> Option<Message> {
+    if request.message_type() != MessageType::Query {
+      return None;
+    }
+    if request.op_code() != OpCode::Query {
+      return Some(gen_response(request, ResponseCode::NotImp));
+    }
+    let Some(query) = request.queries().first() else {
+      return Some(gen_response(request, ResponseCode::FormErr));
+    };
+    let inner = self.inner.read().ok()?;
+    let name = name_key(query.name());
+    let mut records = inner.lookup(network, query.name());
+    if records.is_empty() {
+      let is_local = name.ends_with(&format!(".{LOCAL_DOMAIN}"));
+      if name == LOCAL_DOMAIN || is_local {
+        let mut response = gen_response(request, ResponseCode::NXDomain);
+        response.set_authoritative(true);
+        return Some(response);
+      }
+      return None;
+    }
+    let query_type = query.query_type();
+    let mut answers = Vec::new();
+    for _ in 0..MAX_CNAME_HOPS {
+      let matching = records
+        .iter()
+        .filter(|record| {
+          query_type == RecordType::ANY || record.record_type() == query_type
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+      if !matching.is_empty() {
+        answers.extend(matching);
+        break;
+      }
+      // Follow the alias while the target is in the zone
+      let Some(alias) = records
+        .iter()
+        .find(|record| record.record_type() == RecordType::CNAME)
+      else {
+        break;
+      };
+      answers.push(alias.clone());
+      let Some(RData::CNAME(CNAME(target))) = alias.data() else {
+        break;
+      };
+      let target = target.clone();
+      records = inner.lookup(network, &target);
+    }
+    let mut response = gen_response(request, ResponseCode::NoError);
+    response.set_authoritative(true);
+    response.add_answers(answers);
+    Some(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::net::Ipv4Addr;
+
+  use trust_dns_proto::op::Query;
+
+  fn gen_request(name: &str, query_type: RecordType) -> Message {
+    let mut request = Message::new();
+    request
+      .set_id(42)
+      .set_message_type(MessageType::Query)
+      .set_op_code(OpCode::Query)
+      .set_recursion_desired(true);
+    request.add_query(Query::query(parse_name(name).unwrap(), query_type));
+    request
+  }
+
+  fn gen_entry(name: &str, kind: Option<DnsRecordType>) -> DnsEntry {
+    DnsEntry {
+      name: name.to_owned(),
+      kind,
+      ip_address: String::new(),
+      target: None,
+      target_key: None,
+      port: None,
+      priority: None,
+      weight: None,
+      text: None,
+      ttl: None,
+    }
+  }
+
+  #[test]
+  fn entries() {
+    let mut entry = gen_entry("api.test.com", None);
+    entry.ip_address = "::1".to_owned();
+    let record = gen_entry_record(&entry).unwrap();
+    assert_eq!(record.record_type(), RecordType::AAAA);
+    assert_eq!(record.ttl(), DEFAULT_TTL);
+    entry.kind = Some(DnsRecordType::A);
+    assert!(gen_entry_record(&entry).is_err());
+    let mut entry = gen_entry("*.test.com", Some(DnsRecordType::MX));
+    assert!(gen_entry_record(&entry).is_err());
+    entry.target = Some("mail.test.com".to_owned());
+    entry.ttl = Some(300);
+    let record = gen_entry_record(&entry).unwrap();
+    assert_eq!(record.record_type(), RecordType::MX);
+    assert_eq!(record.ttl(), 300);
+    entry.name = "www.*.test.com".to_owned();
+    assert!(gen_entry_record(&entry).is_err());
+    let mut entry = gen_entry("_http._tcp.test.com", Some(DnsRecordType::SRV));
+    entry.target = Some("app.global.nanocl.internal".to_owned());
+    assert!(gen_entry_record(&entry).is_err());
+    entry.port = Some(80);
+    assert!(gen_entry_record(&entry).is_ok());
+    let mut entry = gen_entry("test.com", Some(DnsRecordType::TXT));
+    entry.text = Some(vec!["a".repeat(MAX_TXT_LENGTH + 1)]);
+    assert!(gen_entry_record(&entry).is_err());
+  }
+
+  #[test]
+  fn resolve() {
+    let zone = Zone::default();
+    zone.set_namespace(
+      "global",
+      vec![Record::from_rdata(
+        parse_name("app.global.nanocl.internal").unwrap(),
+        10,
+        RData::A(A(Ipv4Addr::new(10, 0, 0, 2))),
+      )],
+    );
+    let mut www = gen_entry("www.test.com", Some(DnsRecordType::CNAME));
+    www.target = Some("app.global.nanocl.internal".to_owned());
+    let mut wildcard = gen_entry("*.test.com", None);
+    wildcard.ip_address = "10.0.0.3".to_owned();
+    let mut srv = gen_entry("_http._tcp.test.com", Some(DnsRecordType::SRV));
+    srv.target = Some("app.global.nanocl.internal".to_owned());
+    srv.port = Some(80);
+    let mut txt = gen_entry("_http._tcp.test.com", Some(DnsRecordType::TXT));
+    txt.text = Some(vec!["path=/".to_owned()]);
+    let records = [www, wildcard, srv, txt]
+      .iter()
+      .map(gen_entry_record)
+      .collect::<IoResult<Vec<_>>>()
+      .unwrap();
+    zone.set_networks(HashMap::from([("Private".to_owned(), records)]));
+    let response = zone
+      .resolve("Private", &gen_request("WWW.test.com", RecordType::A))
+      .unwrap();
+    assert_eq!(response.id(), 42);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers().len(), 2);
+    assert_eq!(response.answers()[1].record_type(), RecordType::A);
+    // The wildcard answer with the name of the query
+    let request = gen_request("api.v1.test.com", RecordType::A);
+    let response = zone.resolve("Private", &request).unwrap();
+    assert_eq!(response.answers().len(), 1);
+    assert_eq!(
+      response.answers()[0].name(),
+      &parse_name("api.v1.test.com").unwrap()
+    );
+    for query_type in [RecordType::SRV, RecordType::TXT] {
+      let request = gen_request("_http._tcp.test.com", query_type);
+      let response = zone.resolve("Private", &request).unwrap();
+      assert_eq!(response.answers().len(), 1);
+      assert_eq!(response.answers()[0].record_type(), query_type);
+    }
+    // Records of a network are not served on the others
+    let request = gen_request("www.test.com", RecordType::A);
+    assert!(zone.resolve("Public", &request).is_none());
+    let request = gen_request("app.global.nanocl.internal", RecordType::AAAA);
+    let response = zone.resolve("Public", &request).unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.answers().is_empty());
+    let request = gen_request("none.global.nanocl.internal", RecordType::A);
+    let response = zone.resolve("Public", &request).unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    // A cargo without record is removed without changing the others
+    let name = parse_name("app.global.nanocl.internal").unwrap();
+    zone.set_target("global", &name, Vec::new());
+    let request = gen_request("app.global.nanocl.internal", RecordType::A);
+    let response = zone.resolve("Public", &request).unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    let request = gen_request("www.test.com", RecordType::A);
+    let response = zone.resolve("Private", &request).unwrap();
+    assert_eq!(response.answers().len(), 1);
+  }
+}
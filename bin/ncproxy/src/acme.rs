@@ -0,0 +1,327 @@
+use std::sync::{Arc, Mutex};
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use instant_acme::{
+  Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier,
+  NewAccount, NewOrder, Order, OrderStatus,
+};
+
+use nanocl_utils::io_error::{IoError, FromIo, IoResult};
+
+use nanocld_client::NanocldClient;
+use nanocld_client::stubs::proxy::{ProxyAcmeConfig, ProxySslConfig};
+use nanocld_client::stubs::secret::{SecretPartial, SecretUpdate};
+
+use crate::nginx::Nginx;
+
+/// Name of the secret where the ACME account credentials are stored
+const ACCOUNT_SECRET: &str = "ncproxy-acme-account";
+/// Number of times the state of an order is polled before giving up
+const MAX_POLL: u32 = 10;
+
+fn acme_error(err: impl std::fmt::Display) -> IoError {
+  IoError::interupted("Acme", err.to_string().as_str())
+}
+
+/// Issue and renew certificates with ACME HTTP-01.
+/// The challenges are written in the challenge directory of nginx
+/// and the certificates are stored as `Tls` secrets in nanocld.
+#[derive(Clone)]
+pub struct Acme {
+  /// Directory url of the ACME server
+  pub directory_url: String,
+  /// Directory where the challenges are written
+  pub challenge_dir: String,
+  /// Renew the certificates expiring within this duration
+  pub renew_before: Duration,
+  /// Domains with an order in progress
+  pending: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Acme {
+  pub fn new(
+    directory_url: &str,
+    renew_before_days: u64,
+    nginx: &Nginx,
+  ) -> Self {
+    Self {
+      directory_url: directory_url.to_owned(),
+      challenge_dir: nginx.acme_challenge_dir.to_owned(),
+      renew_before: Duration::from_secs(renew_before_days * 24 * 60 * 60),
+      pending: Arc::new(Mutex::new(HashSet::new())),
+    }
+  }
+
+  /// Load the account saved for the current directory or create a new one
+  async fn get_account(
+    &self,
+    email: Option<&str>,
+    client: &NanocldClient,
+  ) -> IoResult<Account> {
+    let secret = client.inspect_secret(ACCOUNT_SECRET).await.ok();
+    if let Some(secret) = &secret {
+      if secret.data["DirectoryUrl"] == self.directory_url.as_str() {
+        let credentials = serde_json::from_value::<AccountCredentials>(
+          secret.data["Credentials"].clone(),
+        )
+        .map_err(|err| err.map_err_context(|| "AccountCredentials"))?;
+        return Account::from_credentials(credentials)
+          .await
+          .map_err(acme_error);
+      }
+    }
+    let contact = email.map(|email| format!("mailto:{email}"));
+    let contact = contact.as_deref().map(|c| vec![c]).unwrap_or_default();
+    let (account, credentials) = Account::create(
+      &NewAccount {
+        contact: &contact,
+        terms_of_service_agreed: true,
+        only_return_existing: false,
+      },
+      &self.directory_url,
+      None,
+    )
+    .await
+    .map_err(acme_error)?;
+    let data = serde_json::json!({
+      "DirectoryUrl": self.directory_url,
+      "Credentials": credentials,
+    });
+    match secret {
+      Some(_) => {
+        let update = SecretUpdate {
+          data,
+          metadata: None,
+        };
+        client.patch_secret(ACCOUNT_SECRET, &update).await?;
+      }
+      None => {
+        let item = SecretPartial {
+          key: ACCOUNT_SECRET.to_owned(),
+          kind: "AcmeAccount".to_owned(),
+          immutable: None,
+          data,
+          metadata: None,
+        };
+        client.create_secret(&item).await?;
+      }
+    }
+    log::info!("Created ACME account on {}", self.directory_url);
+    Ok(account)
+  }
+
+  /// Wait for the order to leave the pending and processing states
+  async fn wait_order(&self, order: &mut Order) -> IoResult<OrderStatus> {
+    let mut delay = Duration::from_millis(250);
+    for _ in 0..MAX_POLL {
+      let state = order.refresh().await.map_err(acme_error)?;
+      match state.status {
+        OrderStatus::Pending | OrderStatus::Processing => {}
+        status => return Ok(status),
+      }
+      ntex::time::sleep(delay).await;
+      delay *= 2;
+    }
+    Err(acme_error("Timeout while waiting for the order"))
+  }
+
+  /// Answer the HTTP-01 challenges of an order
+  /// and return the paths of the written challenges
+  async fn set_challenges(&self, order: &mut Order) -> IoResult<Vec<String>> {
+    let authorizations = order.authorizations().await.map_err(acme_error)?;
+    let mut paths = Vec::new();
+    for authorization in &authorizations {
+      match authorization.status {
+        AuthorizationStatus::Pending => {}
+        AuthorizationStatus::Valid => continue,
+        status => {
+          return Err(acme_error(format!(
+            "Unexpected authorization status {status:?}"
+          )))
+        }
+      }
+      let challenge = authorization
+        .challenges
+        .iter()
+        .find(|challenge| challenge.r#type == ChallengeType::Http01)
+        .ok_or_else(|| acme_error("No HTTP-01 challenge found"))?;
+      let key_authorization = order.key_authorization(challenge);
+      let path = format!("{}/{}", self.challenge_dir, challenge.token);
+      tokio::fs::write(&path, key_authorization.as_str()).await?;
+      paths.push(path);
+      order
+        .set_challenge_ready(&challenge.url)
+        .await
+        .map_err(acme_error)?;
+    }
+    Ok(paths)
+  }
+
+  /// Order a certificate for the domain
+  /// and return the certificate chain and the private key
+  async fn order(
+    &self,
+    domain: &str,
+    config: &ProxyAcmeConfig,
+    client: &NanocldClient,
+  ) -> IoResult<(String, String)> {
+    let account = self.get_account(config.email.as_deref(), client).await?;
+    let mut order = account
+      .new_order(&NewOrder {
+        identifiers: &[Identifier::Dns(domain.to_owned())],
+      })
+      .await
+      .map_err(acme_error)?;
+    let paths = self.set_challenges(&mut order).await;
+    let status = match &paths {
+      Ok(_) => self.wait_order(&mut order).await,
+      Err(_) => Ok(OrderStatus::Invalid),
+    };
+    for path in paths.as_deref().unwrap_or_default() {
+      let _ = tokio::fs::remove_file(path).await;
+    }
+    paths?;
+    let status = status?;
+    if status != OrderStatus::Ready {
+      return Err(acme_error(format!(
+        "Order for {domain} is {status:?} instead of ready"
+      )));
+    }
+    let mut params = rcgen::CertificateParams::new(vec![domain.to_owned()]);
+    params.distinguished_name = rcgen::DistinguishedName::new();
+    let certificate =
+      rcgen::Certificate::from_params(params).map_err(acme_error)?;
+    let csr = certificate.serialize_request_der().map_err(acme_error)?;
+    order.finalize(&csr).await.map_err(acme_error)?;
+    let mut delay = Duration::from_millis(250);
+    for _ in 0..MAX_POLL {
+      if let Some(chain) = order.certificate().await.map_err(acme_error)? {
+        return Ok((chain, certificate.serialize_private_key_pem()));
+      }
+      ntex::time::sleep(delay).await;
+      delay *= 2;
+    }
+    Err(acme_error(format!(
+      "Timeout while waiting for the certificate of {domain}"
+    )))
+  }
+
+  /// Save the certificate of a domain in his `Tls` secret
+  async fn save_certificate(
+    &self,
+    secret: &str,
+    domain: &str,
+    certificate: String,
+    certificate_key: String,
+    client: &NanocldClient,
+  ) -> IoResult<()> {
+    let data = serde_json::to_value(ProxySslConfig {
+      certificate,
+      certificate_key,
+      certificate_client: None,
+      verify_client: None,
+      dh_param: None,
+    })
+    .map_err(|err| err.map_err_context(|| "ProxySslConfig"))?;
+    let metadata = Some(serde_json::json!({ "AcmeDomain": domain }));
+    if client.inspect_secret(secret).await.is_ok() {
+      let update = SecretUpdate { data, metadata };
+      client.patch_secret(secret, &update).await?;
+      return Ok(());
+    }
+    let item = SecretPartial {
+      key: secret.to_owned(),
+      kind: "Tls".to_owned(),
+      immutable: None,
+      data,
+      metadata,
+    };
+    client.create_secret(&item).await?;
+    Ok(())
+  }
+
+  /// Check if the certificate stored in the secret is missing or expire soon
+  async fn need_renewal(&self, secret: &str, client: &NanocldClient) -> bool {
+    let Ok(secret) = client.inspect_secret(secret).await else {
+      return true;
+    };
+    let Ok(ssl) = serde_json::from_value::<ProxySslConfig>(secret.data) else {
+      return true;
+    };
+    let Some(not_after) = get_not_after(&ssl.certificate) else {
+      return true;
+    };
+    let renew_at = SystemTime::now() + self.renew_before;
+    let renew_at = renew_at
+      .duration_since(UNIX_EPOCH)
+      .unwrap_or_default()
+      .as_secs() as i64;
+    renew_at >= not_after
+  }
+
+  /// Issue or renew the certificate of a domain when needed.
+  /// Return true if a new certificate has been issued.
+  pub async fn ensure_certificate(
+    &self,
+    domain: &str,
+    config: &ProxyAcmeConfig,
+    client: &NanocldClient,
+  ) -> IoResult<bool> {
+    let secret = config.secret_name(domain);
+    if !self.need_renewal(&secret, client).await {
+      return Ok(false);
+    }
+    {
+      let mut pending = self.pending.lock().map_err(acme_error)?;
+      if !pending.insert(domain.to_owned()) {
+        log::debug!("An order is already in progress for {domain}");
+        return Ok(false);
+      }
+    }
+    log::info!("Ordering ACME certificate for {domain}");
+    let res = match self.order(domain, config, client).await {
+      Ok((certificate, certificate_key)) => {
+        self
+          .save_certificate(
+            &secret,
+            domain,
+            certificate,
+            certificate_key,
+            client,
+          )
+          .await
+      }
+      Err(err) => Err(err),
+    };
+    if let Ok(mut pending) = self.pending.lock() {
+      pending.remove(domain);
+    }
+    res?;
+    log::info!("ACME certificate for {domain} saved in secret {secret}");
+    Ok(true)
+  }
+}
+
+/// Get the expiry date of a pem certificate as a unix timestamp
+fn get_not_after(certificate: &str) -> Option<i64> {
+  let (_, pem) =
+    x509_parser::pem::parse_x509_pem(certificate.as_bytes()).ok()?;
+  let certificate = pem.parse_x509().ok()?;
+  Some(certificate.validity().not_after.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn not_after() {
+    let mut params = rcgen::CertificateParams::new(vec!["example.com".into()]);
+    params.not_after = rcgen::date_time_ymd(2030, 1, 1);
+    let certificate = rcgen::Certificate::from_params(params).unwrap();
+    let pem = certificate.serialize_pem().unwrap();
+    assert_eq!(get_not_after(&pem), Some(1893456000));
+    assert_eq!(get_not_after("not a certificate"), None);
+  }
+}
@@ -5,6 +5,19 @@ pub struct Cli {
   /// Path to nginx config directory
   #[clap(long)]
   pub conf_dir: Option<String>,
+  /// Directory url of the ACME server used to issue certificates
+  #[clap(
+    long,
+    default_value = "https://acme-v02.api.letsencrypt.org/directory"
+  )]
+  pub acme_directory_url: String,
+  /// Directory where the ACME HTTP-01 challenges are written,
+  /// it must be shared with nginx
+  #[clap(long, default_value = "/opt/acme-challenge")]
+  pub acme_challenge_dir: String,
+  /// Number of days before expiry to renew an ACME certificate
+  #[clap(long, default_value = "30")]
+  pub acme_renew_before: u64,
 }
 
 #[cfg(test)]
@@ -15,8 +28,20 @@ mod tests {
   fn parse() {
     let args = Cli::parse_from(["nanocl-ncproxy", "--conf-dir", "/etc/nginx"]);
     assert_eq!(args.conf_dir, Some("/etc/nginx".into()));
+    assert_eq!(
+      args.acme_directory_url,
+      "https://acme-v02.api.letsencrypt.org/directory"
+    );
+    assert_eq!(args.acme_challenge_dir, "/opt/acme-challenge");
+    assert_eq!(args.acme_renew_before, 30);
     let args = Cli::parse_from(["nanocl-ncproxy"]);
     assert_eq!(args.conf_dir, None);
+    let args = Cli::parse_from([
+      "nanocl-ncproxy",
+      "--acme-directory-url",
+      "https://pebble:14000/dir",
+    ]);
+    assert_eq!(args.acme_directory_url, "https://pebble:14000/dir");
     let _ = Cli::try_parse();
   }
 }
@@ -0,0 +1,292 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+
+use nanocld_client::stubs::proxy::{ProxyHealthCheck, UpstreamTarget};
+
+use crate::nginx::NginxConfKind;
+
+/// An upstream whose instances are health checked
+#[derive(Clone, Debug)]
+pub struct CheckedUpstream {
+  /// Kind of the conf where the upstream is written
+  pub kind: NginxConfKind,
+  /// Key of the targeted cargo or vm
+  pub target_key: String,
+  /// Port of the targeted cargo or vm
+  pub port: u16,
+  /// Ip addresses of all the instances
+  pub addresses: Vec<String>,
+  /// The health check to run
+  pub check: ProxyHealthCheck,
+  /// When the instances were checked for the last time
+  last_check: Option<Instant>,
+}
+
+impl CheckedUpstream {
+  pub fn new(
+    kind: NginxConfKind,
+    target: &UpstreamTarget,
+    addresses: Vec<String>,
+    check: ProxyHealthCheck,
+  ) -> Self {
+    Self {
+      kind,
+      target_key: target.key.clone(),
+      port: target.port,
+      addresses,
+      check,
+      last_check: None,
+    }
+  }
+
+  fn interval(&self) -> Duration {
+    Duration::from_secs(self.check.interval.unwrap_or(10))
+  }
+}
+
+/// Health of an instance with the number of consecutive results
+#[derive(Clone, Debug)]
+struct InstanceHealth {
+  healthy: bool,
+  failures: u32,
+  successes: u32,
+}
+
+impl Default for InstanceHealth {
+  fn default() -> Self {
+    Self {
+      healthy: true,
+      failures: 0,
+      successes: 0,
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+  upstreams: HashMap<String, CheckedUpstream>,
+  instances: HashMap<(String, String), InstanceHealth>,
+  /// When each rule registered each upstream
+  rules: HashMap<(String, String), Instant>,
+}
+
+impl Inner {
+  /// Stop checking the upstreams no longer registered by a rule
+  fn prune(&mut self) {
+    let rules = &self.rules;
+    self.upstreams.retain(|upstream_key, _| {
+      rules.keys().any(|(_, key)| key == upstream_key)
+    });
+    let upstreams = &self.upstreams;
+    self
+      .instances
+      .retain(|(upstream_key, _), _| upstreams.contains_key(upstream_key));
+  }
+}
+
+/// Health of the instances of the upstreams having a health check
+/// Instances are healthy until they reach the unhealthy threshold
+#[derive(Clone, Debug, Default)]
+pub struct UpstreamHealth {
+  inner: Arc<Mutex<Inner>>,
+}
+
+impl UpstreamHealth {
+  /// Register or update an upstream to check for a rule
+  pub fn register(
+    &self,
+    name: &str,
+    upstream_key: &str,
+    upstream: CheckedUpstream,
+  ) {
+    let Ok(mut inner) = self.inner.lock() else {
+      return;
+    };
+    inner
+      .rules
+      .insert((name.to_owned(), upstream_key.to_owned()), Instant::now());
+    inner.instances.retain(|(key, address), _| {
+      key != upstream_key || upstream.addresses.contains(address)
+    });
+    let last_check = inner
+      .upstreams
+      .get(upstream_key)
+      .and_then(|upstream| upstream.last_check);
+    inner.upstreams.insert(
+      upstream_key.to_owned(),
+      CheckedUpstream {
+        last_check,
+        ..upstream
+      },
+    );
+  }
+
+  /// Remove the upstreams registered by a rule before a time,
+  /// or all of them when no time is given.
+  /// An upstream is still checked while another rule registered it
+  pub fn unregister(&self, name: &str, before: Option<Instant>) {
+    let Ok(mut inner) = self.inner.lock() else {
+      return;
+    };
+    inner.rules.retain(|(rule, _), registered_at| {
+      rule != name || before.is_some_and(|before| *registered_at >= before)
+    });
+    inner.prune();
+  }
+
+  /// Get the addresses of the healthy instances of an upstream
+  /// If all the instances are unhealthy they are all returned
+  /// so the upstream is never empty
+  pub fn filter_healthy(
+    &self,
+    upstream_key: &str,
+    addresses: &[String],
+  ) -> Vec<String> {
+    let Ok(inner) = self.inner.lock() else {
+      return addresses.to_vec();
+    };
+    let healthy = addresses
+      .iter()
+      .filter(|address| {
+        inner
+          .instances
+          .get(&(upstream_key.to_owned(), address.to_string()))
+          .map(|instance| instance.healthy)
+          .unwrap_or(true)
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+    if healthy.is_empty() {
+      log::warn!("All instances of {upstream_key} are unhealthy keeping them");
+      return addresses.to_vec();
+    }
+    healthy
+  }
+
+  /// Get the upstreams that must be checked now
+  pub fn due(&self) -> Vec<(String, CheckedUpstream)> {
+    let Ok(mut inner) = self.inner.lock() else {
+      return Vec::new();
+    };
+    let now = Instant::now();
+    inner
+      .upstreams
+      .iter_mut()
+      .filter(|(_, upstream)| match upstream.last_check {
+        Some(last_check) => {
+          now.duration_since(last_check) >= upstream.interval()
+        }
+        None => true,
+      })
+      .map(|(key, upstream)| {
+        upstream.last_check = Some(now);
+        (key.clone(), upstream.clone())
+      })
+      .collect()
+  }
+
+  /// Get an upstream by key
+  pub fn get(&self, upstream_key: &str) -> Option<CheckedUpstream> {
+    let inner = self.inner.lock().ok()?;
+    inner.upstreams.get(upstream_key).cloned()
+  }
+
+  /// Record the result of a check of an instance
+  /// Return the new health of the instance when it changed
+  pub fn record(
+    &self,
+    upstream_key: &str,
+    address: &str,
+    success: bool,
+    check: &ProxyHealthCheck,
+  ) -> Option<bool> {
+    let mut inner = self.inner.lock().ok()?;
+    let instance = inner
+      .instances
+      .entry((upstream_key.to_owned(), address.to_owned()))
+      .or_default();
+    if success {
+      instance.failures = 0;
+      instance.successes += 1;
+      if !instance.healthy
+        && instance.successes >= check.healthy_threshold.unwrap_or(2)
+      {
+        instance.healthy = true;
+        return Some(true);
+      }
+    } else {
+      instance.successes = 0;
+      instance.failures += 1;
+      if instance.healthy
+        && instance.failures >= check.unhealthy_threshold.unwrap_or(3)
+      {
+        instance.healthy = false;
+        return Some(false);
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn transitions() {
+    let health = UpstreamHealth::default();
+    let check = ProxyHealthCheck {
+      path: "/health".into(),
+      expected_status: None,
+      interval: None,
+      timeout: None,
+      unhealthy_threshold: Some(2),
+      healthy_threshold: Some(1),
+    };
+    let target = UpstreamTarget {
+      key: "app.global.c".into(),
+      port: 80,
+      path: None,
+      disable_logging: None,
+      health_check: Some(check.clone()),
+    };
+    let addresses = vec!["10.0.0.1".to_owned(), "10.0.0.2".to_owned()];
+    let upstream = CheckedUpstream::new(
+      NginxConfKind::Site,
+      &target,
+      addresses.clone(),
+      check.clone(),
+    );
+    health.register("app", "cargo-app-80", upstream.clone());
+    health.register("canary", "cargo-app-80", upstream);
+    assert_eq!(health.due().len(), 1);
+    assert!(health.due().is_empty());
+    assert_eq!(
+      health.record("cargo-app-80", "10.0.0.1", false, &check),
+      None
+    );
+    assert_eq!(
+      health.record("cargo-app-80", "10.0.0.1", false, &check),
+      Some(false)
+    );
+    assert_eq!(
+      health.filter_healthy("cargo-app-80", &addresses),
+      vec!["10.0.0.2".to_owned()]
+    );
+    for _ in 0..2 {
+      health.record("cargo-app-80", "10.0.0.2", false, &check);
+    }
+    assert_eq!(health.filter_healthy("cargo-app-80", &addresses), addresses);
+    assert_eq!(
+      health.record("cargo-app-80", "10.0.0.1", true, &check),
+      Some(true)
+    );
+    // The upstream is still checked for the other rule
+    health.unregister("canary", None);
+    assert!(health.get("cargo-app-80").is_some());
+    // Registrations not refreshed by a new render are removed
+    health.unregister("app", Some(Instant::now()));
+    assert!(health.get("cargo-app-80").is_none());
+  }
+}
@@ -3,7 +3,9 @@ use clap::Parser;
 use nanocl_utils::logger;
 
 mod cli;
+mod acme;
 mod nginx;
+mod health;
 mod utils;
 mod server;
 mod version;
@@ -1,11 +1,16 @@
 use std::fs;
+use std::sync::Arc;
 use std::str::FromStr;
 
+use futures::lock::{Mutex, MutexGuard};
+
 use nanocl_utils::io_error::{IoError, FromIo, IoResult};
 
 use nanocld_client::stubs::proxy::ProxyRule;
 
-#[derive(Debug)]
+use crate::health::UpstreamHealth;
+
+#[derive(Clone, Debug)]
 pub enum NginxConfKind {
   Site,
   Stream,
@@ -52,23 +57,59 @@ impl FromStr for NginxConfKind {
 #[derive(Clone, Debug)]
 pub struct Nginx {
   pub conf_dir: String,
+  /// Directory where the ACME HTTP-01 challenges are served from
+  pub acme_challenge_dir: String,
+  /// Directory of the certificates and htpasswd files used by the confs
+  pub secrets_dir: String,
+  /// Health of the instances of the upstreams having a health check
+  pub upstream_health: UpstreamHealth,
+  /// Lock held while the confs are staged and swapped
+  staging_lock: Arc<Mutex<()>>,
 }
 
 impl Nginx {
   pub fn new(conf_dir: &str) -> Self {
     Self {
       conf_dir: conf_dir.to_owned(),
+      acme_challenge_dir: "/opt/acme-challenge".to_owned(),
+      secrets_dir: "/opt/secrets".to_owned(),
+      upstream_health: UpstreamHealth::default(),
+      staging_lock: Arc::new(Mutex::new(())),
+    }
+  }
+
+  pub fn with_acme_challenge_dir(mut self, acme_challenge_dir: &str) -> Self {
+    self.acme_challenge_dir = acme_challenge_dir.to_owned();
+    self
+  }
+
+  #[cfg(test)]
+  pub fn with_secrets_dir(mut self, secrets_dir: &str) -> Self {
+    self.secrets_dir = secrets_dir.to_owned();
+    self
+  }
+
+  #[inline]
+  fn gen_conf_dir(&self, kind: &NginxConfKind) -> String {
+    match kind {
+      NginxConfKind::Site => format!("{}/sites-enabled", &self.conf_dir),
+      NginxConfKind::Stream => format!("{}/streams-enabled", &self.conf_dir),
     }
   }
 
   #[inline]
   fn gen_conf_path(&self, name: &str, kind: &NginxConfKind) -> String {
+    format!("{}/{name}.conf", self.gen_conf_dir(kind))
+  }
+
+  #[inline]
+  fn gen_known_good_dir(&self, kind: &NginxConfKind) -> String {
     match kind {
       NginxConfKind::Site => {
-        format!("{}/sites-enabled/{name}.conf", &self.conf_dir)
+        format!("{}/staging/known-good/sites-enabled", &self.conf_dir)
       }
       NginxConfKind::Stream => {
-        format!("{}/streams-enabled/{name}.conf", &self.conf_dir)
+        format!("{}/staging/known-good/streams-enabled", &self.conf_dir)
       }
     }
   }
@@ -96,6 +137,27 @@ impl Nginx {
         format!("Cannot create directory {streams_enabled_dir}")
       })
     })?;
+    // Ensure staging directories exists
+    for kind in [NginxConfKind::Site, NginxConfKind::Stream] {
+      let known_good_dir = self.gen_known_good_dir(&kind);
+      fs::create_dir_all(&known_good_dir).map_err(|err| {
+        err.map_err_context(|| {
+          format!("Cannot create directory {known_good_dir}")
+        })
+      })?;
+    }
+    // Ensure secrets directory exists
+    fs::create_dir_all(&self.secrets_dir).map_err(|err| {
+      err.map_err_context(|| {
+        format!("Cannot create directory {}", self.secrets_dir)
+      })
+    })?;
+    // Ensure acme challenge directory exists
+    fs::create_dir_all(&self.acme_challenge_dir).map_err(|err| {
+      err.map_err_context(|| {
+        format!("Cannot create directory {}", self.acme_challenge_dir)
+      })
+    })?;
     Ok(())
   }
 
@@ -139,10 +201,211 @@ impl Nginx {
 
   #[inline]
   pub async fn delete_conf_file(&self, name: &str) {
+    let _lock = self.lock_staging().await;
+    self.upstream_health.unregister(name, None);
     let path = self.gen_conf_path(name, &NginxConfKind::Site);
     let _ = tokio::fs::remove_file(&path).await;
     let path = self.gen_conf_path(name, &NginxConfKind::Stream);
     let _ = tokio::fs::remove_file(&path).await;
+    if let Err(err) = self.remove_stale_secrets() {
+      log::warn!("{err}");
+    }
+  }
+
+  /// ## Write secret file
+  ///
+  /// Write a certificate or a htpasswd file in the secrets directory.
+  /// The file name must be unique to his content so the files used
+  /// by the live confs are never changed before the staged confs are swapped
+  ///
+  /// ## Arguments
+  ///
+  /// * [file_name](str) - The name of the file
+  /// * [data](str) - The content of the file
+  ///
+  /// ## Returns
+  ///
+  /// * [Ok](String) - The path of the file
+  /// * [Err](IoError) - The file cannot be written
+  ///
+  pub fn write_secret_file(
+    &self,
+    file_name: &str,
+    data: &str,
+  ) -> IoResult<String> {
+    let path = format!("{}/{file_name}", self.secrets_dir);
+    if fs::read_to_string(&path).is_ok_and(|current| current == data) {
+      return Ok(path);
+    }
+    swap_file(&path, data)?;
+    Ok(path)
+  }
+
+  /// ## Remove stale secrets
+  ///
+  /// Remove the files of the secrets directory no longer used
+  /// by the live or the known good confs
+  ///
+  /// ## Returns
+  ///
+  /// * [Ok](Ok) - The stale files have been removed
+  /// * [Err](IoError) - A directory cannot be read
+  ///
+  pub fn remove_stale_secrets(&self) -> IoResult<()> {
+    let mut confs = String::new();
+    for kind in [NginxConfKind::Site, NginxConfKind::Stream] {
+      for dir in [self.gen_conf_dir(&kind), self.gen_known_good_dir(&kind)] {
+        for file_name in list_confs(&dir)? {
+          confs += &fs::read_to_string(format!("{dir}/{file_name}"))
+            .unwrap_or_default();
+        }
+      }
+    }
+    let entries = fs::read_dir(&self.secrets_dir).map_err(|err| {
+      err.map_err_context(|| format!("Unable to read {}", self.secrets_dir))
+    })?;
+    for entry in entries.flatten() {
+      let path = entry.path().to_string_lossy().to_string();
+      if !entry.path().is_file() || confs.contains(&path) {
+        continue;
+      }
+      let _ = fs::remove_file(&path);
+    }
+    Ok(())
+  }
+
+  /// ## Lock staging
+  ///
+  /// Wait for the confs to be no longer staged or swapped by someone else
+  ///
+  /// ## Returns
+  ///
+  /// * [MutexGuard](MutexGuard) - Release the lock when dropped
+  ///
+  pub async fn lock_staging(&self) -> MutexGuard<'_, ()> {
+    self.staging_lock.lock().await
+  }
+
+  /// ## Staging
+  ///
+  /// Get a copy of nginx writing his confs in the staging directory
+  ///
+  /// ## Returns
+  ///
+  /// * [Nginx](Nginx) - The staging nginx
+  ///
+  pub fn staging(&self) -> Self {
+    Self {
+      conf_dir: format!("{}/staging", self.conf_dir),
+      ..self.clone()
+    }
+  }
+
+  /// ## Prepare staging
+  ///
+  /// Replace the confs of the staging directory by a copy of the live ones
+  /// so a rule can be rendered and tested with the others before the swap
+  ///
+  /// ## Returns
+  ///
+  /// * [Ok](Ok) - The staging directory is ready
+  /// * [Err](IoError) - The staging directory cannot be written
+  ///
+  pub fn prepare_staging(&self) -> IoResult<()> {
+    let staging = self.staging();
+    for kind in [NginxConfKind::Site, NginxConfKind::Stream] {
+      let staging_dir = staging.gen_conf_dir(&kind);
+      if fs::metadata(&staging_dir).is_ok() {
+        fs::remove_dir_all(&staging_dir).map_err(|err| {
+          err.map_err_context(|| format!("Cannot remove {staging_dir}"))
+        })?;
+      }
+      fs::create_dir_all(&staging_dir).map_err(|err| {
+        err.map_err_context(|| format!("Cannot create directory {staging_dir}"))
+      })?;
+      let live_dir = self.gen_conf_dir(&kind);
+      for file_name in list_confs(&live_dir)? {
+        let path = format!("{live_dir}/{file_name}");
+        fs::copy(&path, format!("{staging_dir}/{file_name}")).map_err(
+          |err| err.map_err_context(|| format!("Unable to stage {path}")),
+        )?;
+      }
+    }
+    Ok(())
+  }
+
+  /// ## Commit staging
+  ///
+  /// Swap the staged confs that differ from the live ones.
+  /// Each conf is renamed over the live one so nginx never reads it partially
+  /// and the previous version is kept as known good.
+  ///
+  /// ## Returns
+  ///
+  /// * [Ok](Ok) - The kind and file name of the swapped confs
+  /// * [Err](IoError) - A conf cannot be swapped
+  ///
+  pub fn commit_staging(&self) -> IoResult<Vec<(NginxConfKind, String)>> {
+    let staging = self.staging();
+    let mut swapped = Vec::new();
+    for kind in [NginxConfKind::Site, NginxConfKind::Stream] {
+      let staging_dir = staging.gen_conf_dir(&kind);
+      let known_good_dir = self.gen_known_good_dir(&kind);
+      for file_name in list_confs(&staging_dir)? {
+        let staged_path = format!("{staging_dir}/{file_name}");
+        let data = fs::read_to_string(&staged_path).map_err(|err| {
+          err.map_err_context(|| format!("Unable to read {staged_path}"))
+        })?;
+        let live_path = format!("{}/{file_name}", self.gen_conf_dir(&kind));
+        let known_good_path = format!("{known_good_dir}/{file_name}");
+        match fs::read_to_string(&live_path) {
+          Ok(live) if live == data => continue,
+          Ok(live) => fs::write(&known_good_path, live).map_err(|err| {
+            err.map_err_context(|| format!("Unable to write {known_good_path}"))
+          })?,
+          // No known good version the conf is removed on restore
+          Err(_) => {
+            let _ = fs::remove_file(&known_good_path);
+          }
+        }
+        swap_file(&live_path, &data)?;
+        swapped.push((kind.clone(), file_name));
+      }
+    }
+    Ok(swapped)
+  }
+
+  /// ## Restore known good
+  ///
+  /// Put back the last known good version of confs swapped by
+  /// [commit_staging](Nginx::commit_staging)
+  ///
+  /// ## Arguments
+  ///
+  /// * [swapped](Vec<(NginxConfKind, String)>) - The confs to restore
+  ///
+  /// ## Returns
+  ///
+  /// * [Ok](Ok) - The confs have been restored
+  /// * [Err](IoError) - A conf cannot be restored
+  ///
+  pub fn restore_known_good(
+    &self,
+    swapped: &[(NginxConfKind, String)],
+  ) -> IoResult<()> {
+    for (kind, file_name) in swapped {
+      let live_path = format!("{}/{file_name}", self.gen_conf_dir(kind));
+      let known_good_path =
+        format!("{}/{file_name}", self.gen_known_good_dir(kind));
+      match fs::read_to_string(&known_good_path) {
+        Ok(data) => swap_file(&live_path, &data)?,
+        Err(_) => {
+          let _ = fs::remove_file(&live_path);
+        }
+      }
+      log::warn!("Restored known good version of {live_path}");
+    }
+    Ok(())
   }
 
   // TODO: Uncommand to enable sync resources
@@ -173,3 +436,92 @@ impl Nginx {
   //   Ok(())
   // }
 }
+
+/// List the file names of the confs in a directory
+fn list_confs(dir: &str) -> IoResult<Vec<String>> {
+  let entries = fs::read_dir(dir)
+    .map_err(|err| err.map_err_context(|| format!("Unable to read {dir}")))?;
+  let mut file_names = Vec::new();
+  for entry in entries {
+    let entry = entry
+      .map_err(|err| err.map_err_context(|| format!("Unable to read {dir}")))?;
+    let file_name = entry.file_name().to_string_lossy().to_string();
+    if file_name.ends_with(".conf") && entry.path().is_file() {
+      file_names.push(file_name);
+    }
+  }
+  Ok(file_names)
+}
+
+/// Write a file next to the destination then rename it over
+/// the destination so the content is replaced atomically
+fn swap_file(path: &str, data: &str) -> IoResult<()> {
+  let (dir, file_name) = path.rsplit_once('/').unwrap_or((".", path));
+  let tmp_path = format!("{dir}/.{file_name}.tmp");
+  fs::write(&tmp_path, data).map_err(|err| {
+    err.map_err_context(|| format!("Unable to create {tmp_path} file"))
+  })?;
+  fs::rename(&tmp_path, path).map_err(|err| {
+    err.map_err_context(|| format!("Unable to swap {path} file"))
+  })?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn staging() {
+    let nginx = Nginx::new("/tmp/nginx-staging-test")
+      .with_secrets_dir("/tmp/nginx-staging-test/secrets");
+    let _ = fs::remove_dir_all(&nginx.conf_dir);
+    nginx.ensure().unwrap();
+    nginx
+      .write_conf_file("app", "server {}", &NginxConfKind::Site)
+      .unwrap();
+    nginx.prepare_staging().unwrap();
+    let staging = nginx.staging();
+    staging
+      .write_conf_file("app", "server { listen 80; }", &NginxConfKind::Site)
+      .unwrap();
+    staging
+      .write_conf_file("db", "server {}", &NginxConfKind::Stream)
+      .unwrap();
+    let swapped = nginx.commit_staging().unwrap();
+    assert_eq!(swapped.len(), 2);
+    let app_path = nginx.gen_conf_path("app", &NginxConfKind::Site);
+    let db_path = nginx.gen_conf_path("db", &NginxConfKind::Stream);
+    assert_eq!(
+      fs::read_to_string(&app_path).unwrap(),
+      "server { listen 80; }"
+    );
+    nginx.restore_known_good(&swapped).unwrap();
+    assert_eq!(fs::read_to_string(&app_path).unwrap(), "server {}");
+    assert!(fs::metadata(db_path).is_err());
+    nginx.prepare_staging().unwrap();
+    assert!(nginx.commit_staging().unwrap().is_empty());
+    fs::remove_dir_all(&nginx.conf_dir).unwrap();
+  }
+
+  #[test]
+  fn secrets() {
+    let nginx = Nginx::new("/tmp/nginx-secrets-test")
+      .with_secrets_dir("/tmp/nginx-secrets-test/secrets");
+    let _ = fs::remove_dir_all(&nginx.conf_dir);
+    nginx.ensure().unwrap();
+    let used = nginx.write_secret_file("app-1.cert", "CERT").unwrap();
+    let stale = nginx.write_secret_file("app-2.cert", "NEW").unwrap();
+    nginx
+      .write_conf_file(
+        "app",
+        &format!("ssl_certificate {used};"),
+        &NginxConfKind::Site,
+      )
+      .unwrap();
+    nginx.remove_stale_secrets().unwrap();
+    assert!(fs::metadata(&used).is_ok());
+    assert!(fs::metadata(stale).is_err());
+    fs::remove_dir_all(&nginx.conf_dir).unwrap();
+  }
+}
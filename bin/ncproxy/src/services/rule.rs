@@ -18,6 +18,7 @@ use crate::nginx::Nginx;
   ),
   responses(
     (status = 200, description = "The created rule", body = ResourceProxyRule),
+    (status = 400, description = "The rule is invalid"),
   ),
 ))]
 #[web::put("/rules/{name}")]
@@ -34,12 +35,10 @@ pub async fn apply_rule(
       NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
   }
 
+  // The conf is validated in staging before being swapped in
+  // so an invalid rule is reported without touching the live confs
   utils::create_resource_conf(&path.1, &payload, &client, &nginx).await?;
-  if let Err(err) = utils::reload_config(&client).await {
-    nginx.delete_conf_file(&path.1).await;
-    utils::reload_config(&client).await?;
-    return Err(HttpError::bad_request(err.to_string()));
-  }
+  utils::reload_config(&client).await?;
 
   Ok(web::HttpResponse::Ok().json(&payload))
 }
@@ -0,0 +1,102 @@
+/// Issue and renew the ACME certificates of the http rules
+/// A first pass is made at startup then every RENEW_INTERVAL
+/// Certificates are also ensured when a ProxyRule is created or patched
+use std::time::Duration;
+
+use ntex::rt;
+
+use nanocl_utils::io_error::{IoResult, FromIo};
+
+use nanocld_client::NanocldClient;
+use nanocld_client::stubs::proxy::{ProxyRule, ProxySsl};
+use nanocld_client::stubs::resource::{ResourcePartial, ResourceQuery};
+
+use crate::utils;
+use crate::acme::Acme;
+use crate::nginx::Nginx;
+
+/// Interval between two checks of the certificates to renew
+const RENEW_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Ensure the ACME certificates of a ProxyRule resource
+/// and update his nginx configuration when a certificate has been issued
+pub(crate) async fn ensure_resource(
+  resource: &ResourcePartial,
+  acme: &Acme,
+  nginx: &Nginx,
+  client: &NanocldClient,
+) -> IoResult<()> {
+  let proxy_rule = utils::serialize_proxy_rule(resource)?;
+  let mut issued = false;
+  for rule in &proxy_rule.rules {
+    let ProxyRule::Http(rule) = rule else {
+      continue;
+    };
+    let (Some(ProxySsl::Acme(ssl)), Some(domain)) = (&rule.ssl, &rule.domain)
+    else {
+      continue;
+    };
+    match acme.ensure_certificate(domain, &ssl.acme, client).await {
+      Ok(true) => issued = true,
+      Ok(false) => {}
+      Err(err) => log::warn!("Unable to ensure certificate of {domain}: {err}"),
+    }
+  }
+  if !issued {
+    return Ok(());
+  }
+  utils::create_resource_conf(&resource.name, &proxy_rule, client, nginx)
+    .await?;
+  utils::reload_config(client).await?;
+  Ok(())
+}
+
+/// Ensure the ACME certificates of all ProxyRule resources
+async fn ensure_all(
+  acme: &Acme,
+  nginx: &Nginx,
+  client: &NanocldClient,
+) -> IoResult<()> {
+  let query = ResourceQuery {
+    kind: Some("ProxyRule".into()),
+    contains: None,
+  };
+  let resources = client.list_resource(Some(query)).await.map_err(|err| {
+    err.map_err_context(|| "Unable to list resources from nanocl daemon")
+  })?;
+  for resource in resources {
+    let resource: ResourcePartial = resource.into();
+    if let Err(err) = ensure_resource(&resource, acme, nginx, client).await {
+      log::warn!("{err}");
+    }
+  }
+  Ok(())
+}
+
+async fn r#loop(acme: &Acme, nginx: &Nginx, client: &NanocldClient) {
+  loop {
+    log::debug!("Checking ACME certificates to renew");
+    if let Err(err) = ensure_all(acme, nginx, client).await {
+      log::warn!("{err}");
+    }
+    ntex::time::sleep(RENEW_INTERVAL).await;
+  }
+}
+
+/// Spawn new thread with a loop renewing the ACME certificates
+pub(crate) fn spawn(acme: &Acme, nginx: &Nginx) {
+  let acme = acme.clone();
+  let nginx = nginx.clone();
+  rt::Arbiter::new().exec_fn(move || {
+    #[allow(unused)]
+    let mut client = NanocldClient::connect_with_unix_default();
+    #[cfg(any(feature = "dev", feature = "test"))]
+    {
+      client =
+        NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
+    }
+    ntex::rt::spawn(async move {
+      r#loop(&acme, &nginx, &client).await;
+    });
+  });
+}
@@ -13,8 +13,11 @@ use nanocld_client::stubs::resource::ResourcePartial;
 
 use crate::utils;
 use crate::version;
+use crate::acme::Acme;
 use crate::nginx::Nginx;
 
+use super::acme;
+
 /// Update the nginx configuration when a cargo is started, patched
 async fn update_cargo_rule(
   name: &str,
@@ -90,8 +93,28 @@ async fn update_resource_rule(
   Ok(())
 }
 
+/// Issue the ACME certificates of a resource in background
+fn spawn_ensure_acme(
+  resource: ResourcePartial,
+  acme: &Acme,
+  nginx: &Nginx,
+  client: &NanocldClient,
+) {
+  let acme = acme.clone();
+  let nginx = nginx.clone();
+  let client = client.clone();
+  rt::spawn(async move {
+    if let Err(err) =
+      acme::ensure_resource(&resource, &acme, &nginx, &client).await
+    {
+      log::warn!("{err}");
+    }
+  });
+}
+
 async fn on_event(
   event: Event,
+  acme: Acme,
   nginx: Nginx,
   client: NanocldClient,
 ) -> IoResult<()> {
@@ -112,6 +135,22 @@ async fn on_event(
         log::warn!("{err}");
       }
     }
+    Event::CargoReconciled(ev) => {
+      log::debug!("received cargo reconciled event: {ev:#?}");
+      if let Err(err) =
+        update_cargo_rule(&ev.name, &ev.namespace_name, &nginx, &client).await
+      {
+        log::warn!("{err}");
+      }
+    }
+    Event::CargoRolledBack(ev) => {
+      log::debug!("received cargo rolled back event: {ev:#?}");
+      if let Err(err) =
+        update_cargo_rule(&ev.name, &ev.namespace_name, &nginx, &client).await
+      {
+        log::warn!("{err}");
+      }
+    }
     Event::CargoStopped(ev) => {
       log::debug!("received cargo stopped event: {ev:#?}");
       if let Err(err) =
@@ -137,6 +176,7 @@ async fn on_event(
       if let Err(err) = update_resource_rule(&resource, &nginx, &client).await {
         log::warn!("{err}");
       }
+      spawn_ensure_acme(resource, &acme, &nginx, &client);
     }
     Event::ResourcePatched(ev) => {
       if ev.kind.as_str() != "ProxyRule" {
@@ -147,6 +187,7 @@ async fn on_event(
       if let Err(err) = update_resource_rule(&resource, &nginx, &client).await {
         log::warn!("{err}");
       }
+      spawn_ensure_acme(resource, &acme, &nginx, &client);
     }
     Event::ResourceDeleted(ev) => {
       if ev.kind.as_str() != "ProxyRule" {
@@ -231,7 +272,7 @@ async fn ensure_resource_config(client: &NanocldClient) {
   }
 }
 
-async fn r#loop(client: &NanocldClient, nginx: &Nginx) {
+async fn r#loop(client: &NanocldClient, acme: &Acme, nginx: &Nginx) {
   loop {
     log::info!("Subscribing to nanocl daemon events..");
     match client.watch_events().await {
@@ -247,7 +288,8 @@ async fn r#loop(client: &NanocldClient, nginx: &Nginx) {
           let Ok(event) = event else {
             break;
           };
-          if let Err(err) = on_event(event, nginx.clone(), client.clone()).await
+          if let Err(err) =
+            on_event(event, acme.clone(), nginx.clone(), client.clone()).await
           {
             log::warn!("{err}");
           }
@@ -262,7 +304,8 @@ async fn r#loop(client: &NanocldClient, nginx: &Nginx) {
 }
 
 /// Spawn new thread with event loop to watch for nanocld events
-pub(crate) fn spawn(nginx: &Nginx) {
+pub(crate) fn spawn(acme: &Acme, nginx: &Nginx) {
+  let acme = acme.clone();
   let nginx = nginx.clone();
   rt::Arbiter::new().exec_fn(move || {
     #[allow(unused)]
@@ -273,7 +316,7 @@ pub(crate) fn spawn(nginx: &Nginx) {
         NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
     }
     ntex::rt::spawn(async move {
-      r#loop(&client, &nginx).await;
+      r#loop(&client, &acme, &nginx).await;
     });
   });
 }
@@ -0,0 +1,146 @@
+/// Run the health checks of the upstreams generated with one
+/// The instances failing their check are removed from the upstream block
+/// and added back when they recover, each change is reported to nanocld
+use std::time::Duration;
+
+use ntex::rt;
+use ntex::http;
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+
+use nanocld_client::NanocldClient;
+use nanocld_client::stubs::proxy::{ProxyHealthCheck, ProxyUpstreamHealth};
+
+use crate::utils;
+use crate::nginx::Nginx;
+use crate::health::CheckedUpstream;
+
+/// Interval between two looks for upstreams to check
+const TICK: Duration = Duration::from_secs(1);
+
+/// Request the health check path of an instance
+async fn check_instance(
+  client: &http::client::Client,
+  address: &str,
+  port: u16,
+  check: &ProxyHealthCheck,
+) -> bool {
+  let url = format!("http://{address}:{port}{}", check.path);
+  let timeout = Duration::from_secs(check.timeout.unwrap_or(2));
+  let timeout = ntex::time::Millis::from(timeout);
+  match client.get(url.as_str()).timeout(timeout).send().await {
+    Ok(res) => res.status().as_u16() == check.expected_status.unwrap_or(200),
+    Err(err) => {
+      log::debug!("Health check of {url} failed: {err}");
+      false
+    }
+  }
+}
+
+/// Check the instances of an upstream
+/// and write it again when an instance changed of health
+async fn check_upstream(
+  upstream_key: String,
+  upstream: CheckedUpstream,
+  http_client: &http::client::Client,
+  nginx: &Nginx,
+  client: &NanocldClient,
+) -> bool {
+  let mut changes = Vec::new();
+  for address in &upstream.addresses {
+    let success =
+      check_instance(http_client, address, upstream.port, &upstream.check)
+        .await;
+    if let Some(healthy) = nginx.upstream_health.record(
+      &upstream_key,
+      address,
+      success,
+      &upstream.check,
+    ) {
+      changes.push(ProxyUpstreamHealth {
+        target_key: upstream.target_key.clone(),
+        port: upstream.port,
+        address: address.clone(),
+        healthy,
+      });
+    }
+  }
+  if changes.is_empty() {
+    return false;
+  }
+  // The upstream may have been updated while checking
+  let upstream = nginx.upstream_health.get(&upstream_key).unwrap_or(upstream);
+  let ip_addresses = nginx
+    .upstream_health
+    .filter_healthy(&upstream_key, &upstream.addresses);
+  {
+    let _lock = nginx.lock_staging().await;
+    if let Err(err) = utils::write_upstream(
+      &upstream.kind,
+      &upstream_key,
+      upstream.port,
+      &ip_addresses,
+      nginx,
+    ) {
+      log::warn!("{err}");
+    }
+  }
+  for change in changes {
+    log::info!(
+      "Instance {}:{} of {} is now {}",
+      change.address,
+      change.port,
+      change.target_key,
+      if change.healthy {
+        "healthy"
+      } else {
+        "unhealthy"
+      }
+    );
+    if let Err(err) = client.report_proxy_upstream_health(&change).await {
+      log::warn!("Unable to report upstream health: {err}");
+    }
+  }
+  true
+}
+
+async fn r#loop(nginx: &Nginx, client: &NanocldClient) {
+  let http_client = http::client::Client::build().finish();
+  loop {
+    let changed = nginx
+      .upstream_health
+      .due()
+      .into_iter()
+      .map(|(upstream_key, upstream)| {
+        check_upstream(upstream_key, upstream, &http_client, nginx, client)
+      })
+      .collect::<FuturesUnordered<_>>()
+      .collect::<Vec<_>>()
+      .await
+      .into_iter()
+      .any(|changed| changed);
+    if changed {
+      if let Err(err) = utils::reload_config(client).await {
+        log::warn!("{err}");
+      }
+    }
+    ntex::time::sleep(TICK).await;
+  }
+}
+
+/// Spawn new thread with a loop running the health checks
+pub(crate) fn spawn(nginx: &Nginx) {
+  let nginx = nginx.clone();
+  rt::Arbiter::new().exec_fn(move || {
+    #[allow(unused)]
+    let mut client = NanocldClient::connect_with_unix_default();
+    #[cfg(any(feature = "dev", feature = "test"))]
+    {
+      client =
+        NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
+    }
+    ntex::rt::spawn(async move {
+      r#loop(&nginx, &client).await;
+    });
+  });
+}
@@ -1,17 +1,25 @@
 use nanocl_utils::io_error::IoResult;
 
 use crate::cli::Cli;
+use crate::acme::Acme;
 use crate::nginx::Nginx;
 
+use super::acme;
 use super::event;
+use super::health_check;
 use super::network_log;
 
 pub fn init(cli: &Cli) -> IoResult<Nginx> {
-  let nginx = Nginx::new(&cli.conf_dir.clone().unwrap_or("/etc/nginx".into()));
+  let nginx = Nginx::new(&cli.conf_dir.clone().unwrap_or("/etc/nginx".into()))
+    .with_acme_challenge_dir(&cli.acme_challenge_dir);
   nginx.ensure()?;
   nginx.write_default_conf()?;
 
-  event::spawn(&nginx);
+  let acme = Acme::new(&cli.acme_directory_url, cli.acme_renew_before, &nginx);
+
+  event::spawn(&acme, &nginx);
+  acme::spawn(&acme, &nginx);
+  health_check::spawn(&nginx);
   network_log::spawn();
 
   Ok(nginx)
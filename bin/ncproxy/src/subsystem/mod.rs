@@ -1,5 +1,7 @@
 mod init;
+mod acme;
 mod event;
+mod health_check;
 mod network_log;
 
 pub use init::init;
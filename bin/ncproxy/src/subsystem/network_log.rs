@@ -1,126 +1,272 @@
-/// Watch for change inside the access log directory
-/// and print them to the console
-/// that way we can see what is happening in real time with docker logs
-/// so the daemon will be able to save them to the database
-/// and broadcast them in real time
-use std::fs::File;
-use std::path::Path;
+/// Read the json access logs written by nginx in the access log directory
+/// and push them by batch to the nanocl daemon
+/// that will save them as http and stream metrics.
+/// The position read in each log is saved in a cursor file
+/// only once the daemon saved the lines, so none are lost on restart
+/// and the end of a rotated log is read before the new one.
+use std::fs::{self, File};
 use std::time::Duration;
-use std::io::{Read, Seek, BufReader, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::os::unix::fs::MetadataExt;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
 
 use ntex::rt;
-use notify::{Config, Watcher, RecursiveMode, RecommendedWatcher};
 
-pub fn print_last_line(path: &Path) {
-  if !path.exists() {
-    return;
+use nanocl_utils::io_error::{IoResult, FromIo};
+
+use nanocld_client::NanocldClient;
+use nanocld_client::stubs::http_metric::ProxyLogBatch;
+
+/// Directory where nginx write the access logs
+const LOG_DIR: &str = "/var/log/nginx/access";
+/// Maximum number of lines pushed at once
+const MAX_BATCH: usize = 500;
+/// Interval between two reads of the logs when there is nothing to push
+const TICK: Duration = Duration::from_secs(1);
+/// Maximum interval between two pushes when the daemon is unavailable
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Position read in an access log
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Cursor {
+  /// Inode of the log, it change when the log is rotated
+  inode: u64,
+  /// Number of bytes read
+  offset: u64,
+}
+
+impl Cursor {
+  fn parse(data: &str) -> Option<Self> {
+    let (inode, offset) = data.trim().split_once(' ')?;
+    Some(Self {
+      inode: inode.parse().ok()?,
+      offset: offset.parse().ok()?,
+    })
   }
-  let file = match File::open(path) {
-    Ok(file) => file,
-    Err(e) => {
-      log::warn!("open file error: {:?}", e);
-      return;
+}
+
+/// An access log of nginx with the position read
+#[derive(Debug)]
+struct AccessLog {
+  path: PathBuf,
+  cursor_path: PathBuf,
+  cursor: Cursor,
+}
+
+impl AccessLog {
+  /// Load the cursor of the log, it start at the end of the log
+  /// when there is no cursor yet to not push the lines again
+  fn new(dir: &Path, name: &str) -> Self {
+    let path = dir.join(name);
+    let cursor_path = dir.join(format!(".{name}.cursor"));
+    let cursor = fs::read_to_string(&cursor_path)
+      .ok()
+      .and_then(|data| Cursor::parse(&data))
+      .or_else(|| {
+        let metadata = fs::metadata(&path).ok()?;
+        Some(Cursor {
+          inode: metadata.ino(),
+          offset: metadata.len(),
+        })
+      })
+      .unwrap_or_default();
+    Self {
+      path,
+      cursor_path,
+      cursor,
     }
-  };
-  let mut buf_reader = BufReader::new(file);
+  }
 
-  let mut pos = match buf_reader.seek(SeekFrom::End(-2)) {
-    Ok(pos) => pos,
-    Err(e) => {
-      log::warn!("seek error: {:?}", e);
-      return;
+  /// Find the file of the log matching the cursor
+  /// which is the rotated log when the rotation happened after the last read
+  fn find_file(&self) -> Option<(PathBuf, fs::Metadata)> {
+    let metadata = fs::metadata(&self.path).ok()?;
+    if metadata.ino() == self.cursor.inode {
+      return Some((self.path.clone(), metadata));
     }
-  };
-  let mut last_line = String::new();
-
-  while pos > 0 {
-    match buf_reader.seek(SeekFrom::Start(pos)) {
-      Ok(_) => {}
-      Err(e) => {
-        log::warn!("seek error: {:?}", e);
-        return;
+    let mut rotated = self.path.clone().into_os_string();
+    rotated.push(".1");
+    let rotated = PathBuf::from(rotated);
+    match fs::metadata(&rotated) {
+      Ok(rotated_metadata)
+        if rotated_metadata.ino() == self.cursor.inode
+          && rotated_metadata.len() > self.cursor.offset =>
+      {
+        Some((rotated, rotated_metadata))
       }
+      _ => Some((self.path.clone(), metadata)),
     }
-    let mut buffer = [0; 1];
-    let res = buf_reader.read_exact(&mut buffer);
-    if buffer[0] == b'\n' || res.is_err() {
-      break;
-    }
-    last_line.insert(0, buffer[0] as char);
-    pos -= 1;
   }
 
-  let file_name = path
-    .file_name()
-    .unwrap_or_default()
-    .to_str()
-    .unwrap_or_default();
+  /// Read at most `max` complete lines after the cursor
+  /// and return them with the cursor after the last line
+  fn read(&self, max: usize) -> IoResult<(Vec<String>, Cursor)> {
+    let Some((path, metadata)) = self.find_file() else {
+      return Ok((Vec::new(), self.cursor.clone()));
+    };
+    let mut cursor = self.cursor.clone();
+    // The log has been rotated or truncated
+    if metadata.ino() != cursor.inode || metadata.len() < cursor.offset {
+      cursor = Cursor {
+        inode: metadata.ino(),
+        offset: 0,
+      };
+    }
+    let file = File::open(&path).map_err(|err| {
+      err.map_err_context(|| format!("Unable to open {}", path.display()))
+    })?;
+    let mut reader = BufReader::new(file);
+    reader.seek(SeekFrom::Start(cursor.offset))?;
+    let mut lines = Vec::new();
+    while lines.len() < max {
+      let mut line = String::new();
+      let len = reader.read_line(&mut line)?;
+      // The last line is read again once nginx finished to write it
+      if len == 0 || !line.ends_with('\n') {
+        break;
+      }
+      cursor.offset += len as u64;
+      let line = line.trim();
+      if !line.is_empty() {
+        lines.push(line.to_owned());
+      }
+    }
+    Ok((lines, cursor))
+  }
 
-  log::debug!("{}", file_name);
-  match file_name {
-    "http.log" => {
-      println!("#HTTP {last_line}");
+  /// Save the cursor once the lines have been pushed
+  fn commit(&mut self, cursor: Cursor) -> IoResult<()> {
+    if cursor == self.cursor {
+      return Ok(());
     }
-    "stream.log" => {
-      println!("#STREAM {last_line}");
+    let tmp_path = self.cursor_path.with_extension("tmp");
+    fs::write(&tmp_path, format!("{} {}", cursor.inode, cursor.offset))?;
+    fs::rename(&tmp_path, &self.cursor_path)?;
+    self.cursor = cursor;
+    Ok(())
+  }
+}
+
+/// Parse the lines of an access log as json
+/// Invalid lines are skipped since they would never be accepted
+fn parse_lines(name: &str, lines: Vec<String>) -> Vec<serde_json::Value> {
+  lines
+    .into_iter()
+    .filter_map(|line| match serde_json::from_str(&line) {
+      Ok(value) => Some(value),
+      Err(err) => {
+        log::warn!("Skipping invalid line of {name}: {err}");
+        None
+      }
+    })
+    .collect()
+}
+
+/// Push the next batch of lines to the daemon
+/// Return true if the batch was full and more lines may be waiting
+async fn push(
+  http_log: &mut AccessLog,
+  stream_log: &mut AccessLog,
+  client: &NanocldClient,
+) -> IoResult<bool> {
+  let (http_lines, http_cursor) = http_log.read(MAX_BATCH)?;
+  let (stream_lines, stream_cursor) =
+    stream_log.read(MAX_BATCH - http_lines.len())?;
+  let len = http_lines.len() + stream_lines.len();
+  let batch = ProxyLogBatch {
+    http: parse_lines("http.log", http_lines),
+    stream: parse_lines("stream.log", stream_lines),
+  };
+  if !batch.http.is_empty() || !batch.stream.is_empty() {
+    let ingestion = client.ingest_http_metric(&batch).await?;
+    log::debug!(
+      "Pushed {} http and {} stream metrics, {} rejected",
+      ingestion.http,
+      ingestion.stream,
+      ingestion.rejected
+    );
+  }
+  http_log.commit(http_cursor)?;
+  stream_log.commit(stream_cursor)?;
+  Ok(len == MAX_BATCH)
+}
+
+async fn r#loop(client: &NanocldClient) {
+  let dir = Path::new(LOG_DIR);
+  let mut http_log = AccessLog::new(dir, "http.log");
+  let mut stream_log = AccessLog::new(dir, "stream.log");
+  let mut delay = TICK;
+  loop {
+    match push(&mut http_log, &mut stream_log, client).await {
+      Ok(true) => {
+        delay = TICK;
+        continue;
+      }
+      Ok(false) => delay = TICK,
+      // The lines stay in the logs until the daemon accept them
+      Err(err) => {
+        log::warn!("Unable to push access logs: {err}");
+        delay = std::cmp::min(delay * 2, MAX_BACKOFF);
+      }
     }
-    _ => {}
+    ntex::time::sleep(delay).await;
   }
 }
 
-/// Spawn new thread and watch for change inside the access log directory
-/// to print them to the console that way we can see what is happening in real time with docker logs
-/// and the nanocl daemon will be able to save them to the database
+/// Spawn new thread with a loop pushing the access logs to the daemon
 pub(crate) fn spawn() {
   rt::Arbiter::new().exec_fn(|| {
-    let path = Path::new("/var/log/nginx/access");
-    if !path.exists() {
-      log::debug!("{} doesn't exists logs wont be saved", path.display());
+    if !Path::new(LOG_DIR).exists() {
+      log::debug!("{LOG_DIR} doesn't exists logs wont be saved");
       return;
     }
-    let (tx, rx) = std::sync::mpsc::channel();
-
-    // Automatically select the best implementation for your platform.
-    // You can also access each implementation directly e.g. INotifyWatcher.
-    let mut watcher = match RecommendedWatcher::new(
-      tx,
-      Config::default()
-        .with_compare_contents(true)
-        .with_poll_interval(Duration::from_secs(2)),
-    ) {
-      Ok(watcher) => watcher,
-      Err(e) => {
-        log::warn!("watcher error: {:?}", e);
-        return;
-      }
-    };
-
-    // Add a path to be watched. All files and directories at that path and
-    // below will be monitored for changes.
-    watcher.watch(path, RecursiveMode::Recursive).unwrap();
-
-    log::debug!("watching change of: {}", path.display());
-    for res in rx {
-      match res {
-        Ok(event) => match &event.kind {
-          notify::EventKind::Modify(e) => match e {
-            notify::event::ModifyKind::Data(_) => {
-              log::debug!("modified event: {:?}", event);
-              let path = &event.paths.get(0);
-              if let Some(path) = path {
-                print_last_line(path)
-              }
-            }
-            notify::event::ModifyKind::Other => {}
-            _ => {}
-          },
-          notify::EventKind::Other => {
-            log::debug!("other");
-          }
-          _ => {}
-        },
-        Err(e) => log::warn!("watch error: {:?}", e),
-      }
+    #[allow(unused)]
+    let mut client = NanocldClient::connect_with_unix_default();
+    #[cfg(any(feature = "dev", feature = "test"))]
+    {
+      client =
+        NanocldClient::connect_to("http://ndaemon.nanocl.internal:8585", None);
     }
+    ntex::rt::spawn(async move {
+      r#loop(&client).await;
+    });
   });
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn access_log() {
+    let dir = Path::new("/tmp/ncproxy-access-log-test");
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let path = dir.join("http.log");
+    fs::write(&path, "{\"a\":1}\n").unwrap();
+    // Lines written before the first start are not pushed
+    let mut log = AccessLog::new(dir, "http.log");
+    fs::write(&path, "{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n{\"a\"").unwrap();
+    let (lines, cursor) = log.read(1).unwrap();
+    assert_eq!(lines, vec!["{\"a\":2}"]);
+    log.commit(cursor).unwrap();
+    let (lines, cursor) = log.read(10).unwrap();
+    assert_eq!(lines, vec!["{\"a\":3}"]);
+    log.commit(cursor).unwrap();
+    // The cursor is kept across restarts
+    let mut log = AccessLog::new(dir, "http.log");
+    assert!(log.read(10).unwrap().0.is_empty());
+    // The end of the rotated log is read before the new log
+    fs::write(&path, "{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n{\"a\":4}\n").unwrap();
+    fs::rename(&path, dir.join("http.log.1")).unwrap();
+    fs::write(&path, "{\"b\":1}\n").unwrap();
+    let (lines, cursor) = log.read(10).unwrap();
+    assert_eq!(lines, vec!["{\"a\":4}"]);
+    log.commit(cursor).unwrap();
+    let (lines, cursor) = log.read(10).unwrap();
+    assert_eq!(lines, vec!["{\"b\":1}"]);
+    log.commit(cursor).unwrap();
+    assert!(log.read(10).unwrap().0.is_empty());
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -10,9 +10,12 @@ use nanocld_client::stubs::resource::{ResourceQuery, ResourcePartial};
 use nanocld_client::stubs::proxy::{
   ProxyRule, StreamTarget, ProxyStreamProtocol, ProxyRuleHttp, UpstreamTarget,
   ProxyHttpLocation, ProxyRuleStream, LocationTarget, ResourceProxyRule,
+  SplitTarget, RouteMatch, ProxyBasicAuth, ProxyCache, ProxyCacheZone,
+  ProxyCompression, ProxyHttpProtocol,
 };
 use nanocld_client::stubs::vm::VmInspect;
 
+use crate::health::CheckedUpstream;
 use crate::nginx::{Nginx, NginxConfKind};
 
 /// Serialize a ProxyRule
@@ -82,9 +85,60 @@ async fn get_listen(
   }
 }
 
-fn create_cargo_upstream(
+/// Write the upstream block of the instances of a cargo or a vm
+pub(crate) fn write_upstream(
   kind: &NginxConfKind,
+  upstream_key: &str,
   port: u16,
+  ip_addresses: &[String],
+  nginx: &Nginx,
+) -> IoResult<()> {
+  let upstream = format!(
+    "
+upstream {upstream_key} {{
+  hash $remote_addr consistent;
+{}
+}}
+",
+    ip_addresses
+      .iter()
+      .map(|ip_address| format!("  server {ip_address}:{port};"))
+      .collect::<Vec<String>>()
+      .join("\n")
+  );
+  nginx.write_conf_file(upstream_key, &upstream, kind)
+}
+
+/// Register the instances of a target having a health check for a rule
+/// and return the ones that can receive traffic.
+/// The upstream is shared by the rules targeting the same cargo or vm,
+/// so the instances ejected by the check of another rule are removed too.
+fn filter_healthy(
+  kind: &NginxConfKind,
+  name: &str,
+  upstream_key: &str,
+  target: &UpstreamTarget,
+  ip_addresses: Vec<String>,
+  nginx: &Nginx,
+) -> Vec<String> {
+  let Some(check) = &target.health_check else {
+    return nginx
+      .upstream_health
+      .filter_healthy(upstream_key, &ip_addresses);
+  };
+  let upstream =
+    CheckedUpstream::new(kind.clone(), target, ip_addresses, check.clone());
+  let ip_addresses = nginx
+    .upstream_health
+    .filter_healthy(upstream_key, &upstream.addresses);
+  nginx.upstream_health.register(name, upstream_key, upstream);
+  ip_addresses
+}
+
+fn create_cargo_upstream(
+  kind: &NginxConfKind,
+  name: &str,
+  target: &UpstreamTarget,
   cargo: &CargoInspect,
   nginx: &Nginx,
 ) -> IoResult<String> {
@@ -124,27 +178,17 @@ fn create_cargo_upstream(
     ));
   }
   log::debug!("ip_addresses: {:?}", ip_addresses);
-  let upstream_key = format!("cargo-{}-{}", cargo.key, port);
-  let upstream = format!(
-    "
-upstream {upstream_key} {{
-  hash $remote_addr consistent;
-{}
-}}
-",
-    ip_addresses
-      .iter()
-      .map(|ip_address| format!("  server {ip_address}:{port};"))
-      .collect::<Vec<String>>()
-      .join("\n")
-  );
-  nginx.write_conf_file(&upstream_key, &upstream, kind)?;
+  let upstream_key = format!("cargo-{}-{}", cargo.key, target.port);
+  let ip_addresses =
+    filter_healthy(kind, name, &upstream_key, target, ip_addresses, nginx);
+  write_upstream(kind, &upstream_key, target.port, &ip_addresses, nginx)?;
   Ok(upstream_key)
 }
 
 fn create_vm_upstream(
   kind: &NginxConfKind,
-  port: u16,
+  name: &str,
+  target: &UpstreamTarget,
   vm: &VmInspect,
   nginx: &Nginx,
 ) -> IoResult<String> {
@@ -178,31 +222,20 @@ fn create_vm_upstream(
     ip_addresses.push(ip_address);
   }
   log::debug!("ip_addresses: {:?}", ip_addresses);
-  let upstream_key = format!("vm-{}-{}", vm.key, port);
-  let upstream = format!(
-    "
-upstream {upstream_key} {{
-  hash $remote_addr consistent;
-{}
-}}
-",
-    ip_addresses
-      .iter()
-      .map(|ip_address| format!("  server {ip_address}:{port};"))
-      .collect::<Vec<String>>()
-      .join("\n")
-  );
-  nginx.write_conf_file(&upstream_key, &upstream, kind)?;
+  let upstream_key = format!("vm-{}-{}", vm.key, target.port);
+  let ip_addresses =
+    filter_healthy(kind, name, &upstream_key, target, ip_addresses, nginx);
+  write_upstream(kind, &upstream_key, target.port, &ip_addresses, nginx)?;
   Ok(upstream_key)
 }
 
 async fn gen_upstream(
   kind: &NginxConfKind,
+  name: &str,
   target: &UpstreamTarget,
   client: &NanocldClient,
   nginx: &Nginx,
 ) -> IoResult<String> {
-  let port = target.port;
   let (target_name, target_namespace, target_kind) =
     extract_upstream_target(&target.key)?;
 
@@ -216,7 +249,7 @@ async fn gen_upstream(
             format!("Unable to inspect cargo {target_name}")
           })
         })?;
-      create_cargo_upstream(kind, port, &cargo, nginx)
+      create_cargo_upstream(kind, name, target, &cargo, nginx)
     }
     "v" => {
       let vm = client
@@ -225,7 +258,7 @@ async fn gen_upstream(
         .map_err(|err| {
           err.map_err_context(|| format!("Unable to inspect vm {target_name}"))
         })?;
-      create_vm_upstream(kind, port, &vm, nginx)
+      create_vm_upstream(kind, name, target, &vm, nginx)
     }
     _ => Err(IoError::invalid_data(
       "UpstreamTarget",
@@ -261,8 +294,625 @@ async fn gen_unix_stream(path: &str, nginx: &Nginx) -> IoResult<String> {
   Ok(upstream_key)
 }
 
+/// Generate an id from a config to name his nginx zones and variables
+/// It's a FNV-1a hash so the id stay the same across builds
+fn gen_id(config: &str) -> String {
+  let hash = config.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+  });
+  format!("{hash:x}")
+}
+
+/// Ensure a header or a cookie name can be used as a nginx variable
+fn validate_route_name(kind: &str, name: &str) -> IoResult<()> {
+  let is_valid = !name.is_empty()
+    && name.chars().all(|c| {
+      c.is_ascii_alphanumeric() || c == '_' || (kind == "Header" && c == '-')
+    });
+  if !is_valid {
+    return Err(IoError::invalid_input(
+      kind,
+      &format!("Invalid name {name}"),
+    ));
+  }
+  Ok(())
+}
+
+/// Ensure a header or a cookie can be matched by a nginx map
+fn validate_route_match(kind: &str, route: &RouteMatch) -> IoResult<()> {
+  validate_route_name(kind, &route.name)?;
+  if route.value.contains(['"', '\\', '\n']) {
+    return Err(IoError::invalid_input(
+      kind,
+      &format!("Invalid value {}", route.value),
+    ));
+  }
+  Ok(())
+}
+
+/// Generate the nginx maps choosing the upstream of a split target
+/// The traffic is shared by weight with `split_clients`
+/// then the sticky cookie and the canary matches override the choice.
+/// The chosen upstream is stored in the variable `$split_{id}_target`
+fn gen_split_conf(
+  id: &str,
+  upstreams: &[(String, u32)],
+  split: &SplitTarget,
+  canary_key: Option<&str>,
+) -> IoResult<String> {
+  let upstreams = upstreams
+    .iter()
+    .filter(|(_, weight)| *weight > 0)
+    .collect::<Vec<_>>();
+  if upstreams.is_empty() {
+    return Err(IoError::invalid_input(
+      "SplitTarget",
+      "At least one upstream must have a weight",
+    ));
+  }
+  let total = upstreams
+    .iter()
+    .map(|(_, weight)| *weight as u64)
+    .sum::<u64>();
+  let index_var = format!("$split_{id}_index");
+  let pick_var = match &split.sticky_cookie {
+    Some(_) => format!("$split_{id}_pick"),
+    None => index_var.clone(),
+  };
+  let shares = upstreams
+    .iter()
+    .enumerate()
+    .map(|(index, (_, weight))| {
+      if index == upstreams.len() - 1 {
+        return format!("  * {index};");
+      }
+      // Rounded down so the total never exceed 100%
+      let share = *weight as u64 * 10000 / total;
+      format!("  {}.{:02}% {index};", share / 100, share % 100)
+    })
+    .collect::<Vec<_>>()
+    .join("\n");
+  let mut conf = format!(
+    "
+split_clients \"$request_id\" {pick_var} {{
+{shares}
+}}
+"
+  );
+  if let Some(cookie) = &split.sticky_cookie {
+    validate_route_name("Cookie", cookie)?;
+    let indexes = (0..upstreams.len())
+      .map(|index| format!("  \"{index}\" {index};"))
+      .collect::<Vec<_>>()
+      .join("\n");
+    conf += &format!(
+      "
+map $cookie_{cookie} {index_var} {{
+  default {pick_var};
+{indexes}
+}}
+"
+    );
+  }
+  let targets = upstreams
+    .iter()
+    .enumerate()
+    .map(|(index, (key, _))| format!("  \"{index}\" {key};"))
+    .collect::<Vec<_>>()
+    .join("\n");
+  let mut target_var = format!("$split_{id}");
+  conf += &format!(
+    "
+map {index_var} {target_var} {{
+  default {};
+{targets}
+}}
+",
+    upstreams[0].0
+  );
+  let (Some(canary), Some(canary_key)) = (&split.canary, canary_key) else {
+    conf += &format!(
+      "
+map {index_var} $split_{id}_target {{
+  default {target_var};
+}}
+"
+    );
+    return Ok(conf);
+  };
+  let matches = [
+    canary.header.as_ref().map(|header| ("Header", header)),
+    canary.cookie.as_ref().map(|cookie| ("Cookie", cookie)),
+  ];
+  for (kind, route) in matches.into_iter().flatten() {
+    validate_route_match(kind, route)?;
+    let (source_var, next_var) = match kind {
+      "Header" => (
+        format!("$http_{}", route.name.to_lowercase().replace('-', "_")),
+        format!("$split_{id}_header"),
+      ),
+      _ => (
+        format!("$cookie_{}", route.name),
+        format!("$split_{id}_cookie"),
+      ),
+    };
+    conf += &format!(
+      "
+map {source_var} {next_var} {{
+  default {target_var};
+  \"{}\" {canary_key};
+}}
+",
+      route.value
+    );
+    target_var = next_var;
+  }
+  conf += &format!(
+    "
+map {index_var} $split_{id}_target {{
+  default {target_var};
+}}
+"
+  );
+  Ok(conf)
+}
+
+/// Generate the upstreams and the maps of a split target
+/// The maps are added to the http context of the rule so they're removed
+/// with the rule. Return the id of the split used to name his nginx variables
+async fn gen_split(
+  name: &str,
+  path: &str,
+  split: &SplitTarget,
+  http_conf: &mut String,
+  client: &NanocldClient,
+  nginx: &Nginx,
+) -> IoResult<String> {
+  let id = gen_id(&format!("{path}{split:?}"));
+  let mut upstreams = Vec::new();
+  for target in &split.upstreams {
+    let upstream_target = UpstreamTarget {
+      key: target.key.clone(),
+      port: target.port,
+      path: None,
+      disable_logging: None,
+      health_check: target.health_check.clone(),
+    };
+    let upstream_key =
+      gen_upstream(&NginxConfKind::Site, name, &upstream_target, client, nginx)
+        .await?;
+    upstreams.push((upstream_key, target.weight));
+  }
+  let canary_key = match &split.canary {
+    Some(canary) => {
+      let upstream_target = UpstreamTarget {
+        key: canary.key.clone(),
+        port: canary.port,
+        path: None,
+        disable_logging: None,
+        health_check: None,
+      };
+      let upstream_key = gen_upstream(
+        &NginxConfKind::Site,
+        name,
+        &upstream_target,
+        client,
+        nginx,
+      )
+      .await?;
+      Some(upstream_key)
+    }
+    None => None,
+  };
+  *http_conf += &gen_split_conf(&id, &upstreams, split, canary_key.as_deref())?;
+  Ok(id)
+}
+
+/// Ensure an ip address or a CIDR range can be used by allow and deny
+fn validate_ip_range(range: &str) -> IoResult<()> {
+  let (address, prefix) = match range.split_once('/') {
+    Some((address, prefix)) => (address, Some(prefix)),
+    None => (range, None),
+  };
+  let Ok(address) = address.parse::<std::net::IpAddr>() else {
+    return Err(IoError::invalid_input(
+      "IpRange",
+      &format!("Invalid ip address {range}"),
+    ));
+  };
+  let Some(prefix) = prefix else {
+    return Ok(());
+  };
+  let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+  match prefix.parse::<u8>() {
+    Ok(prefix) if prefix <= max_prefix => Ok(()),
+    _ => Err(IoError::invalid_input(
+      "IpRange",
+      &format!("Invalid prefix length {range}"),
+    )),
+  }
+}
+
+/// Generate the content of a htpasswd file
+/// Passwords not marked as hashed are stored with the `{PLAIN}` scheme
+fn gen_htpasswd(basic_auth: &ProxyBasicAuth) -> IoResult<String> {
+  let mut htpasswd = String::new();
+  for user in &basic_auth.users {
+    if user.name.is_empty() || user.name.contains([':', '\n']) {
+      return Err(IoError::invalid_input(
+        "BasicAuth",
+        &format!("Invalid user name {}", user.name),
+      ));
+    }
+    if user.password.contains('\n') {
+      return Err(IoError::invalid_input(
+        "BasicAuth",
+        &format!("Invalid password for user {}", user.name),
+      ));
+    }
+    let password = if user.hashed.unwrap_or_default() {
+      user.password.clone()
+    } else {
+      format!("{{PLAIN}}{}", user.password)
+    };
+    htpasswd += &format!("{}:{password}\n", user.name);
+  }
+  Ok(htpasswd)
+}
+
+/// Get the name of the file of a secret unique to his content
+fn gen_secret_file_name(key: &str, ext: &str, data: &str) -> String {
+  format!("{key}-{}.{ext}", gen_id(data))
+}
+
+/// Write the users of a `BasicAuth` secret to the disk
+/// and return the path of the htpasswd file
+async fn gen_basic_auth_file(
+  key: &str,
+  client: &NanocldClient,
+  nginx: &Nginx,
+) -> IoResult<String> {
+  let secret = client.inspect_secret(key).await?;
+  if secret.kind != "BasicAuth" {
+    return Err(IoError::invalid_input(
+      "BasicAuth",
+      &format!("Secret {key} is not of kind BasicAuth"),
+    ));
+  }
+  let basic_auth = serde_json::from_value::<ProxyBasicAuth>(secret.data)
+    .map_err(|err| {
+      err.map_err_context(|| "Unable to deserialize ProxyBasicAuth")
+    })?;
+  let htpasswd = gen_htpasswd(&basic_auth)?;
+  let file_name = gen_secret_file_name(key, "htpasswd", &htpasswd);
+  nginx.write_secret_file(&file_name, &htpasswd)
+}
+
+/// Generate the rate limit, ip filtering and basic auth directives
+/// of a location, the rate limit zone is added to the http context
+/// of the rule so it's removed with the rule
+async fn gen_location_access(
+  scope: &str,
+  location: &ProxyHttpLocation,
+  http_conf: &mut String,
+  client: &NanocldClient,
+  nginx: &Nginx,
+) -> IoResult<String> {
+  let mut access = String::new();
+  if let Some(rate_limit) = &location.rate_limit {
+    if rate_limit.rate == 0 {
+      return Err(IoError::invalid_input(
+        "ProxyRateLimit",
+        "Rate must be greater than 0",
+      ));
+    }
+    let id = gen_id(&format!("{scope}{}", location.path));
+    let zone = format!("limit_{id}");
+    *http_conf += &format!(
+      "limit_req_zone $binary_remote_addr zone={zone}:10m rate={}r/s;\n",
+      rate_limit.rate
+    );
+    let burst = match rate_limit.burst {
+      Some(burst) => format!(" burst={burst}"),
+      None => String::default(),
+    };
+    let nodelay = if rate_limit.nodelay.unwrap_or_default() {
+      " nodelay"
+    } else {
+      ""
+    };
+    access += &format!(
+      "\n    limit_req zone={zone}{burst}{nodelay};\n    limit_req_status 429;"
+    );
+  }
+  // Denied ranges come first since nginx stops at the first match
+  for range in location.denied_ips.clone().unwrap_or_default() {
+    validate_ip_range(&range)?;
+    access += &format!("\n    deny {range};");
+  }
+  if let Some(allowed_ips) = &location.allowed_ips {
+    for range in allowed_ips {
+      validate_ip_range(range)?;
+      access += &format!("\n    allow {range};");
+    }
+    access += "\n    deny all;";
+  }
+  if let Some(secret) = &location.basic_auth {
+    let path = gen_basic_auth_file(secret, client, nginx).await?;
+    access += &format!(
+      "\n    auth_basic \"Restricted\";\n    auth_basic_user_file {path};"
+    );
+  }
+  Ok(access)
+}
+
+/// Ensure a time can be used by nginx like `30s` or `10m`
+fn validate_time(context: &str, time: &str) -> IoResult<()> {
+  let unit_start = time
+    .find(|c: char| !c.is_ascii_digit())
+    .unwrap_or(time.len());
+  let (value, unit) = time.split_at(unit_start);
+  if value.is_empty()
+    || !["", "ms", "s", "m", "h", "d", "w", "M", "y"].contains(&unit)
+  {
+    return Err(IoError::invalid_input(
+      context,
+      &format!("Invalid time {time}"),
+    ));
+  }
+  Ok(())
+}
+
+/// Ensure a status code can be used by proxy_cache_valid
+fn validate_cache_status(status: &str) -> IoResult<()> {
+  if status == "any" {
+    return Ok(());
+  }
+  match status.parse::<u16>() {
+    Ok(code) if (100..600).contains(&code) => Ok(()),
+    _ => Err(IoError::invalid_input(
+      "ProxyCacheValid",
+      &format!("Invalid status {status}"),
+    )),
+  }
+}
+
+/// Generate the cache directives of a location
+fn gen_location_cache(cache: &ProxyCache) -> IoResult<String> {
+  let mut conf = match cache.zone {
+    ProxyCacheZone::Public => "\n    proxy_cache public-cache;".to_owned(),
+    // Responses are only served back to the same authorization and cookies
+    ProxyCacheZone::Private => format!(
+      "\n    proxy_cache private-cache;\n    proxy_cache_key {};",
+      "$scheme$proxy_host$request_uri$http_authorization$http_cookie"
+    ),
+  };
+  for valid in cache.valid.clone().unwrap_or_default() {
+    if valid.status.is_empty() {
+      return Err(IoError::invalid_input(
+        "ProxyCacheValid",
+        "At least one status is required",
+      ));
+    }
+    for status in &valid.status {
+      validate_cache_status(status)?;
+    }
+    validate_time("ProxyCacheValid", &valid.ttl)?;
+    conf += &format!(
+      "\n    proxy_cache_valid {} {};",
+      valid.status.join(" "),
+      valid.ttl
+    );
+  }
+  if cache.valid.is_none() {
+    conf += "\n    proxy_cache_valid 200 301 302 10m;";
+  }
+  if let Some(bypass) = &cache.bypass {
+    for variable in bypass {
+      let name = variable.strip_prefix('$').unwrap_or_default();
+      if name.is_empty()
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+      {
+        return Err(IoError::invalid_input(
+          "ProxyCache",
+          &format!("Invalid bypass variable {variable}"),
+        ));
+      }
+    }
+    let bypass = bypass.join(" ");
+    conf += &format!(
+      "\n    proxy_cache_bypass {bypass};\n    proxy_no_cache {bypass};"
+    );
+  }
+  conf += "\n    add_header X-Cache-Status $upstream_cache_status;";
+  Ok(conf)
+}
+
+/// Generate the gzip directives of a location
+/// Brotli is rejected since the module isn't shipped with nproxy
+fn gen_location_compression(
+  compression: &ProxyCompression,
+) -> IoResult<String> {
+  if compression.brotli.unwrap_or_default() {
+    return Err(IoError::invalid_input(
+      "ProxyCompression",
+      "Brotli is not supported by the proxy",
+    ));
+  }
+  let level = compression.level.unwrap_or(6);
+  if !(1..=9).contains(&level) {
+    return Err(IoError::invalid_input(
+      "ProxyCompression",
+      "Level must be between 1 and 9",
+    ));
+  }
+  let types = compression.types.clone().unwrap_or_default();
+  for mime_type in &types {
+    if !mime_type.contains('/')
+      || mime_type
+        .chars()
+        .any(|c| c.is_whitespace() || c == ';' || c == '{' || c == '}')
+    {
+      return Err(IoError::invalid_input(
+        "ProxyCompression",
+        &format!("Invalid mime type {mime_type}"),
+      ));
+    }
+  }
+  let types = types.join(" ");
+  let mut conf = String::new();
+  let mut render = |module: &str, enabled: Option<bool>| match enabled {
+    Some(true) => {
+      conf += &format!("\n    {module} on;\n    {module}_comp_level {level};");
+      if !types.is_empty() {
+        conf += &format!("\n    {module}_types {types};");
+      }
+      if let Some(min_length) = compression.min_length {
+        conf += &format!("\n    {module}_min_length {min_length};");
+      }
+    }
+    Some(false) => conf += &format!("\n    {module} off;"),
+    None => {}
+  };
+  render("gzip", compression.gzip);
+  if compression.gzip.unwrap_or_default() {
+    conf += "\n    gzip_proxied any;";
+  }
+  Ok(conf)
+}
+
+/// Generate the cache and compression directives of a location
+fn gen_location_content(location: &ProxyHttpLocation) -> IoResult<String> {
+  let mut content = String::new();
+  if let Some(cache) = &location.cache {
+    content += &gen_location_cache(cache)?;
+  }
+  if let Some(compression) = &location.compression {
+    content += &gen_location_compression(compression)?;
+  }
+  Ok(content)
+}
+
+/// Get the prefix of the directives of the protocol of a location
+fn get_location_prefix(location: &ProxyHttpLocation) -> &'static str {
+  match location.protocol {
+    Some(ProxyHttpProtocol::Grpc) => "grpc",
+    _ => "proxy",
+  }
+}
+
+/// Generate the directives of a location to talk with his target
+/// like the http version, the extra headers, the timeouts and the tls
+fn gen_location_proxy(location: &ProxyHttpLocation) -> IoResult<String> {
+  let prefix = get_location_prefix(location);
+  let mut conf = String::new();
+  match (&location.protocol, &location.version) {
+    (Some(ProxyHttpProtocol::Grpc), _) => {}
+    (Some(ProxyHttpProtocol::WebSocket), _) => {
+      conf += "\n    proxy_http_version 1.1;";
+    }
+    (_, Some(version)) => {
+      conf += &format!("\n    proxy_http_version {version};");
+    }
+    (_, None) => {}
+  }
+  for header in location.headers.clone().unwrap_or_default() {
+    conf += &format!("\n    {prefix}_set_header {header};");
+  }
+  if location.protocol == Some(ProxyHttpProtocol::WebSocket) {
+    conf += "\n    proxy_set_header Upgrade $http_upgrade;\
+      \n    proxy_set_header Connection $connection_upgrade;";
+  }
+  if let Some(timeouts) = &location.timeouts {
+    for (name, timeout) in [
+      ("connect", &timeouts.connect),
+      ("send", &timeouts.send),
+      ("read", &timeouts.read),
+    ] {
+      let Some(timeout) = timeout else {
+        continue;
+      };
+      validate_time("ProxyTimeouts", timeout)?;
+      conf += &format!("\n    {prefix}_{name}_timeout {timeout};");
+    }
+  }
+  if let Some(tls) = &location.upstream_tls {
+    conf += &format!("\n    {prefix}_ssl_server_name on;");
+    if let Some(server_name) = &tls.server_name {
+      if server_name.is_empty()
+        || server_name
+          .chars()
+          .any(|c| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'))
+      {
+        return Err(IoError::invalid_input(
+          "ProxyUpstreamTls",
+          &format!("Invalid server name {server_name}"),
+        ));
+      }
+      conf += &format!("\n    {prefix}_ssl_name {server_name};");
+    }
+    if tls.verify.unwrap_or_default() {
+      let trusted_certificate = tls
+        .trusted_certificate
+        .as_deref()
+        .unwrap_or("/etc/ssl/certs/ca-certificates.crt");
+      if !trusted_certificate.starts_with('/')
+        || trusted_certificate.chars().any(|c| {
+          !(c.is_ascii_alphanumeric() || ['/', '.', '-', '_'].contains(&c))
+        })
+      {
+        return Err(IoError::invalid_input(
+          "ProxyUpstreamTls",
+          &format!("Invalid trusted certificate {trusted_certificate}"),
+        ));
+      }
+      conf += &format!(
+        "\n    {prefix}_ssl_verify on;\
+        \n    {prefix}_ssl_trusted_certificate {trusted_certificate};"
+      );
+    }
+  }
+  Ok(conf)
+}
+
+/// Generate the headers forwarding the informations of the client
+fn gen_forwarded_headers(location: &ProxyHttpLocation) -> String {
+  let prefix = get_location_prefix(location);
+  format!(
+    "
+    {prefix}_set_header Host $host;
+    {prefix}_set_header X-Forwarded-Scheme $scheme;
+    {prefix}_set_header X-Forwarded-Proto  $scheme;
+    {prefix}_set_header X-Forwarded-For    $proxy_add_x_forwarded_for;
+    {prefix}_set_header X-Real-IP          $remote_addr;"
+  )
+}
+
+/// Generate the directive passing the requests of a location to an address
+/// The scheme of the protocol is added when the address has none
+fn gen_location_pass(location: &ProxyHttpLocation, address: &str) -> String {
+  let prefix = get_location_prefix(location);
+  if address.contains("://") {
+    return format!("\n    {prefix}_pass {address};");
+  }
+  let scheme = match (prefix, location.upstream_tls.is_some()) {
+    ("grpc", false) => "grpc",
+    ("grpc", true) => "grpcs",
+    (_, false) => "http",
+    (_, true) => "https",
+  };
+  format!("\n    {prefix}_pass {scheme}://{address};")
+}
+
+/// Generate the locations of a http rule
+/// The directives they need in the http context are added to `http_conf`.
+/// A location with an invalid access control reject the whole rule
+/// so it's never served without his restrictions.
 async fn gen_locations(
+  name: &str,
+  scope: &str,
   location_rules: &Vec<ProxyHttpLocation>,
+  http_conf: &mut String,
   client: &NanocldClient,
   nginx: &Nginx,
 ) -> IoResult<Vec<String>> {
@@ -270,24 +920,30 @@ async fn gen_locations(
   for rule in location_rules {
     let path = &rule.path;
 
-    let version = match &rule.version {
-      None => String::default(),
-      Some(version) => format!("\n    proxy_http_version {};", version),
-    };
+    let access = gen_location_access(scope, rule, http_conf, client, nginx)
+      .await
+      .map_err(|err| {
+        err.map_err_context(|| format!("Invalid access of location {path}"))
+      })?;
 
-    let headers = rule.headers.clone().unwrap_or_default().into_iter().fold(
-      String::new(),
-      |mut acc, elem| {
-        acc += &format!("\n    proxy_set_header {elem};");
-        acc
-      },
-    );
+    let content = gen_location_content(rule).map_err(|err| {
+      err.map_err_context(|| format!("Invalid content of location {path}"))
+    })?;
+
+    let proxy = gen_location_proxy(rule).map_err(|err| {
+      err.map_err_context(|| format!("Invalid proxy of location {path}"))
+    })?;
 
     match &rule.target {
       LocationTarget::Upstream(upstream_target) => {
-        let Ok(upstream_key) =
-          gen_upstream(&NginxConfKind::Site, upstream_target, client, nginx)
-            .await
+        let Ok(upstream_key) = gen_upstream(
+          &NginxConfKind::Site,
+          name,
+          upstream_target,
+          client,
+          nginx,
+        )
+        .await
         else {
           log::warn!("Unable to generate cargo upstream for location rule {:?} got error", rule);
           continue;
@@ -298,31 +954,55 @@ async fn gen_locations(
           } else {
             ""
           };
+        let forwarded = gen_forwarded_headers(rule);
+        let pass = gen_location_pass(
+          rule,
+          &format!(
+            "{upstream_key}{}",
+            upstream_target.path.clone().unwrap_or_default()
+          ),
+        );
         let location = format!(
           "
-  location {path} {{{version}{headers}
-    proxy_set_header Host $host;
-    proxy_set_header X-Forwarded-Scheme $scheme;
-    proxy_set_header X-Forwarded-Proto  $scheme;
-    proxy_set_header X-Forwarded-For    $proxy_add_x_forwarded_for;
-    proxy_set_header X-Real-IP          $remote_addr;
-    proxy_pass http://{upstream_key}{};
+  location {path} {{{access}{content}{proxy}{forwarded}{pass}
     {disable_logging}
-  }}",
-          upstream_target.path.clone().unwrap_or("".into())
+  }}"
+        );
+        locations.push(location);
+      }
+      LocationTarget::Split(split) => {
+        let id = gen_split(name, path, split, http_conf, client, nginx)
+          .await
+          .map_err(|err| {
+            err.map_err_context(|| format!("Invalid split of location {path}"))
+          })?;
+        let disable_logging = if split.disable_logging.unwrap_or_default() {
+          "access_log off;"
+        } else {
+          ""
+        };
+        let sticky_cookie = match &split.sticky_cookie {
+          Some(cookie) => format!(
+            "\n    add_header Set-Cookie \"{cookie}=$split_{id}_index; Path=/; HttpOnly\";"
+          ),
+          None => String::default(),
+        };
+        let forwarded = gen_forwarded_headers(rule);
+        let pass = gen_location_pass(rule, &format!("$split_{id}_target"));
+        let location = format!(
+          "
+  location {path} {{{access}{content}{sticky_cookie}{proxy}{forwarded}{pass}
+    {disable_logging}
+  }}"
         );
         locations.push(location);
       }
       LocationTarget::Unix(unix) => {
         let upstream_key = gen_unix_stream(&unix.unix_path, nginx).await?;
+        let forwarded = gen_forwarded_headers(rule);
+        let pass = gen_location_pass(rule, &format!("{upstream_key}/"));
         let location = format!(
-          "location {path} {{{version}{headers}
-    proxy_pass http://{upstream_key}/;
-    proxy_set_header Host $host;
-    proxy_set_header X-Forwarded-Scheme $scheme;
-    proxy_set_header X-Forwarded-Proto  $scheme;
-    proxy_set_header X-Forwarded-For    $proxy_add_x_forwarded_for;
-    proxy_set_header X-Real-IP          $remote_addr;
+          "location {path} {{{access}{content}{proxy}{forwarded}{pass}
   }}
   "
         );
@@ -334,17 +1014,17 @@ async fn gen_locations(
           Some(redirect) => {
             format!(
               "
-  location {path} {{{version}{headers}
+  location {path} {{{access}
     return {redirect} {url};
   }}
 "
             )
           }
           None => {
+            let pass = gen_location_pass(rule, &url);
             format!(
               "
-  location {path} {{{version}{headers}
-    proxy_pass {url};
+  location {path} {{{access}{content}{proxy}{pass}
   }}
 "
             )
@@ -357,49 +1037,92 @@ async fn gen_locations(
   Ok(locations)
 }
 
+/// Write the certificates of a `Tls` secret to the disk
+/// and return the ssl config with their paths
+async fn get_secret_ssl_config(
+  key: &str,
+  client: &NanocldClient,
+  nginx: &Nginx,
+) -> IoResult<ProxySslConfig> {
+  let secret = client.inspect_secret(key).await?;
+  let mut ssl_config = serde_json::from_value::<ProxySslConfig>(secret.data)
+    .map_err(|err| {
+      err.map_err_context(|| "Unable to deserialize ProxySslConfig")
+    })?;
+  let write = |ext: &str, data: &str| {
+    let file_name = gen_secret_file_name(&secret.key, ext, data);
+    nginx.write_secret_file(&file_name, data)
+  };
+  ssl_config.certificate = write("cert", &ssl_config.certificate)?;
+  ssl_config.certificate_key = write("key", &ssl_config.certificate_key)?;
+  if let Some(certificate_client) = &ssl_config.certificate_client {
+    ssl_config.certificate_client =
+      Some(write("client.cert", certificate_client)?);
+  }
+  if let Some(dh_param) = &ssl_config.dh_param {
+    ssl_config.dh_param = Some(write("pem", dh_param)?);
+  }
+  Ok(ssl_config)
+}
+
 async fn get_ssl_config(
   ssl: &ProxySsl,
   client: &NanocldClient,
+  nginx: &Nginx,
 ) -> IoResult<ProxySslConfig> {
   match ssl {
     ProxySsl::Config(ssl_config) => Ok(ssl_config.clone()),
     ProxySsl::Secret(secret) => {
-      let secret = client.inspect_secret(secret).await?;
-      let mut ssl_config =
-        serde_json::from_value::<ProxySslConfig>(secret.data).map_err(
-          |err| err.map_err_context(|| "Unable to deserialize ProxySslConfig"),
-        )?;
-      let cert_path = format!("/opt/secrets/{}.cert", secret.key);
-      tokio::fs::write(&cert_path, ssl_config.certificate.clone()).await?;
-      let key_path = format!("/opt/secrets/{}.key", secret.key);
-      tokio::fs::write(&key_path, ssl_config.certificate_key.clone()).await?;
-      if let Some(certificate_client) = ssl_config.certificate_client {
-        let certificate_client_path =
-          format!("/opt/secrets/{}.client.cert", secret.key);
-        tokio::fs::write(&certificate_client_path, certificate_client).await?;
-        ssl_config.certificate_client = Some(certificate_client_path);
-      }
-      if let Some(dh_param) = ssl_config.dh_param {
-        let dh_param_path = format!("/opt/secrets/{}.pem", secret.key);
-        tokio::fs::write(&dh_param_path, dh_param).await?;
-        ssl_config.dh_param = Some(dh_param_path);
-      }
-      ssl_config.certificate = cert_path;
-      ssl_config.certificate_key = key_path;
-      Ok(ssl_config)
+      get_secret_ssl_config(secret, client, nginx).await
     }
+    ProxySsl::Acme(_) => Err(IoError::invalid_input(
+      "ProxySsl",
+      "Acme is only supported by http rules with a domain",
+    )),
   }
 }
 
+/// Location serving the ACME HTTP-01 challenges
+fn gen_acme_location(nginx: &Nginx) -> String {
+  format!(
+    "
+  location ^~ /.well-known/acme-challenge/ {{
+    alias {}/;
+    default_type text/plain;
+  }}",
+    nginx.acme_challenge_dir
+  )
+}
+
 async fn gen_http_server_block(
+  name: &str,
   rule: &ProxyRuleHttp,
   client: &NanocldClient,
   nginx: &Nginx,
 ) -> IoResult<String> {
+  // The plain listener only speaks http/1.1 so grpc require http2 over ssl
+  if rule.ssl.is_none()
+    && rule
+      .locations
+      .iter()
+      .any(|location| location.protocol == Some(ProxyHttpProtocol::Grpc))
+  {
+    return Err(IoError::invalid_input(
+      "ProxyHttpLocation",
+      "Grpc require the rule to have ssl",
+    ));
+  }
   let listen_http = get_listen(&rule.network, 80, client).await?;
-  let locations = gen_locations(&rule.locations, client, nginx)
-    .await?
-    .join("\n");
+  let scope = format!(
+    "{}{}",
+    rule.domain.clone().unwrap_or_default(),
+    rule.network
+  );
+  let mut http_conf = String::new();
+  let locations =
+    gen_locations(name, &scope, &rule.locations, &mut http_conf, client, nginx)
+      .await?
+      .join("\n");
   let http_host = match &rule.domain {
     Some(domain) => format!(
       "  server_name {domain};\n  if ($host != {domain}) {{ return 404; }}\n",
@@ -408,44 +1131,76 @@ async fn gen_http_server_block(
     None => String::default(),
   };
 
-  let ssl = if let Some(ssl) = &rule.ssl {
-    if let Ok(ssl) = get_ssl_config(ssl, client).await {
-      let certificate = &ssl.certificate;
-      let certificate_key = &ssl.certificate_key;
-      let ssl_dh_param = match &ssl.dh_param {
-        Some(ssl_dh_param) => {
-          format!("\n  ssl_dhparam          {ssl_dh_param};\n")
-        }
-        None => String::default(),
+  let (ssl_config, is_acme) = match &rule.ssl {
+    Some(ProxySsl::Acme(ssl)) => {
+      let Some(domain) = &rule.domain else {
+        return Err(IoError::invalid_input(
+          "ProxySsl",
+          "Acme require the rule to have a domain",
+        ));
       };
-      let listen_https = get_listen(&rule.network, 443, client).await?;
-      let mut base = format!(
-        "
-    listen {listen_https} http2 ssl;
-
-    if ($scheme != https) {{
-      return 301 https://$host$request_uri;
-    }}
-
-    ssl_certificate      {certificate};
-    ssl_certificate_key  {certificate_key};{ssl_dh_param}
-  "
-      );
+      // The certificate is missing until the first challenge succeed
+      let secret = ssl.acme.secret_name(domain);
+      (
+        get_secret_ssl_config(&secret, client, nginx).await.ok(),
+        true,
+      )
+    }
+    Some(ssl) => (get_ssl_config(ssl, client, nginx).await.ok(), false),
+    None => (None, false),
+  };
+  // The ACME challenges must stay reachable over http
+  let https_redirect = if is_acme {
+    "
+  set $https_redirect \"\";
+  if ($scheme != https) {
+    set $https_redirect \"1\";
+  }
+  if ($uri ~ \"^/\\.well-known/acme-challenge/\") {
+    set $https_redirect \"\";
+  }
+  if ($https_redirect) {
+    return 301 https://$host$request_uri;
+  }
+"
+  } else {
+    "
+  if ($scheme != https) {
+    return 301 https://$host$request_uri;
+  }
+"
+  };
 
-      if let Some(certificate_client) = &ssl.certificate_client {
-        base += &format!("  ssl_client_certificate {certificate_client};\n");
+  let ssl = if let Some(ssl) = ssl_config {
+    let certificate = &ssl.certificate;
+    let certificate_key = &ssl.certificate_key;
+    let ssl_dh_param = match &ssl.dh_param {
+      Some(ssl_dh_param) => {
+        format!("\n  ssl_dhparam          {ssl_dh_param};\n")
       }
+      None => String::default(),
+    };
+    let listen_https = get_listen(&rule.network, 443, client).await?;
+    let mut base = format!(
+      "
+  listen {listen_https} http2 ssl;
+{https_redirect}
+  ssl_certificate      {certificate};
+  ssl_certificate_key  {certificate_key};{ssl_dh_param}
+"
+    );
 
-      if let Some(client_verification) = &ssl.verify_client {
-        base += &format!(
-          "  ssl_verify_client {};\n",
-          if *client_verification { "on" } else { "off" }
-        );
-      }
-      base
-    } else {
-      String::default()
+    if let Some(certificate_client) = &ssl.certificate_client {
+      base += &format!("  ssl_client_certificate {certificate_client};\n");
+    }
+
+    if let Some(client_verification) = &ssl.verify_client {
+      base += &format!(
+        "  ssl_verify_client {};\n",
+        if *client_verification { "on" } else { "off" }
+      );
     }
+    base
   } else {
     String::default()
   };
@@ -459,11 +1214,18 @@ async fn gen_http_server_block(
     None => String::default(),
   };
 
+  let acme_location = if is_acme {
+    gen_acme_location(nginx)
+  } else {
+    String::default()
+  };
+
   let conf = format!(
     "
+{http_conf}
 server {{
   listen {listen_http};
-{http_host}{ssl}{includes}
+{http_host}{ssl}{includes}{acme_location}
 {locations}
 }}\n",
   );
@@ -471,6 +1233,7 @@ server {{
 }
 
 async fn gen_stream_server_block(
+  name: &str,
   rule: &ProxyRuleStream,
   client: &NanocldClient,
   nginx: &Nginx,
@@ -480,7 +1243,8 @@ async fn gen_stream_server_block(
 
   let upstream_key = match &rule.target {
     StreamTarget::Upstream(cargo_target) => {
-      gen_upstream(&NginxConfKind::Stream, cargo_target, client, nginx).await?
+      gen_upstream(&NginxConfKind::Stream, name, cargo_target, client, nginx)
+        .await?
     }
     StreamTarget::Unix(unix) => gen_unix_stream(&unix.unix_path, nginx).await?,
     StreamTarget::Uri(_) => {
@@ -492,7 +1256,7 @@ async fn gen_stream_server_block(
   };
 
   let ssl = if let Some(ssl) = &rule.ssl {
-    let ssl = get_ssl_config(ssl, client).await?;
+    let ssl = get_ssl_config(ssl, client, nginx).await?;
     let certificate = &ssl.certificate;
     let certificate_key = &ssl.certificate_key;
     let ssl_dh_param = match &ssl.dh_param {
@@ -553,10 +1317,11 @@ async fn resource_to_nginx_conf(
   for rule in resource_proxy.rules.iter() {
     match rule {
       ProxyRule::Http(rule) => {
-        http_conf += &gen_http_server_block(rule, client, nginx).await?;
+        http_conf += &gen_http_server_block(name, rule, client, nginx).await?;
       }
       ProxyRule::Stream(rule) => {
-        stream_conf += &gen_stream_server_block(rule, client, nginx).await?;
+        stream_conf +=
+          &gen_stream_server_block(name, rule, client, nginx).await?;
       }
     }
   }
@@ -572,28 +1337,27 @@ async fn resource_to_nginx_conf(
   Ok(())
 }
 
-/// Reload the proxy configuration
-/// This function will reload the nginx configuration
-pub(crate) async fn reload_config(client: &NanocldClient) -> IoResult<()> {
-  log::info!("Reloading proxy configuration");
-
+/// Execute a command inside the proxy container
+/// and return an error with his output if it failed
+async fn exec_proxy(cmd: Vec<String>, client: &NanocldClient) -> IoResult<()> {
+  let context = format!("Unable to execute {}", cmd.join(" "));
   let exec_options = CreateExecOptions {
     attach_stderr: Some(true),
     attach_stdout: Some(true),
-    cmd: Some(vec!["nginx".into(), "-s".into(), "reload".into()]),
+    cmd: Some(cmd),
     ..Default::default()
   };
   let start_res = client
     .create_exec("nproxy", exec_options, Some("system".into()))
     .await
-    .map_err(|err| err.map_err_context(|| "Unable to reload proxy configs"))?;
+    .map_err(|err| err.map_err_context(|| &context))?;
   let mut start_stream = client
     .start_exec(
       &start_res.id,
       bollard_next::exec::StartExecOptions::default(),
     )
     .await
-    .map_err(|err| err.map_err_context(|| "Unable to reload proxy configs"))?;
+    .map_err(|err| err.map_err_context(|| &context))?;
   let mut output = String::default();
   while let Some(output_log) = start_stream.next().await {
     let Ok(output_log) = output_log else {
@@ -603,20 +1367,52 @@ pub(crate) async fn reload_config(client: &NanocldClient) -> IoResult<()> {
   }
   let inspect_result = client.inspect_exec(&start_res.id).await?;
   match inspect_result.exit_code {
-    Some(code) => {
-      if code == 0 {
-        log::info!("Proxy configuration reloaded");
-        return Ok(());
-      }
-      Err(IoError::invalid_data("nproxy reload", &output))
-    }
-    None => Ok(()),
+    Some(code) if code != 0 => Err(IoError::invalid_data(&context, &output)),
+    _ => Ok(()),
   }
 }
 
+/// Execute a nginx command inside the proxy container
+async fn exec_nginx(args: &[&str], client: &NanocldClient) -> IoResult<()> {
+  let mut cmd = vec!["nginx".to_owned()];
+  cmd.extend(args.iter().map(|arg| arg.to_string()));
+  exec_proxy(cmd, client).await
+}
+
+/// Test the proxy configuration
+/// This function will check the nginx configuration with `nginx -t`
+pub(crate) async fn test_config(client: &NanocldClient) -> IoResult<()> {
+  exec_nginx(&["-t"], client).await
+}
+
+/// Test the staged proxy configuration
+/// The main nginx configuration is copied with his includes
+/// pointing to the staging directory then checked with `nginx -t`
+async fn test_staging_config(client: &NanocldClient) -> IoResult<()> {
+  let script = "sed \
+    -e 's#/etc/nginx/sites-enabled/#/etc/nginx/staging/sites-enabled/#' \
+    -e 's#/etc/nginx/streams-enabled/#/etc/nginx/staging/streams-enabled/#' \
+    /etc/nginx/nginx.conf > /etc/nginx/staging/nginx.conf \
+    && nginx -t -q -c /etc/nginx/staging/nginx.conf";
+  let cmd = vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()];
+  exec_proxy(cmd, client).await
+}
+
+/// Reload the proxy configuration
+/// This function will test then reload the nginx configuration
+pub(crate) async fn reload_config(client: &NanocldClient) -> IoResult<()> {
+  log::info!("Reloading proxy configuration");
+  test_config(client).await?;
+  exec_nginx(&["-s", "reload"], client).await?;
+  log::info!("Proxy configuration reloaded");
+  Ok(())
+}
+
 /// Create a new resource configuration
-/// This function will create a new configuration file for the given resource
-/// and reload the nginx configuration
+/// This function will render the configuration of the given resource
+/// in the staging directory and test it with the live configurations.
+/// The configuration files are only swapped in when it's valid
+/// and their last known good version is restored if the swap break nginx.
 /// The resource must be a ProxyRule
 pub(crate) async fn create_resource_conf(
   name: &str,
@@ -624,7 +1420,26 @@ pub(crate) async fn create_resource_conf(
   client: &NanocldClient,
   nginx: &Nginx,
 ) -> IoResult<()> {
-  resource_to_nginx_conf(client, nginx, name, proxy_rule).await?;
+  let _lock = nginx.lock_staging().await;
+  let rendered_at = std::time::Instant::now();
+  nginx.prepare_staging()?;
+  resource_to_nginx_conf(client, &nginx.staging(), name, proxy_rule).await?;
+  if let Err(err) = test_staging_config(client).await {
+    return Err(IoError::invalid_data(
+      format!("Invalid nginx configuration for rule {name}"),
+      err.inner.to_string(),
+    ));
+  }
+  let swapped = nginx.commit_staging()?;
+  if let Err(err) = test_config(client).await {
+    nginx.restore_known_good(&swapped)?;
+    return Err(err);
+  }
+  // Stop checking the targets removed from the rule
+  nginx.upstream_health.unregister(name, Some(rendered_at));
+  if let Err(err) = nginx.remove_stale_secrets() {
+    log::warn!("{err}");
+  }
   Ok(())
 }
 
@@ -691,9 +1506,31 @@ pub(crate) async fn list_resource_by_cargo(
     client.list_resource(Some(query)).await.map_err(|err| {
       err.map_err_context(|| "Unable to list resources from nanocl daemon")
     })?;
+  let query = ResourceQuery {
+    contains: Some(
+      serde_json::json!({ "Rules": [ { "Locations": [ { "Target": { "Upstreams": [ { "Key": target_key } ] } } ] } ] }).to_string(),
+    ),
+    kind: Some("ProxyRule".into()),
+  };
+  let split_resources =
+    client.list_resource(Some(query)).await.map_err(|err| {
+      err.map_err_context(|| "Unable to list resources from nanocl daemon")
+    })?;
+  let query = ResourceQuery {
+    contains: Some(
+      serde_json::json!({ "Rules": [ { "Locations": [ { "Target": { "Canary": { "Key": target_key } } } ] } ] }).to_string(),
+    ),
+    kind: Some("ProxyRule".into()),
+  };
+  let canary_resources =
+    client.list_resource(Some(query)).await.map_err(|err| {
+      err.map_err_context(|| "Unable to list resources from nanocl daemon")
+    })?;
   let resources = http_ressources
     .into_iter()
     .chain(stream_resources.into_iter())
+    .chain(split_resources.into_iter())
+    .chain(canary_resources.into_iter())
     .collect::<Vec<nanocld_client::stubs::resource::Resource>>();
   log::debug!(
     "matching resources for target: {target_key}:\n{:?}",
@@ -717,9 +1554,25 @@ pub(crate) async fn list_resource_by_secret(
     ),
     kind: Some("ProxyRule".into()),
   };
-  let resources = client.list_resource(Some(query)).await.map_err(|err| {
-    err.map_err_context(|| "Unable to list resources from nanocl daemon")
-  })?;
+  let ssl_resources =
+    client.list_resource(Some(query)).await.map_err(|err| {
+      err.map_err_context(|| "Unable to list resources from nanocl daemon")
+    })?;
+  let query = ResourceQuery {
+    contains: Some(
+      serde_json::json!({ "Rules": [ { "Locations": [ { "BasicAuth": secret } ] } ] })
+        .to_string(),
+    ),
+    kind: Some("ProxyRule".into()),
+  };
+  let basic_auth_resources =
+    client.list_resource(Some(query)).await.map_err(|err| {
+      err.map_err_context(|| "Unable to list resources from nanocl daemon")
+    })?;
+  let resources = ssl_resources
+    .into_iter()
+    .chain(basic_auth_resources.into_iter())
+    .collect::<Vec<nanocld_client::stubs::resource::Resource>>();
   log::debug!("matching resources for secret: {secret}:\n{:?}", resources);
   Ok(resources)
 }
@@ -762,7 +1615,8 @@ pub(crate) mod tests {
 
   pub fn generate_server() -> ntex::web::test::TestServer {
     before();
-    let nginx = Nginx::new("/tmp/nginx");
+    let nginx = Nginx::new("/tmp/nginx")
+      .with_acme_challenge_dir("/tmp/nginx/acme-challenge");
     nginx.ensure().unwrap();
     // Create test server
     ntex::web::test::server(move || {
@@ -771,4 +1625,244 @@ pub(crate) mod tests {
         .configure(services::ntex_config)
     })
   }
+
+  #[test]
+  fn stable_id() {
+    assert_eq!(super::gen_id(""), "cbf29ce484222325");
+    assert_eq!(super::gen_id("a"), "af63dc4c8601ec8c");
+  }
+
+  #[test]
+  fn split_conf() {
+    use nanocld_client::stubs::proxy::{SplitTarget, CanaryTarget, RouteMatch};
+
+    let upstreams = vec![
+      ("cargo-blue-80".to_owned(), 90),
+      ("cargo-green-80".to_owned(), 10),
+    ];
+    let mut split = SplitTarget {
+      upstreams: vec![],
+      sticky_cookie: Some("release".into()),
+      canary: Some(CanaryTarget {
+        key: "canary.global.c".into(),
+        port: 80,
+        header: Some(RouteMatch {
+          name: "X-Canary".into(),
+          value: "always".into(),
+        }),
+        cookie: None,
+      }),
+      disable_logging: None,
+    };
+    let conf =
+      super::gen_split_conf("id", &upstreams, &split, Some("cargo-canary-80"))
+        .unwrap();
+    assert!(conf.contains("split_clients \"$request_id\" $split_id_pick {"));
+    assert!(conf.contains("  90.00% 0;\n  * 1;"));
+    assert!(conf.contains("map $cookie_release $split_id_index {"));
+    assert!(conf.contains("map $http_x_canary $split_id_header {"));
+    assert!(conf.contains("  \"always\" cargo-canary-80;"));
+    assert!(conf.contains("map $split_id_index $split_id_target {"));
+    split.sticky_cookie = Some("bad-cookie".into());
+    assert!(super::gen_split_conf("id", &upstreams, &split, None).is_err());
+    split.sticky_cookie = None;
+    let upstreams = vec![("cargo-blue-80".to_owned(), 0)];
+    assert!(super::gen_split_conf("id", &upstreams, &split, None).is_err());
+  }
+
+  #[test]
+  fn location_access() {
+    use nanocld_client::stubs::proxy::{ProxyBasicAuth, ProxyBasicAuthUser};
+
+    assert!(super::validate_ip_range("10.0.0.1").is_ok());
+    assert!(super::validate_ip_range("10.0.0.0/8").is_ok());
+    assert!(super::validate_ip_range("fd00::/64").is_ok());
+    assert!(super::validate_ip_range("10.0.0.0/33").is_err());
+    assert!(super::validate_ip_range("all").is_err());
+    let mut basic_auth = ProxyBasicAuth {
+      users: vec![
+        ProxyBasicAuthUser {
+          name: "admin".into(),
+          password: "$ecret".into(),
+          hashed: None,
+        },
+        ProxyBasicAuthUser {
+          name: "bot".into(),
+          password: "{SSHA}hash".into(),
+          hashed: Some(true),
+        },
+      ],
+    };
+    assert_eq!(
+      super::gen_htpasswd(&basic_auth).unwrap(),
+      "admin:{PLAIN}$ecret\nbot:{SSHA}hash\n"
+    );
+    basic_auth.users[0].name = "ad:min".into();
+    assert!(super::gen_htpasswd(&basic_auth).is_err());
+  }
+
+  #[ntex::test]
+  async fn locations_access() {
+    use nanocld_client::NanocldClient;
+    use nanocld_client::stubs::proxy::ProxyHttpLocation;
+
+    let client = NanocldClient::connect_with_unix_default();
+    let nginx = Nginx::new("/tmp/nginx-access-test");
+    let mut locations =
+      serde_json::from_value::<Vec<ProxyHttpLocation>>(serde_json::json!([{
+        "Path": "/admin",
+        "RateLimit": { "Rate": 10 },
+        "Target": { "Url": "http://example.com" },
+      }]))
+      .unwrap();
+    let mut http_conf = String::new();
+    let conf = super::gen_locations(
+      "access",
+      "scope",
+      &locations,
+      &mut http_conf,
+      &client,
+      &nginx,
+    )
+    .await
+    .unwrap();
+    assert!(conf[0].contains("limit_req zone=limit_"));
+    assert!(http_conf.starts_with("limit_req_zone $binary_remote_addr"));
+    // An invalid access reject the rule instead of serving it unrestricted
+    locations[0].denied_ips = Some(vec!["all".into()]);
+    let mut http_conf = String::new();
+    assert!(super::gen_locations(
+      "access",
+      "scope",
+      &locations,
+      &mut http_conf,
+      &client,
+      &nginx
+    )
+    .await
+    .is_err());
+  }
+
+  #[test]
+  fn location_content() {
+    use nanocld_client::stubs::proxy::{
+      ProxyCache, ProxyCacheZone, ProxyCacheValid, ProxyCompression,
+    };
+
+    let mut cache = ProxyCache {
+      zone: ProxyCacheZone::Public,
+      valid: Some(vec![ProxyCacheValid {
+        status: vec!["200".into(), "404".into()],
+        ttl: "5m".into(),
+      }]),
+      bypass: Some(vec!["$http_authorization".into()]),
+    };
+    assert_eq!(
+      super::gen_location_cache(&cache).unwrap(),
+      "\n    proxy_cache public-cache;\
+      \n    proxy_cache_valid 200 404 5m;\
+      \n    proxy_cache_bypass $http_authorization;\
+      \n    proxy_no_cache $http_authorization;\
+      \n    add_header X-Cache-Status $upstream_cache_status;"
+    );
+    cache.bypass = Some(vec!["http_authorization".into()]);
+    assert!(super::gen_location_cache(&cache).is_err());
+    cache.bypass = None;
+    cache.valid.as_mut().unwrap()[0].ttl = "5 minutes".into();
+    assert!(super::gen_location_cache(&cache).is_err());
+    let mut compression = ProxyCompression {
+      gzip: Some(true),
+      brotli: Some(false),
+      level: None,
+      types: Some(vec!["application/json".into()]),
+      min_length: Some(1024),
+    };
+    assert_eq!(
+      super::gen_location_compression(&compression).unwrap(),
+      "\n    gzip on;\n    gzip_comp_level 6;\
+      \n    gzip_types application/json;\n    gzip_min_length 1024;\
+      \n    gzip_proxied any;"
+    );
+    compression.level = Some(10);
+    assert!(super::gen_location_compression(&compression).is_err());
+    compression.level = None;
+    compression.brotli = Some(true);
+    assert!(super::gen_location_compression(&compression).is_err());
+  }
+
+  #[test]
+  fn location_proxy() {
+    use nanocld_client::stubs::proxy::{ProxyHttpLocation, ProxyHttpProtocol};
+
+    let mut location =
+      serde_json::from_value::<ProxyHttpLocation>(serde_json::json!({
+        "Path": "/",
+        "Protocol": "Grpc",
+        "Headers": ["X-Api v1"],
+        "Timeouts": { "Read": "1h" },
+        "UpstreamTls": { "Verify": true, "ServerName": "api.internal" },
+        "Target": { "Key": "api.global.c", "Port": 50051 },
+      }))
+      .unwrap();
+    assert_eq!(
+      super::gen_location_proxy(&location).unwrap(),
+      "\n    grpc_set_header X-Api v1;\
+      \n    grpc_read_timeout 1h;\
+      \n    grpc_ssl_server_name on;\
+      \n    grpc_ssl_name api.internal;\
+      \n    grpc_ssl_verify on;\
+      \n    grpc_ssl_trusted_certificate /etc/ssl/certs/ca-certificates.crt;"
+    );
+    assert_eq!(
+      super::gen_location_pass(&location, "cargo-api"),
+      "\n    grpc_pass grpcs://cargo-api;"
+    );
+    location.protocol = Some(ProxyHttpProtocol::WebSocket);
+    location.headers = None;
+    location.upstream_tls = None;
+    assert_eq!(
+      super::gen_location_proxy(&location).unwrap(),
+      "\n    proxy_http_version 1.1;\
+      \n    proxy_set_header Upgrade $http_upgrade;\
+      \n    proxy_set_header Connection $connection_upgrade;\
+      \n    proxy_read_timeout 1h;"
+    );
+    assert_eq!(
+      super::gen_location_pass(&location, "http://example.com"),
+      "\n    proxy_pass http://example.com;"
+    );
+    location.timeouts.as_mut().unwrap().read = Some("forever".into());
+    assert!(super::gen_location_proxy(&location).is_err());
+    location.timeouts = None;
+    location.upstream_tls = serde_json::from_value(serde_json::json!({
+      "Verify": true,
+      "TrustedCertificate": "/etc/ssl/ca.crt;\n  include /etc/passwd",
+    }))
+    .unwrap();
+    assert!(super::gen_location_proxy(&location).is_err());
+  }
+
+  #[ntex::test]
+  async fn grpc_without_ssl() {
+    use nanocld_client::NanocldClient;
+    use nanocld_client::stubs::proxy::ProxyRuleHttp;
+
+    let client = NanocldClient::connect_with_unix_default();
+    let nginx = Nginx::new("/tmp/nginx-grpc-test");
+    let rule = serde_json::from_value::<ProxyRuleHttp>(serde_json::json!({
+      "Network": "Public",
+      "Locations": [{
+        "Path": "/",
+        "Protocol": "Grpc",
+        "Target": { "Key": "api.global.c", "Port": 50051 },
+      }],
+    }))
+    .unwrap();
+    let err = super::gen_http_server_block("grpc", &rule, &client, &nginx)
+      .await
+      .unwrap_err();
+    assert!(err
+      .to_string()
+      .contains("Grpc require the rule to have ssl"));
+  }
 }
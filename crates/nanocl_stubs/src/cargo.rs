@@ -64,6 +64,92 @@ pub struct CargoSummary {
   pub instance_running: usize,
 }
 
+/// Status of the last reconciliation of the instances of a cargo
+/// The reconciler periodically restores the number of instances
+/// expected by the replication mode of the cargo
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoReconcileStatus {
+  /// Date of the last reconciliation
+  pub reconciled_at: chrono::NaiveDateTime,
+  /// Number of instances expected on the node
+  pub expected: usize,
+  /// Number of instances found before the reconciliation
+  pub found: usize,
+  /// Number of instances created by the reconciliation
+  pub created: usize,
+  /// Number of instances removed by the reconciliation
+  pub removed: usize,
+  /// Error of the reconciliation if it failed
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub error: Option<String>,
+}
+
+/// Status of the automatic rollback of the last update of a cargo
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoRollbackStatus {
+  /// Unique identifier of the cargo config of the update
+  pub config_key: uuid::Uuid,
+  /// Date of the start of the watch of the new instances
+  pub started_at: chrono::NaiveDateTime,
+  /// Date of the end of the watch, none while the instances are watched
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub finished_at: Option<chrono::NaiveDateTime>,
+  /// The cargo has been reverted to his previous config
+  pub rolled_back: bool,
+  /// Failure that caused the rollback or error of the watch or of the rollback
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub error: Option<String>,
+}
+
+/// A scaling of a cargo from the api or by his autoscaler
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoScaleHistory {
+  /// Unique identifier of the scaling
+  pub key: uuid::Uuid,
+  /// Date of the scaling
+  pub created_at: chrono::NaiveDateTime,
+  /// Key of the cargo
+  pub cargo_key: String,
+  /// Name of the node where the cargo has been scaled
+  pub node_name: String,
+  /// Number of instances before the scaling
+  pub previous_replicas: usize,
+  /// Number of instances after the scaling
+  pub replicas: usize,
+  /// Average cpu usage of the instances in percent
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub cpu_usage: Option<f64>,
+  /// Average memory usage of the instances in percent
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub memory_usage: Option<f64>,
+  /// Reason of the scaling
+  pub reason: String,
+}
+
 /// Cargo Inspect is a detailed view of a cargo
 /// It contains all the information about the cargo
 /// It also contains the list of containers
@@ -88,6 +174,18 @@ pub struct CargoInspect {
   pub instance_running: usize,
   /// List of containers
   pub instances: Vec<NodeContainerSummary>,
+  /// Status of the last reconciliation of the instances
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub reconcile_status: Option<CargoReconcileStatus>,
+  /// Status of the automatic rollback of the last update
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub rollback_status: Option<CargoRollbackStatus>,
 }
 
 /// Kind of ExecOutput
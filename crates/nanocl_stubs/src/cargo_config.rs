@@ -6,6 +6,7 @@ pub use bollard_next::models::HostConfig;
 pub use bollard_next::models::HealthConfig;
 
 use crate::cargo::CargoInspect;
+use crate::generic::GenericDependency;
 
 /// Auto is used to automatically define that the number of replicas in the cluster
 /// Number is used to manually set the number of replicas
@@ -45,6 +46,152 @@ pub struct ReplicationStatic {
   pub number: usize,
 }
 
+/// Update strategy used when the configuration of a cargo change
+/// Instances are replaced by batch instead of being recreated all at once
+/// A new instance must be running (or healthy if an health check is defined)
+/// before the old one is removed
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct UpdateStrategy {
+  /// Maximum number of instances that can be created above the desired number of replicas
+  /// during the update (default: 1)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub max_surge: Option<usize>,
+  /// Maximum number of instances that can be unavailable during the update (default: 0)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub max_unavailable: Option<usize>,
+  /// Number of instances replaced at the same time
+  /// (default and maximum: max_surge + max_unavailable)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub batch_size: Option<usize>,
+  /// Time in seconds to wait for a new instance to be ready (default: 60)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub timeout: Option<u64>,
+}
+
+/// Automatic rollback of a cargo to his previous config
+/// when the new instances fail to start or are reported unhealthy
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoRollback {
+  /// Enable the automatic rollback (default: false)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub enabled: Option<bool>,
+  /// Time in seconds during which the new instances are watched (default: 60)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub timeout: Option<u64>,
+  /// Number of consecutive failed health probes before rolling back
+  /// (default: rollback when docker report the instance unhealthy)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub failed_probes: Option<i64>,
+}
+
+/// Horizontal autoscaling of a cargo
+/// The number of instances is adjusted between the min and max replicas
+/// to keep the average cpu or memory usage of the instances near the target
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoAutoscale {
+  /// Minimum number of replicas (default: 1)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub min_replicas: Option<usize>,
+  /// Maximum number of replicas
+  pub max_replicas: usize,
+  /// Target average cpu usage of the instances in percent
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub target_cpu: Option<u64>,
+  /// Target average memory usage of the instances in percent of their limit
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub target_memory: Option<u64>,
+  /// Time in seconds to wait after a scaling before scaling up (default: 60)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub scale_up_cooldown: Option<u64>,
+  /// Time in seconds to wait after a scaling before scaling down (default: 300)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub scale_down_cooldown: Option<u64>,
+}
+
+/// A container attached to every instance of a cargo
+/// Used to define init containers and sidecars
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoContainer {
+  /// Name of the container, unique in the cargo
+  pub name: String,
+  /// Container configuration
+  pub container: Config,
+}
+
+/// A secret mounted as files in the instances of a cargo
+/// Each key of the secret data is written in a file named after it
+/// The files are refreshed in place when the secret is patched
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoSecretFile {
+  /// Key of the secret
+  pub secret: String,
+  /// Directory of the files inside the container
+  pub target: String,
+  /// Permissions of the files (default: 0o444)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub mode: Option<u32>,
+  /// Keys of the secret data to write, every key if not set
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub keys: Option<Vec<String>>,
+}
+
 /// A cargo config partial is used to create a Cargo
 #[derive(Debug, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
@@ -73,6 +220,48 @@ pub struct CargoConfigPartial {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub replication: Option<ReplicationMode>,
+  /// Update strategy of the cargo instances when the config change
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub update_strategy: Option<UpdateStrategy>,
+  /// Automatic rollback of the cargo when an update fail
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub rollback: Option<CargoRollback>,
+  /// Horizontal autoscaling of the cargo instances
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub autoscale: Option<CargoAutoscale>,
+  /// Cargoes or virtual machines to wait for before applying the cargo
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub depends_on: Option<Vec<GenericDependency>>,
+  /// Containers to run to completion before each instance is started
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub init_containers: Option<Vec<CargoContainer>>,
+  /// Containers sharing the network namespace of each instance
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub sidecars: Option<Vec<CargoContainer>>,
+  /// Secrets mounted as files in each instance
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub secret_files: Option<Vec<CargoSecretFile>>,
 }
 
 /// Payload used to patch a cargo
@@ -113,6 +302,48 @@ pub struct CargoConfigUpdate {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub replication: Option<ReplicationMode>,
+  /// Update strategy of the cargo instances when the config change
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub update_strategy: Option<UpdateStrategy>,
+  /// Automatic rollback of the cargo when an update fail
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub rollback: Option<CargoRollback>,
+  /// Horizontal autoscaling of the cargo instances
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub autoscale: Option<CargoAutoscale>,
+  /// Cargoes or virtual machines to wait for before applying the cargo
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub depends_on: Option<Vec<GenericDependency>>,
+  /// Containers to run to completion before each instance is started
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub init_containers: Option<Vec<CargoContainer>>,
+  /// Containers sharing the network namespace of each instance
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub sidecars: Option<Vec<CargoContainer>>,
+  /// Secrets mounted as files in each instance
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub secret_files: Option<Vec<CargoSecretFile>>,
 }
 
 impl From<CargoConfigPartial> for CargoConfigUpdate {
@@ -123,6 +354,13 @@ impl From<CargoConfigPartial> for CargoConfigUpdate {
       replication: cargo_config.replication,
       metadata: cargo_config.metadata,
       secrets: cargo_config.secrets,
+      update_strategy: cargo_config.update_strategy,
+      rollback: cargo_config.rollback,
+      autoscale: cargo_config.autoscale,
+      depends_on: cargo_config.depends_on,
+      init_containers: cargo_config.init_containers,
+      sidecars: cargo_config.sidecars,
+      secret_files: cargo_config.secret_files,
     }
   }
 }
@@ -165,6 +403,48 @@ pub struct CargoConfig {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub replication: Option<ReplicationMode>,
+  /// Update strategy of the cargo instances when the config change
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub update_strategy: Option<UpdateStrategy>,
+  /// Automatic rollback of the cargo when an update fail
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub rollback: Option<CargoRollback>,
+  /// Horizontal autoscaling of the cargo instances
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub autoscale: Option<CargoAutoscale>,
+  /// Cargoes or virtual machines to wait for before applying the cargo
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub depends_on: Option<Vec<GenericDependency>>,
+  /// Containers to run to completion before each instance is started
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub init_containers: Option<Vec<CargoContainer>>,
+  /// Containers sharing the network namespace of each instance
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub sidecars: Option<Vec<CargoContainer>>,
+  /// Secrets mounted as files in each instance
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub secret_files: Option<Vec<CargoSecretFile>>,
 }
 
 impl From<CargoConfig> for CargoConfigPartial {
@@ -175,6 +455,13 @@ impl From<CargoConfig> for CargoConfigPartial {
       container: cargo_config.container,
       metadata: cargo_config.metadata,
       secrets: cargo_config.secrets,
+      update_strategy: cargo_config.update_strategy,
+      rollback: cargo_config.rollback,
+      autoscale: cargo_config.autoscale,
+      depends_on: cargo_config.depends_on,
+      init_containers: cargo_config.init_containers,
+      sidecars: cargo_config.sidecars,
+      secret_files: cargo_config.secret_files,
     }
   }
 }
@@ -187,6 +474,13 @@ impl From<CargoInspect> for CargoConfigPartial {
       container: cargo_inspect.config.container,
       metadata: cargo_inspect.config.metadata,
       secrets: cargo_inspect.config.secrets,
+      update_strategy: cargo_inspect.config.update_strategy,
+      rollback: cargo_inspect.config.rollback,
+      autoscale: cargo_inspect.config.autoscale,
+      depends_on: cargo_inspect.config.depends_on,
+      init_containers: cargo_inspect.config.init_containers,
+      sidecars: cargo_inspect.config.sidecars,
+      secret_files: cargo_inspect.config.secret_files,
     }
   }
 }
@@ -24,6 +24,12 @@ pub struct DaemonConfig {
   pub advertise_addr: String,
   /// Config directory
   pub conf_dir: String,
+  /// Path to the master key of the secrets shared by the nodes
+  #[cfg_attr(feature = "serde", serde(default = "default_secret_key_file"))]
+  pub secret_key_file: String,
+  /// Path to the token authenticating the nodes of the cluster
+  #[cfg_attr(feature = "serde", serde(default = "default_cluster_token_file"))]
+  pub cluster_token_file: String,
   /// Group id
   pub gid: u32,
 }
@@ -43,6 +49,10 @@ pub struct DaemonConfigFile {
   pub gateway: Option<String>,
   /// Hostname to use for the node automatically detected if not set
   pub hostname: Option<String>,
+  /// Path to the master key of the secrets shared by the nodes
+  pub secret_key_file: Option<String>,
+  /// Path to the token authenticating the nodes of the cluster
+  pub cluster_token_file: Option<String>,
 }
 
 impl Default for DaemonConfig {
@@ -50,6 +60,8 @@ impl Default for DaemonConfig {
     Self {
       docker_host: default_host(),
       conf_dir: "/etc/nanocl".into(),
+      secret_key_file: default_secret_key_file(),
+      cluster_token_file: default_cluster_token_file(),
       gid: 0,
       hostname: String::default(),
       hosts: vec!["/run/nanocl.sock".into()],
@@ -64,3 +76,11 @@ impl Default for DaemonConfig {
 fn default_host() -> String {
   "/var/run/docker.sock".to_owned()
 }
+
+fn default_secret_key_file() -> String {
+  "/etc/nanocl/secret.key".to_owned()
+}
+
+fn default_cluster_token_file() -> String {
+  "/etc/nanocl/cluster.token".to_owned()
+}
@@ -1,13 +1,89 @@
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
+/// Type of a dns record
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(clippy::upper_case_acronyms)]
+pub enum DnsRecordType {
+  /// Ipv4 address
+  A,
+  /// Ipv6 address
+  AAAA,
+  /// Alias to another domain name
+  CNAME,
+  /// Domain name and port of a service
+  SRV,
+  /// Text strings
+  TXT,
+  /// Mail server of the domain
+  MX,
+}
+
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
 pub struct DnsEntry {
+  /// Domain name of the record, a `*.` prefix match every sub domain
   pub name: String,
+  /// Type of the record, deduced from the ip address if not set
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub kind: Option<DnsRecordType>,
+  /// Ip address or namespace gateway (`<namespace>.nsp`) of A and AAAA
+  /// records, empty for the other records
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "String::is_empty")
+  )]
   pub ip_address: String,
+  /// Domain name targeted by CNAME, SRV and MX records
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub target: Option<String>,
+  /// Key of the cargo (`<name>.<namespace>.c`) or the vm
+  /// (`<name>.<namespace>.v`) targeted by a SRV record instead of a domain
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub target_key: Option<String>,
+  /// Port of the service of a SRV record
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub port: Option<u16>,
+  /// Priority of SRV and MX records, lower is preferred (default: 10)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub priority: Option<u16>,
+  /// Weight of SRV records with the same priority (default: 0)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub weight: Option<u16>,
+  /// Strings of a TXT record
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub text: Option<Vec<String>>,
+  /// Time to live of the record in seconds (default: 60)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub ttl: Option<u32>,
 }
 
 #[derive(Clone, Debug)]
@@ -28,3 +28,42 @@ pub struct GenericCount {
   /// Number of items
   pub count: i64,
 }
+
+/// Condition a dependency must reach before its dependent is applied
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub enum GenericDependencyCondition {
+  /// The instances are running
+  #[default]
+  Started,
+  /// The instances are running and their health checks pass
+  Healthy,
+  /// The instances exited with a success code,
+  /// the restart policy of a cargo awaited to complete default to `no`
+  Completed,
+}
+
+/// Dependency of a cargo or a virtual machine
+/// on another cargo or virtual machine of the same Statefile
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct GenericDependency {
+  /// Name of the cargo or virtual machine to depend on
+  pub name: String,
+  /// Condition to reach before applying the dependent (default: Started)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub condition: Option<GenericDependencyCondition>,
+  /// Time in seconds to wait for the condition (default: 300)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub timeout: Option<u64>,
+}
@@ -67,6 +67,11 @@ pub struct HttpMetric {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub http_accept_language: Option<String>,
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub upstream_cache_status: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -113,4 +118,113 @@ pub struct HttpMetricCountQuery {
     serde(default, deserialize_with = "deserialize_status_between")
   )]
   pub status: Option<(i64, Option<i64>)>,
+  /// Count only the responses with this cache status like `HIT` or `MISS`
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub cache_status: Option<String>,
+}
+
+/// Access log lines of the proxy to save as http and stream metrics
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyLogBatch {
+  /// Lines of the http access log
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub http: Vec<serde_json::Value>,
+  /// Lines of the stream access log
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub stream: Vec<serde_json::Value>,
+}
+
+/// Number of lines of a batch saved or rejected
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyLogIngestion {
+  /// Number of http metrics saved
+  pub http: usize,
+  /// Number of stream metrics saved
+  pub stream: usize,
+  /// Number of lines that are not valid metrics
+  pub rejected: usize,
+}
+
+/// How the http metrics are grouped in the statistics
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub enum HttpMetricGroupBy {
+  /// By host of the requests
+  #[default]
+  Host,
+  /// By the first segments of the uri of the requests
+  UriPrefix,
+  /// By upstream address that handled the requests
+  Upstream,
+  /// By status class like `2xx` or `5xx`
+  StatusClass,
+}
+
+/// Filter and grouping of the http metrics statistics
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct HttpMetricStatsQuery {
+  /// How the metrics are grouped, default to `Host`
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub group_by: Option<HttpMetricGroupBy>,
+  /// Duration of a time bucket in seconds, default to 3600
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub bucket: Option<i64>,
+  /// Only use the metrics of the last seconds, default to 86400
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub since: Option<i64>,
+  /// Number of segments of the uri prefix, default to 1
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub uri_depth: Option<i64>,
+}
+
+/// Statistics of the http metrics of a group in a time bucket
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct HttpMetricStats {
+  /// Start of the time bucket
+  pub bucket: chrono::NaiveDateTime,
+  /// Value of the group like the host or the status class
+  pub group: String,
+  /// Number of requests
+  pub requests: i64,
+  /// Number of requests per second
+  pub request_rate: f64,
+  /// Median of the request time in seconds
+  pub p50: f64,
+  /// 95th percentile of the request time in seconds
+  pub p95: f64,
+  /// 99th percentile of the request time in seconds
+  pub p99: f64,
+  /// Ratio of requests with a 5xx status
+  pub error_ratio: f64,
+  /// Number of bytes sent to the clients
+  pub bytes_sent: i64,
 }
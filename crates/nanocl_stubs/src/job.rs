@@ -0,0 +1,187 @@
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use crate::cargo_config::Config;
+
+/// ## JobPartial
+///
+/// A partial job object. This is used to create a job.
+/// A job run a container to completion once or on a cron schedule,
+/// failed runs are retried with an exponential backoff.
+///
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct JobPartial {
+  /// Name of the job
+  pub name: String,
+  /// Metadata of the job (user defined)
+  #[cfg_attr(feature = "utoipa", schema(value_type = HashMap<String, Any>))]
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub metadata: Option<serde_json::Value>,
+  /// List of secrets to use as environment variables
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub secrets: Option<Vec<String>>,
+  /// Cron expression to run the job periodically, with or without seconds
+  /// Example: `*/5 * * * *` to run it every 5 minutes
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub schedule: Option<String>,
+  /// Number of times a failed run is retried (default: 0)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub retries: Option<usize>,
+  /// Time in seconds to wait before the first retry,
+  /// doubled at each retry (default: 10)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub backoff: Option<u64>,
+  /// Number of past runs to keep (default: 10)
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub history_limit: Option<usize>,
+  /// Container configuration of the job
+  pub container: Config,
+}
+
+/// ## Job
+///
+/// A job with his creation and last update date
+///
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct Job {
+  /// Name of the job
+  pub name: String,
+  /// Creation date of the job
+  pub created_at: chrono::NaiveDateTime,
+  /// Last update date of the job
+  pub updated_at: chrono::NaiveDateTime,
+  /// Configuration of the job
+  pub config: JobPartial,
+}
+
+/// ## JobRunStatus
+///
+/// Status of a run of a job
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub enum JobRunStatus {
+  /// The container of the job is running
+  Running,
+  /// The container exited with a success code
+  Succeeded,
+  /// The container exited with an error code or could not be started
+  Failed,
+}
+
+impl std::fmt::Display for JobRunStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      JobRunStatus::Running => write!(f, "Running"),
+      JobRunStatus::Succeeded => write!(f, "Succeeded"),
+      JobRunStatus::Failed => write!(f, "Failed"),
+    }
+  }
+}
+
+impl std::str::FromStr for JobRunStatus {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "Running" => Ok(JobRunStatus::Running),
+      "Succeeded" => Ok(JobRunStatus::Succeeded),
+      "Failed" => Ok(JobRunStatus::Failed),
+      _ => Err(format!("Unknown job run status {s}")),
+    }
+  }
+}
+
+/// ## JobRun
+///
+/// An attempt to run a job to completion
+///
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct JobRun {
+  /// Unique identifier of the run
+  pub key: uuid::Uuid,
+  /// Name of the job
+  pub job_name: String,
+  /// Node where the job has been run
+  pub node_name: String,
+  /// Start date of the run
+  pub created_at: chrono::NaiveDateTime,
+  /// End date of the run
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub finished_at: Option<chrono::NaiveDateTime>,
+  /// Attempt number of the run, 0 for the first try
+  pub attempt: usize,
+  /// Status of the run
+  pub status: JobRunStatus,
+  /// Exit code of the container
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub exit_code: Option<i64>,
+  /// Last lines of the output of the container or the error of the run
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub logs: Option<String>,
+}
+
+/// ## JobInspect
+///
+/// Detailed information about a job with his past runs, the most recent first
+///
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct JobInspect {
+  /// Name of the job
+  pub name: String,
+  /// Creation date of the job
+  pub created_at: chrono::NaiveDateTime,
+  /// Last update date of the job
+  pub updated_at: chrono::NaiveDateTime,
+  /// Configuration of the job
+  pub config: JobPartial,
+  /// Date of the next scheduled run
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub next_run_at: Option<chrono::NaiveDateTime>,
+  /// Past runs of the job
+  pub runs: Vec<JobRun>,
+}
@@ -17,3 +17,4 @@ pub mod vm_image;
 pub mod metric;
 pub mod http_metric;
 pub mod secret;
+pub mod job;
@@ -10,6 +10,7 @@ pub enum MetricKind {
   Memory,
   Network,
   Disk,
+  Cargo,
 }
 
 impl ToString for MetricKind {
@@ -19,10 +20,34 @@ impl ToString for MetricKind {
       MetricKind::Memory => "MEMORY".to_string(),
       MetricKind::Network => "NETWORK".to_string(),
       MetricKind::Disk => "DISK".to_string(),
+      MetricKind::Cargo => "CARGO".to_string(),
     }
   }
 }
 
+/// Usage of the running instances of a cargo on a node,
+/// the data of a `CARGO` metric is a map of these by cargo key
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CargoMetric {
+  /// Number of running instances
+  pub instances: usize,
+  /// Average cpu usage of the instances in percent of one cpu
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub cpu_usage: Option<f64>,
+  /// Average memory usage of the instances in percent of their limit
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub memory_usage: Option<f64>,
+}
+
 /// Metric entry
 #[derive(Debug)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
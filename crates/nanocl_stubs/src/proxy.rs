@@ -42,6 +42,47 @@ pub struct ProxySslConfig {
   pub dh_param: Option<String>,
 }
 
+/// Config to issue and renew the certificate of a domain with ACME HTTP-01
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyAcmeConfig {
+  /// Contact email of the ACME account
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub email: Option<String>,
+  /// Name of the `Tls` secret where the certificate is stored
+  /// default to `acme-<domain>`
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub secret: Option<String>,
+}
+
+impl ProxyAcmeConfig {
+  /// Name of the `Tls` secret where the certificate of the domain is stored
+  pub fn secret_name(&self, domain: &str) -> String {
+    match &self.secret {
+      Some(secret) => secret.to_owned(),
+      None => format!("acme-{domain}"),
+    }
+  }
+}
+
+/// Issue the certificate automatically with ACME
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxySslAcme {
+  /// The ACME config
+  pub acme: ProxyAcmeConfig,
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -49,6 +90,64 @@ pub struct ProxySslConfig {
 pub enum ProxySsl {
   Config(ProxySslConfig),
   Secret(String),
+  Acme(ProxySslAcme),
+}
+
+/// Active health check of the instances of a cargo or a vm
+/// Failing instances are removed from the upstream until they recover
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyHealthCheck {
+  /// The http path to request
+  pub path: String,
+  /// The expected http status, default to 200
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub expected_status: Option<u16>,
+  /// Seconds between two checks, default to 10
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub interval: Option<u64>,
+  /// Seconds before a check is considered failed, default to 2
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub timeout: Option<u64>,
+  /// Consecutive failures before removing an instance, default to 3
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub unhealthy_threshold: Option<u32>,
+  /// Consecutive successes before adding back an instance, default to 2
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub healthy_threshold: Option<u32>,
+}
+
+/// Health of an instance of a cargo or a vm reported by the proxy
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyUpstreamHealth {
+  /// The key of the cargo or the vm
+  pub target_key: String,
+  /// The port of the cargo or the vm
+  pub port: u16,
+  /// The ip address of the instance
+  pub address: String,
+  /// Whether the instance is in the upstream
+  pub healthy: bool,
 }
 
 /// Config for targetting a cargo or a vm
@@ -73,6 +172,12 @@ pub struct UpstreamTarget {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub disable_logging: Option<bool>,
+  /// Remove the instances failing this health check
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub health_check: Option<ProxyHealthCheck>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -113,6 +218,90 @@ pub struct HttpTarget {
   pub redirect: Option<UrlRedirect>,
 }
 
+/// A cargo or a vm receiving a share of the traffic of a split target
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct WeightedTarget {
+  /// The key of the cargo or the vm to target
+  pub key: String,
+  /// The port of the cargo or the vm to target
+  pub port: u16,
+  /// The share of the traffic relative to the other targets
+  pub weight: u32,
+  /// Remove the instances failing this health check
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub health_check: Option<ProxyHealthCheck>,
+}
+
+/// Match a request by the value of a header or a cookie
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct RouteMatch {
+  /// The name of the header or the cookie
+  pub name: String,
+  /// The value it must be equal to
+  pub value: String,
+}
+
+/// A cargo or a vm receiving the requests matching a header or a cookie
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct CanaryTarget {
+  /// The key of the cargo or the vm to target
+  pub key: String,
+  /// The port of the cargo or the vm to target
+  pub port: u16,
+  /// Route the requests having this header to the canary
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub header: Option<RouteMatch>,
+  /// Route the requests having this cookie to the canary
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub cookie: Option<RouteMatch>,
+}
+
+/// Split the traffic of a location between multiple cargoes or vms
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct SplitTarget {
+  /// The targets sharing the traffic by weight
+  pub upstreams: Vec<WeightedTarget>,
+  /// Name of a cookie keeping a client on the same target
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub sticky_cookie: Option<String>,
+  /// The target of the requests matching a header or a cookie
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub canary: Option<CanaryTarget>,
+  /// Disable logging for this target
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub disable_logging: Option<bool>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -124,6 +313,8 @@ pub enum LocationTarget {
   Http(HttpTarget),
   /// Target a specific unix socket
   Unix(UnixTarget),
+  /// Split the traffic between multiple cargoes or vms
+  Split(SplitTarget),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -219,6 +410,264 @@ pub struct ProxyHttpLocation {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub version: Option<f64>,
+  /// Limit the rate of requests per client ip
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub rate_limit: Option<ProxyRateLimit>,
+  /// Ip addresses or CIDR ranges allowed, the others are denied
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub allowed_ips: Option<Vec<String>>,
+  /// Ip addresses or CIDR ranges denied
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub denied_ips: Option<Vec<String>>,
+  /// Name of a secret of kind `BasicAuth` with the allowed users
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub basic_auth: Option<String>,
+  /// Cache the responses of the location
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub cache: Option<ProxyCache>,
+  /// Compress the responses of the location
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub compression: Option<ProxyCompression>,
+  /// Protocol used to talk with the target, default to `Http`
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub protocol: Option<ProxyHttpProtocol>,
+  /// Timeouts of the connection with the target
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub timeouts: Option<ProxyTimeouts>,
+  /// Talk with the target over tls
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub upstream_tls: Option<ProxyUpstreamTls>,
+}
+
+/// Protocol used by a location to talk with his target
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub enum ProxyHttpProtocol {
+  /// Plain http with the version of the location
+  Http,
+  /// Http 1.1 forwarding the upgrade headers
+  WebSocket,
+  /// Http 2 with `grpc_pass`, the clients need the rule to use ssl
+  Grpc,
+}
+
+/// Timeouts of a location like `60s`, `5m` or `1h`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyTimeouts {
+  /// Timeout to establish the connection with the target
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub connect: Option<String>,
+  /// Timeout between two writes to the target
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub send: Option<String>,
+  /// Timeout between two reads from the target
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub read: Option<String>,
+}
+
+/// Tls settings of the connection with the target of a location
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyUpstreamTls {
+  /// Verify the certificate of the target, default to false
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub verify: Option<bool>,
+  /// Path of the trusted CA certificates in the proxy
+  /// default to the CA certificates of the system
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub trusted_certificate: Option<String>,
+  /// The server name to send and verify, default to the target address
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub server_name: Option<String>,
+}
+
+/// Cache zones defined by the proxy
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub enum ProxyCacheZone {
+  /// Responses are shared between all clients
+  Public,
+  /// Responses are cached per authorization and cookies
+  Private,
+}
+
+/// How long the responses with the given status codes are cached
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyCacheValid {
+  /// The status codes like `200` or `any`
+  pub status: Vec<String>,
+  /// The time to live like `10m`, `1h` or `1d`
+  pub ttl: String,
+}
+
+/// Cache the responses of a location in a zone of the proxy
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyCache {
+  /// The zone where the responses are stored
+  pub zone: ProxyCacheZone,
+  /// Time to live per status codes, default to 10m for 200, 301 and 302
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub valid: Option<Vec<ProxyCacheValid>>,
+  /// Variables like `$http_authorization` or `$cookie_nocache`
+  /// the cache is bypassed when one of them is not empty and not 0
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub bypass: Option<Vec<String>>,
+}
+
+/// Compress the responses of a location
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyCompression {
+  /// Compress with gzip
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub gzip: Option<bool>,
+  /// Compress with brotli when the client accept it,
+  /// not supported by the proxy image yet so only false is accepted
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub brotli: Option<bool>,
+  /// The compression level from 1 to 9, default to 6
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub level: Option<u8>,
+  /// Mime types to compress in addition to `text/html`
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub types: Option<Vec<String>>,
+  /// Minimum length of a response to compress it
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub min_length: Option<u64>,
+}
+
+/// Limit the rate of requests of a location per client ip
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyRateLimit {
+  /// The number of requests per second allowed
+  pub rate: u32,
+  /// The number of requests above the rate that are queued, default to 0
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub burst: Option<u32>,
+  /// Serve the queued requests without waiting
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub nodelay: Option<bool>,
+}
+
+/// A user of a secret of kind `BasicAuth`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyBasicAuthUser {
+  /// The name of the user
+  pub name: String,
+  /// The password in plain text or hashed when `hashed` is true
+  pub password: String,
+  /// The password is hashed with a scheme supported by nginx
+  /// like `{SSHA}` or `$apr1$`
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub hashed: Option<bool>,
+}
+
+/// Data of a secret of kind `BasicAuth`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct ProxyBasicAuth {
+  /// The users allowed to access the locations using the secret
+  pub users: Vec<ProxyBasicAuthUser>,
 }
 
 /// Defines a proxy rule http config
@@ -1,6 +1,7 @@
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
+use crate::job::JobPartial;
 use crate::secret::SecretPartial;
 use crate::vm_config::VmConfigPartial;
 use crate::cargo_config::CargoConfigPartial;
@@ -20,6 +21,8 @@ pub struct StateMeta {
   pub api_version: String,
   /// Kind of Statefile (Deployment, Cargo, VirtualMachine, Resource)
   pub kind: String,
+  /// Name of the Statefile used to label the applied objects
+  pub name: Option<String>,
 }
 
 /// ## StateResource
@@ -48,6 +51,19 @@ pub struct StateSecret {
   pub secrets: Vec<SecretPartial>,
 }
 
+/// ## StateJob
+///
+/// Statefile that represent the `Job` kind
+///
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct StateJob {
+  /// List of jobs to create
+  pub jobs: Vec<JobPartial>,
+}
+
 /// ## StateCargo
 ///
 /// Statefile that represent the `Cargo` kind
@@ -97,6 +113,8 @@ pub struct StateDeployment {
   pub cargoes: Option<Vec<CargoConfigPartial>>,
   /// List of virtual machines to create and run
   pub virtual_machines: Option<Vec<VmConfigPartial>>,
+  /// List of jobs to create
+  pub jobs: Option<Vec<JobPartial>>,
 }
 
 /// ## StateStreamStatus
@@ -117,6 +135,50 @@ pub enum StateStreamStatus {
   NotFound,
   /// The cargo or virtual machine is not changed apply is skipped
   UnChanged,
+  /// The cargo or virtual machine would be created (dry run)
+  Create,
+  /// The cargo or virtual machine would be updated (dry run)
+  Update,
+  /// The cargo or virtual machine is no longer declared and is deleted (or would be on a dry run)
+  Pruned,
+}
+
+/// ## StateApplyQuery
+///
+/// Query parameters of the apply of a Statefile
+///
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StateApplyQuery {
+  /// Only compute what would be created, updated or left unchanged
+  pub dry_run: Option<bool>,
+  /// Delete the objects labeled with the Statefile name that are no longer declared
+  pub prune: Option<bool>,
+}
+
+/// ## StateDiff
+///
+/// A field that would be changed by the apply of a Statefile
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct StateDiff {
+  /// Path of the field separated by dots (ex: Container.Image)
+  pub path: String,
+  /// Current value of the field if any
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub old: Option<serde_json::Value>,
+  /// Desired value of the field if any
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub new: Option<serde_json::Value>,
 }
 
 /// ## StateStreamKind
@@ -134,6 +196,8 @@ pub enum StateStreamKind {
   VirtualMachine,
   /// The stream is used to apply or remove a resource
   Resource,
+  /// The stream is used to report the update of a cargo instance
+  CargoInstance,
 }
 
 /// ## StateStream
@@ -150,16 +214,63 @@ pub struct StateStream {
   pub kind: String,
   /// Some context information about the status
   pub context: Option<String>,
-  /// The status of the element (Pending, Failed, Success, NotFound, UnChanged)
+  /// The field level changes of an element that would be updated (dry run)
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub diff: Option<Vec<StateDiff>>,
+  /// The status of the element (Pending, Failed, Success, NotFound, UnChanged, Create, Update)
   pub status: StateStreamStatus,
 }
 
 impl StateStream {
+  pub fn new_create(kind: &str, key: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: kind.to_owned(),
+      context: None,
+      diff: None,
+      status: StateStreamStatus::Create,
+    }
+  }
+
+  pub fn new_update(kind: &str, key: &str, diff: Vec<StateDiff>) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: kind.to_owned(),
+      context: None,
+      diff: Some(diff),
+      status: StateStreamStatus::Update,
+    }
+  }
+
+  pub fn new_pruned(kind: &str, key: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: kind.to_owned(),
+      context: None,
+      diff: None,
+      status: StateStreamStatus::Pruned,
+    }
+  }
+
+  pub fn new_error(kind: &str, key: &str, err: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: kind.to_owned(),
+      context: Some(err.to_owned()),
+      diff: None,
+      status: StateStreamStatus::Failed,
+    }
+  }
+
   pub fn new_cargo_pending(key: &str) -> Self {
     StateStream {
       key: key.to_owned(),
       kind: "Cargo".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::Pending,
     }
   }
@@ -169,6 +280,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Cargo".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::NotFound,
     }
   }
@@ -178,6 +290,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Cargo".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::UnChanged,
     }
   }
@@ -187,6 +300,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Cargo".to_string(),
       context: Some(err.to_owned()),
+      diff: None,
       status: StateStreamStatus::Failed,
     }
   }
@@ -196,6 +310,37 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Cargo".to_string(),
       context: None,
+      diff: None,
+      status: StateStreamStatus::Success,
+    }
+  }
+
+  pub fn new_cargo_instance_pending(key: &str, ctx: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "CargoInstance".to_string(),
+      context: Some(ctx.to_owned()),
+      diff: None,
+      status: StateStreamStatus::Pending,
+    }
+  }
+
+  pub fn new_cargo_instance_error(key: &str, err: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "CargoInstance".to_string(),
+      context: Some(err.to_owned()),
+      diff: None,
+      status: StateStreamStatus::Failed,
+    }
+  }
+
+  pub fn new_cargo_instance_success(key: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "CargoInstance".to_string(),
+      context: None,
+      diff: None,
       status: StateStreamStatus::Success,
     }
   }
@@ -205,6 +350,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "VirtualMachine".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::UnChanged,
     }
   }
@@ -214,6 +360,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "VirtualMachine".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::Pending,
     }
   }
@@ -223,6 +370,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "VirtualMachine".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::NotFound,
     }
   }
@@ -232,6 +380,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "VirtualMachine".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::Success,
     }
   }
@@ -241,6 +390,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "VirtualMachine".to_string(),
       context: Some(err.to_owned()),
+      diff: None,
       status: StateStreamStatus::Failed,
     }
   }
@@ -250,6 +400,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Resource".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::Pending,
     }
   }
@@ -259,6 +410,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Resource".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::NotFound,
     }
   }
@@ -268,6 +420,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Resource".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::UnChanged,
     }
   }
@@ -277,6 +430,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Resource".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::Success,
     }
   }
@@ -286,6 +440,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Resource".to_string(),
       context: Some(err.to_owned()),
+      diff: None,
       status: StateStreamStatus::Failed,
     }
   }
@@ -295,6 +450,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Secret".to_string(),
       context: Some(err.to_owned()),
+      diff: None,
       status: StateStreamStatus::Failed,
     }
   }
@@ -304,6 +460,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Secret".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::Pending,
     }
   }
@@ -313,6 +470,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Secret".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::NotFound,
     }
   }
@@ -322,6 +480,7 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Secret".to_string(),
       context: None,
+      diff: None,
       status: StateStreamStatus::UnChanged,
     }
   }
@@ -331,6 +490,57 @@ impl StateStream {
       key: key.to_owned(),
       kind: "Secret".to_string(),
       context: None,
+      diff: None,
+      status: StateStreamStatus::Success,
+    }
+  }
+
+  pub fn new_job_error(key: &str, err: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "Job".to_string(),
+      context: Some(err.to_owned()),
+      diff: None,
+      status: StateStreamStatus::Failed,
+    }
+  }
+
+  pub fn new_job_pending(key: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "Job".to_string(),
+      context: None,
+      diff: None,
+      status: StateStreamStatus::Pending,
+    }
+  }
+
+  pub fn new_job_not_found(key: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "Job".to_string(),
+      context: None,
+      diff: None,
+      status: StateStreamStatus::NotFound,
+    }
+  }
+
+  pub fn new_job_unchanged(key: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "Job".to_string(),
+      context: None,
+      diff: None,
+      status: StateStreamStatus::UnChanged,
+    }
+  }
+
+  pub fn new_job_success(key: &str) -> Self {
+    StateStream {
+      key: key.to_owned(),
+      kind: "Job".to_string(),
+      context: None,
+      diff: None,
       status: StateStreamStatus::Success,
     }
   }
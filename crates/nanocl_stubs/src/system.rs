@@ -6,9 +6,12 @@ use serde::{Serialize, Deserialize};
 
 use crate::config::DaemonConfig;
 
-use super::cargo::CargoInspect;
+use super::cargo::{CargoInspect, CargoScaleHistory};
+use super::vm::VmInspect;
 use super::resource::Resource;
 use super::secret::Secret;
+use super::job::{Job, JobRun};
+use super::proxy::ProxyUpstreamHealth;
 
 /// HostInfo contains information about the host and the docker daemon
 #[derive(Debug, Clone)]
@@ -44,6 +47,8 @@ pub enum Event {
   /// NamespaceCreated is sent when a namespace is created
   NamespaceCreated(String),
   /// NamespaceDeleted is sent when a namespace is deleted
+  NamespaceDeleted(String),
+  /// CargoCreated is sent when a cargo is created
   CargoCreated(Box<CargoInspect>),
   /// CargoDeleted is sent when a cargo is deleted
   CargoDeleted(Box<CargoInspect>),
@@ -53,6 +58,24 @@ pub enum Event {
   CargoStopped(Box<CargoInspect>),
   /// CargoPatched is sent when a cargo is patched
   CargoPatched(Box<CargoInspect>),
+  /// CargoRolledBack is sent when a cargo is reverted to his previous config
+  /// after an update that failed to start or to be healthy
+  CargoRolledBack(Box<CargoInspect>),
+  /// CargoReconciled is sent when instances of a cargo are created or removed
+  /// to match his replication mode
+  CargoReconciled(Box<CargoInspect>),
+  /// CargoAutoscaled is sent when the autoscaler change the number of instances of a cargo
+  CargoAutoscaled(Box<CargoScaleHistory>),
+  /// VmCreated is sent when a virtual machine is created
+  VmCreated(Box<VmInspect>),
+  /// VmDeleted is sent when a virtual machine is deleted
+  VmDeleted(Box<VmInspect>),
+  /// VmStarted is sent when a virtual machine is started
+  VmStarted(Box<VmInspect>),
+  /// VmStopped is sent when a virtual machine is stopped
+  VmStopped(Box<VmInspect>),
+  /// VmPatched is sent when a virtual machine is patched
+  VmPatched(Box<VmInspect>),
   /// ResourceCreated is sent when a resource is created
   ResourceCreated(Box<Resource>),
   /// ResourceDeleted is sent when a resource is deleted
@@ -65,17 +88,43 @@ pub enum Event {
   SecretDeleted(Box<Secret>),
   /// SecretPatched is sent when a secret is patched
   SecretPatched(Box<Secret>),
+  /// JobCreated is sent when a job is created
+  JobCreated(Box<Job>),
+  /// JobDeleted is sent when a job is deleted
+  JobDeleted(Box<Job>),
+  /// JobPatched is sent when a job is updated
+  JobPatched(Box<Job>),
+  /// JobRunFinished is sent when a run of a job succeeded or failed
+  JobRunFinished(Box<JobRun>),
+  /// ProxyUpstreamHealthChanged is sent when the proxy remove an instance
+  /// failing his health check from an upstream or add it back
+  ProxyUpstreamHealthChanged(Box<ProxyUpstreamHealth>),
 }
 
 impl std::fmt::Display for Event {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     match self {
       Event::NamespaceCreated(key) => write!(f, "NamespaceCreated({key})"),
+      Event::NamespaceDeleted(key) => write!(f, "NamespaceDeleted({key})"),
       Event::CargoCreated(cargo) => write!(f, "CargoCreated({})", cargo.key),
       Event::CargoDeleted(cargo) => write!(f, "CargoDeleted({})", cargo.key),
       Event::CargoStarted(cargo) => write!(f, "CargoStarted({})", cargo.key),
       Event::CargoStopped(cargo) => write!(f, "CargoStopped({})", cargo.key),
       Event::CargoPatched(cargo) => write!(f, "CargoPatched({})", cargo.key),
+      Event::CargoRolledBack(cargo) => {
+        write!(f, "CargoRolledBack({})", cargo.key)
+      }
+      Event::CargoReconciled(cargo) => {
+        write!(f, "CargoReconciled({})", cargo.key)
+      }
+      Event::CargoAutoscaled(history) => {
+        write!(f, "CargoAutoscaled({})", history.cargo_key)
+      }
+      Event::VmCreated(vm) => write!(f, "VmCreated({})", vm.key),
+      Event::VmDeleted(vm) => write!(f, "VmDeleted({})", vm.key),
+      Event::VmStarted(vm) => write!(f, "VmStarted({})", vm.key),
+      Event::VmStopped(vm) => write!(f, "VmStopped({})", vm.key),
+      Event::VmPatched(vm) => write!(f, "VmPatched({})", vm.key),
       Event::ResourceCreated(resource) => {
         write!(f, "ResourceCreated({})", resource.name)
       }
@@ -94,6 +143,24 @@ impl std::fmt::Display for Event {
       Event::SecretPatched(secret) => {
         write!(f, "SecretPatched({})", secret.key)
       }
+      Event::JobCreated(job) => write!(f, "JobCreated({})", job.name),
+      Event::JobDeleted(job) => write!(f, "JobDeleted({})", job.name),
+      Event::JobPatched(job) => write!(f, "JobPatched({})", job.name),
+      Event::JobRunFinished(run) => {
+        write!(f, "JobRunFinished({} {})", run.job_name, run.status)
+      }
+      Event::ProxyUpstreamHealthChanged(health) => write!(
+        f,
+        "ProxyUpstreamHealthChanged({} {}:{} {})",
+        health.target_key,
+        health.address,
+        health.port,
+        if health.healthy {
+          "healthy"
+        } else {
+          "unhealthy"
+        }
+      ),
     }
   }
 }
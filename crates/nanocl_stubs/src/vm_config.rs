@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
 use crate::vm::VmInspect;
+use crate::generic::GenericDependency;
 
 /// Disk representation of a VM
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -141,6 +142,12 @@ pub struct VmConfigPartial {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub host_config: Option<VmHostConfig>,
+  /// Cargoes or virtual machines to wait for before applying the vm
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub depends_on: Option<Vec<GenericDependency>>,
 }
 
 /// Payload used to patch a vm
@@ -199,6 +206,12 @@ pub struct VmConfigUpdate {
     serde(skip_serializing_if = "Option::is_none")
   )]
   pub host_config: Option<VmHostConfig>,
+  /// Cargoes or virtual machines to wait for before applying the vm
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub depends_on: Option<Vec<GenericDependency>>,
 }
 
 impl From<VmConfigPartial> for VmConfigUpdate {
@@ -212,6 +225,7 @@ impl From<VmConfigPartial> for VmConfigUpdate {
       password: vm_config.password,
       ssh_key: vm_config.ssh_key,
       metadata: vm_config.metadata,
+      depends_on: vm_config.depends_on,
     }
   }
 }
@@ -280,6 +294,12 @@ pub struct VmConfig {
   pub labels: Option<HashMap<String, String>>,
   /// A vm's resources (cpu, memory, network)
   pub host_config: VmHostConfig,
+  /// Cargoes or virtual machines to wait for before applying the vm
+  #[cfg_attr(
+    feature = "serde",
+    serde(skip_serializing_if = "Option::is_none")
+  )]
+  pub depends_on: Option<Vec<GenericDependency>>,
 }
 
 impl From<VmConfig> for VmConfigUpdate {
@@ -293,6 +313,7 @@ impl From<VmConfig> for VmConfigUpdate {
       password: vm_config.password,
       ssh_key: vm_config.ssh_key,
       metadata: vm_config.metadata,
+      depends_on: vm_config.depends_on,
     }
   }
 }
@@ -310,6 +331,7 @@ impl From<VmInspect> for VmConfigPartial {
       labels: vm_inspect.config.labels,
       host_config: Some(vm_inspect.config.host_config),
       metadata: vm_inspect.config.metadata,
+      depends_on: vm_inspect.config.depends_on,
     }
   }
 }
@@ -8,6 +8,7 @@ use nanocl_stubs::generic::GenericNspQuery;
 use nanocl_stubs::cargo::{
   Cargo, CargoSummary, CargoInspect, OutputLog, CargoKillOptions,
   CargoDeleteQuery, CargoLogQuery, CargoStatsQuery, CargoStats,
+  CargoScaleHistory,
 };
 use nanocl_stubs::cargo_config::{
   CargoConfigUpdate, CargoConfigPartial, CargoConfig,
@@ -386,6 +387,45 @@ impl NanocldClient {
     Self::res_json(res).await
   }
 
+  /// ## List scale histories of a cargo
+  ///
+  /// List the scaling decisions taken by the autoscaler of a cargo
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) - The name of the cargo to list the scale histories
+  /// * [namespace](Option<String>) - The namespace where belong the cargo
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - A [Vec](Vec) of [CargoScaleHistory](CargoScaleHistory)
+  ///   * [Err](HttpClientError) - The scale histories could not be listed
+  ///
+  /// ## Example
+  ///
+  /// ```no_run,ignore
+  /// use nanocld_client::NanocldClient;
+  ///
+  /// let client = NanocldClient::connect_to("http://localhost:8585", None);
+  /// let histories = client.list_scale_history_cargo("my-cargo", None).await.unwrap();
+  /// ```
+  ///
+  pub async fn list_scale_history_cargo(
+    &self,
+    name: &str,
+    namespace: Option<String>,
+  ) -> Result<Vec<CargoScaleHistory>, HttpClientError> {
+    let res = self
+      .send_get(
+        format!("/{}/cargoes/{name}/scale/histories", &self.version),
+        Some(GenericNspQuery { namespace }),
+      )
+      .await?;
+
+    Self::res_json(res).await
+  }
+
   /// ## Revert a cargo to a specific history
   ///
   /// ## Arguments
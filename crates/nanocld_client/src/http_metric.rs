@@ -1,4 +1,7 @@
-use nanocl_stubs::http_metric::{HttpMetric, HttpMetricListQuery};
+use nanocl_stubs::http_metric::{
+  HttpMetric, HttpMetricListQuery, HttpMetricStatsQuery, HttpMetricStats,
+  ProxyLogBatch, ProxyLogIngestion,
+};
 use nanocl_utils::http_client_error::HttpClientError;
 
 use super::http_client::NanocldClient;
@@ -14,6 +17,61 @@ impl NanocldClient {
 
     Self::res_json(res).await
   }
+
+  /// ## Stats http metric
+  ///
+  /// Get the rate, latency percentiles, error ratio and bytes sent
+  /// of the http metrics by time bucket and group
+  ///
+  /// ## Arguments
+  ///
+  /// * [query](Option<HttpMetricStatsQuery>) - How the metrics are grouped
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Vec<HttpMetricStats>) - The statistics of each bucket and group
+  ///   * [Err](HttpClientError) - The statistics could not be computed
+  ///
+  pub async fn stats_http_metric(
+    &self,
+    query: Option<HttpMetricStatsQuery>,
+  ) -> Result<Vec<HttpMetricStats>, HttpClientError> {
+    let res = self
+      .send_get(format!("/{}/http_metrics/stats", &self.version), query)
+      .await?;
+
+    Self::res_json(res).await
+  }
+
+  /// ## Ingest http metrics
+  ///
+  /// Used by the proxy to save his access logs as http and stream metrics
+  ///
+  /// ## Arguments
+  ///
+  /// * [batch](ProxyLogBatch) - The access log lines to save
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](ProxyLogIngestion) - The number of lines saved and rejected
+  ///   * [Err](HttpClientError) - The batch could not be saved
+  ///
+  pub async fn ingest_http_metric(
+    &self,
+    batch: &ProxyLogBatch,
+  ) -> Result<ProxyLogIngestion, HttpClientError> {
+    let res = self
+      .send_post(
+        format!("/{}/http_metrics/ingest", &self.version),
+        Some(batch),
+        None::<String>,
+      )
+      .await?;
+
+    Self::res_json(res).await
+  }
 }
 
 #[cfg(test)]
@@ -28,4 +86,12 @@ mod tests {
     assert!(res.is_ok());
     Ok(())
   }
+
+  #[ntex::test]
+  async fn stats_metric() -> Result<(), HttpClientError> {
+    let client = NanocldClient::connect_to("http://localhost:8585", None);
+    let res = client.stats_http_metric(None::<HttpMetricStatsQuery>).await;
+    assert!(res.is_ok());
+    Ok(())
+  }
 }
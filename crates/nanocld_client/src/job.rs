@@ -0,0 +1,217 @@
+use nanocl_utils::http_client_error::HttpClientError;
+use nanocl_stubs::job::{Job, JobPartial, JobInspect};
+
+use super::http_client::NanocldClient;
+
+impl NanocldClient {
+  /// ## List all jobs
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - A [Vec](Vec) of [jobs](Job)
+  ///   * [Err](HttpClientError) - The jobs could not be listed
+  ///
+  /// ## Example
+  ///
+  /// ```no_run,ignore
+  /// use nanocld_client::NanocldClient;
+  ///
+  /// let client = NanocldClient::connect_to("http://localhost:8585", None);
+  /// let jobs = client.list_job().await;
+  /// ```
+  ///
+  pub async fn list_job(&self) -> Result<Vec<Job>, HttpClientError> {
+    let res = self
+      .send_get(format!("/{}/jobs", &self.version), None::<String>)
+      .await?;
+
+    Self::res_json(res).await
+  }
+
+  /// ## Create a new job
+  ///
+  /// ## Arguments
+  ///
+  /// * [item](JobPartial) - The config of the job to create
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - The created [job](Job)
+  ///   * [Err](HttpClientError) - The job could not be created
+  ///
+  pub async fn create_job(
+    &self,
+    item: &JobPartial,
+  ) -> Result<Job, HttpClientError> {
+    let res = self
+      .send_post(
+        format!("/{}/jobs", &self.version),
+        Some(item),
+        None::<String>,
+      )
+      .await?;
+
+    Self::res_json(res).await
+  }
+
+  /// ## Put a job
+  ///
+  /// Replace the config of a job
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) - The name of the job to update
+  /// * [item](JobPartial) - The new config of the job
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - The updated [job](Job)
+  ///   * [Err](HttpClientError) - The job could not be updated
+  ///
+  pub async fn put_job(
+    &self,
+    name: &str,
+    item: &JobPartial,
+  ) -> Result<Job, HttpClientError> {
+    let res = self
+      .send_put(
+        format!("/{}/jobs/{name}", &self.version),
+        Some(item),
+        None::<String>,
+      )
+      .await?;
+
+    Self::res_json(res).await
+  }
+
+  /// ## Inspect a job
+  ///
+  /// Inspect a job by it's name to get his config and his past runs
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) - The name of the job to inspect
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - The desired [job](JobInspect)
+  ///   * [Err](HttpClientError) - The job could not be inspected
+  ///
+  /// ## Example
+  ///
+  /// ```no_run,ignore
+  /// use nanocld_client::NanocldClient;
+  ///
+  /// let client = NanocldClient::connect_to("http://localhost:8585", None);
+  /// let job = client.inspect_job("my-job").await?;
+  /// ```
+  ///
+  pub async fn inspect_job(
+    &self,
+    name: &str,
+  ) -> Result<JobInspect, HttpClientError> {
+    let res = self
+      .send_get(
+        format!("/{}/jobs/{name}/inspect", &self.version),
+        None::<String>,
+      )
+      .await?;
+
+    Self::res_json(res).await
+  }
+
+  /// ## Start a job
+  ///
+  /// Run a job now in background, the result is available in his runs
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) - The name of the job to start
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - The job run was started
+  ///   * [Err](HttpClientError) - The job could not be started
+  ///
+  pub async fn start_job(&self, name: &str) -> Result<(), HttpClientError> {
+    self
+      .send_post(
+        format!("/{}/jobs/{name}/start", &self.version),
+        None::<String>,
+        None::<String>,
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  /// ## Delete a job
+  ///
+  /// Delete a job by it's name with his past runs
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) - The name of the job to delete
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - The job was deleted
+  ///   * [Err](HttpClientError) - The job could not be deleted
+  ///
+  /// ## Example
+  ///
+  /// ```no_run,ignore
+  /// use nanocld_client::NanocldClient;
+  ///
+  /// let client = NanocldClient::connect_to("http://localhost:8585", None);
+  /// client.delete_job("my-job").await?;
+  /// ```
+  ///
+  pub async fn delete_job(&self, name: &str) -> Result<(), HttpClientError> {
+    self
+      .send_delete(format!("/{}/jobs/{name}", &self.version), None::<String>)
+      .await?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use nanocl_stubs::cargo_config::Config;
+
+  #[ntex::test]
+  async fn basic() {
+    const JOB_NAME: &str = "job-test";
+    let client = NanocldClient::connect_to("http://localhost:8585", None);
+
+    client.list_job().await.unwrap();
+
+    let job = JobPartial {
+      name: JOB_NAME.to_string(),
+      container: Config {
+        image: Some("nexthat/nanocl-get-started:latest".to_string()),
+        cmd: Some(vec!["echo".into(), "hello".into()]),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let job = client.create_job(&job).await.unwrap();
+    assert_eq!(job.name, JOB_NAME);
+
+    let job = client.inspect_job(JOB_NAME).await.unwrap();
+    assert_eq!(job.name, JOB_NAME);
+
+    client.start_job(JOB_NAME).await.unwrap();
+    client.delete_job(JOB_NAME).await.unwrap();
+  }
+}
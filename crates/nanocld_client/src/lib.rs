@@ -12,6 +12,7 @@ pub(crate) mod vm_image;
 pub(crate) mod http_metric;
 pub(crate) mod node;
 pub(crate) mod secret;
+pub(crate) mod job;
 pub use bollard_next;
 pub mod error;
 pub use http_client::*;
@@ -1,4 +1,5 @@
 use nanocl_utils::http_client_error::HttpClientError;
+use nanocl_stubs::generic::GenericCount;
 use nanocl_stubs::secret::{Secret, SecretPartial, SecretUpdate};
 
 use super::http_client::NanocldClient;
@@ -60,21 +61,23 @@ impl NanocldClient {
   ///
   /// ## Arguments
   ///
-  /// * [secret](SecretUpdate) - The key of the secret to create
+  /// * [key](str) - The key of the secret to patch
+  /// * [secret](SecretUpdate) - The new data of the secret
   ///
   /// ## Returns
   ///
   /// * [Result](Result)
-  ///   * [Ok](Ok) - The created [secret](Secret)
-  ///   * [Err](HttpClientError) - The secret could not be created
+  ///   * [Ok](Ok) - The patched [secret](Secret)
+  ///   * [Err](HttpClientError) - The secret could not be patched
   ///
   pub async fn patch_secret(
     &self,
+    key: &str,
     item: &SecretUpdate,
   ) -> Result<Secret, HttpClientError> {
     let res = self
       .send_patch(
-        format!("/{}/secrets", &self.version),
+        format!("/{}/secrets/{key}", &self.version),
         Some(item),
         None::<String>,
       )
@@ -150,6 +153,39 @@ impl NanocldClient {
 
     Ok(())
   }
+
+  /// ## Rotate the secret key
+  ///
+  /// Generate a new master key and re-encrypt every secret with it
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - The [number](GenericCount) of re-encrypted secrets
+  ///   * [Err](HttpClientError) - The master key could not be rotated
+  ///
+  /// ## Example
+  ///
+  /// ```no_run,ignore
+  /// use nanocld_client::NanocldClient;
+  ///
+  /// let client = NanocldClient::connect_to("http://localhost:8585", None);
+  /// let res = client.rotate_secret_key().await?;
+  /// ```
+  ///
+  pub async fn rotate_secret_key(
+    &self,
+  ) -> Result<GenericCount, HttpClientError> {
+    let res = self
+      .send_post(
+        format!("/{}/secrets/rotate-key", &self.version),
+        None::<String>,
+        None::<String>,
+      )
+      .await?;
+
+    Self::res_json(res).await
+  }
 }
 
 #[cfg(test)]
@@ -177,6 +213,13 @@ mod tests {
     let secret = client.inspect_secret(SECRET_KEY).await.unwrap();
     assert_eq!(secret.key, SECRET_KEY);
 
+    let update = SecretUpdate {
+      data: serde_json::json!({"key": "new value"}),
+      metadata: None,
+    };
+    let secret = client.patch_secret(SECRET_KEY, &update).await.unwrap();
+    assert_eq!(secret.data, update.data);
+
     client.delete_secret(SECRET_KEY).await.unwrap();
   }
 }
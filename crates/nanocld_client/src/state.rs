@@ -3,7 +3,7 @@ use ntex::channel::mpsc::Receiver;
 use nanocl_utils::http_error::HttpError;
 use nanocl_utils::http_client_error::HttpClientError;
 
-use nanocl_stubs::state::StateStream;
+use nanocl_stubs::state::{StateStream, StateApplyQuery};
 
 use crate::http_client::NanocldClient;
 
@@ -11,13 +11,10 @@ impl NanocldClient {
   pub async fn apply_state(
     &self,
     data: &serde_json::Value,
+    query: Option<&StateApplyQuery>,
   ) -> Result<Receiver<Result<StateStream, HttpError>>, HttpClientError> {
     let res = self
-      .send_put(
-        format!("/{}/state/apply", &self.version),
-        Some(data),
-        None::<String>,
-      )
+      .send_put(format!("/{}/state/apply", &self.version), Some(data), query)
       .await?;
 
     Ok(Self::res_stream(res).await)
@@ -4,6 +4,7 @@ use nanocl_utils::http_error::HttpError;
 use nanocl_utils::http_client_error::HttpClientError;
 
 use nanocl_stubs::node::NodeContainerSummary;
+use nanocl_stubs::proxy::ProxyUpstreamHealth;
 use nanocl_stubs::system::{Event, Version, HostInfo, ProccessQuery};
 
 use super::http_client::NanocldClient;
@@ -67,6 +68,36 @@ impl NanocldClient {
     Ok(Self::res_stream(res).await)
   }
 
+  /// ## Report the health of an upstream instance
+  ///
+  /// Used by the proxy to broadcast the instances
+  /// removed from or added back to an upstream
+  ///
+  /// ## Arguments
+  ///
+  /// * [item](ProxyUpstreamHealth) - The new health of the instance
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result)
+  ///   * [Ok](Ok) - The health change has been broadcasted
+  ///   * [Err](HttpClientError) - The health change could not be reported
+  ///
+  pub async fn report_proxy_upstream_health(
+    &self,
+    item: &ProxyUpstreamHealth,
+  ) -> Result<(), HttpClientError> {
+    self
+      .send_post(
+        format!("/{}/events/proxy-upstream-health", &self.version),
+        Some(item),
+        None::<String>,
+      )
+      .await?;
+
+    Ok(())
+  }
+
   /// ## Ping the daemon
   ///
   /// Check if the daemon is running
@@ -5,5 +5,4 @@ docker pull cockroachdb/cockroach:v23.1.11
 docker pull ghcr.io/nxthat/metrsd:0.3.1
 docker pull nexthat/nanocl-get-started:latest
 docker pull ghcr.io/nxthat/nanocl-dev:dev
-docker build --network host -t ndns:dev -f ./bin/ndns/Dockerfile .
 docker build --network host -t nproxy:dev -f ./bin/nproxy/Dockerfile .
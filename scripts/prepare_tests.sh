@@ -8,3 +8,5 @@ chmod 777 -R /run/nanocl
 mkdir -p /var/lib/nanocl/vms/images
 mkdir -p /var/lib/nanocl/nginx/sites-enabled
 chmod 777 -R /var/lib/nanocl
+mkdir -p /etc/nanocl
+chmod 777 -R /etc/nanocl